@@ -67,18 +67,31 @@ pub type SpannedPattern<Id> = Spanned<Pattern<Id>, BytePos>;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Pattern<Id> {
-    Constructor(TypedIdent<Id>, Vec<TypedIdent<Id>>),
+    /// A constructor applied to patterns for each of its arguments, eg. `Cons x (Cons 1 Nil)`
+    Constructor(TypedIdent<Id>, Vec<SpannedPattern<Id>>),
     Record {
         typ: ArcType<Id>,
         types: Vec<(Id, Option<Id>)>,
         fields: Vec<(Id, Option<Id>)>,
     },
+    /// Tuple pattern, eg. `(x, Some y)`
+    Tuple {
+        typ: ArcType<Id>,
+        elems: Vec<SpannedPattern<Id>>,
+    },
     Ident(TypedIdent<Id>),
+    /// Matches values equal to the literal
+    Literal(Literal),
+    /// The `_` pattern which matches any value without binding it
+    Wildcard(ArcType<Id>),
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Alternative<Id> {
     pub pattern: SpannedPattern<Id>,
+    /// Expression which must evaluate to `True` for the alternative to be selected,
+    /// eg. `| Some x if x > 0 -> ...`
+    pub guard: Option<SpannedExpr<Id>>,
     pub expr: SpannedExpr<Id>,
 }
 
@@ -125,14 +138,21 @@ pub enum Expr<Id> {
         types: Vec<(Id, Option<ArcType<Id>>)>,
         exprs: Vec<(Id, Option<SpannedExpr<Id>>)>,
     },
-    /// Tuple construction
-    Tuple(Vec<SpannedExpr<Id>>),
+    /// Tuple construction, eg. `(1, "a")`. Tuples with two or more elements are records with
+    /// the fields `_0`, `_1`, ...
+    Tuple {
+        typ: ArcType<Id>,
+        elems: Vec<SpannedExpr<Id>>,
+    },
     /// Declare a series of value bindings
     LetBindings(Vec<ValueBinding<Id>>, Box<SpannedExpr<Id>>),
     /// Declare a series of type aliases
     TypeBindings(Vec<TypeBinding<Id>>, Box<SpannedExpr<Id>>),
     /// A group of sequenced expressions
     Block(Vec<SpannedExpr<Id>>),
+    /// An expression which could not be parsed. Inserted by the parser when it recovers from a
+    /// syntax error so that the rest of the expression can still be used
+    Error,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -140,6 +160,9 @@ pub struct TypeBinding<Id> {
     pub comment: Option<String>,
     pub name: Id,
     pub alias: Alias<Id, ArcType<Id>>,
+    /// The name of each constructor of a variant type, in the same order as the variants of
+    /// `alias`, spanning its location in the source
+    pub constructors: Vec<Spanned<Id, BytePos>>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -202,6 +225,9 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
             v.visit_expr(&mut **expr);
             for alt in alts.iter_mut() {
                 v.visit_pattern(&mut alt.pattern);
+                if let Some(ref mut guard) = alt.guard {
+                    v.visit_expr(guard);
+                }
                 v.visit_expr(&mut alt.expr);
             }
         }
@@ -219,8 +245,9 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
                 }
             }
         }
-        Expr::Tuple(ref mut exprs) => {
-            for expr in exprs {
+        Expr::Tuple { ref mut typ, ref mut elems } => {
+            v.visit_typ(typ);
+            for expr in elems {
                 v.visit_expr(expr);
             }
         }
@@ -230,7 +257,7 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
         }
         Expr::TypeBindings(_, ref mut expr) => v.visit_expr(&mut *expr),
         Expr::Ident(ref mut id) => v.visit_typ(&mut id.typ),
-        Expr::Literal(..) => (),
+        Expr::Literal(..) | Expr::Error => (),
         Expr::Block(ref mut exprs) => {
             for expr in exprs {
                 v.visit_expr(expr);
@@ -245,13 +272,136 @@ pub fn walk_mut_pattern<V: ?Sized + MutVisitor>(v: &mut V, p: &mut Pattern<V::Id
         Pattern::Constructor(ref mut id, ref mut args) => {
             v.visit_typ(&mut id.typ);
             for arg in args {
-                v.visit_typ(&mut arg.typ);
+                v.visit_pattern(arg);
             }
         }
         Pattern::Record { ref mut typ, .. } => {
             v.visit_typ(typ);
         }
+        Pattern::Tuple { ref mut typ, ref mut elems } => {
+            v.visit_typ(typ);
+            for elem in elems {
+                v.visit_pattern(elem);
+            }
+        }
         Pattern::Ident(ref mut id) => v.visit_typ(&mut id.typ),
+        Pattern::Wildcard(ref mut typ) => v.visit_typ(typ),
+        Pattern::Literal(_) => (),
+    }
+}
+
+/// Visitor trait which walks over expressions without modifying them. Works like `MutVisitor`
+/// except that it takes shared references.
+pub trait Visitor {
+    type Ident;
+
+    fn visit_expr(&mut self, e: &SpannedExpr<Self::Ident>) {
+        walk_expr(self, e);
+    }
+
+    fn visit_pattern(&mut self, e: &SpannedPattern<Self::Ident>) {
+        walk_pattern(self, &e.value);
+    }
+
+    fn visit_typ(&mut self, _: &ArcType<Self::Ident>) {}
+}
+
+pub fn walk_expr<V: ?Sized + Visitor>(v: &mut V, e: &SpannedExpr<V::Ident>) {
+    match e.value {
+        Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+            v.visit_expr(&**pred);
+            v.visit_expr(&**if_true);
+            v.visit_expr(&**if_false);
+        }
+        Expr::Infix(ref lhs, ref id, ref rhs) => {
+            v.visit_expr(&**lhs);
+            v.visit_typ(&id.typ);
+            v.visit_expr(&**rhs);
+        }
+        Expr::LetBindings(ref bindings, ref body) => {
+            for bind in bindings {
+                v.visit_pattern(&bind.name);
+                v.visit_expr(&bind.expr);
+                v.visit_typ(&bind.typ);
+            }
+            v.visit_expr(&**body);
+        }
+        Expr::App(ref func, ref args) => {
+            v.visit_expr(&**func);
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        Expr::Projection(ref expr, _, ref typ) => {
+            v.visit_expr(&**expr);
+            v.visit_typ(typ);
+        }
+        Expr::Match(ref expr, ref alts) => {
+            v.visit_expr(&**expr);
+            for alt in alts {
+                v.visit_pattern(&alt.pattern);
+                if let Some(ref guard) = alt.guard {
+                    v.visit_expr(guard);
+                }
+                v.visit_expr(&alt.expr);
+            }
+        }
+        Expr::Array(ref a) => {
+            v.visit_typ(&a.typ);
+            for expr in &a.exprs {
+                v.visit_expr(expr);
+            }
+        }
+        Expr::Record { ref typ, ref exprs, .. } => {
+            v.visit_typ(typ);
+            for field in exprs {
+                if let Some(ref expr) = field.1 {
+                    v.visit_expr(expr);
+                }
+            }
+        }
+        Expr::Tuple { ref typ, ref elems } => {
+            v.visit_typ(typ);
+            for expr in elems {
+                v.visit_expr(expr);
+            }
+        }
+        Expr::Lambda(ref lambda) => {
+            v.visit_typ(&lambda.id.typ);
+            v.visit_expr(&*lambda.body);
+        }
+        Expr::TypeBindings(_, ref expr) => v.visit_expr(&*expr),
+        Expr::Ident(ref id) => v.visit_typ(&id.typ),
+        Expr::Literal(..) | Expr::Error => (),
+        Expr::Block(ref exprs) => {
+            for expr in exprs {
+                v.visit_expr(expr);
+            }
+        }
+    }
+}
+
+/// Walks a pattern, calling `visit_*` on all relevant elements
+pub fn walk_pattern<V: ?Sized + Visitor>(v: &mut V, p: &Pattern<V::Ident>) {
+    match *p {
+        Pattern::Constructor(ref id, ref args) => {
+            v.visit_typ(&id.typ);
+            for arg in args {
+                v.visit_pattern(arg);
+            }
+        }
+        Pattern::Record { ref typ, .. } => {
+            v.visit_typ(typ);
+        }
+        Pattern::Tuple { ref typ, ref elems } => {
+            v.visit_typ(typ);
+            for elem in elems {
+                v.visit_pattern(elem);
+            }
+        }
+        Pattern::Ident(ref id) => v.visit_typ(&id.typ),
+        Pattern::Wildcard(ref typ) => v.visit_typ(typ),
+        Pattern::Literal(_) => (),
     }
 }
 
@@ -278,38 +428,30 @@ impl Typed for Expr<Symbol> {
         match *self {
             Expr::Ident(ref id) => id.typ.clone(),
             Expr::Projection(_, _, ref typ) => typ.clone(),
-            Expr::Literal(ref lit) => {
-                match *lit {
-                    Literal::Int(_) => Type::int(),
-                    Literal::Float(_) => Type::float(),
-                    Literal::Byte(_) => Type::byte(),
-                    Literal::String(_) => Type::string(),
-                    Literal::Char(_) => Type::char(),
-                }
-            }
+            Expr::Literal(ref lit) => lit.env_type_of(env),
             Expr::IfElse(_, ref arm, _) => arm.env_type_of(env),
-            Expr::Tuple(ref exprs) => {
-                assert!(exprs.is_empty());
-                Type::unit()
-            }
+            Expr::Tuple { ref typ, .. } => typ.clone(),
             Expr::Infix(_, ref op, _) => {
-                if let Type::App(_, ref args) = *op.typ.clone() {
-                    if let Type::App(_, ref args) = *args[1] {
-                        return args[1].clone();
-                    }
-                }
-                panic!("Expected function type in binop");
+                op.typ
+                    .as_function()
+                    .and_then(|(_, ret)| ret.as_function())
+                    .map_or_else(Type::hole, |(_, ret)| ret.clone())
             }
             Expr::LetBindings(_, ref expr) |
             Expr::TypeBindings(_, ref expr) => expr.env_type_of(env),
             Expr::App(ref func, ref args) => {
                 get_return_type(env, &func.env_type_of(env), args.len())
             }
-            Expr::Match(_, ref alts) => alts[0].expr.env_type_of(env),
+            Expr::Match(_, ref alts) => {
+                alts.first().map_or_else(Type::hole, |alt| alt.expr.env_type_of(env))
+            }
             Expr::Array(ref array) => array.typ.clone(),
             Expr::Lambda(ref lambda) => lambda.id.typ.clone(),
             Expr::Record { ref typ, .. } => typ.clone(),
-            Expr::Block(ref exprs) => exprs.last().expect("Expr in block").env_type_of(env),
+            Expr::Block(ref exprs) => {
+                exprs.last().map_or_else(Type::hole, |expr| expr.env_type_of(env))
+            }
+            Expr::Error => Type::hole(),
         }
     }
 }
@@ -329,25 +471,44 @@ impl Typed for Pattern<Symbol> {
         // Identifier patterns might be a function so use the identifier's type instead
         match *self {
             Pattern::Ident(ref id) => id.typ.clone(),
-            Pattern::Record { ref typ, .. } => typ.clone(),
+            Pattern::Record { ref typ, .. } |
+            Pattern::Tuple { ref typ, .. } |
+            Pattern::Wildcard(ref typ) => typ.clone(),
             Pattern::Constructor(ref id, ref args) => get_return_type(env, &id.typ, args.len()),
+            Pattern::Literal(ref lit) => lit.env_type_of(env),
+        }
+    }
+}
+
+impl Typed for Literal {
+    type Ident = Symbol;
+
+    fn env_type_of(&self, _: &TypeEnv) -> ArcType {
+        match *self {
+            Literal::Int(_) => Type::int(),
+            Literal::Float(_) => Type::float(),
+            Literal::Byte(_) => Type::byte(),
+            Literal::String(_) => Type::string(),
+            Literal::Char(_) => Type::char(),
         }
     }
 }
 
+/// Returns the type which is returned after applying `arg_count` arguments to `alias_type`. As
+/// expressions which failed to typecheck may not have function types where one is expected, a
+/// hole is returned if `alias_type` does not take that many arguments.
 fn get_return_type(env: &TypeEnv, alias_type: &ArcType, arg_count: usize) -> ArcType {
     if arg_count == 0 {
         alias_type.clone()
     } else {
-        match alias_type.as_function() {
+        match alias_type.as_any_function() {
             Some((_, ret)) => get_return_type(env, ret, arg_count - 1),
             None => {
                 match alias_type.as_alias() {
                     Some((id, alias_args)) => {
                         let (args, typ) = match env.find_type_info(&id).map(Alias::deref) {
                             Some(&AliasData { ref args, typ: Some(ref typ), .. }) => (args, typ),
-                            Some(&AliasData { .. }) => panic!("ICE: '{:?}' does not exist", id),
-                            None => panic!("Unexpected type {:?} is not a function", alias_type),
+                            _ => return Type::hole(),
                         };
 
                         let typ = types::instantiate(typ.clone(), |gen| {
@@ -361,11 +522,7 @@ fn get_return_type(env: &TypeEnv, alias_type: &ArcType, arg_count: usize) -> Arc
 
                         get_return_type(env, &typ, arg_count)
                     }
-                    None => {
-                        panic!("ICE: Expected function with {} more arguments, found {:?}",
-                               arg_count,
-                               alias_type)
-                    }
+                    None => Type::hole(),
                 }
             }
         }
@@ -0,0 +1,417 @@
+//! Rendering of errors as diagnostics which show the lines of source code that the error refers
+//! to, with carets underlining each location of interest.
+//!
+//! ```text
+//! error: Expected the following types to be equal
+//!  --> test:2:15
+//!   |
+//! 2 | let x : Int = "abc"
+//!   |               ^^^^^
+//!   |     - expected because of this annotation
+//!   = note: Expected: Int
+//! ```
+
+use std::cmp;
+use std::fmt;
+use std::iter;
+
+use pos::{BytePos, Column, Location, Span};
+use source::Source;
+
+/// Spans which cover more lines than this only show the first and last line
+const MAX_SPANNED_LINES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelStyle {
+    /// The location where the error occured, underlined with `^`
+    Primary,
+    /// A location which helps to explain the error, underlined with `-`
+    Secondary,
+}
+
+/// A span in the source together with a message which is shown next to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span<BytePos>,
+    pub message: String,
+    pub style: LabelStyle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable code which identifies the kind of error
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    /// Additional information which is shown after the source lines
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(message: S) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn severity(mut self, severity: Severity) -> Diagnostic {
+        self.severity = severity;
+        self
+    }
+
+    pub fn code<S: Into<String>>(mut self, code: S) -> Diagnostic {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Adds a label for the location where the error occured
+    pub fn primary<S: Into<String>>(self, span: Span<BytePos>, message: S) -> Diagnostic {
+        self.label(span, message, LabelStyle::Primary)
+    }
+
+    /// Adds a label for a location which is related to the error
+    pub fn secondary<S: Into<String>>(self, span: Span<BytePos>, message: S) -> Diagnostic {
+        self.label(span, message, LabelStyle::Secondary)
+    }
+
+    fn label<S: Into<String>>(mut self,
+                              span: Span<BytePos>,
+                              message: S,
+                              style: LabelStyle)
+                              -> Diagnostic {
+        self.labels.push(Label {
+            span: span,
+            message: message.into(),
+            style: style,
+        });
+        self
+    }
+
+    pub fn note<S: Into<String>>(mut self, note: S) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+/// Trait for errors which can describe themselves as a `Diagnostic`
+pub trait AsDiagnostic {
+    /// Creates a diagnostic for the error, `span` being the location where it occured
+    fn as_diagnostic(&self, span: Span<BytePos>) -> Diagnostic;
+}
+
+const BOLD: &'static str = "1";
+const RED: &'static str = "1;31";
+const YELLOW: &'static str = "1;33";
+const BLUE: &'static str = "1;34";
+
+/// Writes `value` surrounded by the ANSI codes for `style` if `style` is set
+struct Paint<T> {
+    style: Option<&'static str>,
+    value: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Paint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.style {
+            Some(style) => write!(f, "\x1b[{}m{}\x1b[0m", style, self.value),
+            None => self.value.fmt(f),
+        }
+    }
+}
+
+/// Like `Source::location` but a position at the end of a line is placed one column after the
+/// last character instead of at it, which makes it suitable for the end of a span
+pub fn location(source: &Source, byte: BytePos) -> Option<Location> {
+    let line = source.line_number_at_byte(byte);
+    source.line(line).and_then(|(start, text)| {
+        let offset = byte.to_usize().wrapping_sub(start.to_usize());
+        if offset <= text.len() && text.is_char_boundary(offset) {
+            Some(Location {
+                line: line,
+                column: Column::from(text[..offset].chars().count()),
+                absolute: byte,
+            })
+        } else {
+            None
+        }
+    })
+}
+
+/// A label which has been resolved to locations in the source
+struct LineLabel<'a> {
+    label: &'a Label,
+    start: Location,
+    end: Location,
+}
+
+/// Renders diagnostics for the errors in a single source file
+pub struct Renderer<'a> {
+    source_name: &'a str,
+    source: &'a Source<'a>,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    /// Creates a renderer which does not use colours
+    pub fn new(source_name: &'a str, source: &'a Source<'a>) -> Renderer<'a> {
+        Renderer {
+            source_name: source_name,
+            source: source,
+            color: false,
+        }
+    }
+
+    /// Sets whether the diagnostics are rendered with ANSI colour codes
+    pub fn color(mut self, color: bool) -> Renderer<'a> {
+        self.color = color;
+        self
+    }
+
+    fn paint<T>(&self, style: &'static str, value: T) -> Paint<T> {
+        Paint {
+            style: if self.color { Some(style) } else { None },
+            value: value,
+        }
+    }
+
+    pub fn render<W>(&self, out: &mut W, diagnostic: &Diagnostic) -> fmt::Result
+        where W: ?Sized + fmt::Write,
+    {
+        let severity_style = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let severity = match diagnostic.code {
+            Some(ref code) => format!("{}[{}]", diagnostic.severity, code),
+            None => diagnostic.severity.to_string(),
+        };
+        try!(write!(out,
+                    "{}{}\n",
+                    self.paint(severity_style, severity),
+                    self.paint(BOLD, format!(": {}", diagnostic.message))));
+
+        let labels: Vec<_> = diagnostic.labels
+            .iter()
+            .filter_map(|label| {
+                let start = location(self.source, label.span.start);
+                match (start, location(self.source, label.span.end)) {
+                    (Some(start), Some(end)) => {
+                        Some(LineLabel {
+                            label: label,
+                            start: start,
+                            end: end,
+                        })
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+
+        let mut lines = Vec::new();
+        for label in &labels {
+            let start = label.start.line.to_usize();
+            let end = label.end.line.to_usize();
+            if end - start < MAX_SPANNED_LINES {
+                lines.extend(start..end + 1);
+            } else {
+                lines.push(start);
+                lines.push(end);
+            }
+        }
+        lines.sort();
+        lines.dedup();
+        let width = lines.last().map_or(1, |&line| (line + 1).to_string().len());
+
+        let location = labels.iter()
+            .find(|label| label.label.style == LabelStyle::Primary)
+            .or(labels.first())
+            .map(|label| label.start);
+        if let Some(location) = location {
+            try!(write!(out,
+                        "{:width$}{} {}:{}:{}\n",
+                        "",
+                        self.paint(BLUE, "-->"),
+                        self.source_name,
+                        location.line,
+                        location.column,
+                        width = width));
+            try!(write!(out, "{:width$} {}\n", "", self.paint(BLUE, "|"), width = width));
+        }
+
+        let mut previous = None;
+        for &line_number in &lines {
+            if previous.map_or(false, |previous| line_number > previous + 1) {
+                try!(write!(out, "{}\n", self.paint(BLUE, "...")));
+            }
+            previous = Some(line_number);
+
+            let text = self.line(line_number);
+            try!(write!(out,
+                        "{} {}\n",
+                        self.paint(BLUE, format!("{:>width$} |", line_number + 1, width = width)),
+                        text));
+
+            for label in &labels {
+                let start_line = label.start.line.to_usize();
+                let end_line = label.end.line.to_usize();
+                let start = label.start.column.to_usize();
+                let end = label.end.column.to_usize();
+                let (from, to, message) = if start_line == line_number {
+                    if end_line == line_number {
+                        (start, end, &label.label.message[..])
+                    } else {
+                        // The message is shown at the last line of the span
+                        (start, text.chars().count(), "")
+                    }
+                } else if end_line == line_number {
+                    let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+                    (indent, end, &label.label.message[..])
+                } else {
+                    continue;
+                };
+                try!(self.underline(out, width, text, from, to, label.label.style, message));
+            }
+        }
+
+        for note in &diagnostic.notes {
+            try!(write!(out,
+                        "{:width$} {} {}\n",
+                        "",
+                        self.paint(BLUE, "="),
+                        self.paint(BOLD, format!("note: {}", note)),
+                        width = width));
+        }
+        Ok(())
+    }
+
+    fn line(&self, line_number: usize) -> &'a str {
+        self.source
+            .line(line_number.into())
+            .map_or("", |(_, line)| line.trim_right_matches('\r'))
+    }
+
+    /// Writes a row which underlines the characters `from..to` of `text`
+    fn underline<W>(&self,
+                    out: &mut W,
+                    width: usize,
+                    text: &str,
+                    from: usize,
+                    to: usize,
+                    style: LabelStyle,
+                    message: &str)
+                    -> fmt::Result
+        where W: ?Sized + fmt::Write,
+    {
+        try!(write!(out, "{:width$} {} ", "", self.paint(BLUE, "|"), width = width));
+        // Keep any tabs so that the underline is aligned with the text above it
+        let indent: String = text.chars()
+            .chain(iter::repeat(' '))
+            .take(from)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let (mark, mark_style) = match style {
+            LabelStyle::Primary => ('^', RED),
+            LabelStyle::Secondary => ('-', BLUE),
+        };
+        let marks: String = iter::repeat(mark).take(cmp::max(to.saturating_sub(from), 1)).collect();
+        try!(write!(out, "{}{}", indent, self.paint(mark_style, marks)));
+        if !message.is_empty() {
+            try!(write!(out, " {}", self.paint(mark_style, message)));
+        }
+        write!(out, "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pos::{BytePos, Span};
+    use source::Source;
+
+    use super::*;
+
+    fn span(start: usize, end: usize) -> Span<BytePos> {
+        Span {
+            start: BytePos::from(start),
+            end: BytePos::from(end),
+        }
+    }
+
+    fn render(source: &str, diagnostic: &Diagnostic) -> String {
+        let source = Source::new(source);
+        let mut out = String::new();
+        Renderer::new("test", &source).color(false).render(&mut out, diagnostic).unwrap();
+        out
+    }
+
+    #[test]
+    fn primary_and_secondary_labels() {
+        let source = "let x = 1\nlet y : Int = \"abc\"\ny";
+        let start = source.find('"').unwrap();
+        let name = source.find("y :").unwrap();
+        let diagnostic = Diagnostic::new("Type mismatch")
+            .primary(span(start, start + 5), "found `String`")
+            .secondary(span(name, name + 1), "expected because of this annotation")
+            .note("Expected: Int");
+        assert_eq!(render(source, &diagnostic),
+                   r#"error: Type mismatch
+ --> test:2:15
+  |
+2 | let y : Int = "abc"
+  |               ^^^^^ found `String`
+  |     - expected because of this annotation
+  = note: Expected: Int
+"#);
+    }
+
+    #[test]
+    fn multi_line_label() {
+        let source = "let f x =\n    x\n        #Int+ 1\nf";
+        let start = source.find('x').unwrap();
+        let end = source.find('1').unwrap() + 1;
+        let diagnostic = Diagnostic::new("Error")
+            .severity(Severity::Warning)
+            .primary(span(start, end), "here");
+        assert_eq!(render(source, &diagnostic),
+                   r#"warning: Error
+ --> test:1:7
+  |
+1 | let f x =
+  |       ^^^
+2 |     x
+3 |         #Int+ 1
+  |         ^^^^^^^ here
+"#);
+    }
+
+    #[test]
+    fn colors() {
+        let source = Source::new("abc");
+        let mut out = String::new();
+        Renderer::new("test", &source)
+            .color(true)
+            .render(&mut out, &Diagnostic::new("Error").primary(span(0, 3), ""))
+            .unwrap();
+        assert!(out.starts_with("\x1b[1;31merror\x1b[0m"), "{:?}", out);
+        assert!(out.contains("\x1b[1;31m^^^\x1b[0m"), "{:?}", out);
+    }
+}
@@ -5,6 +5,7 @@ use std::any::Any;
 use std::error::Error as StdError;
 use std::fmt;
 
+use diagnostic::{AsDiagnostic, Diagnostic, Renderer, Severity};
 use pos::{BytePos, Location, Span, Spanned};
 use source::Source;
 
@@ -48,7 +49,7 @@ impl<T: fmt::Display + fmt::Debug + Any> StdError for Errors<T> {
 
 #[derive(Debug)]
 struct SourceContext<E> {
-    line: String,
+    span: Span<BytePos>,
     error: Spanned<E, Location>,
 }
 
@@ -56,10 +57,9 @@ impl<E> SourceContext<E> {
     fn new(source: &Source, error: Spanned<E, BytePos>) -> SourceContext<E> {
         let start = source.location(error.span.start).unwrap();
         let end = source.location(error.span.end).unwrap();
-        let (_, line) = source.line_at_byte(error.span.start).unwrap();
 
         SourceContext {
-            line: line.to_string(),
+            span: error.span,
             error: Spanned {
                 span: Span {
                     start: start,
@@ -75,6 +75,8 @@ impl<E> SourceContext<E> {
 #[derive(Debug)]
 pub struct InFile<E> {
     source_name: String,
+    source: String,
+    severity: Severity,
     error: Errors<SourceContext<E>>,
 }
 
@@ -82,43 +84,69 @@ impl<E: fmt::Display> InFile<E> {
     /// Creates a new `InFile` error which states that the error occured in `file` using the file
     /// contents in `source` to provide a context to the span.
     pub fn new(source_name: &str, source: &str, error: Errors<Spanned<E, BytePos>>) -> InFile<E> {
-        let source = Source::new(source);
+        let errors = {
+            let source = Source::new(source);
+            error.errors
+                .into_iter()
+                .map(|error| SourceContext::new(&source, error))
+                .collect()
+        };
 
         InFile {
             source_name: source_name.to_string(),
-            error: Errors {
-                errors: error.errors
-                    .into_iter()
-                    .map(|error| SourceContext::new(&source, error))
-                    .collect(),
-            },
+            source: source.to_string(),
+            severity: Severity::Error,
+            error: Errors { errors: errors },
         }
     }
-}
 
-impl<E: fmt::Display> fmt::Display for InFile<E> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for error in &self.error.errors {
-            let Span { start, end } = error.error.span;
+    /// Sets the severity which the errors are displayed with (defaults to `Severity::Error`)
+    pub fn severity(mut self, severity: Severity) -> InFile<E> {
+        self.severity = severity;
+        self
+    }
 
-            try!(write!(f, "{}:{}\n{}\n", self.source_name, error.error, error.line));
+    pub fn source_name(&self) -> &str {
+        &self.source_name
+    }
 
-            for _ in 0..start.column.to_usize() {
-                try!(write!(f, " "));
-            }
+    /// Returns the contents of the file
+    pub fn source(&self) -> &str {
+        &self.source
+    }
 
-            try!(write!(f, "^"));
-            for _ in (start.column.to_usize() + 1)..end.column.to_usize() {
-                try!(write!(f, "~"));
-            }
+    /// Returns the errors together with the span in the source where they occured
+    pub fn errors(&self) -> Vec<&Spanned<E, Location>> {
+        self.error.errors.iter().map(|error| &error.error).collect()
+    }
+}
 
-            try!(writeln!(f, ""));
+impl<E: AsDiagnostic> InFile<E> {
+    /// Returns a diagnostic for each error, in the same order as `errors`
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.error
+            .errors
+            .iter()
+            .map(|error| error.error.value.as_diagnostic(error.span).severity(self.severity))
+            .collect()
+    }
+}
+
+impl<E: AsDiagnostic> fmt::Display for InFile<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let source = Source::new(&self.source);
+        let renderer = Renderer::new(&self.source_name, &source);
+        for (i, diagnostic) in self.diagnostics().iter().enumerate() {
+            if i != 0 {
+                try!(writeln!(f, ""));
+            }
+            try!(renderer.render(f, diagnostic));
         }
         Ok(())
     }
 }
 
-impl<E: fmt::Display + fmt::Debug + Any> StdError for InFile<E> {
+impl<E: AsDiagnostic + fmt::Debug + Any> StdError for InFile<E> {
     fn description(&self) -> &str {
         "Error in file"
     }
@@ -207,14 +207,34 @@ impl Instantiator {
     }
 }
 
+/// Replaces the generic variables in `typ` using `f`. Variables which are bound by a `forall`
+/// inside of `typ` are left as they are.
 pub fn instantiate<F>(typ: ArcType, mut f: F) -> ArcType
     where F: FnMut(&Generic<Symbol>) -> Option<ArcType>,
 {
-    types::walk_move_type(typ,
-                          &mut |typ| {
-                              match *typ {
-                                  Type::Generic(ref x) => f(x),
-                                  _ => None,
-                              }
-                          })
+    instantiate_generics(&typ, &mut f).unwrap_or(typ)
+}
+
+fn instantiate_generics(typ: &Type<Symbol>,
+                        f: &mut FnMut(&Generic<Symbol>) -> Option<ArcType>)
+                        -> Option<ArcType> {
+    match *typ {
+        Type::Generic(ref x) => f(x),
+        Type::Forall(ref params, ref typ) => {
+            let mut unbound = |generic: &Generic<Symbol>| {
+                if params.iter().any(|param| param.id == generic.id) {
+                    None
+                } else {
+                    f(generic)
+                }
+            };
+            instantiate_generics(typ, &mut unbound).map(|typ| Type::forall(params.clone(), typ))
+        }
+        _ => {
+            types::walk_move_type_opt(typ,
+                                      &mut types::ControlVisitation(|typ: &Type<Symbol>| {
+                                          instantiate_generics(typ, f)
+                                      }))
+        }
+    }
 }
@@ -8,6 +8,7 @@ extern crate quick_error;
 extern crate pretty;
 
 pub mod ast;
+pub mod diagnostic;
 pub mod error;
 pub mod fixed;
 pub mod fnv;
@@ -159,6 +159,10 @@ pub enum BuiltinType {
     Array,
     /// Type constructor for functions, `(->) a b : Type -> Type -> Type`
     Function,
+    /// Type constructor for functions whose argument is passed implicitly, `[a] -> b`. The
+    /// argument is filled in by the typechecker with a value from the scope which has the
+    /// argument's type.
+    ImplicitFunction,
 }
 
 impl BuiltinType {
@@ -195,6 +199,7 @@ impl BuiltinType {
             BuiltinType::Unit => "()",
             BuiltinType::Array => "Array",
             BuiltinType::Function => "->",
+            BuiltinType::ImplicitFunction => "[->]",
         }
     }
 }
@@ -311,6 +316,10 @@ pub enum Type<Id, T = ArcType<Id>> {
     /// Variant for "generic" variables. These occur in signatures as lowercase identifers `a`, `b`
     /// etc and are what unbound type variables are eventually made into.
     Generic(Generic<Id>),
+    /// A type with explicitly quantified variables `forall a b . (a -> b) -> f a -> f b`.
+    /// The variables are bound by the `forall` and instantiated each time a value of the type is
+    /// used, which lets record fields and function arguments be polymorphic on their own.
+    Forall(Vec<Generic<Id>>, T),
     /// A builtin type
     Builtin(BuiltinType),
     /// A record type
@@ -391,10 +400,29 @@ impl<Id, T> Type<Id, T>
                   |body, arg| Type::app(function.clone(), vec![arg, body]))
     }
 
+    /// Creates a function type whose arguments are all implicit, `[arg1] -> [arg2] -> ret`
+    pub fn implicit_function(args: Vec<T>, ret: T) -> T
+        where T: Clone,
+    {
+        let function: T = Type::builtin(BuiltinType::ImplicitFunction);
+        args.into_iter()
+            .rev()
+            .fold(ret,
+                  |body, arg| Type::app(function.clone(), vec![arg, body]))
+    }
+
     pub fn generic(typ: Generic<Id>) -> T {
         T::from(Type::Generic(typ))
     }
 
+    pub fn forall(params: Vec<Generic<Id>>, typ: T) -> T {
+        if params.is_empty() {
+            typ
+        } else {
+            T::from(Type::Forall(params, typ))
+        }
+    }
+
     pub fn builtin(typ: BuiltinType) -> T {
         T::from(Type::Builtin(typ))
     }
@@ -454,6 +482,25 @@ impl<Id, T> Type<Id, T>
         None
     }
 
+    /// Returns the argument and return type of a function which takes an implicit argument
+    pub fn as_implicit_function(&self) -> Option<(&T, &T)> {
+        if let Type::App(ref app, ref args) = *self {
+            if args.len() == 2 {
+                if let Type::Builtin(BuiltinType::ImplicitFunction) = **app {
+                    return Some((&args[0], &args[1]));
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the argument and return type of `self` if it is a function, regardless of
+    /// whether the argument is implicit or not. At runtime both kinds of functions are called
+    /// in the same way.
+    pub fn as_any_function(&self) -> Option<(&T, &T)> {
+        self.as_function().or_else(|| self.as_implicit_function())
+    }
+
     pub fn as_alias(&self) -> Option<(&Id, &[T])> {
         match *self {
             Type::App(ref id, ref args) => {
@@ -640,13 +687,40 @@ impl<'a, Id, T> Iterator for ArgIterator<'a, T>
 {
     type Item = &'a T;
     fn next(&mut self) -> Option<&'a T> {
-        self.typ.as_function().map(|(arg, ret)| {
+        self.typ.as_any_function().map(|(arg, ret)| {
             self.typ = ret;
             arg
         })
     }
 }
 
+/// Returns true if the constructor type `typ` of a variant does not return its type applied to
+/// distinct type variables, such as `IntLit : Int -> Expr Int`. These constructors can only be
+/// written using the `| IntLit : Int -> Expr Int` form.
+pub fn is_gadt_variant<Id, T>(typ: &T) -> bool
+    where T: Deref<Target = Type<Id, T>>,
+          Id: AsRef<str>,
+{
+    let mut iter = arg_iter(typ);
+    while let Some(_) = iter.next() {}
+    match **iter.typ {
+        Type::App(_, ref args) => {
+            args.iter().enumerate().any(|(i, arg)| {
+                match **arg {
+                    Type::Generic(ref gen) => {
+                        args[..i].iter().any(|prev| match **prev {
+                            Type::Generic(ref prev) => prev.id.as_ref() == gen.id.as_ref(),
+                            _ => false,
+                        })
+                    }
+                    _ => true,
+                }
+            })
+        }
+        _ => false,
+    }
+}
+
 impl<Id> ArcType<Id> {
     /// Returns the lowest level which this type contains. The level informs from where type
     /// variables where created.
@@ -813,7 +887,28 @@ impl<'a, I, T, E> DisplayType<'a, I, T, E>
             Type::Hole => arena.text("_"),
             Type::Variable(ref var) => arena.text(format!("{}", var.id)),
             Type::Generic(ref gen) => arena.text(gen.id.as_ref()),
+            Type::Forall(ref params, ref typ) => {
+                let mut doc = arena.text("forall");
+                for param in params {
+                    doc = doc.append(" ").append(param.id.as_ref());
+                }
+                let doc = chain![arena;
+                                 doc,
+                                 " .",
+                                 arena.newline(),
+                                 top(self.env, typ).pretty(arena)];
+                enclose(p, Prec::Function, arena, doc)
+            }
             Type::App(ref t, ref args) => {
+                if let Some((arg, ret)) = self.typ.as_implicit_function() {
+                    let doc = chain![arena;
+                                     "[",
+                                     top(self.env, arg).pretty(arena).group(),
+                                     "] ->",
+                                     arena.newline(),
+                                     top(self.env, ret).pretty(arena)];
+                    return enclose(p, Prec::Function, arena, doc);
+                }
                 match self.typ.as_function() {
                     Some((arg, ret)) => {
                         let doc = chain![arena;
@@ -844,6 +939,13 @@ impl<'a, I, T, E> DisplayType<'a, I, T, E>
                     first = false;
                     doc = doc.append("| ")
                         .append(variant.0.as_ref());
+                    if is_gadt_variant(&variant.1) {
+                        doc = chain![arena;
+                                     doc,
+                                     " : ",
+                                     top(self.env, &variant.1).pretty(arena)];
+                        continue;
+                    }
                     for arg in arg_iter(&variant.1) {
                         doc = chain![arena;
                                      doc,
@@ -1005,6 +1107,7 @@ pub fn walk_type_<I, T, F: ?Sized>(typ: &T, f: &mut F)
             }
         }
         Type::Record(ref row) => f.walk(row),
+        Type::Forall(_, ref typ) => f.walk(typ),
         Type::ExtendRow { ref types, ref fields, ref rest } => {
             for field in types {
                 if let Some(ref typ) = field.typ.typ {
@@ -1156,6 +1259,9 @@ pub fn walk_move_type_opt<F: ?Sized, I, T>(typ: &Type<I, T>, f: &mut F) -> Optio
             merge(id, f.visit(id), args, new_args, Type::app)
         }
         Type::Record(ref row) => f.visit(row).map(|row| T::from(Type::Record(row))),
+        Type::Forall(ref params, ref typ) => {
+            f.visit(typ).map(|typ| Type::forall(params.clone(), typ))
+        }
         Type::ExtendRow { ref types, ref fields, ref rest } => {
             let new_fields = walk_move_types(fields, |field| {
                 f.visit(&field.typ).map(|typ| {
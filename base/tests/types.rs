@@ -45,6 +45,37 @@ fn show_function() {
                        "Int -> Int -> Int");
 }
 
+#[test]
+fn show_implicit_function() {
+    let int: ArcType<&str> = Type::int();
+    let int_int = Type::function(vec![int.clone()], int.clone());
+    assert_eq_display!(format!("{}", Type::implicit_function(vec![int.clone()], int_int.clone())),
+                       "[Int] -> Int -> Int");
+
+    assert_eq_display!(format!("{}",
+                               Type::function(vec![Type::implicit_function(vec![int.clone()],
+                                                                           int.clone())],
+                                              int.clone())),
+                       "([Int] -> Int) -> Int");
+}
+
+#[test]
+fn show_forall() {
+    let a: ArcType<&str> = Type::generic(Generic {
+        kind: Kind::typ(),
+        id: "a",
+    });
+    let id = Type::forall(vec![Generic {
+                                   kind: Kind::typ(),
+                                   id: "a",
+                               }],
+                          Type::function(vec![a.clone()], a.clone()));
+    assert_eq_display!(format!("{}", id), "forall a . a -> a");
+
+    assert_eq_display!(format!("{}", Type::function(vec![id.clone()], Type::int())),
+                       "(forall a . a -> a) -> Int");
+}
+
 fn some_record() -> ArcType<&'static str> {
     let data = |s, a| ArcType::from(type_con(s, a));
     let f = Type::function(vec![data("a", vec![])], Type::string());
@@ -180,6 +211,18 @@ fn variants() {
     assert_eq_display!(format!("{}", typ), "| A Int | B");
 }
 
+#[test]
+fn gadt_variants() {
+    let a: ArcType<&str> = Type::generic(Generic {
+        kind: Kind::typ(),
+        id: "a",
+    });
+    let expr = |arg| Type::app(Type::ident("Expr"), vec![arg]);
+    let typ = Type::variants(vec![("IntLit", Type::function(vec![Type::int()], expr(Type::int()))),
+                                  ("Id", Type::function(vec![expr(a.clone())], expr(a.clone())))]);
+    assert_eq_display!(format!("{}", typ), "| IntLit : Int -> Expr Int | Id (Expr a)");
+}
+
 #[test]
 fn show_kind() {
     let two_args = Kind::function(Kind::typ(), Kind::function(Kind::typ(), Kind::typ()));
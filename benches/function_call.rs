@@ -7,6 +7,7 @@ extern crate gluon;
 use gluon::{Compiler, new_vm};
 use gluon::vm::thread::{Status, Thread};
 use gluon::vm::api::{FunctionRef, primitive};
+use gluon::vm::optimize::OptimizationLevel;
 
 // Benchmarks function calls
 #[bench]
@@ -87,3 +88,38 @@ fn gluon_rust_boundary_overhead(b: &mut ::test::Bencher) {
         ::test::black_box(result)
     })
 }
+
+fn record_function_call(b: &mut ::test::Bencher, level: OptimizationLevel) {
+    let vm = new_vm();
+    let text = r#"
+    let add_Int l r : Int -> Int -> Int = l #Int+ r
+    let lt_Int l r : Int -> Int -> Bool = l #Int< r
+    let num_Int = { add = add_Int }
+    let ord_Int = { lt = lt_Int }
+    let sum n acc : Int -> Int -> Int =
+        if ord_Int.lt n 1
+        then acc
+        else sum (num_Int.add n (0 #Int- 1)) (num_Int.add acc n)
+    sum
+    "#;
+    Compiler::new()
+        .optimization_level(level)
+        .load_script(&vm, "sum", text)
+        .unwrap();
+    let mut sum: FunctionRef<fn(i32, i32) -> i32> = vm.get_global("sum").unwrap();
+    b.iter(|| {
+        let result = sum.call(100, 0).unwrap();
+        ::test::black_box(result)
+    })
+}
+
+// Benchmarks calls to small functions retrieved from records, with and without inlining
+#[bench]
+fn record_function_call_no_inline(b: &mut ::test::Bencher) {
+    record_function_call(b, OptimizationLevel::Peephole)
+}
+
+#[bench]
+fn record_function_call_inline(b: &mut ::test::Bencher) {
+    record_function_call(b, OptimizationLevel::Inline)
+}
@@ -5,6 +5,7 @@ use std::cmp::Ordering;
 
 use base::ast::{Expr, SpannedExpr, SpannedPattern, Pattern, TypedIdent, Typed};
 use base::instantiate;
+use base::metadata::{Metadata, MetadataEnv};
 use base::pos::{BytePos, Span};
 use base::scoped_map::ScopedMap;
 use base::symbol::Symbol;
@@ -44,6 +45,42 @@ impl<E: TypeEnv> OnFound for GetType<E> {
     fn nothing(&mut self) {}
 }
 
+struct GetMetadata<'a, E: 'a> {
+    env: &'a E,
+    metadata: Option<&'a Metadata>,
+}
+
+impl<'a, E: MetadataEnv> OnFound for GetMetadata<'a, E> {
+    fn expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        self.metadata = expr_metadata(self.env, expr);
+    }
+
+    fn ident(&mut self, context: &SpannedExpr<Symbol>, ident: &Symbol, _: &ArcType) {
+        let env = self.env;
+        self.metadata = match context.value {
+            Expr::Projection(ref expr, _, _) => {
+                expr_metadata(env, expr).and_then(|metadata| metadata.module.get(ident.as_ref()))
+            }
+            Expr::Infix(..) => env.get_metadata(ident),
+            _ => None,
+        };
+    }
+
+    fn nothing(&mut self) {}
+}
+
+fn expr_metadata<'a, E>(env: &'a E, expr: &SpannedExpr<Symbol>) -> Option<&'a Metadata>
+    where E: MetadataEnv,
+{
+    match expr.value {
+        Expr::Ident(ref id) => env.get_metadata(&id.name),
+        Expr::Projection(ref expr, ref field, _) => {
+            expr_metadata(env, expr).and_then(|metadata| metadata.module.get(field.as_ref()))
+        }
+        _ => None,
+    }
+}
+
 pub struct Suggestion {
     pub name: String,
     pub typ: ArcType,
@@ -76,11 +113,14 @@ impl<E: TypeEnv> OnFound for Suggest<E> {
             Pattern::Ident(ref id) => {
                 self.stack.insert(id.name.clone(), id.typ.clone());
             }
-            Pattern::Constructor(_, ref args) => {
+            Pattern::Constructor(_, ref args) |
+            Pattern::Tuple { elems: ref args, .. } => {
                 for arg in args {
-                    self.stack.insert(arg.name.clone(), arg.typ.clone());
+                    self.on_pattern(arg);
                 }
             }
+            Pattern::Literal(_) |
+            Pattern::Wildcard(_) => (),
         }
     }
 
@@ -162,8 +202,11 @@ impl<F> FindVisitor<F>
     fn visit_one<'e, I>(&mut self, iter: I)
         where I: IntoIterator<Item = &'e SpannedExpr<Symbol>>,
     {
-        let (_, expr) = self.select_spanned(iter, |e| e.span);
-        self.visit_expr(expr.unwrap());
+        match self.select_spanned(iter, |e| e.span) {
+            (_, Some(expr)) => self.visit_expr(expr),
+            // Empty arrays and blocks
+            (_, None) => self.on_found.nothing(),
+        }
     }
 
     fn visit_pattern(&mut self, pattern: &SpannedPattern<Symbol>) {
@@ -188,7 +231,8 @@ impl<F> FindVisitor<F>
                     .map(|x| &***x))
             }
             Expr::Match(ref expr, ref alts) => {
-                self.visit_one(once(&**expr).chain(alts.iter().map(|alt| &alt.expr)))
+                let alt_exprs = alts.iter().flat_map(|alt| alt.guard.iter().chain(once(&alt.expr)));
+                self.visit_one(once(&**expr).chain(alt_exprs))
             }
             Expr::Infix(ref l, ref op, ref r) => {
                 match (l.span.containment(&self.pos), r.span.containment(&self.pos)) {
@@ -235,8 +279,9 @@ impl<F> FindVisitor<F>
                 }
                 self.visit_expr(&lambda.body)
             }
-            Expr::Tuple(ref args) => self.visit_one(args),
+            Expr::Tuple { ref elems, .. } => self.visit_one(elems),
             Expr::Block(ref exprs) => self.visit_one(exprs),
+            Expr::Error => self.on_found.nothing(),
         };
     }
 }
@@ -269,3 +314,21 @@ pub fn suggest<T>(env: &T, expr: &SpannedExpr<Symbol>, pos: BytePos) -> Vec<Sugg
     visitor.visit_expr(expr);
     visitor.on_found.result
 }
+
+/// Returns the metadata of the identifier or field at `pos`
+pub fn get_metadata<'a, T>(env: &'a T,
+                           expr: &SpannedExpr<Symbol>,
+                           pos: BytePos)
+                           -> Option<&'a Metadata>
+    where T: MetadataEnv,
+{
+    let mut visitor = FindVisitor {
+        pos: pos,
+        on_found: GetMetadata {
+            env: env,
+            metadata: None,
+        },
+    };
+    visitor.visit_expr(expr);
+    visitor.on_found.metadata
+}
@@ -0,0 +1,359 @@
+//! Long form explanations of the errors reported when checking a program. Each error has a stable
+//! code (such as `E0006`) which is returned by the `code` method of the error and shown when the
+//! error is displayed.
+
+/// Returns the explanation of the error with `code`, or `None` if no error has that code
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS.iter().find(|&&(c, _)| c == code).map(|&(_, explanation)| explanation)
+}
+
+/// Returns the codes of all errors which have an explanation
+pub fn codes() -> Vec<&'static str> {
+    EXPLANATIONS.iter().map(|&(code, _)| code).collect()
+}
+
+static EXPLANATIONS: &'static [(&'static str, &'static str)] = &[
+    ("E0001",
+     r#"A variable was used but no binding with that name is in scope.
+
+```
+let x = 1
+y
+```
+
+Check that the name is spelled correctly and that the binding is defined before it is used. Values
+from other modules need to be imported and bound, for instance with
+`let { map } = import "std/list.glu"`."#),
+    ("E0002",
+     r#"A value which is not a function was called with arguments.
+
+```
+let x = 1
+x 2
+```
+
+Only functions can be applied to arguments. This often happens when a function is given too many
+arguments or when parentheses are missing around an argument such as `f (g x)`."#),
+    ("E0003",
+     r#"A type was used but no type with that name is in scope.
+
+```
+let x : Integer = 1
+x
+```
+
+Check that the name is spelled correctly and that the type has been defined with `type` or
+imported before it is used."#),
+    ("E0004",
+     r#"A field was accessed on a record which does not have a field with that name.
+
+```
+let record = { x = 1 }
+record.y
+```
+
+Check the fields of the record type, field names are case sensitive."#),
+    ("E0005",
+     r#"A constructor in a pattern was given a different number of arguments than it was defined
+with.
+
+```
+type Pair = | Pair Int Int
+match Pair 1 2 with
+| Pair x -> x
+```
+
+Each argument of the constructor needs a pattern, use `_` for arguments which are not needed:
+`| Pair x _ -> x`."#),
+    ("E0006",
+     r#"Two types were expected to be equal but were not. This is reported when the type of an
+expression does not match the type which its context expects, for instance when the argument of a
+function has a different type than the function's parameter or when a value does not match its
+type annotation.
+
+```
+let x : Int = "abc"
+x
+```
+
+The error lists the expected type and the type which was found as well as each part of the types
+which did not match. Those parts have their own codes which can be explained as well."#),
+    ("E0007",
+     r#"More than one type with the same name was defined in the same `type` expression.
+
+```
+type Test = Int
+and Test = Float
+1
+```
+
+Rename one of the types."#),
+    ("E0008",
+     r#"A field was given more than once in a record expression or a record pattern.
+
+```
+{ x = 1, x = 2 }
+```
+
+Remove the duplicated field or give it a different name."#),
+    ("E0009",
+     r#"A field access was made on a value whose type is not a record.
+
+```
+let x = 1
+x.field
+```
+
+Only records have fields. If the value is a variant, use `match` to get at its contents."#),
+    ("E0010",
+     r#"No record type with the fields of a record expression or pattern could be found.
+
+```
+{ x = 1, y = 2 }.z
+```
+
+The type of a record expression is inferred from the type which declares the fields it contains.
+Declare a record type with those fields or check that the field names are spelled correctly."#),
+    ("E0011",
+     r#"A `match` expression did not have any alternatives.
+
+Every `match` expression needs at least one alternative which the value can be matched against."#),
+    ("E0012",
+     r#"The alternatives of a `match` expression did not match every possible value. The error
+shows an example of a value which is not matched.
+
+```
+type Option a = | None | Some a
+match Some 1 with
+| Some x -> x
+```
+
+Add alternatives for the missing values or a final `| _ -> ...` alternative which matches any
+value."#),
+    ("E0013",
+     r#"An alternative of a `match` expression can never be selected since all values it matches are
+matched by earlier alternatives.
+
+```
+match 1 with
+| x -> x
+| 2 -> 3
+```
+
+Remove the alternative or move it before the alternatives which match the same values."#),
+    ("E0014",
+     r#"A function which takes an implicit argument was used but no binding in scope has the type
+of the argument.
+
+```
+type Show a = { show : a -> String }
+let show_Int : Show Int = { show = \x -> "Int" }
+let show d x : [Show a] -> a -> String = d.show x
+show 1.0
+```
+
+Implicit arguments are only looked up among the bindings which are in scope where the function is
+used. Define or import a binding with the required type (here `Show Float`) before the use. Bindings
+which take implicit arguments themselves, such as `let show_List d : [Show a] -> Show (List a)`,
+are also considered.
+
+The error is also reported when the type of the argument could not be inferred, for instance in
+`let f x = show x`. Add a type annotation which fixes the type of the argument."#),
+    ("E0015",
+     r#"A function which takes an implicit argument was used but more than one binding in scope has
+the type of the argument.
+
+```
+type Show a = { show : a -> String }
+let show_Int : Show Int = { show = \x -> "Int" }
+let show_Int2 : Show Int = { show = \x -> "Int2" }
+let show d x : [Show a] -> a -> String = d.show x
+show 1
+```
+
+Remove all but one of the bindings from the scope."#),
+    ("E0016",
+     r#"A constructor which was declared with an explicit type does not return the type it is
+declared in.
+
+```
+type Expr a = | IntLit : Int -> Option Int
+1
+```
+
+Constructors declared as `| Name : Type` may return the type applied to more specific arguments
+than its parameters, such as `Expr Int`, but the returned type must be the type being defined."#),
+    ("E0017",
+     r#"A variable bound with `let` is never used.
+
+```
+let x = 1
+2
+```
+
+Remove the binding or, if it is intended to be unused, start its name with an underscore such as
+`_x`. This is a warning which can be allowed or denied with the `unused_variables` lint."#),
+    ("E0018",
+     r#"A binding of an imported module, or of one of its fields, is never used.
+
+```
+let list = import "std/list.glu"
+1
+```
+
+Remove the import or the fields which are not used. This is a warning which can be allowed or
+denied with the `unused_imports` lint."#),
+    ("E0019",
+     r#"A binding has the same name as an earlier binding which is still in scope and hides it.
+
+```
+let x = 1
+let x = x + 1
+x
+```
+
+Give one of the bindings a different name if the earlier binding was not meant to be hidden. This
+is a warning which can be allowed or denied with the `shadowed_variables` lint."#),
+    ("E0020",
+     r#"A parameter of a type declaration, or a variable bound by `forall`, does not occur in the
+type.
+
+```
+type Wrapper a = { value : Int }
+1
+```
+
+Remove the variable or use it in the type. This is a warning which can be allowed or denied with
+the `unused_type_variables` lint."#),
+    ("E0021",
+     r#"A variable bound in the pattern of a `match` alternative is never used.
+
+```
+type Option a = | None | Some a
+match Some 1 with
+| Some x -> 1
+| None -> 0
+```
+
+Replace the variable with `_` or start its name with an underscore. This is a warning which can
+be allowed or denied with the `unused_match_binders` lint."#),
+    ("E0100",
+     r#"Two types did not match while unifying the expected type with the type which was found.
+
+```
+let f x : Int -> Int = x
+f 1.0
+```
+
+Here the argument of `f` is expected to be an `Int` but a `Float` is given."#),
+    ("E0101",
+     r#"A type variable would have to contain itself for two types to be equal, which would make
+the type infinitely large.
+
+```
+let f x = x x
+f
+```
+
+This usually happens when a function is applied to itself or when a value is placed inside a
+structure which is then unified with the value."#),
+    ("E0102",
+     r#"An alias was referred to which does not exist while comparing two types. This is usually
+caused by a type which was defined in a scope which has been left."#),
+    ("E0103",
+     r#"The fields of two record types have different names or are in a different order.
+
+```
+type A = { x : Int }
+type B = { y : Int }
+let a : A = { x = 1 }
+let b : B = a
+b
+```
+
+Records are only equal if they contain the same fields in the same order."#),
+    ("E0104",
+     r#"A recursive type alias was expanded inside of itself while comparing two types and could
+not be unified with the other type.
+
+```
+type Test = { x : Test }
+1
+```
+
+Recursive types need to be declared as variants where each recursive use of the type is wrapped
+in a constructor, for instance `type List a = | Nil | Cons a (List a)`."#),
+    ("E0105",
+     r#"A type variable was used outside of the scope where it was introduced and could not be
+generalized.
+
+This is reported when a type annotation declares a type variable to be generic but the value uses
+it with a specific type from an enclosing binding. Remove the annotation or make it less
+general."#),
+    ("E0106",
+     r#"A record was expected to have fields that it does not have.
+
+```
+let f r = r.x #Int+ r.y
+f { x = 1 }
+```
+
+Add the missing fields to the record."#),
+    ("E0200",
+     r#"Two kinds did not match. The kind of a type describes how many type arguments it takes,
+`Type` is the kind of types with values and `Type -> Type` the kind of types which take a single
+argument.
+
+```
+type Option a = | None | Some a
+let x : Option = None
+x
+```
+
+`Option` needs an argument to be the type of a value, for instance `Option Int`."#),
+    ("E0201",
+     r#"A kind variable would have to contain itself for two kinds to be equal.
+
+```
+type Test a = a a
+1
+```
+
+A type can not be applied to itself."#),
+    ("E0202",
+     r#"A type was used while kindchecking but no type with that name is in scope.
+
+Check that the name is spelled correctly and that the type has been defined or imported."#),
+    ("E0300",
+     r#"An overloaded name was used but none of the bindings with that name have a type which
+matches the type expected by the context.
+
+```
+let { (+) } = prelude.num_Int
+let { (+) } = prelude.num_Float
+1 + "a"
+```
+
+The error lists each binding that was considered together with its type. Add a type annotation
+or change the arguments so that exactly one binding matches."#),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique_and_sorted() {
+        let codes = codes();
+        let mut sorted = codes.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(codes, sorted);
+    }
+
+    #[test]
+    fn explain_code() {
+        assert!(explain("E0006").unwrap().contains("expected"));
+        assert_eq!(explain("E9999"), None);
+    }
+}
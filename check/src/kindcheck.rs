@@ -113,6 +113,7 @@ impl<'a> KindCheck<'a> {
             .or_else(|| {
                 self.locals
                     .iter()
+                    .rev()
                     .find(|t| t.0 == *id)
                     .map(|t| t.1.clone())
             })
@@ -156,7 +157,8 @@ impl<'a> KindCheck<'a> {
             BuiltinType::String | BuiltinType::Byte | BuiltinType::Char | BuiltinType::Int |
             BuiltinType::Float | BuiltinType::Unit => self.type_kind(),
             BuiltinType::Array => self.function1_kind(),
-            BuiltinType::Function => self.function2_kind(),
+            BuiltinType::Function |
+            BuiltinType::ImplicitFunction => self.function2_kind(),
         }
     }
 
@@ -169,6 +171,29 @@ impl<'a> KindCheck<'a> {
                 Ok((gen.kind.clone(), Type::generic(gen)))
             }
             Type::Variable(_) => Ok((self.subs.new_var(), typ.clone())),
+            Type::Forall(ref params, ref body) => {
+                // The variables bound by the `forall` shadow any variables with the same name
+                // while the body is checked
+                let start = self.locals.len();
+                for param in params {
+                    let kind = self.subs.new_var();
+                    self.locals.push((param.id.clone(), kind));
+                }
+                let result = self.kindcheck(body);
+                let params = self.locals
+                    .drain(start..start + params.len())
+                    .map(|(id, kind)| {
+                        Generic {
+                            id: id,
+                            kind: kind,
+                        }
+                    })
+                    .collect();
+                let (kind, body) = try!(result);
+                let type_kind = self.type_kind();
+                try!(self.unify(&type_kind, kind));
+                Ok((self.type_kind(), Type::forall(params, body)))
+            }
             Type::Builtin(builtin_typ) => Ok((self.builtin_kind(builtin_typ), typ.clone())),
             Type::App(ref ctor, ref args) => {
                 let (mut kind, ctor) = try!(self.kindcheck(ctor));
@@ -259,6 +284,10 @@ impl<'a> KindCheck<'a> {
                     }))
                 }
                 Type::Generic(ref var) => Some(Type::generic(self.finalize_generic(var))),
+                Type::Forall(ref params, ref body) => {
+                    let params = params.iter().map(|param| self.finalize_generic(param)).collect();
+                    Some(Type::forall(params, body.clone()))
+                }
                 _ => None,
             }
         })
@@ -290,6 +319,15 @@ pub enum KindError<I> {
     UndefinedType(I),
 }
 
+/// Returns the stable code of an error found while kindchecking
+pub fn error_code<I>(error: &Error<I>) -> &'static str {
+    match *error {
+        UnifyError::TypeMismatch(..) => "E0200",
+        UnifyError::Occurs(..) => "E0201",
+        UnifyError::Other(KindError::UndefinedType(..)) => "E0202",
+    }
+}
+
 pub fn fmt_kind_error<I>(error: &Error<I>, f: &mut fmt::Formatter) -> fmt::Result
     where I: fmt::Display,
 {
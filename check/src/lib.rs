@@ -16,10 +16,14 @@ pub mod typecheck;
 pub mod unify_type;
 pub mod unify;
 pub mod kindcheck;
+pub mod matching;
+pub mod lint;
 mod substitution;
 mod rename;
 pub mod completion;
+pub mod query;
 pub mod metadata;
+pub mod explain;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,330 @@
+//! Lints which find code that is valid but likely to be a mistake, such as bindings which are never
+//! used. They are run on the renamed AST once an expression has been typechecked successfully as
+//! every local binding has a unique symbol at that point.
+
+use base::ast::{self, Expr, Pattern, SpannedExpr, SpannedPattern, TypeBinding, ValueBinding};
+use base::ast::Visitor;
+use base::fnv::FnvSet;
+use base::pos::{BytePos, Span, Spanned};
+use base::scoped_map::ScopedMap;
+use base::symbol::Symbol;
+use base::types::{self, ArcType, Type};
+
+use typecheck::{SpannedTypeError, TypeError};
+
+/// The kinds of problems which are found by the lints
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A `let` binding which is never used
+    UnusedVariables,
+    /// A binding of an imported module which is never used
+    UnusedImports,
+    /// A binding which hides an earlier binding of the same name
+    ShadowedVariables,
+    /// A type parameter or `forall` variable which does not occur in its type
+    UnusedTypeVariables,
+    /// A variable bound in the pattern of a `match` alternative which is never used
+    UnusedMatchBinders,
+}
+
+static LINTS: &'static [Lint] = &[Lint::UnusedVariables,
+                                  Lint::UnusedImports,
+                                  Lint::ShadowedVariables,
+                                  Lint::UnusedTypeVariables,
+                                  Lint::UnusedMatchBinders];
+
+impl Lint {
+    /// Returns every lint
+    pub fn all() -> &'static [Lint] {
+        LINTS
+    }
+
+    /// Returns the name which is used to refer to the lint, such as `unused_variables`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Lint::UnusedVariables => "unused_variables",
+            Lint::UnusedImports => "unused_imports",
+            Lint::ShadowedVariables => "shadowed_variables",
+            Lint::UnusedTypeVariables => "unused_type_variables",
+            Lint::UnusedMatchBinders => "unused_match_binders",
+        }
+    }
+
+    /// Returns the lint called `name`
+    pub fn from_name(name: &str) -> Option<Lint> {
+        LINTS.iter().find(|lint| lint.name() == name).cloned()
+    }
+}
+
+/// How the problems found by a lint are reported
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LintLevel {
+    /// Do not report them
+    Allow,
+    /// Report them as warnings which do not cause typechecking to fail (the default)
+    Warn,
+    /// Report them as errors
+    Deny,
+}
+
+/// A binding which is reported by `lint` if it is never used
+struct Binding {
+    lint: Lint,
+    symbol: Symbol,
+    span: Span<BytePos>,
+}
+
+struct LintVisitor {
+    /// The span of the innermost binding of each name
+    scope: ScopedMap<String, Span<BytePos>>,
+    /// Every local binding which has been visited
+    locals: FnvSet<Symbol>,
+    bindings: Vec<Binding>,
+    used: FnvSet<Symbol>,
+    warnings: Vec<(Lint, SpannedTypeError<Symbol>)>,
+}
+
+impl LintVisitor {
+    fn warn(&mut self, lint: Lint, span: Span<BytePos>, error: TypeError<Symbol>) {
+        self.warnings.push((lint,
+                            Spanned {
+                                span: span,
+                                value: error,
+                            }));
+    }
+
+    /// Brings `symbol` into scope. If `lint` is set the binding is reported if it is never used.
+    fn bind(&mut self, symbol: &Symbol, span: Span<BytePos>, lint: Option<Lint>) {
+        self.locals.insert(symbol.clone());
+        // Spans are empty for code which does not come from the source, such as the implicit
+        // prelude, and names starting with `_` are allowed to be unused
+        let name = symbol.declared_name();
+        if span.start == span.end || name.starts_with('_') {
+            return;
+        }
+        let previous = self.scope.get(name).cloned();
+        if let Some(previous) = previous {
+            self.warn(Lint::ShadowedVariables,
+                      span,
+                      TypeError::ShadowedVariable(Symbol::from(name), previous));
+        }
+        self.scope.insert(String::from(name), span);
+        if let Some(lint) = lint {
+            self.bindings.push(Binding {
+                lint: lint,
+                symbol: symbol.clone(),
+                span: span,
+            });
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &SpannedPattern<Symbol>, lint: Lint) {
+        match pattern.value {
+            Pattern::Ident(ref id) => self.bind(&id.name, pattern.span, Some(lint)),
+            Pattern::Record { ref fields, .. } => {
+                for field in fields {
+                    let name = field.1.as_ref().unwrap_or(&field.0);
+                    self.bind(name, pattern.span, Some(lint));
+                }
+            }
+            Pattern::Constructor(_, ref args) |
+            Pattern::Tuple { elems: ref args, .. } => {
+                for arg in args {
+                    self.bind_pattern(arg, lint);
+                }
+            }
+            Pattern::Literal(_) |
+            Pattern::Wildcard(_) => (),
+        }
+    }
+
+    fn visit_binding(&mut self, bind: &ValueBinding<Symbol>) {
+        self.scope.enter_scope();
+        for arg in &bind.args {
+            self.bind(&arg.name, bind.name.span, None);
+        }
+        self.visit_expr(&bind.expr);
+        self.scope.exit_scope();
+    }
+
+    /// Returns true if `expr` refers to an imported module
+    fn is_import(&self, expr: &SpannedExpr<Symbol>) -> bool {
+        match expr.value {
+            // `import` is replaced by the global name of the module
+            Expr::Ident(ref id) if !self.locals.contains(&id.name) => {
+                match *id.typ {
+                    Type::Record(_) => true,
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn check_type_binding(&mut self, span: Span<BytePos>, bind: &TypeBinding<Symbol>) {
+        let typ = match bind.alias.typ {
+            Some(ref typ) => typ,
+            None => return,
+        };
+        // Constructors declared as `| C : T` may use the parameters only through the type they
+        // return so the parameters of such types are not checked
+        let body: Option<Vec<&ArcType>> = match **typ {
+            Type::Variants(ref variants) => {
+                if variants.iter().any(|variant| types::is_gadt_variant(&variant.1)) {
+                    None
+                } else {
+                    Some(variants.iter().flat_map(|variant| types::arg_iter(&variant.1)).collect())
+                }
+            }
+            _ => Some(vec![typ]),
+        };
+        if let Some(body) = body {
+            for arg in &bind.alias.args {
+                if !body.iter().any(|typ| occurs(typ, &arg.id)) {
+                    self.warn(Lint::UnusedTypeVariables,
+                              span,
+                              TypeError::UnusedTypeVariable(arg.id.clone()));
+                }
+            }
+        }
+        let mut unused = Vec::new();
+        types::walk_type(typ, |typ: &ArcType| {
+            if let Type::Forall(ref params, ref body) = **typ {
+                for param in params {
+                    if !occurs(body, &param.id) {
+                        unused.push(param.id.clone());
+                    }
+                }
+            }
+        });
+        for id in unused {
+            self.warn(Lint::UnusedTypeVariables,
+                      span,
+                      TypeError::UnusedTypeVariable(id));
+        }
+    }
+}
+
+impl Visitor for LintVisitor {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        match expr.value {
+            Expr::Ident(ref id) => {
+                self.used.insert(id.name.clone());
+            }
+            Expr::Infix(_, ref op, _) => {
+                self.used.insert(op.name.clone());
+            }
+            Expr::Record { ref exprs, .. } => {
+                // `{ x }` uses the variable `x`
+                for field in exprs {
+                    if field.1.is_none() {
+                        self.used.insert(field.0.clone());
+                    }
+                }
+            }
+            Expr::LetBindings(ref bindings, ref body) => {
+                self.scope.enter_scope();
+                let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+                for bind in bindings {
+                    if !is_recursive {
+                        self.visit_binding(bind);
+                    }
+                    let lint = if self.is_import(&bind.expr) {
+                        Lint::UnusedImports
+                    } else {
+                        Lint::UnusedVariables
+                    };
+                    self.bind_pattern(&bind.name, lint);
+                }
+                if is_recursive {
+                    for bind in bindings {
+                        self.visit_binding(bind);
+                    }
+                }
+                self.visit_expr(body);
+                self.scope.exit_scope();
+                return;
+            }
+            Expr::Lambda(ref lambda) => {
+                self.scope.enter_scope();
+                for arg in &lambda.args {
+                    self.bind(&arg.name, expr.span, None);
+                }
+                self.visit_expr(&lambda.body);
+                self.scope.exit_scope();
+                return;
+            }
+            Expr::Match(ref scrutinee, ref alts) => {
+                self.visit_expr(scrutinee);
+                for alt in alts {
+                    self.scope.enter_scope();
+                    self.bind_pattern(&alt.pattern, Lint::UnusedMatchBinders);
+                    if let Some(ref guard) = alt.guard {
+                        self.visit_expr(guard);
+                    }
+                    self.visit_expr(&alt.expr);
+                    self.scope.exit_scope();
+                }
+                return;
+            }
+            Expr::TypeBindings(ref bindings, ref body) => {
+                // The bindings end where the expression they are in scope for starts
+                let span = Span {
+                    start: expr.span.start,
+                    end: body.span.start,
+                };
+                for bind in bindings {
+                    self.check_type_binding(span, bind);
+                }
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr);
+    }
+}
+
+/// Returns true if the type variable `id` occurs in `typ`
+fn occurs(typ: &ArcType, id: &Symbol) -> bool {
+    let mut found = false;
+    types::walk_type(typ, |typ: &ArcType| {
+        if let Type::Generic(ref generic) = **typ {
+            found = found || generic.id.name_eq(id);
+        }
+    });
+    found
+}
+
+/// Runs every lint on `expr`, which must have been typechecked and renamed, returning the
+/// problems which were found together with the lint which found them
+pub fn lint(expr: &SpannedExpr<Symbol>) -> Vec<(Lint, SpannedTypeError<Symbol>)> {
+    let mut visitor = LintVisitor {
+        scope: ScopedMap::new(),
+        locals: FnvSet::default(),
+        bindings: Vec::new(),
+        used: FnvSet::default(),
+        warnings: Vec::new(),
+    };
+    visitor.visit_expr(expr);
+
+    let LintVisitor { bindings, used, mut warnings, .. } = visitor;
+    for binding in bindings {
+        if used.contains(&binding.symbol) {
+            continue;
+        }
+        let name = Symbol::from(binding.symbol.declared_name());
+        let error = match binding.lint {
+            Lint::UnusedImports => TypeError::UnusedImport(name),
+            Lint::UnusedMatchBinders => TypeError::UnusedMatchBinder(name),
+            _ => TypeError::UnusedVariable(name),
+        };
+        warnings.push((binding.lint,
+                       Spanned {
+                           span: binding.span,
+                           value: error,
+                       }));
+    }
+    warnings.sort_by_key(|warning| warning.1.span);
+    warnings
+}
@@ -0,0 +1,399 @@
+//! Analysis of the alternatives of `match` expressions which finds alternatives that can never be
+//! selected and values which are not matched by any alternative.
+//!
+//! The analysis works on a matrix of patterns where each row is the pattern(s) of an
+//! alternative. An alternative can be selected only if its row is "useful" with respect to the
+//! rows above it, that is if there is a value which its row matches but no row above it does.
+//! The match is exhaustive if a row of wildcards would not be useful after all alternatives.
+use std::fmt;
+
+use base::ast::Literal;
+
+/// A constructor of a variant type
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constructor<Id> {
+    pub name: Id,
+    /// The number of arguments the constructor takes
+    pub arity: usize,
+}
+
+/// A pattern reduced to the parts which decide which values it matches
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern<Id> {
+    /// Matches any value
+    Wildcard,
+    /// Matches values created by `constructors[index]` whose arguments match `args`
+    Constructor {
+        index: usize,
+        /// All constructors of the type being matched
+        constructors: Vec<Constructor<Id>>,
+        args: Vec<Pattern<Id>>,
+    },
+    /// Matches tuples whose elements match each pattern
+    Tuple(Vec<Pattern<Id>>),
+    /// Matches values equal to the literal
+    Literal(Literal),
+}
+
+/// An alternative of a `match` expression
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alternative<Id> {
+    pub pattern: Pattern<Id>,
+    /// Whether the alternative has a guard, in which case it may not be selected even if the
+    /// pattern matches
+    pub guarded: bool,
+}
+
+/// An example of a value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Witness<Id> {
+    /// Any value
+    Wildcard,
+    Constructor(Id, Vec<Witness<Id>>),
+    Tuple(Vec<Witness<Id>>),
+}
+
+impl<Id: fmt::Display> fmt::Display for Witness<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Witness::Wildcard => write!(f, "_"),
+            Witness::Constructor(ref name, ref args) => {
+                try!(write!(f, "{}", name));
+                for arg in args {
+                    match *arg {
+                        Witness::Constructor(_, ref inner) if !inner.is_empty() => {
+                            try!(write!(f, " ({})", arg))
+                        }
+                        _ => try!(write!(f, " {}", arg)),
+                    }
+                }
+                Ok(())
+            }
+            Witness::Tuple(ref elems) => {
+                try!(write!(f, "("));
+                for (i, elem) in elems.iter().enumerate() {
+                    if i != 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", elem));
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// The result of analyzing the alternatives of a `match` expression
+#[derive(Debug, PartialEq)]
+pub struct Analysis<Id> {
+    /// The indexes of the alternatives which can never be selected
+    pub unreachable: Vec<usize>,
+    /// A value which none of the alternatives match, if there is one
+    pub missing: Option<Witness<Id>>,
+}
+
+/// Analyzes the alternatives of a `match` expression
+pub fn analyze<Id: Clone>(alternatives: &[Alternative<Id>]) -> Analysis<Id> {
+    let mut matrix = Vec::new();
+    let mut unreachable = Vec::new();
+    for (i, alternative) in alternatives.iter().enumerate() {
+        let row = vec![alternative.pattern.clone()];
+        if !is_useful(&matrix, &row) {
+            unreachable.push(i);
+        }
+        // A guarded alternative does not prevent any later alternative from being selected
+        if !alternative.guarded {
+            matrix.push(row);
+        }
+    }
+    Analysis {
+        unreachable: unreachable,
+        missing: missing(&matrix, 1).map(|mut witnesses| witnesses.remove(0)),
+    }
+}
+
+type Row<Id> = Vec<Pattern<Id>>;
+
+/// The outermost part of a pattern which is not a wildcard
+enum Head<'a, Id: 'a> {
+    Constructor(usize, &'a Constructor<Id>),
+    Tuple(usize),
+    Literal(&'a Literal),
+}
+
+// Implemented manually as deriving would require `Id: Copy`
+impl<'a, Id> Clone for Head<'a, Id> {
+    fn clone(&self) -> Head<'a, Id> {
+        *self
+    }
+}
+
+impl<'a, Id> Copy for Head<'a, Id> {}
+
+impl<'a, Id: Clone> Head<'a, Id> {
+    fn new(pattern: &'a Pattern<Id>) -> Option<Head<'a, Id>> {
+        match *pattern {
+            Pattern::Wildcard => None,
+            Pattern::Constructor { index, ref constructors, .. } => {
+                Some(Head::Constructor(index, &constructors[index]))
+            }
+            Pattern::Tuple(ref elems) => Some(Head::Tuple(elems.len())),
+            Pattern::Literal(ref literal) => Some(Head::Literal(literal)),
+        }
+    }
+
+    /// The number of sub-patterns a pattern with this head has
+    fn arity(&self) -> usize {
+        match *self {
+            Head::Constructor(_, constructor) => constructor.arity,
+            Head::Tuple(arity) => arity,
+            Head::Literal(_) => 0,
+        }
+    }
+
+    /// Returns the sub-patterns of `pattern` if it matches the values this head matches
+    fn args(&self, pattern: &Pattern<Id>) -> Option<Vec<Pattern<Id>>> {
+        match (*self, pattern) {
+            (_, &Pattern::Wildcard) => Some(vec![Pattern::Wildcard; self.arity()]),
+            (Head::Constructor(index, _), &Pattern::Constructor { index: i, ref args, .. })
+                if i == index => Some(args.clone()),
+            (Head::Tuple(_), &Pattern::Tuple(ref elems)) => Some(elems.clone()),
+            (Head::Literal(literal), &Pattern::Literal(ref l)) if l == literal => Some(vec![]),
+            _ => None,
+        }
+    }
+
+    fn witness(&self, args: Vec<Witness<Id>>) -> Witness<Id> {
+        match *self {
+            Head::Constructor(_, constructor) => {
+                Witness::Constructor(constructor.name.clone(), args)
+            }
+            Head::Tuple(_) => Witness::Tuple(args),
+            Head::Literal(_) => Witness::Wildcard,
+        }
+    }
+}
+
+/// Returns the rows which match the values `head` matches, with the first column replaced by the
+/// sub-patterns of `head`
+fn specialize<Id: Clone>(matrix: &[Row<Id>], head: Head<Id>) -> Vec<Row<Id>> {
+    matrix.iter()
+        .filter_map(|row| {
+            head.args(&row[0]).map(|mut new_row| {
+                new_row.extend(row[1..].iter().cloned());
+                new_row
+            })
+        })
+        .collect()
+}
+
+/// Returns the rows which match any value in the first column, with the first column removed
+fn default_matrix<Id: Clone>(matrix: &[Row<Id>]) -> Vec<Row<Id>> {
+    matrix.iter()
+        .filter(|row| match row[0] {
+            Pattern::Wildcard => true,
+            _ => false,
+        })
+        .map(|row| row[1..].to_owned())
+        .collect()
+}
+
+/// Returns every head of the type matched in the first column if the column uses all of them,
+/// otherwise returns an example of a value which no pattern in the column matches
+fn complete_heads<Id: Clone>(matrix: &[Row<Id>]) -> Result<Vec<Head<Id>>, Witness<Id>> {
+    let mut constructors = None;
+    let mut used = Vec::new();
+    for row in matrix {
+        match row[0] {
+            Pattern::Constructor { index, constructors: ref all, .. } => {
+                if constructors.is_none() {
+                    constructors = Some(all);
+                    used = vec![false; all.len()];
+                }
+                used[index] = true;
+            }
+            // Tuples have a single constructor so any tuple pattern uses all of them
+            Pattern::Tuple(ref elems) => return Ok(vec![Head::Tuple(elems.len())]),
+            Pattern::Wildcard | Pattern::Literal(_) => (),
+        }
+    }
+    match constructors {
+        Some(constructors) => {
+            match used.iter().position(|&used| !used) {
+                Some(index) => {
+                    let constructor = &constructors[index];
+                    Err(Witness::Constructor(constructor.name.clone(),
+                                             vec![Witness::Wildcard; constructor.arity]))
+                }
+                None => {
+                    Ok(constructors.iter()
+                        .enumerate()
+                        .map(|(index, constructor)| Head::Constructor(index, constructor))
+                        .collect())
+                }
+            }
+        }
+        // There are infinitely many literals so a column of literals can never be complete
+        None => Err(Witness::Wildcard),
+    }
+}
+
+/// Returns true if `row` matches a value which no row in `matrix` matches
+fn is_useful<Id: Clone>(matrix: &[Row<Id>], row: &[Pattern<Id>]) -> bool {
+    if row.is_empty() {
+        return matrix.is_empty();
+    }
+    match Head::new(&row[0]) {
+        Some(head) => {
+            let mut new_row = head.args(&row[0]).expect("Pattern matches its own head");
+            new_row.extend(row[1..].iter().cloned());
+            is_useful(&specialize(matrix, head), &new_row)
+        }
+        None => {
+            match complete_heads(matrix) {
+                Ok(heads) => {
+                    heads.into_iter().any(|head| {
+                        let mut new_row = vec![Pattern::Wildcard; head.arity()];
+                        new_row.extend(row[1..].iter().cloned());
+                        is_useful(&specialize(matrix, head), &new_row)
+                    })
+                }
+                Err(_) => is_useful(&default_matrix(matrix), &row[1..]),
+            }
+        }
+    }
+}
+
+/// Returns `width` values which are not matched by any row in `matrix` (one for each column)
+fn missing<Id: Clone>(matrix: &[Row<Id>], width: usize) -> Option<Vec<Witness<Id>>> {
+    if width == 0 {
+        return if matrix.is_empty() { Some(vec![]) } else { None };
+    }
+    match complete_heads(matrix) {
+        Ok(heads) => {
+            for head in heads {
+                let arity = head.arity();
+                if let Some(mut args) = missing(&specialize(matrix, head), arity + width - 1) {
+                    let rest = args.split_off(arity);
+                    let mut witnesses = vec![head.witness(args)];
+                    witnesses.extend(rest);
+                    return Some(witnesses);
+                }
+            }
+            None
+        }
+        Err(first) => {
+            missing(&default_matrix(matrix), width - 1).map(|rest| {
+                let mut witnesses = vec![first];
+                witnesses.extend(rest);
+                witnesses
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::ast::Literal;
+
+    fn option() -> Vec<Constructor<&'static str>> {
+        vec![Constructor {
+                 name: "None",
+                 arity: 0,
+             },
+             Constructor {
+                 name: "Some",
+                 arity: 1,
+             }]
+    }
+
+    fn none() -> Pattern<&'static str> {
+        Pattern::Constructor {
+            index: 0,
+            constructors: option(),
+            args: vec![],
+        }
+    }
+
+    fn some(arg: Pattern<&'static str>) -> Pattern<&'static str> {
+        Pattern::Constructor {
+            index: 1,
+            constructors: option(),
+            args: vec![arg],
+        }
+    }
+
+    fn alternatives(patterns: Vec<Pattern<&'static str>>) -> Vec<Alternative<&'static str>> {
+        patterns.into_iter()
+            .map(|pattern| {
+                Alternative {
+                    pattern: pattern,
+                    guarded: false,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exhaustive() {
+        let analysis = analyze(&alternatives(vec![some(Pattern::Wildcard), none()]));
+        assert_eq!(analysis.unreachable, Vec::<usize>::new());
+        assert_eq!(analysis.missing, None);
+    }
+
+    #[test]
+    fn missing_constructor() {
+        let analysis = analyze(&alternatives(vec![some(Pattern::Wildcard)]));
+        assert_eq!(analysis.missing.map(|w| w.to_string()),
+                   Some("None".to_string()));
+    }
+
+    #[test]
+    fn nested_missing_constructor() {
+        let analysis = analyze(&alternatives(vec![some(none()), none()]));
+        assert_eq!(analysis.missing.map(|w| w.to_string()),
+                   Some("Some (Some _)".to_string()));
+    }
+
+    #[test]
+    fn unreachable_alternatives() {
+        let analysis = analyze(&alternatives(vec![Pattern::Wildcard,
+                                                  none(),
+                                                  some(none()),
+                                                  some(Pattern::Wildcard)]));
+        assert_eq!(analysis.unreachable, vec![1, 2, 3]);
+        let analysis = analyze(&alternatives(vec![none(), some(Pattern::Wildcard), none()]));
+        assert_eq!(analysis.unreachable, vec![2]);
+    }
+
+    #[test]
+    fn tuples() {
+        let pair = |a, b| Pattern::Tuple(vec![a, b]);
+        let analysis = analyze(&alternatives(vec![pair(none(), Pattern::Wildcard),
+                                                  pair(Pattern::Wildcard, none())]));
+        assert_eq!(analysis.missing.map(|w| w.to_string()),
+                   Some("(Some _, Some _)".to_string()));
+    }
+
+    #[test]
+    fn literals_are_never_exhaustive() {
+        let analysis = analyze(&alternatives(vec![some(Pattern::Literal(Literal::Int(1))),
+                                                  some(Pattern::Literal(Literal::Int(1))),
+                                                  none()]));
+        assert_eq!(analysis.unreachable, vec![1]);
+        assert_eq!(analysis.missing.map(|w| w.to_string()),
+                   Some("Some _".to_string()));
+    }
+
+    #[test]
+    fn guarded_alternatives_do_not_cover_values() {
+        let mut alts = alternatives(vec![none(), some(Pattern::Wildcard)]);
+        alts[1].guarded = true;
+        let analysis = analyze(&alts);
+        assert_eq!(analysis.unreachable, Vec::<usize>::new());
+        assert_eq!(analysis.missing.map(|w| w.to_string()),
+                   Some("Some _".to_string()));
+    }
+}
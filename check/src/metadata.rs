@@ -2,6 +2,7 @@ use std::collections::BTreeMap;
 
 use base::ast::{self, Expr, Pattern, SpannedExpr, SpannedPattern, ValueBinding};
 use base::ast::MutVisitor;
+use base::fnv::FnvMap;
 use base::metadata::{Metadata, MetadataEnv};
 use base::scoped_map::ScopedMap;
 use base::symbol::{Name, Symbol};
@@ -13,8 +14,17 @@ struct Environment<'b> {
 
 /// Queries `expr` for the metadata which it contains.
 pub fn metadata(env: &MetadataEnv, expr: &mut SpannedExpr<Symbol>) -> Metadata {
+    metadata_with_bindings(env, expr).0
+}
+
+/// Queries `expr` for the metadata which it contains and the metadata of every variable which is
+/// bound inside `expr`.
+pub fn metadata_with_bindings(env: &MetadataEnv,
+                              expr: &mut SpannedExpr<Symbol>)
+                              -> (Metadata, FnvMap<Symbol, Metadata>) {
     struct MetadataVisitor<'b> {
         env: Environment<'b>,
+        bindings: FnvMap<Symbol, Metadata>,
     }
 
     impl<'b> MetadataVisitor<'b> {
@@ -48,13 +58,17 @@ pub fn metadata(env: &MetadataEnv, expr: &mut SpannedExpr<Symbol>) -> Metadata {
                 Pattern::Ident(ref mut id) => {
                     self.stack_var(id.name.clone(), metadata);
                 }
-                Pattern::Constructor(..) => (),
+                Pattern::Constructor(..) |
+                Pattern::Tuple { .. } |
+                Pattern::Literal(_) |
+                Pattern::Wildcard(_) => (),
             }
         }
 
         fn stack_var(&mut self, id: Symbol, metadata: Metadata) {
             if metadata.has_data() {
                 debug!("Insert {}", id);
+                self.bindings.insert(id.clone(), metadata.clone());
                 self.env.stack.insert(id, metadata);
             }
         }
@@ -72,6 +86,10 @@ pub fn metadata(env: &MetadataEnv, expr: &mut SpannedExpr<Symbol>) -> Metadata {
                 Expr::Ident(ref mut id) => {
                     self.metadata(&id.name).cloned().unwrap_or_else(Metadata::default)
                 }
+                Expr::Projection(ref mut expr, ref field, _) => {
+                    let mut metadata = self.metadata_expr(expr);
+                    metadata.module.remove(field.as_ref()).unwrap_or_else(Metadata::default)
+                }
                 Expr::Record { ref mut exprs, ref mut types, .. } => {
                     let mut module = BTreeMap::new();
                     for &mut (ref id, ref mut maybe_expr) in exprs {
@@ -158,6 +176,8 @@ pub fn metadata(env: &MetadataEnv, expr: &mut SpannedExpr<Symbol>) -> Metadata {
             env: env,
             stack: ScopedMap::new(),
         },
+        bindings: FnvMap::default(),
     };
-    visitor.metadata_expr(expr)
+    let metadata = visitor.metadata_expr(expr);
+    (metadata, visitor.bindings)
 }
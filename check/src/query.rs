@@ -0,0 +1,383 @@
+//! Queries for where the symbols in a typechecked AST are bound and where they are used
+
+use base::ast::{self, Expr, Pattern, SpannedExpr, SpannedPattern, TypeBinding, Visitor};
+use base::fnv::FnvMap;
+use base::pos::{BytePos, Span};
+use base::symbol::Symbol;
+use base::types::Type;
+
+/// Limits how many bindings and modules are followed when looking for a definition so that
+/// cyclic definitions do not cause an infinite loop
+const MAX_DEPTH: usize = 32;
+
+/// Trait for looking up the typechecked expression of an imported module
+pub trait ModuleEnv {
+    fn find_module(&self, module: &str) -> Option<&SpannedExpr<Symbol>>;
+}
+
+impl ModuleEnv for FnvMap<String, SpannedExpr<Symbol>> {
+    fn find_module(&self, module: &str) -> Option<&SpannedExpr<Symbol>> {
+        self.get(module)
+    }
+}
+
+impl ModuleEnv for () {
+    fn find_module(&self, _module: &str) -> Option<&SpannedExpr<Symbol>> {
+        None
+    }
+}
+
+/// A span in the source of a module
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub source_name: String,
+    pub span: Span<BytePos>,
+}
+
+/// What the cursor points at
+enum Target {
+    Symbol(Symbol),
+    /// A field which is accessed on the record bound to the symbol
+    Field(Symbol, Symbol),
+}
+
+/// Spans are empty for code which does not come from the source, such as the implicit prelude
+fn contains(span: Span<BytePos>, pos: BytePos) -> bool {
+    span.start != span.end && span.start <= pos && pos <= span.end
+}
+
+/// Returns true for identifiers which were inserted by the renamer for record fields written
+/// without a value (`{ x }`) as those do not have a span of their own
+fn is_field_shorthand(record: &SpannedExpr<Symbol>, field: &SpannedExpr<Symbol>) -> bool {
+    field.span == record.span
+}
+
+struct FindTarget {
+    pos: BytePos,
+    target: Option<Target>,
+}
+
+impl Visitor for FindTarget {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        match expr.value {
+            Expr::Ident(ref id) if contains(expr.span, self.pos) => {
+                self.target = Some(Target::Symbol(id.name.clone()));
+            }
+            Expr::Infix(ref l, ref op, ref r) if l.span.end < self.pos &&
+                                                  self.pos < r.span.start => {
+                self.target = Some(Target::Symbol(op.name.clone()));
+            }
+            Expr::Projection(ref record, ref field, _) if record.span.end < self.pos &&
+                                                           contains(expr.span, self.pos) => {
+                if let Expr::Ident(ref id) = record.value {
+                    self.target = Some(Target::Field(id.name.clone(), field.clone()));
+                }
+            }
+            Expr::Record { ref exprs, .. } => {
+                for field in exprs.iter().filter_map(|field| field.1.as_ref()) {
+                    if !is_field_shorthand(expr, field) {
+                        self.visit_expr(field);
+                    }
+                }
+                return;
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &SpannedPattern<Symbol>) {
+        if let Pattern::Ident(ref id) = pattern.value {
+            if contains(pattern.span, self.pos) {
+                self.target = Some(Target::Symbol(id.name.clone()));
+            }
+        }
+        ast::walk_pattern(self, &pattern.value);
+    }
+}
+
+fn find_target(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Option<Target> {
+    let mut visitor = FindTarget {
+        pos: pos,
+        target: None,
+    };
+    visitor.visit_expr(expr);
+    visitor.target
+}
+
+/// Returns the symbol of the identifier, operator or binding at `pos`
+pub fn symbol_at(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Option<Symbol> {
+    match find_target(expr, pos) {
+        Some(Target::Symbol(symbol)) => Some(symbol),
+        _ => None,
+    }
+}
+
+/// Visitor which finds where `symbol` is bound
+struct FindBinding<'s> {
+    symbol: &'s Symbol,
+    span: Option<Span<BytePos>>,
+    /// Set if `symbol` is bound to a field of the record in a variable, as in `let { x } = r` or
+    /// `let x = r.x`
+    field_of: Option<(Symbol, Symbol)>,
+    /// Set if `symbol` is bound directly to another variable, as in `let x = y`
+    alias_of: Option<Symbol>,
+}
+
+impl<'s> Visitor for FindBinding<'s> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        // Arguments do not have a span of their own so the function is used instead
+        match expr.value {
+            Expr::LetBindings(ref bindings, _) => {
+                for bind in bindings {
+                    if bind.args.iter().any(|arg| arg.name == *self.symbol) {
+                        self.span = Some(bind.name.span);
+                    }
+                    match (&bind.name.value, &bind.expr.value) {
+                        (&Pattern::Record { ref fields, .. }, &Expr::Ident(ref record)) => {
+                            let field = fields.iter().find(|&&(ref field, ref alias)| {
+                                alias.as_ref().unwrap_or(field) == self.symbol
+                            });
+                            if let Some(&(ref field, _)) = field {
+                                self.field_of = Some((record.name.clone(), field.clone()));
+                            }
+                        }
+                        (&Pattern::Ident(ref id), &Expr::Projection(ref record, ref field, _))
+                            if id.name == *self.symbol && bind.args.is_empty() => {
+                            if let Expr::Ident(ref record) = record.value {
+                                self.field_of = Some((record.name.clone(), field.clone()));
+                            }
+                        }
+                        (&Pattern::Ident(ref id), &Expr::Ident(ref other))
+                            if id.name == *self.symbol && bind.args.is_empty() => {
+                            self.alias_of = Some(other.name.clone());
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Expr::TypeBindings(ref bindings, ref body) => {
+                let binds_symbol = |bind: &TypeBinding<Symbol>| {
+                    bind.name == *self.symbol ||
+                    match bind.alias.typ.as_ref().map(|typ| &**typ) {
+                        Some(&Type::Variants(ref variants)) => {
+                            variants.iter().any(|variant| variant.0 == *self.symbol)
+                        }
+                        _ => false,
+                    }
+                };
+                if bindings.iter().any(binds_symbol) {
+                    self.span = Some(Span {
+                        start: expr.span.start,
+                        end: body.span.start,
+                    });
+                }
+            }
+            Expr::Lambda(ref lambda) => {
+                if lambda.args.iter().any(|arg| arg.name == *self.symbol) {
+                    self.span = Some(expr.span);
+                }
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &SpannedPattern<Symbol>) {
+        let binds_symbol = match pattern.value {
+            Pattern::Ident(ref id) => id.name == *self.symbol,
+            Pattern::Record { ref types, ref fields, .. } => {
+                fields.iter()
+                    .chain(types)
+                    .any(|&(ref field, ref alias)| alias.as_ref().unwrap_or(field) == self.symbol)
+            }
+            _ => false,
+        };
+        if binds_symbol {
+            self.span = Some(pattern.span);
+        }
+        ast::walk_pattern(self, &pattern.value);
+    }
+}
+
+fn find_binding<'s>(expr: &SpannedExpr<Symbol>, symbol: &'s Symbol) -> FindBinding<'s> {
+    let mut visitor = FindBinding {
+        symbol: symbol,
+        span: None,
+        field_of: None,
+        alias_of: None,
+    };
+    visitor.visit_expr(expr);
+    visitor
+}
+
+/// Returns the name of the module which the variable `symbol` refers to
+fn module_of<M>(modules: &M,
+                expr: &SpannedExpr<Symbol>,
+                symbol: &Symbol,
+                depth: usize)
+                -> Option<String>
+    where M: ?Sized + ModuleEnv,
+{
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let binding = find_binding(expr, symbol);
+    if binding.span.is_some() {
+        binding.alias_of.and_then(|alias| module_of(modules, expr, &alias, depth + 1))
+    } else if modules.find_module(symbol.as_ref()).is_some() {
+        // Imports are expanded into a global variable with the name of the module
+        Some(symbol.as_ref().into())
+    } else {
+        None
+    }
+}
+
+/// Returns where the field `field` of the record exported by `module` is defined
+fn field_definition<M>(modules: &M, module: &str, field: &str, depth: usize) -> Option<Location>
+    where M: ?Sized + ModuleEnv,
+{
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let module_expr = match modules.find_module(module) {
+        Some(module_expr) => module_expr,
+        None => return None,
+    };
+    let mut exported = module_expr;
+    loop {
+        match exported.value {
+            Expr::LetBindings(_, ref body) |
+            Expr::TypeBindings(_, ref body) => exported = body,
+            _ => break,
+        }
+    }
+    match exported.value {
+        Expr::Record { ref types, ref exprs, .. } => {
+            let value = exprs.iter()
+                .find(|&&(ref name, _)| name.declared_name() == field)
+                .and_then(|&(_, ref value)| value.as_ref());
+            if let Some(value) = value {
+                return match value.value {
+                    Expr::Ident(ref id) => {
+                        resolve(modules, module, module_expr, &id.name, depth + 1)
+                    }
+                    _ => {
+                        Some(Location {
+                            source_name: module.into(),
+                            span: value.span,
+                        })
+                    }
+                };
+            }
+            types.iter()
+                .find(|&&(ref name, _)| name.declared_name() == field)
+                .and_then(|&(ref name, _)| resolve(modules, module, module_expr, name, depth + 1))
+        }
+        Expr::Ident(ref id) => {
+            module_of(modules, module_expr, &id.name, depth + 1)
+                .and_then(|module| field_definition(modules, &module, field, depth + 1))
+        }
+        _ => None,
+    }
+}
+
+/// Returns where `symbol` is bound, following bindings which destructure imported modules
+fn resolve<M>(modules: &M,
+              source_name: &str,
+              expr: &SpannedExpr<Symbol>,
+              symbol: &Symbol,
+              depth: usize)
+              -> Option<Location>
+    where M: ?Sized + ModuleEnv,
+{
+    let binding = find_binding(expr, symbol);
+    match binding.span {
+        Some(span) => {
+            let imported = binding.field_of.and_then(|(record, field)| {
+                module_of(modules, expr, &record, depth + 1).and_then(|module| {
+                    field_definition(modules, &module, field.declared_name(), depth + 1)
+                })
+            });
+            Some(imported.unwrap_or_else(|| {
+                Location {
+                    source_name: source_name.into(),
+                    span: span,
+                }
+            }))
+        }
+        None => {
+            modules.find_module(symbol.as_ref()).map(|module_expr| {
+                Location {
+                    source_name: symbol.as_ref().into(),
+                    span: module_expr.span,
+                }
+            })
+        }
+    }
+}
+
+/// Returns where the identifier or field at `pos` is defined. `expr` is the expression of the
+/// module `source_name` and `modules` is used to find definitions in imported modules.
+pub fn definition<M>(modules: &M,
+                     source_name: &str,
+                     expr: &SpannedExpr<Symbol>,
+                     pos: BytePos)
+                     -> Option<Location>
+    where M: ?Sized + ModuleEnv,
+{
+    match find_target(expr, pos) {
+        Some(Target::Symbol(symbol)) => resolve(modules, source_name, expr, &symbol, 0),
+        Some(Target::Field(record, field)) => {
+            module_of(modules, expr, &record, 0)
+                .and_then(|module| field_definition(modules, &module, field.declared_name(), 0))
+        }
+        None => None,
+    }
+}
+
+struct FindReferences<'s> {
+    symbol: &'s Symbol,
+    spans: Vec<Span<BytePos>>,
+}
+
+impl<'s> Visitor for FindReferences<'s> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
+        match expr.value {
+            Expr::Ident(ref id) if id.name == *self.symbol => self.spans.push(expr.span),
+            Expr::Record { ref exprs, .. } => {
+                for field in exprs.iter().filter_map(|field| field.1.as_ref()) {
+                    if !is_field_shorthand(expr, field) {
+                        self.visit_expr(field);
+                    }
+                }
+                return;
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr);
+    }
+}
+
+/// Returns the spans of all identifiers which refer to the same binding as the identifier at
+/// `pos`. Operators used infix and fields in records written as `{ x }` do not have spans of
+/// their own and are not included.
+pub fn references(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Vec<Span<BytePos>> {
+    match symbol_at(expr, pos) {
+        Some(symbol) => {
+            let mut visitor = FindReferences {
+                symbol: &symbol,
+                spans: Vec::new(),
+            };
+            visitor.visit_expr(expr);
+            visitor.spans
+        }
+        None => Vec::new(),
+    }
+}
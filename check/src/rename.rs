@@ -21,6 +21,15 @@ pub enum RenameError {
     },
 }
 
+impl RenameError {
+    /// Returns the stable code of the error which can be passed to `explain::explain`
+    pub fn code(&self) -> &'static str {
+        match *self {
+            RenameError::NoMatchingType { .. } => "E0300",
+        }
+    }
+}
+
 impl fmt::Display for RenameError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -115,15 +124,15 @@ pub fn rename(symbols: &mut SymbolModule,
                     let new_name = self.stack_var(id.name.clone(), pattern.span, id.typ.clone());
                     id.name = new_name;
                 }
-                ast::Pattern::Constructor(ref mut id, ref mut args) => {
-                    let typ = self.env
-                        .find_type(&id.name)
-                        .expect("ICE: Expected constructor")
-                        .clone();
-                    for (arg_type, arg) in types::arg_iter(&typ).zip(args) {
-                        arg.name = self.stack_var(arg.name.clone(), pattern.span, arg_type.clone());
+                ast::Pattern::Constructor(_, ref mut args) |
+                ast::Pattern::Tuple { elems: ref mut args, .. } => {
+                    for arg in args {
+                        let arg_type = arg.env_type_of(&self.env);
+                        self.new_pattern(&arg_type, arg);
                     }
                 }
+                ast::Pattern::Literal(_) |
+                ast::Pattern::Wildcard(_) => (),
             }
         }
 
@@ -234,6 +243,9 @@ pub fn rename(symbols: &mut SymbolModule,
                         self.env.stack.enter_scope();
                         let typ = expr.env_type_of(&self.env);
                         self.new_pattern(&typ, &mut alt.pattern);
+                        if let Some(ref mut guard) = alt.guard {
+                            self.visit_expr(guard);
+                        }
                         self.visit_expr(&mut alt.expr);
                         self.env.stack.exit_scope();
                         self.env.stack_types.exit_scope();
@@ -1,20 +1,25 @@
 //! The main typechecking interface which is responsible for typechecking expressions, patterns,
 //! etc. Only checks which need to be aware of expressions are handled here the actual unifying and
 //! checking of types are done in the `unify_type` and `kindcheck` modules.
+use std::cmp;
 use std::fmt;
 use std::mem;
+use std::rc::Rc;
 
 use base::scoped_map::ScopedMap;
-use base::ast::{DisplayEnv, Expr, Literal, MutVisitor, Pattern, SpannedExpr};
+use base::ast::{self, Alternative, DisplayEnv, Expr, Literal, MutVisitor, Pattern, SpannedExpr};
 use base::ast::{SpannedPattern, TypeBinding, Typed, TypedIdent, ValueBinding};
+use base::diagnostic::{AsDiagnostic, Diagnostic};
 use base::error::Errors;
-use base::fnv::FnvSet;
+use base::fnv::{FnvMap, FnvSet};
 use base::instantiate::{self, Instantiator};
-use base::pos::{BytePos, Span, Spanned};
-use base::symbol::{Symbol, SymbolRef, SymbolModule, Symbols};
+use base::pos::{self, BytePos, Span, Spanned};
+use base::symbol::{Name, Symbol, SymbolRef, SymbolModule, Symbols};
 use base::types::{self, ArcType, Field, ArcKind, Type, Generic, Kind, merge};
 use base::types::{KindEnv, TypeEnv, PrimitiveEnv, Alias, AliasData, TypeVariable};
 use kindcheck::{self, KindCheck};
+use lint::{self, Lint, LintLevel};
+use matching::{self, Witness};
 use substitution::Substitution;
 use unify::Error as UnifyError;
 use unify;
@@ -35,8 +40,9 @@ pub enum TypeError<I> {
     UndefinedField(ArcType<I>, I),
     /// Constructor type was found in a pattern but did not have the expected number of arguments
     PatternError(ArcType<I>, usize),
-    /// Errors found when trying to unify two types
-    Unification(ArcType<I>, ArcType<I>, Vec<unify_type::Error<I>>),
+    /// Errors found when trying to unify two types. The span is the type annotation which the
+    /// expected type came from, if any.
+    Unification(ArcType<I>, ArcType<I>, Vec<unify_type::Error<I>>, Option<Span<BytePos>>),
     /// Error were found when trying to unify the kinds of two types
     KindError(kindcheck::Error<I>),
     /// Errors found during renaming (overload resolution)
@@ -51,6 +57,28 @@ pub enum TypeError<I> {
     UndefinedRecord { fields: Vec<I> },
     /// Found a case expression without any alternatives
     EmptyCase,
+    /// The alternatives of a `match` expression do not match every value. The witness is an
+    /// example of a value which is not matched.
+    NonExhaustiveMatch(Witness<I>),
+    /// An alternative of a `match` expression can never be selected as every value it matches
+    /// is matched by an earlier alternative
+    UnreachableAlternative,
+    /// No binding in scope has the type of an implicit argument
+    NoImplicitArgument(ArcType<I>),
+    /// More than one binding in scope has the type of an implicit argument
+    AmbiguousImplicitArgument(ArcType<I>, Vec<I>),
+    /// A constructor declared as `| C : T` does not return the type it is declared in
+    InvalidConstructorType(I, I, ArcType<I>),
+    /// A `let` binding is never used
+    UnusedVariable(I),
+    /// A binding of an imported module is never used
+    UnusedImport(I),
+    /// A binding hides the earlier binding of the same name at the span
+    ShadowedVariable(I, Span<BytePos>),
+    /// A type parameter or `forall` variable does not occur in its type
+    UnusedTypeVariable(I),
+    /// A variable bound in the pattern of a `match` alternative is never used
+    UnusedMatchBinder(I),
 }
 
 impl<I> From<kindcheck::Error<I>> for TypeError<I>
@@ -70,6 +98,37 @@ impl<I> From<::rename::RenameError> for TypeError<I> {
     }
 }
 
+impl<I> TypeError<I> {
+    /// Returns the stable code of the error which can be passed to `explain::explain`
+    pub fn code(&self) -> &'static str {
+        match *self {
+            UndefinedVariable(..) => "E0001",
+            NotAFunction(..) => "E0002",
+            UndefinedType(..) => "E0003",
+            UndefinedField(..) => "E0004",
+            PatternError(..) => "E0005",
+            Unification(..) => "E0006",
+            DuplicateTypeDefinition(..) => "E0007",
+            DuplicateField(..) => "E0008",
+            InvalidProjection(..) => "E0009",
+            UndefinedRecord { .. } => "E0010",
+            EmptyCase => "E0011",
+            NonExhaustiveMatch(..) => "E0012",
+            UnreachableAlternative => "E0013",
+            NoImplicitArgument(..) => "E0014",
+            AmbiguousImplicitArgument(..) => "E0015",
+            InvalidConstructorType(..) => "E0016",
+            UnusedVariable(..) => "E0017",
+            UnusedImport(..) => "E0018",
+            ShadowedVariable(..) => "E0019",
+            UnusedTypeVariable(..) => "E0020",
+            UnusedMatchBinder(..) => "E0021",
+            KindError(ref err) => kindcheck::error_code(err),
+            Rename(ref err) => err.code(),
+        }
+    }
+}
+
 impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::TypeError::*;
@@ -80,7 +139,7 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
             UndefinedField(ref typ, ref field) => {
                 write!(f, "Type `{}` does not have the field `{}`", typ, field)
             }
-            Unification(ref expected, ref actual, ref errors) => {
+            Unification(ref expected, ref actual, ref errors, _) => {
                 try!(writeln!(f,
                               "Expected the following types to be equal\nExpected: {}\nFound: \
                                {}\n{} errors were found during unification:",
@@ -122,12 +181,90 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
                 Ok(())
             }
             EmptyCase => write!(f, "`case` expression with no alternatives"),
+            NonExhaustiveMatch(ref witness) => {
+                write!(f,
+                       "Non-exhaustive patterns in `match` expression, `{}` is not matched",
+                       witness)
+            }
+            UnreachableAlternative => write!(f, "Unreachable alternative in `match` expression"),
+            NoImplicitArgument(ref typ) => {
+                write!(f,
+                       "No implicit argument of type `{}` could be found in scope",
+                       typ)
+            }
+            AmbiguousImplicitArgument(ref typ, ref candidates) => {
+                try!(write!(f,
+                            "Multiple implicit arguments of type `{}` are in scope: ",
+                            typ));
+                try!(write!(f, "`{}`", candidates[0]));
+                for candidate in &candidates[1..] {
+                    try!(write!(f, ", `{}`", candidate));
+                }
+                Ok(())
+            }
+            InvalidConstructorType(ref constructor, ref typ, ref found) => {
+                write!(f,
+                       "The constructor `{}` must return the type `{}` but it returns `{}`",
+                       constructor,
+                       typ,
+                       found)
+            }
+            UnusedVariable(ref name) => write!(f, "Unused variable `{}`", name),
+            UnusedImport(ref name) => write!(f, "Unused import `{}`", name),
+            ShadowedVariable(ref name, _) => {
+                write!(f, "`{}` shadows an earlier binding of the same name", name)
+            }
+            UnusedTypeVariable(ref name) => write!(f, "Unused type variable `{}`", name),
+            UnusedMatchBinder(ref name) => {
+                write!(f, "Unused variable `{}` in `match` alternative", name)
+            }
+        }
+    }
+}
+
+impl<I: fmt::Display + AsRef<str>> AsDiagnostic for TypeError<I> {
+    fn as_diagnostic(&self, span: Span<BytePos>) -> Diagnostic {
+        match *self {
+            Unification(ref expected, ref actual, ref errors, annotation) => {
+                let mut diagnostic = Diagnostic::new("Expected the following types to be equal")
+                    .code(self.code())
+                    .primary(span, "")
+                    .note(format!("Expected: {}", expected))
+                    .note(format!("Found: {}", actual));
+                if let Some(annotation) = annotation {
+                    diagnostic = diagnostic.secondary(annotation,
+                                                      "expected because of this annotation");
+                }
+                for error in errors {
+                    diagnostic = diagnostic.note(format!("[{}] {}",
+                                                         unify_type::error_code(error),
+                                                         error));
+                }
+                diagnostic
+            }
+            ShadowedVariable(_, previous) => {
+                Diagnostic::new(self.to_string())
+                    .code(self.code())
+                    .primary(span, "")
+                    .secondary(previous, "previous binding")
+            }
+            _ => Diagnostic::new(self.to_string()).code(self.code()).primary(span, ""),
         }
     }
 }
 
 pub type SpannedTypeError<Id> = Spanned<TypeError<Id>, BytePos>;
 
+/// How `match` expressions which do not match every value or which have alternatives that can
+/// never be selected are reported
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MatchCheck {
+    /// Report them as warnings which do not cause typechecking to fail (the default)
+    Warn,
+    /// Report them as errors
+    Deny,
+}
+
 type TcResult<T> = Result<T, TypeError<Symbol>>;
 
 struct Environment<'a> {
@@ -204,6 +341,53 @@ enum TailCall {
     TailCall,
 }
 
+/// How many implicit arguments may be resolved to find a single implicit argument, stops bindings
+/// such as `let eq x : [Eq a] -> Eq a = x` from being resolved forever
+const IMPLICIT_DEPTH_LIMIT: u32 = 32;
+
+/// An implicit argument passed to a function which takes implicit arguments. The argument is
+/// inserted into the AST as an identifier named `placeholder` which is replaced with a binding of
+/// the same type once the type of the argument is known.
+struct ImplicitArg {
+    span: Span<BytePos>,
+    placeholder: Symbol,
+    typ: ArcType,
+    /// The bindings which were in scope where the function was used
+    candidates: ImplicitBindings,
+    /// How many implicit arguments were resolved to reach this argument
+    depth: u32,
+}
+
+/// A binding which may be passed as an implicit argument, followed by the bindings which were in
+/// scope before it. The list is shared by every implicit argument which is created while the
+/// binding is in scope so that creating an argument does not need to copy the scope.
+struct ImplicitBinding {
+    name: Symbol,
+    typ: ArcType,
+    next: ImplicitBindings,
+}
+
+type ImplicitBindings = Option<Rc<ImplicitBinding>>;
+
+/// A local type equality introduced by matching on a constructor which returns a more specific
+/// type than the matched value, such as `a = Int` when `IntLit : Int -> Expr Int` is matched
+/// against a value of type `Expr a`. It only holds inside the alternative of the pattern.
+#[derive(Clone)]
+struct Refinement {
+    variable: ArcType,
+    typ: ArcType,
+}
+
+/// The type of an alternative which was checked under refinements. It is compared with the type
+/// of its `match` expression once the enclosing binding has been checked, as the refined
+/// variables are not known before that.
+struct RefinedAlternative {
+    span: Span<BytePos>,
+    refinements: Vec<Refinement>,
+    expected: ArcType,
+    actual: ArcType,
+}
+
 /// Struct which provides methods to typecheck expressions.
 pub struct Typecheck<'a> {
     environment: Environment<'a>,
@@ -214,8 +398,30 @@ pub struct Typecheck<'a> {
     subs: Substitution<ArcType>,
     inst: Instantiator,
     errors: Errors<SpannedTypeError<Symbol>>,
+    /// Problems which do not cause typechecking to fail
+    warnings: Errors<SpannedTypeError<Symbol>>,
+    match_check: MatchCheck,
+    /// How the problems found by each lint are reported, lints which are not in the map are
+    /// reported as warnings
+    lint_levels: FnvMap<Lint, LintLevel>,
     /// Type variables `let test: a -> b` (`a` and `b`)
     type_variables: ScopedMap<Symbol, ArcType>,
+    /// Implicit arguments which have not been resolved yet
+    implicit_args: Vec<ImplicitArg>,
+    /// Placeholders for the implicit arguments of the expression which is currently checked. They
+    /// are applied to the expression once it has been checked.
+    new_implicit_args: Vec<SpannedExpr<Symbol>>,
+    /// Number of implicit arguments created, used to give each placeholder a unique name
+    implicit_count: u32,
+    /// The bindings in scope which may be passed as implicit arguments, along with the bindings
+    /// which hide them, the most recent binding first
+    implicit_bindings: ImplicitBindings,
+    /// `implicit_bindings` as it was when each of the current scopes was entered
+    implicit_scopes: Vec<ImplicitBindings>,
+    /// Refinements of the `match` alternatives which are currently checked
+    refinements: Vec<Refinement>,
+    /// Alternatives which still need to be checked against the type of their `match`
+    refined_alternatives: Vec<RefinedAlternative>,
 }
 
 /// Error returned when unsuccessfully typechecking an expression
@@ -239,10 +445,36 @@ impl<'a> Typecheck<'a> {
             subs: Substitution::new(),
             inst: Instantiator::new(),
             errors: Errors::new(),
+            warnings: Errors::new(),
+            match_check: MatchCheck::Warn,
+            lint_levels: FnvMap::default(),
             type_variables: ScopedMap::new(),
+            implicit_args: Vec::new(),
+            new_implicit_args: Vec::new(),
+            implicit_count: 0,
+            implicit_bindings: None,
+            implicit_scopes: Vec::new(),
+            refinements: Vec::new(),
+            refined_alternatives: Vec::new(),
         }
     }
 
+    /// Sets whether problems found in the alternatives of `match` expressions are reported as
+    /// warnings or as errors
+    pub fn set_match_check(&mut self, match_check: MatchCheck) {
+        self.match_check = match_check;
+    }
+
+    /// Sets how the problems found by `lint` are reported (default: `LintLevel::Warn`)
+    pub fn set_lint_level(&mut self, lint: Lint, level: LintLevel) {
+        self.lint_levels.insert(lint, level);
+    }
+
+    /// Returns the warnings found since the last call to this function
+    pub fn take_warnings(&mut self) -> Error {
+        mem::replace(&mut self.warnings, Errors::new())
+    }
+
     fn error(&mut self, span: Span<BytePos>, error: TypeError<Symbol>) -> ArcType {
         self.errors.error(Spanned {
             span: span,
@@ -272,6 +504,7 @@ impl<'a> Typecheck<'a> {
             .map(|typ| {
                 let typ = subs.set_type(typ.clone());
                 let typ = inst.instantiate(&typ, |_| subs.new_var());
+                let typ = unify_type::instantiate_forall(subs, typ);
                 debug!("Find {} : {}",
                        symbols.string(id),
                        types::display_type(symbols, &typ));
@@ -292,9 +525,28 @@ impl<'a> Typecheck<'a> {
     }
 
     fn stack_var(&mut self, id: Symbol, typ: ArcType) {
+        self.add_implicit_binding(&id, &typ);
         self.environment.stack.insert(id, typ);
     }
 
+    /// Makes `id` available as an implicit argument if `typ` can be the type of one. Must be
+    /// called before `id` is given the type `typ` on the stack, as a binding which hides an
+    /// implicit argument with the same name is added as well.
+    fn add_implicit_binding(&mut self, id: &Symbol, typ: &ArcType) {
+        let hides_implicit = self.environment
+            .stack
+            .get(id)
+            .map_or(false, |previous| implicit_head(previous).is_some());
+        if hides_implicit || implicit_head(typ).is_some() {
+            let next = self.implicit_bindings.take();
+            self.implicit_bindings = Some(Rc::new(ImplicitBinding {
+                name: id.clone(),
+                typ: typ.clone(),
+                next: next,
+            }));
+        }
+    }
+
     fn stack_type(&mut self, id: Symbol, alias: &Alias<Symbol, ArcType>) {
         // Insert variant constructors into the local scope
         if let Some(ref real_type) = alias.typ {
@@ -322,12 +574,14 @@ impl<'a> Typecheck<'a> {
         self.environment.stack.enter_scope();
         self.environment.stack_types.enter_scope();
         self.original_symbols.enter_scope();
+        self.implicit_scopes.push(self.implicit_bindings.clone());
     }
 
     fn exit_scope(&mut self) {
         self.environment.stack.exit_scope();
         self.environment.stack_types.exit_scope();
         self.original_symbols.exit_scope();
+        self.implicit_bindings = self.implicit_scopes.pop().expect("Scope");
     }
 
     /// Generalizing updates all variables which are above `level` into "generic variables". A
@@ -391,12 +645,20 @@ impl<'a> Typecheck<'a> {
         }
         self.subs.clear();
         self.environment.stack.clear();
+        self.implicit_args.clear();
+        self.implicit_bindings = None;
+        self.implicit_scopes.clear();
+        self.refinements.clear();
+        self.refined_alternatives.clear();
 
         let mut typ = self.typecheck(expr);
         if let Some(expected) = expected_type {
             let expected = self.create_unifiable_signature(expected.clone());
-            typ = self.merge_signature(expr.span, 0, &expected, typ);
+            typ = self.merge_signature(expr.span, None, 0, &expected, typ);
         }
+        self.check_refined_alternatives(0);
+        let mut resolved = self.resolve_implicit_args(0, 0);
+        replace_implicit_args(&mut resolved, expr);
         typ = self.finish_type(0, &typ).unwrap_or(typ);
         typ = types::walk_move_type(typ, &mut unroll_typ);
         // Only the 'tail' expression need to be generalized at this point as all bindings
@@ -407,6 +669,12 @@ impl<'a> Typecheck<'a> {
         } else {
             match ::rename::rename(&mut self.symbols, &self.environment, expr) {
                 Ok(()) => {
+                    // The lints rely on every local binding having a unique name which is only
+                    // the case after renaming
+                    self.check_lints(expr);
+                    if self.errors.has_errors() {
+                        return Err(mem::replace(&mut self.errors, Errors::new()));
+                    }
                     debug!("Typecheck result: {}", typ);
                     Ok(typ)
                 }
@@ -423,6 +691,239 @@ impl<'a> Typecheck<'a> {
         }
     }
 
+    /// Reports the problems found by the lints in `expr` according to the level of each lint
+    fn check_lints(&mut self, expr: &SpannedExpr<Symbol>) {
+        for (lint, warning) in lint::lint(expr) {
+            match self.lint_levels.get(&lint).cloned().unwrap_or(LintLevel::Warn) {
+                LintLevel::Allow => (),
+                LintLevel::Warn => self.warnings.error(warning),
+                LintLevel::Deny => self.errors.error(warning),
+            }
+        }
+    }
+
+    /// Checks that `actual`, the type of a module which has been typechecked by this typechecker,
+    /// provides every type and value declared in the record type `signature`. `fields` holds the
+    /// name of each field of `signature`, spanning its location in the signature's source, and
+    /// errors are reported at the field they concern.
+    ///
+    /// Values must be at least as general as their declared type and types which are declared
+    /// with a definition (`Stack a = List a`) must be defined the same in the module. Types which
+    /// are declared without a definition (`Stack`) are made abstract so their representation is
+    /// hidden from users of the module. Returns the type of the module as seen through
+    /// `signature`, which only contains the declared fields in the order they are declared.
+    pub fn check_signature(&mut self,
+                           signature: &ArcType,
+                           fields: &[Spanned<Symbol, BytePos>],
+                           actual: &ArcType)
+                           -> Result<ArcType, Error> {
+        let field_span = |name: &Symbol| {
+            fields.iter()
+                .find(|field| field.value.name_eq(name))
+                .map_or(Span {
+                            start: BytePos::from(0),
+                            end: BytePos::from(0),
+                        },
+                        |field| field.span)
+        };
+        self.subs.clear();
+        let actual = self.remove_aliases(actual.clone());
+
+        // The types which the signature can refer to. Types declared in the module take
+        // precedence over types which only occur in the types of its values.
+        let mut aliases = FnvMap::default();
+        for field in actual.type_field_iter() {
+            aliases.insert(short_name(&field.typ.name), field.typ.clone());
+        }
+        for field in actual.field_iter() {
+            types::walk_type(&field.typ, |typ: &ArcType| {
+                if let Type::Alias(ref alias) = **typ {
+                    aliases.entry(short_name(&alias.name))
+                        .or_insert_with(|| Alias::from(alias.clone()));
+                }
+            });
+        }
+        let mut constructors = FnvMap::default();
+        for alias in aliases.values() {
+            if let Some(ref typ) = alias.typ {
+                if let Type::Variants(ref variants) = **typ {
+                    for &(ref name, _) in variants {
+                        constructors.insert(short_name(name), name.clone());
+                    }
+                }
+            }
+        }
+
+        let mut exported_types = Vec::new();
+        let mut hidden: Vec<Alias<Symbol, ArcType>> = Vec::new();
+        for field in signature.type_field_iter() {
+            let span = field_span(&field.name);
+            let actual_alias = match actual.type_field_iter()
+                .find(|actual_field| actual_field.name.name_eq(&field.name)) {
+                Some(actual_field) => actual_field.typ.clone(),
+                None => {
+                    self.error(span, UndefinedField(actual.clone(), field.name.clone()));
+                    continue;
+                }
+            };
+            let definition = match field.typ.typ {
+                Some(ref typ) => {
+                    match **typ {
+                        // A bare `Stack` is parsed as an alias of itself
+                        Type::Ident(ref id) if field.typ.args.is_empty() &&
+                                               id.name_eq(&field.typ.name) => None,
+                        _ => Some(typ.clone()),
+                    }
+                }
+                None => None,
+            };
+            match definition {
+                Some(definition) => {
+                    let definition = self.resolve_signature_type(span,
+                                                                 &aliases,
+                                                                 &constructors,
+                                                                 definition);
+                    let actual_type = actual_alias.typ
+                        .clone()
+                        .unwrap_or_else(|| actual_alias.clone().into_type());
+                    if field.typ.args.len() == actual_alias.args.len() {
+                        // Use the names the module gave the arguments of the type
+                        let definition = instantiate::instantiate(definition, |generic| {
+                            field.typ
+                                .args
+                                .iter()
+                                .position(|arg| arg.id == generic.id)
+                                .map(|i| Type::generic(actual_alias.args[i].clone()))
+                        });
+                        self.unify_span(span, &definition, actual_type);
+                    } else {
+                        let expected = Alias::new(field.typ.name.clone(),
+                                                  field.typ.args.clone(),
+                                                  definition)
+                            .into_type();
+                        let actual_type = actual_alias.clone().into_type();
+                        let errors = vec![UnifyError::TypeMismatch(expected.clone(),
+                                                                   actual_type.clone())];
+                        self.error(span, Unification(expected, actual_type, errors, None));
+                    }
+                    exported_types.push(Field {
+                        name: field.name.clone(),
+                        typ: actual_alias,
+                    });
+                }
+                None => {
+                    let opaque = Alias::from(AliasData {
+                        name: actual_alias.name.clone(),
+                        args: actual_alias.args.clone(),
+                        typ: None,
+                    });
+                    hidden.push(opaque.clone());
+                    exported_types.push(Field {
+                        name: field.name.clone(),
+                        typ: opaque,
+                    });
+                }
+            }
+        }
+
+        let mut exported_fields = Vec::new();
+        for field in signature.field_iter() {
+            let span = field_span(&field.name);
+            let actual_type = match actual.field_iter()
+                .find(|actual_field| actual_field.name.name_eq(&field.name)) {
+                Some(actual_field) => actual_field.typ.clone(),
+                None => {
+                    self.error(span, UndefinedField(actual.clone(), field.name.clone()));
+                    continue;
+                }
+            };
+            let mut expected =
+                self.resolve_signature_type(span, &aliases, &constructors, field.typ.clone());
+            if let Err(err) = self.kindcheck(&mut expected) {
+                self.error(span, err);
+                continue;
+            }
+            let level = self.subs.var_id();
+            let actual_type = self.instantiate(&actual_type);
+            self.merge_signature(span, None, level, &expected, actual_type);
+            exported_fields.push(Field {
+                name: field.name.clone(),
+                typ: expected,
+            });
+        }
+
+        if self.errors.has_errors() {
+            return Err(mem::replace(&mut self.errors, Errors::new()));
+        }
+
+        // Replace the abstract types everywhere so that their definitions can not be reached
+        // through the types of the exported values
+        let mut hide = |typ: &Type<Symbol, ArcType>| {
+            match *typ {
+                Type::Alias(ref alias) => {
+                    hidden.iter()
+                        .find(|opaque| opaque.name == alias.name)
+                        .map(|opaque| opaque.clone().into_type())
+                }
+                _ => None,
+            }
+        };
+        for field in &mut exported_types {
+            if field.typ.typ.is_some() {
+                let alias = Alias::make_mut(&mut field.typ);
+                alias.typ = alias.typ.take().map(|typ| types::walk_move_type(typ, &mut hide));
+            }
+        }
+        for field in &mut exported_fields {
+            field.typ = types::walk_move_type(field.typ.clone(), &mut hide);
+        }
+        Ok(Type::record(exported_types, exported_fields))
+    }
+
+    /// Replaces the names in a type from a module signature with the types they refer to
+    fn resolve_signature_type(&mut self,
+                              span: Span<BytePos>,
+                              aliases: &FnvMap<String, Alias<Symbol, ArcType>>,
+                              constructors: &FnvMap<String, Symbol>,
+                              typ: ArcType)
+                              -> ArcType {
+        let mut undefined = Vec::new();
+        let typ = {
+            let environment = &self.environment;
+            let mut f = |typ: &Type<Symbol, ArcType>| {
+                match *typ {
+                    Type::Ident(ref id) => {
+                        let alias = aliases.get(&short_name(id))
+                            .or_else(|| environment.find_type_info(id));
+                        match alias {
+                            Some(alias) => Some(alias.clone().into_type()),
+                            None => {
+                                undefined.push(id.clone());
+                                None
+                            }
+                        }
+                    }
+                    // Constructors are referred to by the symbols they were declared with
+                    Type::Variants(ref variants) => {
+                        let variants = variants.iter()
+                            .map(|&(ref name, ref typ)| {
+                                let name = constructors.get(&short_name(name)).unwrap_or(name);
+                                (name.clone(), typ.clone())
+                            })
+                            .collect();
+                        Some(Type::variants(variants))
+                    }
+                    _ => None,
+                }
+            };
+            types::walk_move_type(typ, &mut f)
+        };
+        for id in undefined {
+            self.error(span, UndefinedType(id));
+        }
+        typ
+    }
+
     /// Main typechecking function. Returns the type of the expression if typechecking was
     /// successful
     fn typecheck(&mut self, mut expr: &mut SpannedExpr<Symbol>) -> ArcType {
@@ -446,12 +947,18 @@ impl<'a> Typecheck<'a> {
                             scope_count += 1;
                         }
                         TailCall::Type(typ) => {
+                            self.insert_implicit_args(expr);
                             returned_type = typ;
                             break;
                         }
                     }
                 }
                 Err(err) => {
+                    // Drop the implicit arguments of the expression as they will never be
+                    // inserted
+                    let len = self.implicit_args.len() - self.new_implicit_args.len();
+                    self.implicit_args.truncate(len);
+                    self.new_implicit_args.clear();
                     returned_type = self.subs.new_var();
                     self.errors.error(Spanned {
                         span: expr.span,
@@ -477,7 +984,7 @@ impl<'a> Typecheck<'a> {
                     id.name = new.clone();
                 }
                 id.typ = try!(self.find(&id.name));
-                Ok(TailCall::Type(id.typ.clone()))
+                Ok(TailCall::Type(self.new_implicit_arg_placeholders(expr_span, id.typ.clone())))
             }
             Expr::Literal(ref lit) => {
                 Ok(TailCall::Type(match *lit {
@@ -495,8 +1002,11 @@ impl<'a> Typecheck<'a> {
                     func_type = try!(self.unify(&f, func_type));
                     func_type = match func_type.as_function() {
                         Some((arg_ty, ret_ty)) => {
+                            let level = self.subs.var_id();
                             let actual = self.typecheck(arg);
-                            self.unify_span(arg.span, arg_ty, actual);
+                            if let Err(err) = self.unify_from(level, arg_ty, actual) {
+                                self.error(arg.span, err);
+                            }
                             ret_ty.clone()
                         }
                         None => return Err(NotAFunction(func_type.clone())),
@@ -540,9 +1050,11 @@ impl<'a> Typecheck<'a> {
                         }
                         _ => {
                             op.typ = try!(self.find(&op.name));
+                            let op_type =
+                                self.new_implicit_arg_placeholders(expr_span, op.typ.clone());
                             let func_type = Type::function(vec![lhs_type, rhs_type],
                                                            self.subs.new_var());
-                            let ret = try!(self.unify(&op.typ, func_type))
+                            let ret = try!(self.unify(&op_type, func_type))
                                 .as_function()
                                 .and_then(|(_, ret)| ret.as_function())
                                 .map(|(_, ret)| ret.clone())
@@ -554,9 +1066,10 @@ impl<'a> Typecheck<'a> {
                 };
                 result.map(TailCall::Type)
             }
-            Expr::Tuple(ref mut exprs) => {
-                assert!(exprs.len() == 0);
-                Ok(TailCall::Type(Type::unit()))
+            Expr::Tuple { ref mut typ, ref mut elems } => {
+                let elem_types = elems.iter_mut().map(|elem| self.typecheck(elem)).collect();
+                *typ = self.tuple_type(elem_types);
+                Ok(TailCall::Type(typ.clone()))
             }
             Expr::Match(ref mut expr, ref mut alts) => {
                 let typ = self.typecheck(&mut **expr);
@@ -564,15 +1077,39 @@ impl<'a> Typecheck<'a> {
 
                 for alt in alts.iter_mut() {
                     self.enter_scope();
+                    let refinements_start = self.refinements.len();
                     self.typecheck_pattern(&mut alt.pattern, typ.clone());
+                    if let Some(ref mut guard) = alt.guard {
+                        let guard_type = self.typecheck(guard);
+                        let bool_type = self.bool();
+                        self.unify_span(guard.span, &bool_type, guard_type);
+                    }
                     let mut alt_type = self.typecheck(&mut alt.expr);
                     self.exit_scope();
+                    if self.refinements.len() > refinements_start {
+                        // The type of the alternative only equals the type of the other
+                        // alternatives under its refinements
+                        let expected = match expected_alt_type {
+                            Some(ref expected) => expected.clone(),
+                            None => self.subs.new_var(),
+                        };
+                        self.refined_alternatives.push(RefinedAlternative {
+                            span: alt.expr.span,
+                            refinements: self.refinements.clone(),
+                            expected: expected.clone(),
+                            actual: alt_type,
+                        });
+                        self.refinements.truncate(refinements_start);
+                        expected_alt_type = Some(expected);
+                        continue;
+                    }
                     // All alternatives must unify to the same type
                     if let Some(ref expected) = expected_alt_type {
                         alt_type = try!(self.unify(expected, alt_type));
                     }
                     expected_alt_type = Some(alt_type);
                 }
+                self.check_alternatives(expr_span, alts);
                 expected_alt_type.ok_or(EmptyCase)
                     .map(TailCall::Type)
             }
@@ -604,7 +1141,10 @@ impl<'a> Typecheck<'a> {
                             .find(|field| field.name.name_eq(field_id))
                             .map(|field| field.typ.clone());
                         *ast_field_typ = match field_type {
-                            Some(typ) => self.instantiate(&typ),
+                            Some(typ) => {
+                                let typ = self.instantiate(&typ);
+                                unify_type::instantiate_forall(&self.subs, typ)
+                            }
                             None => {
                                 // FIXME As the polymorphic `record_type` do not have the type
                                 // fields which `typ` this unification is only done after we
@@ -648,6 +1188,7 @@ impl<'a> Typecheck<'a> {
                 Ok(TailCall::TailCall)
             }
             Expr::Record { ref mut typ, ref mut types, exprs: ref mut fields } => {
+                let level = self.subs.var_id();
                 let mut new_types: Vec<Field<_, _>> = Vec::with_capacity(types.len());
 
                 let mut duplicated_fields = FnvSet::default();
@@ -694,7 +1235,9 @@ impl<'a> Typecheck<'a> {
                 };
                 let id_type = self.instantiate(&id_type);
                 let record_type = self.instantiate_(&record_type);
-                try!(self.unify(&Type::record(new_types, new_fields), record_type));
+                // The declared record type is the expected type so that fields declared with a
+                // `forall` type are checked to be polymorphic
+                try!(self.unify_from(level, &record_type, Type::record(new_types, new_fields)));
                 *typ = id_type.clone();
                 Ok(TailCall::Type(id_type.clone()))
             }
@@ -705,6 +1248,8 @@ impl<'a> Typecheck<'a> {
                 }
                 Ok(TailCall::Type(self.typecheck(last)))
             }
+            // The parser has already reported an error so the expression may have any type
+            Expr::Error => Ok(TailCall::Type(self.subs.new_var())),
         }
     }
 
@@ -718,15 +1263,211 @@ impl<'a> Typecheck<'a> {
         {
             let mut iter1 = function_arg_iter(self, function_type);
             let mut iter2 = args.iter_mut();
-            while let (Some(arg_type), Some(arg)) = (iter1.next(), iter2.next()) {
+            while let (Some((implicit, arg_type)), Some(arg)) = (iter1.next(), iter2.next()) {
                 arg.typ = arg_type;
-                arg_types.push(arg.typ.clone());
+                arg_types.push((implicit, arg.typ.clone()));
                 iter1.tc.stack_var(arg.name.clone(), arg.typ.clone());
             }
         }
         let body_type = self.typecheck(body);
         self.exit_scope();
-        Type::function(arg_types, body_type)
+        arg_types.into_iter()
+            .rev()
+            .fold(body_type, |ret, (implicit, arg)| if implicit {
+                Type::implicit_function(vec![arg], ret)
+            } else {
+                Type::function(vec![arg], ret)
+            })
+    }
+
+    /// Creates a placeholder argument for each implicit argument that a value of type `typ` takes
+    /// and returns the type which is left once those arguments have been applied. The
+    /// placeholders are applied to the expression by `insert_implicit_args`.
+    fn new_implicit_arg_placeholders(&mut self, span: Span<BytePos>, typ: ArcType) -> ArcType {
+        if typ.as_implicit_function().is_none() {
+            return typ;
+        }
+        let candidates = self.implicit_bindings.clone();
+        let (args, typ) = self.new_implicit_args(span, typ, &candidates, 0);
+        self.new_implicit_args.extend(args);
+        typ
+    }
+
+    fn new_implicit_args(&mut self,
+                         span: Span<BytePos>,
+                         mut typ: ArcType,
+                         candidates: &ImplicitBindings,
+                         depth: u32)
+                         -> (Vec<SpannedExpr<Symbol>>, ArcType) {
+        let mut args = Vec::new();
+        loop {
+            let (arg_type, ret) = match typ.as_implicit_function() {
+                Some((arg, ret)) => (arg.clone(), ret.clone()),
+                None => return (args, typ),
+            };
+            self.implicit_count += 1;
+            let placeholder = self.symbols.symbol(format!("implicit:{}", self.implicit_count));
+            self.implicit_args.push(ImplicitArg {
+                span: span,
+                placeholder: placeholder.clone(),
+                typ: arg_type.clone(),
+                candidates: candidates.clone(),
+                depth: depth,
+            });
+            args.push(pos::spanned(span,
+                                   Expr::Ident(TypedIdent {
+                                       name: placeholder,
+                                       typ: arg_type,
+                                   })));
+            typ = ret;
+        }
+    }
+
+    /// Applies the implicit arguments created while checking `expr` to it.
+    /// `f x` becomes `f <implicit> x` and `l + r` becomes `(+) <implicit> l r`.
+    fn insert_implicit_args(&mut self, expr: &mut SpannedExpr<Symbol>) {
+        if self.new_implicit_args.is_empty() {
+            return;
+        }
+        let mut args = mem::replace(&mut self.new_implicit_args, Vec::new());
+        let func = match mem::replace(&mut expr.value, Expr::Error) {
+            Expr::Infix(lhs, op, rhs) => {
+                let op_span = Span {
+                    start: lhs.span.end,
+                    end: rhs.span.start,
+                };
+                args.push(*lhs);
+                args.push(*rhs);
+                pos::spanned(op_span, Expr::Ident(op))
+            }
+            func => pos::spanned(expr.span, func),
+        };
+        expr.value = Expr::App(Box::new(func), args);
+    }
+
+    /// Resolves the implicit arguments created since `start` by finding the binding in scope
+    /// which has the same type as the argument. Arguments whose type depends on variables from an
+    /// enclosing binding (variables below `level`) are left for that binding to resolve.
+    ///
+    /// Returns the expressions which should replace the placeholders of the resolved arguments.
+    fn resolve_implicit_args(&mut self,
+                             start: usize,
+                             level: u32)
+                             -> FnvMap<Symbol, SpannedExpr<Symbol>> {
+        let mut resolved = FnvMap::default();
+        // Arguments whose type still contains type variables are only resolved once nothing
+        // else can be resolved as the variables may be unified with a more specific type
+        let mut final_pass = false;
+        loop {
+            let pending = self.implicit_args.split_off(start);
+            let mut remaining = Vec::new();
+            let mut progress = false;
+            for arg in pending {
+                let typ = self.subs.set_type(arg.typ.clone());
+                if let Some(min_level) = self.variable_level(&typ) {
+                    if !final_pass || min_level < level {
+                        remaining.push(arg);
+                        continue;
+                    }
+                }
+                let candidates = self.implicit_candidates(&arg, &typ);
+                if arg.depth >= IMPLICIT_DEPTH_LIMIT {
+                    self.error(arg.span, NoImplicitArgument(typ));
+                } else if candidates.len() == 1 {
+                    let (name, candidate_type) = candidates.into_iter().next().unwrap();
+                    let expr = self.implicit_arg_expr(&arg, typ, name, candidate_type);
+                    resolved.insert(arg.placeholder, expr);
+                    progress = true;
+                } else if candidates.len() > 1 {
+                    let names = candidates.into_iter().map(|(name, _)| name).collect();
+                    self.error(arg.span, AmbiguousImplicitArgument(typ, names));
+                } else if final_pass {
+                    self.error(arg.span, NoImplicitArgument(typ));
+                } else {
+                    remaining.push(arg);
+                }
+            }
+            self.implicit_args.extend(remaining);
+            if progress {
+                final_pass = false;
+            } else if final_pass {
+                break;
+            } else {
+                final_pass = true;
+            }
+        }
+        resolved
+    }
+
+    /// Returns the lowest level of the type variables in `typ` or `None` if `typ` does not
+    /// contain any type variables
+    fn variable_level(&self, typ: &ArcType) -> Option<u32> {
+        let mut min_level = None;
+        types::walk_type(typ, |typ: &ArcType| if let Type::Variable(ref var) = **typ {
+            let level = self.subs.get_level(var.id);
+            min_level = Some(min_level.map_or(level, |min| cmp::min(min, level)));
+        });
+        min_level
+    }
+
+    /// Returns the bindings which can be passed as an implicit argument of type `typ`
+    fn implicit_candidates(&self, arg: &ImplicitArg, typ: &ArcType) -> Vec<(Symbol, ArcType)> {
+        let head = implicit_head(typ);
+        let mut candidates = Vec::new();
+        // Bindings which are shadowed, or whose type has been updated since they were added, occur
+        // again further down the list
+        let mut seen = FnvSet::default();
+        let mut current = arg.candidates.as_ref();
+        while let Some(binding) = current {
+            current = binding.next.as_ref();
+            if !seen.insert(&binding.name) {
+                continue;
+            }
+            let candidate_type = self.subs.set_type(binding.typ.clone());
+            let same_head = match (head, implicit_head(&candidate_type)) {
+                (Some(l), Some(r)) => l.name_eq(r),
+                (None, _) => true,
+                (Some(_), None) => false,
+            };
+            if !same_head {
+                continue;
+            }
+            let mut instance_type = &candidate_type;
+            while let Some((_, ret)) = instance_type.as_implicit_function() {
+                instance_type = ret;
+            }
+            if ::rename::equivalent(&self.environment, instance_type, typ) {
+                candidates.push((binding.name.clone(), binding.typ.clone()));
+            }
+        }
+        candidates
+    }
+
+    /// Creates the expression which is passed as the implicit argument `arg` of type `typ`. If
+    /// the binding takes implicit arguments itself those are created as well.
+    fn implicit_arg_expr(&mut self,
+                         arg: &ImplicitArg,
+                         typ: ArcType,
+                         name: Symbol,
+                         candidate_type: ArcType)
+                         -> SpannedExpr<Symbol> {
+        let candidate_type = self.subs.set_type(candidate_type);
+        let candidate_type = self.instantiate(&candidate_type);
+        let (args, instance_type) = self.new_implicit_args(arg.span,
+                                                           candidate_type.clone(),
+                                                           &arg.candidates,
+                                                           arg.depth + 1);
+        self.unify_span(arg.span, &typ, instance_type);
+        let id = pos::spanned(arg.span,
+                              Expr::Ident(TypedIdent {
+                                  name: name,
+                                  typ: candidate_type,
+                              }));
+        if args.is_empty() {
+            id
+        } else {
+            pos::spanned(arg.span, Expr::App(Box::new(id), args))
+        }
     }
 
     fn typecheck_pattern(&mut self,
@@ -741,10 +1482,12 @@ impl<'a> Typecheck<'a> {
                 }
                 // Find the enum constructor and return the types for its arguments
                 let ctor_type = self.find_at(span, &id.name);
+                id.typ = ctor_type.clone();
                 let return_type = match self.typecheck_pattern_rec(args, ctor_type) {
                     Ok(return_type) => return_type,
                     Err(err) => self.error(span, err),
                 };
+                self.refine_pattern(&match_type, &return_type);
                 self.unify_span(span, &match_type, return_type)
             }
             Pattern::Record { typ: ref mut curr_typ,
@@ -827,22 +1570,214 @@ impl<'a> Typecheck<'a> {
 
                 match_type
             }
+            Pattern::Tuple { ref mut typ, ref mut elems } => {
+                let elem_types = elems.iter().map(|_| self.subs.new_var()).collect::<Vec<_>>();
+                *typ = self.tuple_type(elem_types.clone());
+                let match_type = self.unify_span(span, &match_type, typ.clone());
+                for (elem, elem_type) in elems.iter_mut().zip(elem_types) {
+                    self.typecheck_pattern(elem, elem_type);
+                }
+                match_type
+            }
             Pattern::Ident(ref mut id) => {
                 self.stack_var(id.name.clone(), match_type.clone());
                 id.typ = match_type.clone();
                 match_type
             }
+            Pattern::Literal(ref literal) => {
+                let literal_type = literal.env_type_of(&self.environment);
+                self.unify_span(span, &match_type, literal_type)
+            }
+            Pattern::Wildcard(ref mut typ) => {
+                *typ = match_type.clone();
+                match_type
+            }
+        }
+    }
+
+    /// Adds a refinement for each type variable in the arguments of `match_type` which the return
+    /// type of a constructor pattern fixes to a more specific type. Only constructors declared as
+    /// `| C : T` can return such types.
+    fn refine_pattern(&mut self, match_type: &ArcType, return_type: &ArcType) {
+        let match_type = self.refine(&self.refinements, match_type.clone());
+        let return_type = self.subs.set_type(return_type.clone());
+        let (match_args, return_args) = match (&*match_type, &*return_type) {
+            (&Type::App(_, ref l), &Type::App(_, ref r)) if l.len() == r.len() => (l, r),
+            _ => return,
+        };
+        for (variable, typ) in match_args.iter().zip(return_args) {
+            let refined = match (&**variable, &**typ) {
+                (_, &Type::Variable(_)) => false,
+                (&Type::Generic(ref l), &Type::Generic(ref r)) => l.id != r.id,
+                (&Type::Variable(_), _) |
+                (&Type::Generic(_), _) => true,
+                _ => false,
+            };
+            if refined {
+                self.refinements.push(Refinement {
+                    variable: variable.clone(),
+                    typ: typ.clone(),
+                });
+            }
         }
     }
 
-    fn typecheck_pattern_rec(&mut self, args: &[TypedIdent], typ: ArcType) -> TcResult<ArcType> {
+    /// Replaces the variables refined by `refinements` in `typ` with the types they are refined to
+    fn refine(&self, refinements: &[Refinement], typ: ArcType) -> ArcType {
+        if refinements.is_empty() {
+            return typ;
+        }
+        let refinements = refinements.iter()
+            .map(|refinement| (self.subs.set_type(refinement.variable.clone()), refinement))
+            .collect::<Vec<_>>();
+        types::walk_move_type(self.subs.set_type(typ), &mut |typ: &Type<Symbol>| {
+            refinements.iter()
+                .find(|&&(ref variable, _)| match (&**variable, typ) {
+                    (&Type::Variable(ref l), &Type::Variable(ref r)) => l.id == r.id,
+                    (&Type::Generic(ref l), &Type::Generic(ref r)) => l.id == r.id,
+                    _ => false,
+                })
+                .map(|&(_, refinement)| refinement.typ.clone())
+        })
+    }
+
+    /// Checks the alternatives from `start` and onwards which were checked under refinements
+    /// against the type of their `match` expression. Refinements of variables which have been
+    /// unified with a type since then no longer apply.
+    fn check_refined_alternatives(&mut self, start: usize) {
+        for alt in self.refined_alternatives.split_off(start) {
+            let refinements = alt.refinements
+                .into_iter()
+                .filter(|refinement| match *self.subs.set_type(refinement.variable.clone()) {
+                    Type::Variable(_) | Type::Generic(_) => true,
+                    _ => false,
+                })
+                .collect::<Vec<_>>();
+            let expected = self.refine(&refinements, alt.expected);
+            let actual = self.refine(&refinements, alt.actual);
+            self.unify_span(alt.span, &expected, actual);
+        }
+    }
+
+    /// Returns the type of a tuple with elements of `elem_types`. Tuples are records with the
+    /// fields `_0`, `_1`, ... except for the empty tuple which is the unit type.
+    fn tuple_type(&mut self, elem_types: Vec<ArcType>) -> ArcType {
+        if elem_types.is_empty() {
+            return Type::unit();
+        }
+        let fields = elem_types.into_iter()
+            .enumerate()
+            .map(|(i, typ)| {
+                Field {
+                    name: self.symbols.symbol(format!("_{}", i)),
+                    typ: typ,
+                }
+            })
+            .collect();
+        Type::record(vec![], fields)
+    }
+
+    /// Reports alternatives which can never be selected and values which none of the
+    /// alternatives match
+    fn check_alternatives(&mut self, span: Span<BytePos>, alts: &[Alternative<Symbol>]) {
+        let alternatives = alts.iter()
+            .map(|alt| {
+                matching::Alternative {
+                    pattern: self.match_pattern(&alt.pattern.value),
+                    guarded: alt.guard.is_some(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let analysis = matching::analyze(&alternatives);
+        for index in analysis.unreachable {
+            self.match_error(alts[index].pattern.span, UnreachableAlternative);
+        }
+        if let Some(witness) = analysis.missing {
+            self.match_error(span, NonExhaustiveMatch(witness));
+        }
+    }
+
+    fn match_error(&mut self, span: Span<BytePos>, error: TypeError<Symbol>) {
+        let error = Spanned {
+            span: span,
+            value: error,
+        };
+        match self.match_check {
+            MatchCheck::Warn => self.warnings.error(error),
+            MatchCheck::Deny => self.errors.error(error),
+        }
+    }
+
+    fn match_pattern(&self, pattern: &Pattern<Symbol>) -> matching::Pattern<Symbol> {
+        match *pattern {
+            Pattern::Constructor(ref id, ref args) => {
+                match self.find_constructors(&id.name) {
+                    Some((index, constructors)) => {
+                        matching::Pattern::Constructor {
+                            index: index,
+                            constructors: constructors,
+                            args: args.iter().map(|arg| self.match_pattern(&arg.value)).collect(),
+                        }
+                    }
+                    // The constructor could not be found, an error has already been reported
+                    None => matching::Pattern::Wildcard,
+                }
+            }
+            Pattern::Tuple { ref elems, .. } => {
+                matching::Pattern::Tuple(elems.iter()
+                    .map(|elem| self.match_pattern(&elem.value))
+                    .collect())
+            }
+            Pattern::Literal(ref literal) => matching::Pattern::Literal(literal.clone()),
+            Pattern::Record { .. } |
+            Pattern::Ident(_) |
+            Pattern::Wildcard(_) => matching::Pattern::Wildcard,
+        }
+    }
+
+    /// Returns the index of `constructor` among the constructors of its type and all of the
+    /// constructors of that type
+    fn find_constructors(&self,
+                         constructor: &Symbol)
+                         -> Option<(usize, Vec<matching::Constructor<Symbol>>)> {
+        let mut typ = match self.environment.find_type(constructor) {
+            Some(typ) => typ,
+            None => return None,
+        };
+        while let Some((_, ret)) = typ.as_function() {
+            typ = ret;
+        }
+        match *self.remove_aliases(typ.clone()) {
+            Type::Variants(ref variants) => {
+                variants.iter()
+                    .position(|variant| variant.0 == *constructor)
+                    .map(|index| {
+                        let constructors = variants.iter()
+                            .map(|variant| {
+                                matching::Constructor {
+                                    name: variant.0.clone(),
+                                    arity: types::arg_iter(&variant.1).count(),
+                                }
+                            })
+                            .collect();
+                        (index, constructors)
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    fn typecheck_pattern_rec(&mut self,
+                             args: &mut [SpannedPattern<Symbol>],
+                             typ: ArcType)
+                             -> TcResult<ArcType> {
         if args.len() == 0 {
             return Ok(typ);
         }
         match typ.as_function() {
             Some((arg, ret)) => {
-                self.stack_var(args[0].name.clone(), arg.clone());
-                self.typecheck_pattern_rec(&args[1..], ret.clone())
+                self.typecheck_pattern(&mut args[0], arg.clone());
+                self.typecheck_pattern_rec(&mut args[1..], ret.clone())
             }
             None => Err(PatternError(typ.clone(), args.len())),
         }
@@ -853,14 +1788,21 @@ impl<'a> Typecheck<'a> {
         self.type_variables.enter_scope();
         let level = self.subs.var_id();
         let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+        let implicit_start = self.implicit_args.len();
+        let refined_start = self.refined_alternatives.len();
         // When the definitions are allowed to be mutually recursive
         if is_recursive {
+            let mut closed = Vec::with_capacity(bindings.len());
             for bind in bindings.iter_mut() {
-                let typ = {
-                    bind.typ = self.create_unifiable_signature(bind.typ.clone());
-                    try!(self.kindcheck(&mut bind.typ));
-                    self.instantiate_signature(&bind.typ)
-                };
+                bind.typ = self.create_unifiable_signature(bind.typ.clone());
+                try!(self.kindcheck(&mut bind.typ));
+                closed.push(self.is_closed_signature(&bind.typ));
+            }
+            for (bind, is_closed) in bindings.iter_mut().zip(closed) {
+                let typ = self.instantiate_signature(&bind.typ);
+                // Recursive uses of a function with a complete signature are instantiated
+                // separately which allows them to be used at different types
+                let typ = if is_closed { bind.typ.clone() } else { typ };
                 self.typecheck_pattern(&mut bind.name, typ);
                 if let Expr::Lambda(ref mut lambda) = bind.expr.value {
                     if let Pattern::Ident(ref name) = bind.name.value {
@@ -872,6 +1814,16 @@ impl<'a> Typecheck<'a> {
         let mut types = Vec::new();
         for bind in bindings.iter_mut() {
             self.type_variables.enter_scope();
+            let bind_implicit_start = self.implicit_args.len();
+            let bind_refined_start = self.refined_alternatives.len();
+
+            // Mismatches with the annotation of a variable binding are reported at the
+            // expression, pointing back at the annotated name
+            let (span, annotation) = match *bind.typ {
+                Type::Hole => (bind.name.span, None),
+                _ if bind.args.is_empty() => (bind.expr.span, Some(bind.name.span)),
+                _ => (bind.name.span, None),
+            };
 
             // Functions which are declared as `let f x = ...` are allowed to be self
             // recursive
@@ -889,12 +1841,21 @@ impl<'a> Typecheck<'a> {
                    bind.name,
                    types::display_type(&self.symbols, &typ));
 
-            typ = self.merge_signature(bind.name.span, level, &bind.typ, typ);
+            typ = self.merge_signature(span, annotation, level, &bind.typ, typ);
 
             if !is_recursive {
+                self.check_refined_alternatives(bind_refined_start);
+                let mut resolved = self.resolve_implicit_args(bind_implicit_start, level);
+                replace_implicit_args(&mut resolved, &mut bind.expr);
                 // Merge the type declaration and the actual type
                 self.generalize_variables(level, &mut bind.expr);
+                let refinements_start = self.refinements.len();
                 self.typecheck_pattern(&mut bind.name, typ);
+                // Patterns of `let` bindings must match every value so they can not refine
+                // any types
+                for refinement in self.refinements.split_off(refinements_start) {
+                    self.unify_span(bind.name.span, &refinement.variable, refinement.typ);
+                }
             } else {
                 types.push(typ);
             }
@@ -907,6 +1868,11 @@ impl<'a> Typecheck<'a> {
                 // in the expression
                 self.unify_span(bind.name.span, &bind.typ, found_typ);
             }
+            self.check_refined_alternatives(refined_start);
+            let mut resolved = self.resolve_implicit_args(implicit_start, level);
+            for bind in bindings.iter_mut() {
+                replace_implicit_args(&mut resolved, &mut bind.expr);
+            }
         }
         // Once all variables inside the let has been unified we can quantify them
         debug!("Generalize {}", level);
@@ -983,6 +1949,37 @@ impl<'a> Typecheck<'a> {
             }
         }
 
+        // Constructors declared as `| C : T` may refine the arguments of the type but they must
+        // still construct the type they are declared in
+        for bind in bindings.iter() {
+            let variants = match bind.alias.typ {
+                Some(ref typ) => {
+                    match **typ {
+                        Type::Variants(ref variants) => variants,
+                        _ => continue,
+                    }
+                }
+                None => continue,
+            };
+            for (i, &(ref constructor, ref typ)) in variants.iter().enumerate() {
+                let mut iter = types::arg_iter(typ);
+                while let Some(_) = iter.next() {}
+                let return_type = iter.typ;
+                let name = return_type.as_alias().map(|(name, _)| name);
+                if name != Some(&bind.alias.name) {
+                    let span = bind.constructors
+                        .get(i)
+                        .map_or(expr.span, |constructor| constructor.span);
+                    self.errors.error(Spanned {
+                        span: span,
+                        value: InvalidConstructorType(constructor.clone(),
+                                                      bind.name.clone(),
+                                                      return_type.clone()),
+                    });
+                }
+            }
+        }
+
         // Finally insert the declared types into the global scope
         for bind in bindings {
             if self.environment.stack_types.get(&bind.name).is_some() {
@@ -1004,7 +2001,17 @@ impl<'a> Typecheck<'a> {
     }
 
     fn finish_binding(&mut self, level: u32, bind: &mut ValueBinding<Symbol>) {
-        match bind.name.value {
+        debug!("{}: {}",
+               match bind.name.value {
+                   Pattern::Ident(ref id) => self.symbols.string(&id.name),
+                   _ => "<pattern>",
+               },
+               types::display_type(&self.symbols, &bind.expr.env_type_of(&self.environment)));
+        self.finish_pattern(level, &mut bind.name);
+    }
+
+    fn finish_pattern(&mut self, level: u32, pattern: &mut SpannedPattern<Symbol>) {
+        match pattern.value {
             Pattern::Ident(ref mut id) => {
                 if let Some(typ) = self.finish_type(level, &id.typ) {
                     id.typ = typ;
@@ -1015,9 +2022,6 @@ impl<'a> Typecheck<'a> {
                 self.intersect_type(level, &id.name, &id.typ);
             }
             Pattern::Record { ref mut typ, ref mut fields, .. } => {
-                debug!("{{ .. }}: {}",
-                       types::display_type(&self.symbols,
-                                           &bind.expr.env_type_of(&self.environment)));
                 if let Some(finished) = self.finish_type(level, typ) {
                     *typ = finished;
                 }
@@ -1027,15 +2031,14 @@ impl<'a> Typecheck<'a> {
                     self.intersect_type(level, field_name, field_type);
                 });
             }
-            Pattern::Constructor(ref id, ref args) => {
-                debug!("{}: {}",
-                       self.symbols.string(&id.name),
-                       types::display_type(&self.symbols,
-                                           &bind.expr.env_type_of(&self.environment)));
+            Pattern::Constructor(_, ref mut args) |
+            Pattern::Tuple { elems: ref mut args, .. } => {
                 for arg in args {
-                    self.intersect_type(level, &arg.name, &arg.typ);
+                    self.finish_pattern(level, arg);
                 }
             }
+            Pattern::Literal(_) |
+            Pattern::Wildcard(_) => (),
         }
     }
 
@@ -1057,8 +2060,9 @@ impl<'a> Typecheck<'a> {
                 symbol_type.clone()
             }
         };
-        *self.environment.stack.get_mut(symbol).unwrap() = self.finish_type(level, &typ)
-            .unwrap_or(typ)
+        let typ = self.finish_type(level, &typ).unwrap_or(typ);
+        self.add_implicit_binding(symbol, &typ);
+        *self.environment.stack.get_mut(symbol).unwrap() = typ;
     }
 
     /// Generate a generic variable name which is not used in the current scope
@@ -1159,6 +2163,18 @@ impl<'a> Typecheck<'a> {
         visitor.visit(typ)
     }
 
+    /// Returns true if `typ` is a complete type annotation which does not refer to the type
+    /// variables of an enclosing binding
+    fn is_closed_signature(&self, typ: &ArcType) -> bool {
+        let mut closed = true;
+        types::walk_type(typ, |typ: &ArcType| match **typ {
+            Type::Hole | Type::Variable(_) => closed = false,
+            Type::Generic(ref gen) if self.type_variables.get(&gen.id).is_some() => closed = false,
+            _ => (),
+        });
+        closed
+    }
+
     fn instantiate_signature(&mut self, typ: &ArcType) -> ArcType {
         let typ = self.instantiate(typ);
         // Put all new generic variable names into scope
@@ -1238,11 +2254,12 @@ impl<'a> Typecheck<'a> {
 
     fn merge_signature(&mut self,
                        span: Span<BytePos>,
+                       annotation: Option<Span<BytePos>>,
                        level: u32,
                        expected: &ArcType,
                        mut actual: ArcType)
                        -> ArcType {
-        let state = unify_type::State::new(&self.environment, &self.subs);
+        let state = unify_type::State::new(&self.environment, &self.subs).with_level(level);
         match unify_type::merge_signature(&self.subs,
                                           &mut self.type_variables,
                                           level,
@@ -1254,8 +2271,8 @@ impl<'a> Typecheck<'a> {
                 let mut expected = expected.clone();
                 expected = self.subs.set_type(expected);
                 actual = self.subs.set_type(actual);
-                let err =
-                    TypeError::Unification(expected, actual, apply_subs(&self.subs, errors.errors));
+                let errors = apply_subs(&self.subs, errors.errors);
+                let err = TypeError::Unification(expected, actual, errors, annotation);
                 self.errors.error(Spanned {
                     span: span,
                     value: err,
@@ -1278,22 +2295,33 @@ impl<'a> Typecheck<'a> {
         }
     }
 
-    fn unify(&self, expected: &ArcType, mut actual: ArcType) -> TcResult<ArcType> {
+    fn unify(&self, expected: &ArcType, actual: ArcType) -> TcResult<ArcType> {
+        self.unify_from(self.subs.var_id(), expected, actual)
+    }
+
+    /// Unifies `expected` with the type `actual` of an expression whose type variables were created
+    /// from `level` onwards. The variables bound by a `forall` in `expected` may not escape into
+    /// the type variables created before `level`.
+    fn unify_from(&self, level: u32, expected: &ArcType, actual: ArcType) -> TcResult<ArcType> {
+        // Refined variables are replaced so that they are not bound outside of the alternative
+        // which refined them
+        let expected = self.refine(&self.refinements, expected.clone());
+        let mut actual = self.refine(&self.refinements, actual);
         debug!("Unify {} <=> {}",
-               types::display_type(&self.symbols, expected),
+               types::display_type(&self.symbols, &expected),
                types::display_type(&self.symbols, &actual));
-        let state = unify_type::State::new(&self.environment, &self.subs);
-        match unify::unify(&self.subs, state, expected, &actual) {
+        let state = unify_type::State::new(&self.environment, &self.subs).with_level(level);
+        match unify::unify(&self.subs, state, &expected, &actual) {
             Ok(typ) => Ok(self.subs.set_type(typ)),
             Err(errors) => {
-                let mut expected = expected.clone();
-                expected = self.subs.set_type(expected);
+                let expected = self.subs.set_type(expected);
                 actual = self.subs.set_type(actual);
                 debug!("Error '{:?}' between:\n>> {}\n>> {}",
                        errors,
                        types::display_type(&self.symbols, &expected),
                        types::display_type(&self.symbols, &actual));
-                Err(TypeError::Unification(expected, actual, apply_subs(&self.subs, errors.errors)))
+                let errors = apply_subs(&self.subs, errors.errors);
+                Err(TypeError::Unification(expected, actual, errors, None))
             }
         }
     }
@@ -1371,6 +2399,11 @@ fn apply_subs(subs: &Substitution<ArcType>,
         .collect()
 }
 
+/// Returns the name of `id` without the module it was declared in
+fn short_name(id: &Symbol) -> String {
+    String::from(Name::new(id.declared_name()).name().as_str())
+}
+
 pub fn extract_generics(args: &[ArcType]) -> Vec<Generic<Symbol>> {
     args.iter()
         .map(|arg| {
@@ -1403,27 +2436,43 @@ fn get_alias_app<'a>(env: &'a TypeEnv,
     }
 }
 
+/// Iterates over the arguments of a function type. Each argument is returned together with
+/// whether it is an implicit argument.
 struct FunctionArgIter<'a, 'b: 'a> {
     tc: &'a mut Typecheck<'b>,
     typ: ArcType,
 }
 
 impl<'a, 'b> Iterator for FunctionArgIter<'a, 'b> {
-    type Item = ArcType;
-    fn next(&mut self) -> Option<ArcType> {
+    type Item = (bool, ArcType);
+    fn next(&mut self) -> Option<(bool, ArcType)> {
         loop {
+            // The variables bound by a `forall` are rigid inside of the function
+            let forall_body = match *self.typ {
+                Type::Forall(_, ref typ) => Some(typ.clone()),
+                _ => None,
+            };
+            if let Some(typ) = forall_body {
+                self.typ = typ;
+                continue;
+            }
             let (arg, new) = match self.typ.as_function() {
-                Some((arg, ret)) => (Some(arg.clone()), ret.clone()),
+                Some((arg, ret)) => (Some((false, arg.clone())), ret.clone()),
                 None => {
-                    match get_alias_app(&self.tc.environment, &self.typ) {
-                        Some((alias, args)) => {
-                            match self.tc.type_of_alias(alias, args) {
-                                Ok(Some(typ)) => (None, typ.clone()),
-                                Ok(None) => return None,
-                                Err(_) => return Some(self.tc.subs.new_var()),
+                    match self.typ.as_implicit_function() {
+                        Some((arg, ret)) => (Some((true, arg.clone())), ret.clone()),
+                        None => {
+                            match get_alias_app(&self.tc.environment, &self.typ) {
+                                Some((alias, args)) => {
+                                    match self.tc.type_of_alias(alias, args) {
+                                        Ok(Some(typ)) => (None, typ.clone()),
+                                        Ok(None) => return None,
+                                        Err(_) => return Some((false, self.tc.subs.new_var())),
+                                    }
+                                }
+                                None => return Some((false, self.tc.subs.new_var())),
                             }
                         }
-                        None => return Some(self.tc.subs.new_var()),
                     }
                 }
             };
@@ -1439,6 +2488,45 @@ fn function_arg_iter<'a, 'b>(tc: &'a mut Typecheck<'b>, typ: ArcType) -> Functio
     FunctionArgIter { tc: tc, typ: typ }
 }
 
+/// Returns the name of the type which a binding of type `typ` can be passed as an implicit
+/// argument for (`Eq` in `Eq Int` and `[Eq a] -> Eq (List a)`)
+fn implicit_head(typ: &ArcType) -> Option<&Symbol> {
+    let mut typ = typ;
+    while let Some((_, ret)) = typ.as_implicit_function() {
+        typ = ret;
+    }
+    typ.as_alias().map(|(id, _)| id)
+}
+
+/// Replaces the placeholders of the implicit arguments in `resolved` which appear in `expr`
+fn replace_implicit_args(resolved: &mut FnvMap<Symbol, SpannedExpr<Symbol>>,
+                         expr: &mut SpannedExpr<Symbol>) {
+    struct ReplaceVisitor<'a> {
+        resolved: &'a mut FnvMap<Symbol, SpannedExpr<Symbol>>,
+    }
+
+    impl<'a> MutVisitor for ReplaceVisitor<'a> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, expr: &mut SpannedExpr<Symbol>) {
+            let replacement = match expr.value {
+                Expr::Ident(ref id) => self.resolved.remove(&id.name),
+                _ => None,
+            };
+            if let Some(replacement) = replacement {
+                *expr = replacement;
+            }
+            // The replacement may contain placeholders for the implicit arguments of the
+            // resolved binding
+            ast::walk_mut_expr(self, expr);
+        }
+    }
+
+    if !resolved.is_empty() {
+        ReplaceVisitor { resolved: resolved }.visit_expr(expr);
+    }
+}
+
 fn primitive_type(op_type: &str) -> ArcType {
     match op_type {
         "Int" => Type::int(),
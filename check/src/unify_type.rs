@@ -2,7 +2,7 @@ use std::fmt;
 use std::mem;
 
 use base::error::Errors;
-use base::types::{self, ArcType, Field, Type, TypeVariable, TypeEnv, merge};
+use base::types::{self, ArcType, Field, Generic, Type, TypeVariable, TypeEnv, merge};
 use base::symbol::{Symbol, SymbolRef};
 use base::instantiate;
 use base::scoped_map::ScopedMap;
@@ -20,6 +20,9 @@ pub struct State<'a> {
     reduced_aliases: Vec<Symbol>,
     subs: &'a Substitution<ArcType>,
     record_context: Option<(ArcType, ArcType)>,
+    /// Type variables with a level below this level belong to the scope enclosing the types which
+    /// are unified. The skolems of a `forall` type may not escape into them.
+    level: Option<u32>,
 }
 
 impl<'a> State<'a> {
@@ -29,8 +32,16 @@ impl<'a> State<'a> {
             reduced_aliases: Vec::new(),
             subs: subs,
             record_context: None,
+            level: None,
         }
     }
+
+    /// Checks that the variables bound by `forall` types are not bound to the type variables which
+    /// were created before `level`
+    pub fn with_level(mut self, level: u32) -> State<'a> {
+        self.level = Some(level);
+        self
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -42,6 +53,28 @@ pub enum TypeError<I> {
     MissingFields(ArcType<I>, Vec<I>),
 }
 
+impl<I> TypeError<I> {
+    /// Returns the stable code of the error which can be passed to `explain::explain`
+    pub fn code(&self) -> &'static str {
+        match *self {
+            TypeError::UndefinedType(..) => "E0102",
+            TypeError::FieldMismatch(..) => "E0103",
+            TypeError::SelfRecursive(..) => "E0104",
+            TypeError::UnableToGeneralize(..) => "E0105",
+            TypeError::MissingFields(..) => "E0106",
+        }
+    }
+}
+
+/// Returns the stable code of an error found while unifying two types
+pub fn error_code<I>(error: &Error<I>) -> &'static str {
+    match *error {
+        UnifyError::TypeMismatch(..) => "E0100",
+        UnifyError::Occurs(..) => "E0101",
+        UnifyError::Other(ref err) => err.code(),
+    }
+}
+
 impl From<instantiate::Error> for Error<Symbol> {
     fn from(error: instantiate::Error) -> Error<Symbol> {
         UnifyError::Other(match error {
@@ -262,6 +295,34 @@ fn do_zip_match<'a, U>(self_: &ArcType,
                 unify_rows(unifier, self_, other)
             }
         }
+        (&Type::Forall(ref l_params, ref l_typ), &Type::Forall(ref r_params, ref r_typ)) => {
+            if l_params.len() != r_params.len() {
+                return Err(UnifyError::TypeMismatch(self_.clone(), other.clone()));
+            }
+            // Both types bind the same skolems so that `forall a . a -> a` and
+            // `forall b . b -> b` are equal
+            let skolems = new_skolems(l_params);
+            let l_typ = skolemize(l_params, &skolems, l_typ);
+            let r_typ = skolemize(r_params, &skolems, r_typ);
+            let typ = unifier.try_match(&l_typ, &r_typ);
+            try!(check_escape(unifier, l_params, &skolems, &[&l_typ, &r_typ]));
+            Ok(typ.map(|typ| Type::forall(l_params.clone(), unskolemize(l_params, &skolems, typ))))
+        }
+        // A polymorphic value is expected. The bound variables are replaced by skolems which can
+        // only be unified with themselves so `other` must work for any type
+        (&Type::Forall(ref params, ref typ), _) => {
+            let skolems = new_skolems(params);
+            let typ = skolemize(params, &skolems, typ);
+            let result = unifier.try_match(&typ, other);
+            try!(check_escape(unifier, params, &skolems, &[other]));
+            Ok(result.map(|typ| Type::forall(params.clone(), unskolemize(params, &skolems, typ))))
+        }
+        // A polymorphic value is used where a more specific type is expected so the bound
+        // variables may be instantiated to any type
+        (_, &Type::Forall(..)) => {
+            let typ = instantiate_forall(unifier.state.subs, other.clone());
+            Ok(unifier.try_match(self_, &typ))
+        }
         (&Type::Ident(ref id), &Type::Alias(ref alias)) if *id == alias.name => {
             Ok(Some(other.clone()))
         }
@@ -277,6 +338,108 @@ fn do_zip_match<'a, U>(self_: &ArcType,
     }
 }
 
+/// Creates a skolem for each variable bound by a `forall`. A skolem is a rigid type variable with
+/// the same name as the bound variable which is only equal to itself.
+fn new_skolems(params: &[Generic<Symbol>]) -> Vec<Generic<Symbol>> {
+    params.iter()
+        .map(|param| {
+            Generic {
+                id: Symbol::from(param.id.as_ref()),
+                kind: param.kind.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Replaces the variables in `params` with `skolems` in `typ`
+fn skolemize(params: &[Generic<Symbol>], skolems: &[Generic<Symbol>], typ: &ArcType) -> ArcType {
+    instantiate::instantiate(typ.clone(), |gen| {
+        params.iter()
+            .position(|param| param.id == gen.id)
+            .map(|i| Type::generic(skolems[i].clone()))
+    })
+}
+
+/// Replaces `skolems` in `typ` with the variables in `params` which they were created from
+fn unskolemize(params: &[Generic<Symbol>], skolems: &[Generic<Symbol>], typ: ArcType) -> ArcType {
+    skolemize(skolems, params, &typ)
+}
+
+/// Returns an error if any of `skolems` were bound to a type variable from outside of the scope
+/// given by the level of the unifier while unifying `types`
+fn check_escape<'a, U>(unifier: &mut UnifierState<'a, U>,
+                       params: &[Generic<Symbol>],
+                       skolems: &[Generic<Symbol>],
+                       types: &[&ArcType])
+                       -> Result<(), Error<Symbol>> {
+    let level = match unifier.state.level {
+        Some(level) => level,
+        None => return Ok(()),
+    };
+    let subs = unifier.state.subs;
+    for typ in types {
+        if let Some(i) = escaped_skolem(subs, level, skolems, typ) {
+            let error = TypeError::UnableToGeneralize(params[i].id.clone());
+            return Err(UnifyError::Other(error));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the index of the skolem which a type variable in `typ`, created before `level`, is
+/// bound to
+fn escaped_skolem(subs: &Substitution<ArcType>,
+                  level: u32,
+                  skolems: &[Generic<Symbol>],
+                  typ: &ArcType)
+                  -> Option<usize> {
+    let mut escaped = None;
+    types::walk_type(typ, |typ: &ArcType| {
+        if escaped.is_some() {
+            return;
+        }
+        if let Type::Variable(ref var) = **typ {
+            let resolved = subs.real(typ);
+            if resolved == typ {
+                return;
+            }
+            escaped = if subs.get_level(var.id) < level {
+                let resolved = subs.set_type(resolved.clone());
+                skolems.iter().position(|skolem| occurs_generic(&resolved, skolem))
+            } else {
+                escaped_skolem(subs, level, skolems, resolved)
+            };
+        }
+    });
+    escaped
+}
+
+fn occurs_generic(typ: &ArcType, generic: &Generic<Symbol>) -> bool {
+    let mut found = false;
+    types::walk_type(typ, |typ: &ArcType| {
+        if let Type::Generic(ref gen) = **typ {
+            found = found || gen.id == generic.id;
+        }
+    });
+    found
+}
+
+/// Replaces the variables bound by `typ` with fresh type variables if `typ` is a `forall` type
+pub fn instantiate_forall(subs: &Substitution<ArcType>, typ: ArcType) -> ArcType {
+    let instantiated = match *typ {
+        Type::Forall(ref params, ref body) => {
+            let variables: Vec<_> = params.iter().map(|_| subs.new_var()).collect();
+            Some(instantiate::instantiate(body.clone(), |gen| {
+                params.iter()
+                    .position(|param| param.id == gen.id)
+                    .map(|i| variables[i].clone())
+            }))
+        }
+        _ => None,
+    };
+    instantiated.unwrap_or(typ)
+}
+
 fn gather_fields<'a, I, J, T>
     (l: I,
      r: J)
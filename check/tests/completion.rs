@@ -4,9 +4,13 @@ extern crate gluon_base as base;
 extern crate gluon_parser as parser;
 extern crate gluon_check as check;
 
+use base::fnv::FnvMap;
+use base::metadata::{Metadata, MetadataEnv};
 use base::pos::BytePos;
+use base::symbol::Symbol;
 use base::types::{Field, Type, ArcType};
 use check::completion;
+use check::metadata::metadata_with_bindings;
 
 mod support;
 use support::{MockEnv, intern, typ};
@@ -317,3 +321,37 @@ test  test1
 
     assert_eq!(result, expected);
 }
+
+struct Bindings(FnvMap<Symbol, Metadata>);
+
+impl MetadataEnv for Bindings {
+    fn get_metadata(&self, id: &Symbol) -> Option<&Metadata> {
+        self.0.get(id)
+    }
+}
+
+#[test]
+fn metadata_at_identifier_and_field() {
+    let _ = env_logger::init();
+
+    let text = r#"
+/// The identity function
+let id x = x
+let record = { id }
+id (record.id 1)
+"#;
+    let (mut expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    let (_, bindings) = metadata_with_bindings(&Bindings(FnvMap::default()), &mut expr);
+    let env = Bindings(bindings);
+
+    let comment = |pos: usize| {
+        completion::get_metadata(&env, &expr, BytePos::from(pos))
+            .and_then(|metadata| metadata.comment.clone())
+    };
+    assert_eq!(comment(text.rfind("id (").unwrap()),
+               Some("The identity function".to_string()));
+    assert_eq!(comment(text.rfind(".id").unwrap() + 1),
+               Some("The identity function".to_string()));
+    assert_eq!(comment(text.rfind("record").unwrap()), None);
+}
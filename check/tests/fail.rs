@@ -4,7 +4,7 @@ extern crate gluon_base as base;
 extern crate gluon_parser as parser;
 extern crate gluon_check as check;
 
-use base::pos::Spanned;
+use base::pos::{BytePos, Span, Spanned};
 use base::types;
 
 mod support;
@@ -42,7 +42,8 @@ macro_rules! assert_unify_err {
         #[allow(unused_imports)]
         use check::unify::Error::{TypeMismatch, Occurs, Other};
         #[allow(unused_imports)]
-        use check::unify_type::TypeError::{FieldMismatch, SelfRecursive, MissingFields};
+        use check::unify_type::TypeError::{FieldMismatch, SelfRecursive, MissingFields,
+                                           UnableToGeneralize};
 
         let symbols = support::get_local_interner();
 
@@ -52,7 +53,7 @@ macro_rules! assert_unify_err {
             Err(err) => {
                 for err in err.errors.iter() {
                     match *err {
-                        Spanned { value: Unification(_, _, ref errors), .. } => {
+                        Spanned { value: Unification(_, _, ref errors, _), .. } => {
                             let mut iter = errors.iter();
                             $(
                             match iter.next() {
@@ -89,6 +90,32 @@ match { x = 1 } with
     assert_unify_err!(result, Other(MissingFields(..)));
 }
 
+#[test]
+fn guard_is_not_bool() {
+    let _ = env_logger::init();
+    let text = r"
+match 1 with
+| x if x -> 1
+| _ -> 2
+";
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn literal_pattern_type_mismatch() {
+    let _ = env_logger::init();
+    let text = r#"
+match 1 with
+| "a" -> 1
+| _ -> 2
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
 #[test]
 fn undefined_type() {
     let _ = env_logger::init();
@@ -345,3 +372,204 @@ let { Test, Test, x = y, x } = { Test, x = 1 }
     let result = support::typecheck(text);
     assert_err!(result, DuplicateField(..), DuplicateField(..));
 }
+
+#[test]
+fn annotation_mismatch_points_at_annotation() {
+    use check::typecheck::TypeError::Unification;
+
+    let _ = ::env_logger::init();
+    let text = r#"
+let x : Int = "abc"
+x
+"#;
+    let result = support::typecheck(text);
+    let err = result.unwrap_err();
+    let span_text = |span: Span<BytePos>| &text[span.start.to_usize()..span.end.to_usize()];
+    match err.errors[0] {
+        Spanned { span, value: Unification(_, _, _, Some(annotation)) } => {
+            assert_eq!(span_text(span), r#""abc""#);
+            assert_eq!(span_text(annotation), "x");
+        }
+        ref err => panic!("Expected an unification error with an annotation, found {}", err),
+    }
+}
+
+#[test]
+fn no_implicit_argument() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Show a = { show : a -> String }
+let show_Int : Show Int = { show = \x -> "" }
+let show d x : [Show a] -> a -> String = d.show x
+show 1.0
+"#;
+    let result = support::typecheck(text);
+    assert_err!(result, NoImplicitArgument(..));
+}
+
+#[test]
+fn ambiguous_implicit_argument() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Show a = { show : a -> String }
+let show_Int : Show Int = { show = \x -> "" }
+let show_Int2 : Show Int = { show = \x -> "" }
+let show d x : [Show a] -> a -> String = d.show x
+show 1
+"#;
+    let result = support::typecheck(text);
+    assert_err!(result, AmbiguousImplicitArgument(..));
+}
+
+#[test]
+fn higher_rank_argument_is_not_polymorphic() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let f g : (forall a . a -> a) -> Int = g 1
+f (\x -> x #Int+ 1)
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn forall_record_field_is_not_polymorphic() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Option a = | None | Some a
+type Functor f = { map : forall a b . (a -> b) -> f a -> f b }
+let functor_Option : Functor Option = {
+    map = \f x -> Some 1
+}
+functor_Option
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn forall_variable_escapes_into_enclosing_lambda() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Id = { id : forall a . a -> a }
+\y -> { id = \x -> y }
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, Other(UnableToGeneralize(..)));
+}
+
+#[test]
+fn forall_variable_is_not_the_variable_of_an_enclosing_signature() {
+    use check::typecheck::TypeError::Unification;
+    use check::unify::Error::Other;
+    use check::unify_type::TypeError::UnableToGeneralize;
+
+    let _ = ::env_logger::init();
+    let text = r#"
+type Id = { id : forall a . a -> a }
+let f x : a -> Id = { id = \y -> x }
+f
+"#;
+    let result = support::typecheck(text);
+    let err = result.unwrap_err();
+    // The `a` of the signature is also reported as not matching the escaped variable
+    match err.errors[0].value {
+        Unification(_, _, ref errors, _) if errors.len() == 1 => {
+            match errors[0] {
+                Other(UnableToGeneralize(_)) => (),
+                ref error => panic!("Expected the variable to escape, found {}", error),
+            }
+        }
+        ref err => panic!("Expected an unification error, found {}", err),
+    }
+}
+
+#[test]
+fn gadt_alternative_does_not_match_refined_type() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Expr a = | IntLit : Int -> Expr Int | IsZero : Expr Int -> Expr Bool
+let eval e : Expr a -> a =
+    match e with
+    | IntLit x -> x
+    | IsZero x -> 1
+eval (IntLit 1)
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn gadt_constructor_must_return_its_type() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Expr a = | IntLit : Int -> Int
+1
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, InvalidConstructorType(..));
+}
+
+#[test]
+fn invalid_constructor_type_points_at_constructor() {
+    use check::typecheck::TypeError::InvalidConstructorType;
+
+    let _ = ::env_logger::init();
+    let text = r#"
+type Expr a =
+    | IntLit : Int -> Expr Int
+    | IsZero : Expr Int -> Bool
+1
+"#;
+    let result = support::typecheck(text);
+    let err = result.unwrap_err();
+    let span_text = |span: Span<BytePos>| &text[span.start.to_usize()..span.end.to_usize()];
+    match err.errors[0] {
+        Spanned { span, value: InvalidConstructorType(..) } => {
+            assert_eq!(span_text(span), "IsZero");
+        }
+        ref err => panic!("Expected an invalid constructor type error, found {}", err),
+    }
+}
+
+#[test]
+fn module_signature_more_general_than_module() {
+    let _ = ::env_logger::init();
+    let text = r#"
+{ f = \x -> x #Int+ 1 }
+"#;
+    let result = support::typecheck_signature(text, "{ f : a -> Int }");
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn module_signature_declares_missing_value() {
+    let _ = ::env_logger::init();
+    let text = r#"
+{ x = 1 }
+"#;
+    let result = support::typecheck_signature(text, "{ x : Int, y : Int }");
+
+    assert_err!(result, UndefinedField(..));
+}
+
+#[test]
+fn module_signature_errors_point_at_the_declared_field() {
+    let _ = ::env_logger::init();
+    let text = r#"
+{ x = 1, y = "" }
+"#;
+    let signature = "{ x : Int, y : Int }";
+    let result = support::typecheck_signature(text, signature);
+
+    let err = result.unwrap_err();
+    let span_text = |span: Span<BytePos>| &signature[span.start.to_usize()..span.end.to_usize()];
+    assert_eq!(err.errors.len(), 1);
+    assert_eq!(span_text(err.errors[0].span), "y");
+}
@@ -0,0 +1,118 @@
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+extern crate gluon_check as check;
+
+use check::lint::{Lint, LintLevel};
+use check::typecheck::Typecheck;
+
+mod support;
+
+/// Typechecks `text` with the lints set to `levels`, returning the errors and warnings which were
+/// found
+fn check_lints(text: &str, levels: &[(Lint, LintLevel)]) -> (Vec<String>, Vec<String>) {
+    let mut expr = support::parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = support::MockEnv::new();
+    let interner = support::get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env);
+    for &(lint, level) in levels {
+        tc.set_lint_level(lint, level);
+    }
+
+    let errors = match tc.typecheck_expr(&mut expr) {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors.errors.into_iter().map(|error| error.value.to_string()).collect(),
+    };
+    let warnings = tc.take_warnings()
+        .errors
+        .into_iter()
+        .map(|error| error.value.to_string())
+        .collect();
+    (errors, warnings)
+}
+
+#[test]
+fn unused_variable_is_a_warning_by_default() {
+    let _ = env_logger::init();
+    let text = r"
+let x = 1
+in let y = 2
+in y
+";
+    assert_eq!(check_lints(text, &[]),
+               (vec![], vec!["Unused variable `x`".to_string()]));
+}
+
+#[test]
+fn unused_variable_can_be_denied_or_allowed() {
+    let _ = env_logger::init();
+    let text = r"
+let x = 1
+in 2
+";
+    assert_eq!(check_lints(text, &[(Lint::UnusedVariables, LintLevel::Deny)]),
+               (vec!["Unused variable `x`".to_string()], vec![]));
+    assert_eq!(check_lints(text, &[(Lint::UnusedVariables, LintLevel::Allow)]),
+               (vec![], vec![]));
+}
+
+#[test]
+fn underscore_and_argument_bindings_may_be_unused() {
+    let _ = env_logger::init();
+    let text = r"
+let _x = 1
+in let f y = 2
+in f 3
+";
+    assert_eq!(check_lints(text, &[]), (vec![], vec![]));
+}
+
+#[test]
+fn shadowed_variable() {
+    let _ = env_logger::init();
+    let text = r"
+let x = 1
+in let x = x #Int+ 1
+in x
+";
+    assert_eq!(check_lints(text, &[]),
+               (vec![], vec!["`x` shadows an earlier binding of the same name".to_string()]));
+}
+
+#[test]
+fn unused_type_variable() {
+    let _ = env_logger::init();
+    let text = r"
+type Wrapper a = { value : Int }
+in type Option a = | None | Some a
+in 1
+";
+    assert_eq!(check_lints(text, &[]),
+               (vec![], vec!["Unused type variable `a`".to_string()]));
+}
+
+#[test]
+fn unused_match_binder() {
+    let _ = env_logger::init();
+    let text = r"
+type Option a = | None | Some a
+in match Some 1 with
+    | Some x -> 1
+    | None -> 0
+";
+    assert_eq!(check_lints(text, &[]),
+               (vec![], vec!["Unused variable `x` in `match` alternative".to_string()]));
+}
+
+#[test]
+fn variable_used_by_a_record_field_without_a_value() {
+    let _ = env_logger::init();
+    let text = r"
+let x = 1
+in { x }
+";
+    assert_eq!(check_lints(text, &[]), (vec![], vec![]));
+}
@@ -0,0 +1,123 @@
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+extern crate gluon_check as check;
+
+use base::pos::Spanned;
+use check::typecheck::{MatchCheck, Typecheck, TypeError};
+
+mod support;
+
+/// Typechecks `text`, returning the errors and warnings which were found in `match` expressions
+fn check_matches(text: &str, match_check: MatchCheck) -> (Vec<String>, Vec<String>) {
+    let mut expr = support::parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = support::MockEnv::new();
+    let interner = support::get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env);
+    tc.set_match_check(match_check);
+
+    fn match_errors(errors: Vec<Spanned<TypeError<base::symbol::Symbol>, base::pos::BytePos>>)
+                    -> Vec<String> {
+        errors.into_iter()
+            .filter(|error| match error.value {
+                TypeError::NonExhaustiveMatch(_) |
+                TypeError::UnreachableAlternative => true,
+                _ => false,
+            })
+            .map(|error| error.value.to_string())
+            .collect()
+    }
+    let errors = match tc.typecheck_expr(&mut expr) {
+        Ok(_) => Vec::new(),
+        Err(errors) => match_errors(errors.errors),
+    };
+    (errors, match_errors(tc.take_warnings().errors))
+}
+
+#[test]
+fn exhaustive_match() {
+    let _ = env_logger::init();
+    let text = r"
+type Option a = | None | Some a
+in match Some 1 with
+    | Some x -> x
+    | None -> 2
+";
+    assert_eq!(check_matches(text, MatchCheck::Deny), (vec![], vec![]));
+}
+
+#[test]
+fn non_exhaustive_match_is_a_warning_by_default() {
+    let _ = env_logger::init();
+    let text = r"
+type Option a = | None | Some a
+in match Some 1 with
+    | Some x -> x
+";
+    let expected = "Non-exhaustive patterns in `match` expression, `None` is not matched";
+    assert_eq!(check_matches(text, MatchCheck::Warn),
+               (vec![], vec![expected.to_string()]));
+    assert_eq!(check_matches(text, MatchCheck::Deny),
+               (vec![expected.to_string()], vec![]));
+}
+
+#[test]
+fn unreachable_alternative() {
+    let _ = env_logger::init();
+    let text = r"
+type Test = | A | B Int
+in match A with
+    | A -> 1
+    | x -> 2
+    | B y -> y
+";
+    assert_eq!(check_matches(text, MatchCheck::Deny),
+               (vec!["Unreachable alternative in `match` expression".to_string()], vec![]));
+}
+
+#[test]
+fn nested_non_exhaustive_match() {
+    let _ = env_logger::init();
+    let text = r"
+type Option a = | None | Some a
+in match Some (Some 1) with
+    | Some (Some 1) -> 1
+    | Some None -> 2
+    | None -> 3
+";
+    let expected = "Non-exhaustive patterns in `match` expression, `Some (Some _)` is not \
+                    matched";
+    assert_eq!(check_matches(text, MatchCheck::Deny),
+               (vec![expected.to_string()], vec![]));
+}
+
+#[test]
+fn guarded_alternative_does_not_make_match_exhaustive() {
+    let _ = env_logger::init();
+    let text = r"
+type Option a = | None | Some a
+in match Some 1 with
+    | Some x if x #Int< 0 -> x
+    | Some x -> x
+    | None if True -> 2
+";
+    let expected = "Non-exhaustive patterns in `match` expression, `None` is not matched";
+    assert_eq!(check_matches(text, MatchCheck::Deny),
+               (vec![expected.to_string()], vec![]));
+}
+
+#[test]
+fn exhaustive_tuple_match() {
+    let _ = env_logger::init();
+    let text = r"
+type Option a = | None | Some a
+in match (Some 1, None) with
+    | (Some x, _) -> x
+    | (None, Some y) -> y
+    | (None, None) -> 0
+";
+    assert_eq!(check_matches(text, MatchCheck::Deny), (vec![], vec![]));
+}
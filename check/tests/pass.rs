@@ -6,6 +6,7 @@ extern crate gluon_check as check;
 
 use base::ast::{self, Expr, Pattern, Typed};
 use base::pos::{BytePos, Span};
+use base::symbol::Symbol;
 use base::types::{self, Field, Generic, Kind, Type};
 
 use support::{MockEnv, alias, intern, typ};
@@ -261,6 +262,38 @@ in match Some 1 with
     assert_eq!(result, expected);
 }
 
+#[test]
+fn nested_pattern() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Option a = | None | Some a
+in match Some (Some "") with
+    | Some (Some x) -> x
+    | _ -> "a"
+"#;
+    let result = support::typecheck(text);
+    let expected = Ok(typ("String"));
+
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn tuple_pattern() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let (x, y) = (1, "a")
+in match (y, x) with
+    | ("b", z) -> z
+    | _ -> x
+"#;
+    let result = support::typecheck(text);
+    let expected = Ok(typ("Int"));
+
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn real_type() {
     let _ = env_logger::init();
@@ -864,3 +897,248 @@ make_applicative applicative_Function
     let result = support::typecheck(text);
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
+
+#[test]
+fn expressions_with_syntax_errors_are_skipped() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let x = 1 #Int+
+let y : Int = x
+y
+"#;
+    let (_, result) = support::typecheck_partial_expr(text);
+    assert_eq!(result, Ok(typ("Int")));
+}
+
+#[test]
+fn implicit_argument() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Show a = { show : a -> String }
+let show_Int : Show Int = { show = \x -> "Int" }
+let show_Float : Show Float = { show = \x -> "Float" }
+let show d x : [Show a] -> a -> String = d.show x
+{ x = show 1, y = show 2.0 }
+"#;
+    let result = support::typecheck(text);
+    let fields = vec![
+        Field {
+            name: intern("x"),
+            typ: typ("String"),
+        },
+        Field {
+            name: intern("y"),
+            typ: typ("String"),
+        },
+    ];
+    let expected = Ok(Type::record(vec![], fields));
+
+    assert_eq!(result.map(support::close_record), expected);
+}
+
+#[test]
+fn implicit_argument_inserted_into_expression() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Show a = { show : a -> String }
+type List a = | Nil | Cons a (List a)
+let show_Int : Show Int = { show = \x -> "Int" }
+let show_List d : [Show a] -> Show (List a) = { show = \x -> "List" }
+let show d x : [Show a] -> a -> String = d.show x
+show (Cons 1 Nil)
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert_eq!(result, Ok(typ("String")));
+
+    fn tail_expr(expr: &ast::SpannedExpr<Symbol>) -> &ast::SpannedExpr<Symbol> {
+        match expr.value {
+            Expr::LetBindings(_, ref body) |
+            Expr::TypeBindings(_, ref body) => tail_expr(body),
+            _ => expr,
+        }
+    }
+    fn name(expr: &ast::SpannedExpr<Symbol>) -> &str {
+        match expr.value {
+            Expr::Ident(ref id) => id.name.declared_name(),
+            _ => panic!("Expected an identifier, found {:?}", expr),
+        }
+    }
+    // show (show_List show_Int) (Cons 1 Nil)
+    assert_match!(tail_expr(&expr).value, Expr::App(ref func, _) => {
+        assert_match!(func.value, Expr::App(ref show, ref implicit_args) => {
+            assert_eq!(name(show), "show");
+            assert_match!(implicit_args[0].value, Expr::App(ref show_list, ref args) => {
+                assert_eq!(name(show_list), "show_List");
+                assert_eq!(name(&args[0]), "show_Int");
+            });
+        });
+    });
+}
+
+#[test]
+fn higher_rank_function_argument() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let f g : (forall a . a -> a) -> String =
+    let x = g 1
+    g "a"
+f (\x -> x)
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(typ("String")));
+}
+
+#[test]
+fn forall_in_record_field() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Option a = | None | Some a
+type Functor f = { map : forall a b . (a -> b) -> f a -> f b }
+let functor_Option : Functor Option = {
+    map = \f x ->
+        match x with
+        | Some y -> Some (f y)
+        | None -> None
+}
+let map_twice functor : Functor Option -> Option String =
+    let x = functor.map (\x -> x #Int+ 1) (Some 1)
+    functor.map (\x -> "a") x
+map_twice functor_Option
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(support::typ_a("Option", vec![typ("String")])));
+}
+
+#[test]
+fn gadt_typed_interpreter() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Expr a =
+    | IntLit : Int -> Expr Int
+    | IsZero : Expr Int -> Expr Bool
+    | Add : Expr Int -> Expr Int -> Expr Int
+    | If : Expr Bool -> Expr a -> Expr a -> Expr a
+let eval e : Expr a -> a =
+    match e with
+    | IntLit x -> x
+    | IsZero x -> eval x #Int== 0
+    | Add l r -> eval l #Int+ eval r
+    | If p t f -> if eval p then eval t else eval f
+eval (If (IsZero (IntLit 0)) (IntLit 1) (Add (IntLit 2) (IntLit 3)))
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(typ("Int")));
+}
+
+#[test]
+fn gadt_refinement_is_local_to_alternative() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Expr a = | IntLit : Int -> Expr Int | IsZero : Expr Int -> Expr Bool
+let size e : Expr a -> Int =
+    match e with
+    | IntLit x -> x
+    | IsZero x -> 1 #Int+ size x
+size (IsZero (IntLit 0))
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(typ("Int")));
+}
+
+#[test]
+fn mutually_recursive_gadts() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Expr a =
+    | IntLit : Int -> Expr Int
+    | IsZero : Expr Int -> Expr Bool
+    | If : Expr Bool -> Expr a -> Expr a -> Expr a
+    | Force : Thunk a -> Expr a
+and Thunk a =
+    | Delay : Expr a -> Thunk a
+    | Now : Int -> Thunk Int
+let eval e : Expr a -> a =
+    match e with
+    | IntLit x -> x
+    | IsZero x -> eval x #Int== 0
+    | If p t f -> if eval p then eval t else eval f
+    | Force t -> force t
+and force t : Thunk a -> a =
+    match t with
+    | Delay e -> eval e
+    | Now x -> x
+eval (If (IsZero (Force (Now 0))) (Force (Delay (IntLit 1))) (IntLit 2))
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(typ("Int")));
+}
+
+#[test]
+fn module_signature_hides_abstract_types_and_undeclared_fields() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Stack a = | Empty | Cons a (Stack a)
+let push x s = Cons x s
+{ Stack, empty = Empty, push, size = 0 }
+"#;
+    let signature = r#"
+{ Stack, empty : Stack a, push : a -> Stack a -> Stack a }
+"#;
+    let result = support::typecheck_signature(text, signature);
+
+    let module = result.unwrap_or_else(|err| panic!("{}", err));
+    let stack = module.type_field_iter().next().expect("Stack");
+    assert_eq!(stack.typ.typ, None);
+    let fields: Vec<&str> = module.field_iter().map(|field| field.name.as_ref()).collect();
+    assert_eq!(fields, ["empty", "push"]);
+}
+
+#[test]
+fn module_signature_with_transparent_type_and_specialized_value() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Pair a = (a, a)
+let pair x = (x, x)
+{ Pair, pair, id = \x -> x }
+"#;
+    let signature = r#"
+{ Pair a = (a, a), pair : b -> Pair b, id : Int -> Int }
+"#;
+    let result = support::typecheck_signature(text, signature);
+
+    let module = result.unwrap_or_else(|err| panic!("{}", err));
+    let pair = module.type_field_iter().next().expect("Pair");
+    assert!(pair.typ.typ.is_some());
+    let id = module.field_iter().find(|field| field.name.as_ref() == "id").expect("id");
+    assert_eq!(id.typ, Type::function(vec![typ("Int")], typ("Int")));
+}
+
+#[test]
+fn implicit_argument_hidden_by_binding() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Show a = { show : a -> String }
+let show_Int : Show Int = { show = \x -> "Int" }
+let show_Int2 : Show Int = { show = \x -> "Int2" }
+let show_Int2 = 1
+let show d x : [Show a] -> a -> String = d.show x
+show show_Int2
+"#;
+    let result = support::typecheck(text);
+    assert_eq!(result, Ok(typ("String")));
+}
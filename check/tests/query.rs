@@ -0,0 +1,89 @@
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+extern crate gluon_check as check;
+
+use base::pos::{BytePos, Span};
+use check::query;
+
+mod support;
+
+fn span_text(text: &str, span: Span<BytePos>) -> &str {
+    &text[span.start.to_usize()..span.end.to_usize()]
+}
+
+#[test]
+fn definition_of_variable() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let abc = 1
+let f x = x #Int+ abc
+f 2
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let definition = |pos: usize| {
+        query::definition(&(), "test", &expr, BytePos::from(pos)).map(|location| {
+            assert_eq!(location.source_name, "test");
+            location.span.start
+        })
+    };
+
+    assert_eq!(definition(text.rfind("abc").unwrap()),
+               Some(BytePos::from(text.find("abc").unwrap())));
+    assert_eq!(definition(text.find("x #Int+").unwrap()),
+               Some(BytePos::from(text.find("f x").unwrap())));
+    assert_eq!(definition(text.rfind("f 2").unwrap()),
+               Some(BytePos::from(text.find("f x").unwrap())));
+    // Bindings are their own definition
+    assert_eq!(definition(text.find("abc").unwrap()),
+               Some(BytePos::from(text.find("abc").unwrap())));
+}
+
+#[test]
+fn definition_of_constructor() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = 1
+type Test = | A Int | B
+let y : Test = B
+y
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let location = query::definition(&(), "test", &expr, BytePos::from(text.rfind("B").unwrap()));
+    assert_eq!(location.map(|location| span_text(text, location.span).trim()),
+               Some("type Test = | A Int | B"));
+}
+
+#[test]
+fn references_of_variable() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let abc = 1
+let f x = x #Int+ abc
+f (abc #Int+ f abc)
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let references = |pos: usize| query::references(&expr, BytePos::from(pos));
+
+    let abc = references(text.find("abc").unwrap());
+    assert_eq!(abc.len(), 3, "{:?}", abc);
+    assert!(abc.iter().all(|&span| span_text(text, span) == "abc"));
+    assert_eq!(abc, references(text.rfind("abc").unwrap()));
+
+    let f = references(text.rfind("f abc").unwrap());
+    assert_eq!(f.iter().map(|&span| span.start).collect::<Vec<_>>(),
+               vec![BytePos::from(text.find("f (").unwrap()),
+                    BytePos::from(text.rfind("f abc").unwrap())]);
+
+    assert_eq!(references(text.find("= 1").unwrap()), vec![]);
+}
@@ -140,6 +140,23 @@ pub fn typecheck_expr(text: &str) -> (SpannedExpr<Symbol>, Result<ArcType, typec
     (expr, result)
 }
 
+/// Typechecks the module `text` and checks it against the record type `signature`
+#[allow(dead_code)]
+pub fn typecheck_signature(text: &str, signature: &str) -> Result<ArcType, typecheck::Error> {
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let (signature, fields) =
+        parser::parse_signature(&mut SymbolModule::new("test".into(), &mut interner), signature)
+            .unwrap_or_else(|err| panic!("{}", err));
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env);
+
+    let typ = try!(tc.typecheck_expr(&mut expr));
+    tc.check_signature(&signature, &fields, &typ)
+}
+
 #[allow(dead_code)]
 pub fn typecheck_partial_expr(text: &str)
                               -> (SpannedExpr<Symbol>, Result<ArcType, typecheck::Error>) {
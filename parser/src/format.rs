@@ -0,0 +1,974 @@
+//! Pretty printer which turns gluon source code into its canonically formatted form.
+//!
+//! Comments are not part of the AST so they are collected from the trivia of the token stream
+//! and emitted in front of the binding, alternative or element which follows them.
+
+use std::usize;
+
+use base::ast::{is_operator_char, DisplayEnv, Expr, IdentEnv, Literal, Pattern, SpannedExpr,
+                SpannedPattern, TypeBinding, ValueBinding};
+use base::pos::BytePos;
+use base::types::{self, ArcType, BuiltinType, Type};
+
+use combine_language::Fixity;
+
+use lexer::{tokenize, Token, Trivia};
+use {Error, fixity, parse_expr_, precedence};
+
+/// The width which the printer tries to keep lines within
+const WIDTH: usize = 100;
+/// Number of spaces added for each level of indentation
+const INDENT: usize = 4;
+
+struct StringEnv;
+
+impl DisplayEnv for StringEnv {
+    type Ident = String;
+
+    fn string<'a>(&'a self, ident: &'a String) -> &'a str {
+        ident
+    }
+}
+
+impl IdentEnv for StringEnv {
+    fn from_str(&mut self, s: &str) -> String {
+        String::from(s)
+    }
+}
+
+/// Parses `input` and returns it formatted in the canonical style. Any comments in `input` are
+/// kept in the output.
+pub fn format_expr(input: &str) -> Result<String, Error> {
+    let expr = try!(parse_expr_(&mut StringEnv, input).map_err(|(_, err)| err));
+    let scanned = try!(scan(input));
+    let mut printer = Printer::new(input, scanned);
+    let start = printer.start_of(&expr);
+    printer.line(start);
+    printer.expr(&expr);
+    Ok(printer.finish())
+}
+
+#[derive(Clone, Copy)]
+struct Comment {
+    start: usize,
+    end: usize,
+    /// `true` if the comment is preceded by code on the same line
+    trailing: bool,
+}
+
+/// Source positions which are not recorded in the AST
+struct Scanned {
+    comments: Vec<Comment>,
+    /// Start and end of each doc comment
+    doc_comments: Vec<(usize, usize)>,
+    /// Positions of the `type` and `and` keywords
+    keywords: Vec<(usize, bool)>,
+}
+
+/// Collects the comments and the keywords which start type bindings from the tokens of `source`
+fn scan(source: &str) -> Result<Scanned, Error> {
+    let mut scanned = Scanned {
+        comments: Vec::new(),
+        doc_comments: Vec::new(),
+        keywords: Vec::new(),
+    };
+    for token in try!(tokenize(source)) {
+        for trivia in &token.leading_trivia {
+            match trivia.value {
+                Trivia::LineComment | Trivia::BlockComment => {
+                    let start = trivia.span.start.to_usize();
+                    let end = trivia.span.end.to_usize();
+                    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+                    scanned.comments.push(Comment {
+                        start: start,
+                        end: source[..end].trim_right().len(),
+                        trailing: !source[line_start..start].trim().is_empty(),
+                    });
+                }
+                Trivia::Whitespace => (),
+            }
+        }
+        let start = token.token.span.start.to_usize();
+        match token.token.value {
+            // Doc comments are stored in the AST
+            Token::DocComment(_) => {
+                scanned.doc_comments.push((start, token.token.span.end.to_usize()))
+            }
+            Token::Type => scanned.keywords.push((start, true)),
+            Token::And => scanned.keywords.push((start, false)),
+            _ => (),
+        }
+    }
+    Ok(scanned)
+}
+
+fn is_hole<Id>(typ: &ArcType<Id>) -> bool {
+    match **typ {
+        Type::Hole => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the expression can be started on the same line as the `=` or `->` before it
+/// and continue over multiple lines
+fn hangs<Id>(expr: &SpannedExpr<Id>) -> bool {
+    match expr.value {
+        Expr::Lambda(_) => true,
+        Expr::Record { ref types, ref exprs, .. } => !types.is_empty() || !exprs.is_empty(),
+        Expr::Array(ref array) => !array.exprs.is_empty(),
+        Expr::Tuple { ref elems, .. } => !elems.is_empty(),
+        Expr::App(_, ref args) => args.last().map_or(false, |arg| hangs(arg)),
+        Expr::Infix(_, _, ref rhs) => hangs(rhs),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the expression never needs parentheses
+fn is_atomic<Id>(expr: &SpannedExpr<Id>) -> bool {
+    match expr.value {
+        Expr::Literal(Literal::Int(i)) => i >= 0,
+        Expr::Literal(Literal::Float(f)) => f >= 0.0,
+        Expr::Ident(_) |
+        Expr::Literal(_) |
+        Expr::Record { .. } |
+        Expr::Array(_) |
+        Expr::Tuple { .. } |
+        Expr::Projection(..) => true,
+        _ => false,
+    }
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    doc_comments: Vec<(usize, usize)>,
+    keywords: Vec<(usize, bool)>,
+    next_keyword: usize,
+    out: String,
+    indent: usize,
+    /// Set while trying to print something on a single line
+    flat: bool,
+    /// Set if something could not be printed on a single line while `flat` was set
+    failed: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, scanned: Scanned) -> Printer<'a> {
+        Printer {
+            source: source,
+            comments: scanned.comments,
+            next_comment: 0,
+            doc_comments: scanned.doc_comments,
+            keywords: scanned.keywords,
+            next_keyword: 0,
+            out: String::new(),
+            indent: 0,
+            flat: false,
+            failed: false,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush_comments(usize::MAX);
+        let len = self.out.trim_right().len();
+        self.out.truncate(len);
+        self.out.push('\n');
+        self.out
+    }
+
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+        if self.flat && self.column() > WIDTH {
+            self.failed = true;
+        }
+    }
+
+    fn line_start(&self) -> usize {
+        self.out.rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn column(&self) -> usize {
+        self.out[self.line_start()..].chars().count()
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out[self.line_start()..].trim().is_empty()
+    }
+
+    fn trim_line_end(&mut self) {
+        let len = self.out.trim_right_matches(' ').len();
+        self.out.truncate(len);
+    }
+
+    fn newline(&mut self) {
+        if self.flat {
+            self.failed = true;
+            return;
+        }
+        self.trim_line_end();
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            for _ in 0..self.indent {
+                self.out.push(' ');
+            }
+        }
+    }
+
+    /// Starts a new line for the item at `pos`, emitting the comments which precede it first
+    fn line(&mut self, pos: BytePos) {
+        if self.flat {
+            self.failed = true;
+            return;
+        }
+        let pos = pos.to_usize();
+        self.flush_comments(pos);
+        self.blank_line(pos);
+        self.newline();
+    }
+
+    fn flush_comments(&mut self, pos: usize) {
+        let source = self.source;
+        while self.next_comment < self.comments.len() &&
+              self.comments[self.next_comment].start < pos {
+            let comment = self.comments[self.next_comment];
+            self.next_comment += 1;
+            if comment.trailing && !self.at_line_start() {
+                self.push(" ");
+            } else {
+                self.blank_line(comment.start);
+                self.newline();
+            }
+            self.push(&source[comment.start..comment.end]);
+        }
+    }
+
+    /// Emits an empty line if the item at `pos` were separated from the code before it by an
+    /// empty line
+    fn blank_line(&mut self, pos: usize) {
+        if self.out.trim().is_empty() {
+            return;
+        }
+        let source = self.source;
+        let mut end = pos.min(source.len());
+        let line_start = source[..end].rfind('\n').map_or(0, |i| i + 1);
+        // The item may be preceded by a keyword on the same line
+        match source[line_start..end].trim() {
+            "" | "let" | "type" | "and" | "|" => end = line_start,
+            _ => return,
+        }
+        // Skip the doc comments of the item
+        loop {
+            let doc = self.doc_comments.iter().rev().find(|doc| doc.1 <= end).cloned();
+            match doc {
+                Some((start, doc_end)) if source[doc_end..end].trim().is_empty() &&
+                                          !source[doc_end..end].contains("\n\n") => {
+                    end = source[..start].rfind('\n').map_or(0, |i| i + 1);
+                }
+                _ => break,
+            }
+        }
+        let code = source[..end].trim_right();
+        if !code.is_empty() && source[code.len()..end].matches('\n').count() > 1 {
+            self.trim_line_end();
+            self.out.push('\n');
+        }
+    }
+
+    /// Tries to print `f` on a single line, undoing any output if that was not possible
+    fn try_flat<F>(&mut self, f: F) -> bool
+        where F: FnOnce(&mut Printer<'a>),
+    {
+        if self.flat {
+            if !self.failed {
+                f(self);
+            }
+            return !self.failed;
+        }
+        let len = self.out.len();
+        self.flat = true;
+        f(self);
+        self.flat = false;
+        let fits = !self.failed && self.column() <= WIDTH;
+        self.failed = false;
+        if !fits {
+            self.out.truncate(len);
+        }
+        fits
+    }
+
+    /// Returns the position of the first token of `expr`
+    fn start_of<Id>(&self, expr: &SpannedExpr<Id>) -> BytePos {
+        match expr.value {
+            // Nested bindings share the span of the outermost binding so the position needs to be
+            // retrieved from the bindings themselves
+            Expr::LetBindings(ref binds, _) => binds[0].name.span.start,
+            Expr::TypeBindings(..) => {
+                self.keywords[self.next_keyword..]
+                    .iter()
+                    .find(|keyword| keyword.1)
+                    .map_or(expr.span.start, |keyword| BytePos::from(keyword.0))
+            }
+            _ => expr.span.start,
+        }
+    }
+
+    fn ident(&mut self, name: &str) {
+        if name.starts_with(is_operator_char) {
+            self.push("(");
+            self.push(name);
+            self.push(")");
+        } else {
+            self.push(name);
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match *literal {
+            Literal::Byte(b) => self.push(&format!("{}b", b)),
+            Literal::Int(i) => self.push(&i.to_string()),
+            Literal::Float(f) => {
+                let mut s = f.to_string();
+                if !s.contains('.') {
+                    s.push_str(".0");
+                }
+                self.push(&s);
+            }
+            Literal::String(ref s) => {
+                self.out.push('"');
+                for c in s.chars() {
+                    self.escaped(c, '"');
+                }
+                self.out.push('"');
+            }
+            Literal::Char(c) => {
+                self.out.push('\'');
+                self.escaped(c, '\'');
+                self.out.push('\'');
+            }
+        }
+    }
+
+    fn escaped(&mut self, c: char, quote: char) {
+        match c {
+            '\\' => self.push("\\\\"),
+            '\n' => self.push("\\n"),
+            '\r' => self.push("\\r"),
+            '\t' => self.push("\\t"),
+            _ if c == quote => {
+                self.out.push('\\');
+                self.out.push(c);
+            }
+            _ => self.out.push(c),
+        }
+    }
+
+    fn doc_comment(&mut self, comment: &str) {
+        for line in comment.trim().lines() {
+            self.push("/// ");
+            self.push(line.trim());
+            self.trim_line_end();
+            self.newline();
+        }
+    }
+
+    fn expr<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>) {
+        self.expr_(expr, true)
+    }
+
+    /// Prints `expr`. `tail` is `false` if more of the enclosing expression follows `expr` and
+    /// `expr` must therefore not extend as far as possible.
+    fn expr_<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>, tail: bool) {
+        if self.flat && self.failed {
+            return;
+        }
+        match expr.value {
+            Expr::Ident(ref id) => self.ident(id.name.as_ref()),
+            Expr::Literal(ref literal) => self.literal(literal),
+            Expr::App(ref func, ref args) => {
+                self.arg(func);
+                for arg in args {
+                    self.push(" ");
+                    self.arg(arg);
+                }
+            }
+            Expr::Lambda(ref lambda) => {
+                self.push("\\");
+                for (i, arg) in lambda.args.iter().enumerate() {
+                    if i != 0 {
+                        self.push(" ");
+                    }
+                    self.ident(arg.name.as_ref());
+                }
+                self.push(" ->");
+                self.body(&lambda.body);
+            }
+            Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+                self.if_else(pred, if_true, if_false)
+            }
+            Expr::Match(ref expr, ref alts) => {
+                if self.flat {
+                    self.failed = true;
+                    return;
+                }
+                let indent = self.indent;
+                self.indent = self.column();
+                self.push("match ");
+                self.operand(expr);
+                self.push(" with");
+                for alt in alts {
+                    self.line(alt.pattern.span.start);
+                    self.push("| ");
+                    self.pattern(&alt.pattern);
+                    if let Some(ref guard) = alt.guard {
+                        self.push(" if ");
+                        self.operand(guard);
+                    }
+                    self.push(" ->");
+                    self.body(&alt.expr);
+                }
+                self.indent = indent;
+            }
+            Expr::Infix(ref lhs, ref op, ref rhs) => self.infix(lhs, op.name.as_ref(), rhs, tail),
+            Expr::Projection(ref expr, ref field, _) => {
+                self.arg(expr);
+                self.push(".");
+                self.ident(field.as_ref());
+            }
+            Expr::Array(ref array) => {
+                let positions: Vec<_> = array.exprs
+                    .iter()
+                    .map(|expr| Some(self.start_of(expr)))
+                    .collect();
+                self.sequence("[",
+                              "]",
+                              &positions,
+                              true,
+                              |p: &mut Printer<'a>, i| p.expr(&array.exprs[i]));
+            }
+            Expr::Record { ref types, ref exprs, .. } => {
+                if types.is_empty() && exprs.is_empty() {
+                    self.push("{}");
+                    return;
+                }
+                let positions: Vec<_> = types.iter()
+                    .map(|_| None)
+                    .chain(exprs.iter().map(|field| field.1.as_ref().map(|e| self.start_of(e))))
+                    .collect();
+                self.sequence("{ ", " }", &positions, true, |p: &mut Printer<'a>, i| {
+                    if i < types.len() {
+                        let (ref name, ref typ) = types[i];
+                        p.push(name.as_ref());
+                        if let Some(ref typ) = *typ {
+                            p.push(" = ");
+                            p.typ(typ, 0);
+                        }
+                    } else {
+                        let (ref name, ref expr) = exprs[i - types.len()];
+                        p.ident(name.as_ref());
+                        if let Some(ref expr) = *expr {
+                            p.push(" =");
+                            p.body(expr);
+                        }
+                    }
+                });
+            }
+            Expr::Tuple { ref elems, .. } => {
+                let positions: Vec<_> = elems.iter().map(|e| Some(self.start_of(e))).collect();
+                self.sequence("(",
+                              ")",
+                              &positions,
+                              false,
+                              |p: &mut Printer<'a>, i| p.expr(&elems[i]));
+            }
+            Expr::LetBindings(ref binds, ref body) => self.let_bindings(binds, body),
+            Expr::TypeBindings(ref binds, ref body) => self.type_bindings(binds, body),
+            Expr::Block(ref exprs) => {
+                if self.flat {
+                    self.failed = true;
+                    return;
+                }
+                let indent = self.indent;
+                self.indent = self.column();
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i != 0 {
+                        let pos = self.start_of(expr);
+                        self.line(pos);
+                    }
+                    self.expr(expr);
+                }
+                self.indent = indent;
+            }
+            Expr::Error => {
+                // Keep the source text which could not be parsed as is
+                let source = self.source;
+                self.push(&source[expr.span.start.to_usize()..expr.span.end.to_usize()]);
+            }
+        }
+    }
+
+    /// Prints an expression which is followed by more code, adding parentheses unless it is
+    /// an application, an infix expression or an atomic expression
+    fn operand<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>) {
+        match expr.value {
+            Expr::App(..) |
+            Expr::Infix(..) => self.expr_(expr, false),
+            _ if is_atomic(expr) => self.expr(expr),
+            _ => self.parens(expr),
+        }
+    }
+
+    /// Prints a function or an argument to a function
+    fn arg<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>) {
+        if is_atomic(expr) {
+            self.expr(expr)
+        } else {
+            self.parens(expr)
+        }
+    }
+
+    fn parens<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>) {
+        self.push("(");
+        self.expr(expr);
+        self.push(")");
+    }
+
+    /// Prints the expression which follows a `=` or a `->`
+    fn body<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>) {
+        if self.try_flat(|p: &mut Printer<'a>| {
+            p.push(" ");
+            p.expr(expr);
+        }) || self.flat {
+            return;
+        }
+        if hangs(expr) {
+            self.push(" ");
+            self.expr(expr);
+        } else {
+            self.indent += INDENT;
+            let pos = self.start_of(expr);
+            self.line(pos);
+            self.expr(expr);
+            self.indent -= INDENT;
+        }
+    }
+
+    fn if_else<Id: AsRef<str>>(&mut self,
+                               pred: &SpannedExpr<Id>,
+                               if_true: &SpannedExpr<Id>,
+                               if_false: &SpannedExpr<Id>) {
+        if self.try_flat(|p: &mut Printer<'a>| {
+            p.push("if ");
+            p.operand(pred);
+            p.push(" then ");
+            p.operand(if_true);
+            p.push(" else ");
+            p.expr(if_false);
+        }) || self.flat {
+            return;
+        }
+        self.push("if ");
+        self.operand(pred);
+        self.push(" then");
+        self.indented(if_true);
+        self.newline();
+        self.push("else");
+        match if_false.value {
+            Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+                self.push(" ");
+                self.if_else(pred, if_true, if_false);
+            }
+            _ => self.indented(if_false),
+        }
+    }
+
+    /// Prints `expr` on a new, indented line
+    fn indented<Id: AsRef<str>>(&mut self, expr: &SpannedExpr<Id>) {
+        self.indent += INDENT;
+        let pos = self.start_of(expr);
+        self.line(pos);
+        self.expr(expr);
+        self.indent -= INDENT;
+    }
+
+    fn infix<Id: AsRef<str>>(&mut self,
+                             lhs: &SpannedExpr<Id>,
+                             op: &str,
+                             rhs: &SpannedExpr<Id>,
+                             tail: bool) {
+        let prec = precedence(op);
+        let right_assoc = match fixity(op) {
+            Fixity::Right => true,
+            Fixity::Left => false,
+        };
+        let break_line = !self.flat && self.at_line_start();
+        self.infix_operand(lhs, prec, right_assoc, false);
+        if break_line && !(tail && hangs(rhs)) {
+            let fits = self.try_flat(|p: &mut Printer<'a>| {
+                p.push(" ");
+                p.push(op);
+                p.push(" ");
+                p.infix_operand(rhs, prec, !right_assoc, tail);
+            });
+            if !fits {
+                self.indent += INDENT;
+                self.newline();
+                self.push(op);
+                self.push(" ");
+                self.infix_operand(rhs, prec, !right_assoc, tail);
+                self.indent -= INDENT;
+            }
+        } else {
+            self.push(" ");
+            self.push(op);
+            self.push(" ");
+            self.infix_operand(rhs, prec, !right_assoc, tail);
+        }
+    }
+
+    fn infix_operand<Id: AsRef<str>>(&mut self,
+                                     expr: &SpannedExpr<Id>,
+                                     prec: i32,
+                                     parens_on_equal: bool,
+                                     tail: bool) {
+        match expr.value {
+            Expr::Infix(ref lhs, ref op, ref rhs) => {
+                let op_prec = precedence(op.name.as_ref());
+                if op_prec < prec || (op_prec == prec && parens_on_equal) {
+                    self.parens(expr);
+                } else {
+                    self.infix(lhs, op.name.as_ref(), rhs, tail);
+                }
+            }
+            Expr::Lambda(_) if tail => self.expr(expr),
+            _ => self.operand(expr),
+        }
+    }
+
+    /// Prints a comma separated sequence, on a single line if it fits and otherwise with one item
+    /// on each line
+    fn sequence<F>(&mut self,
+                   open: &str,
+                   close: &str,
+                   positions: &[Option<BytePos>],
+                   trailing_comma: bool,
+                   item: F)
+        where F: Fn(&mut Printer<'a>, usize),
+    {
+        if self.try_flat(|p: &mut Printer<'a>| {
+            p.push(open);
+            for i in 0..positions.len() {
+                if i != 0 {
+                    p.push(", ");
+                }
+                item(p, i);
+            }
+            p.push(close);
+        }) || self.flat {
+            return;
+        }
+        self.push(open.trim());
+        self.indent += INDENT;
+        for (i, pos) in positions.iter().enumerate() {
+            match *pos {
+                Some(pos) => self.line(pos),
+                None => self.newline(),
+            }
+            item(self, i);
+            if trailing_comma || i + 1 != positions.len() {
+                self.push(",");
+            }
+        }
+        self.indent -= INDENT;
+        self.newline();
+        self.push(close.trim());
+    }
+
+    fn let_bindings<Id: AsRef<str>>(&mut self,
+                                    binds: &[ValueBinding<Id>],
+                                    body: &SpannedExpr<Id>) {
+        if self.flat {
+            self.failed = true;
+            return;
+        }
+        let indent = self.indent;
+        self.indent = self.column();
+        for (i, bind) in binds.iter().enumerate() {
+            if i != 0 {
+                self.line(bind.name.span.start);
+            }
+            if let Some(ref comment) = bind.comment {
+                self.doc_comment(comment);
+            }
+            self.push(if i == 0 { "let " } else { "and " });
+            self.pattern(&bind.name);
+            for arg in &bind.args {
+                self.push(" ");
+                self.ident(arg.name.as_ref());
+            }
+            if !is_hole(&bind.typ) {
+                self.push(" : ");
+                self.typ(&bind.typ, 0);
+            }
+            self.push(" =");
+            self.body(&bind.expr);
+        }
+        let pos = self.start_of(body);
+        self.line(pos);
+        self.expr(body);
+        self.indent = indent;
+    }
+
+    fn type_bindings<Id: AsRef<str>>(&mut self,
+                                     binds: &[TypeBinding<Id>],
+                                     body: &SpannedExpr<Id>) {
+        if self.flat {
+            self.failed = true;
+            return;
+        }
+        let indent = self.indent;
+        self.indent = self.column();
+        for (i, bind) in binds.iter().enumerate() {
+            // Consume the keyword of the binding so `start_of` finds the next binding
+            let is_type = i == 0;
+            let index = self.keywords[self.next_keyword..]
+                .iter()
+                .position(|keyword| keyword.1 == is_type);
+            if let Some(index) = index {
+                self.next_keyword += index + 1;
+                if i != 0 {
+                    let pos = self.keywords[self.next_keyword - 1].0;
+                    self.line(BytePos::from(pos));
+                }
+            } else if i != 0 {
+                self.newline();
+            }
+            if let Some(ref comment) = bind.comment {
+                self.doc_comment(comment);
+            }
+            self.push(if i == 0 { "type " } else { "and " });
+            self.push(bind.name.as_ref());
+            for arg in &bind.alias.args {
+                self.push(" ");
+                self.push(arg.id.as_ref());
+            }
+            self.push(" =");
+            if let Some(ref typ) = bind.alias.typ {
+                match **typ {
+                    Type::Variants(_) => {
+                        if !self.try_flat(|p: &mut Printer<'a>| {
+                            p.push(" ");
+                            p.typ(typ, 0);
+                        }) {
+                            self.indent += INDENT;
+                            self.variants(typ);
+                            self.indent -= INDENT;
+                        }
+                    }
+                    _ => {
+                        self.push(" ");
+                        self.typ(typ, 0);
+                    }
+                }
+            }
+        }
+        let pos = self.start_of(body);
+        self.line(pos);
+        self.expr(body);
+        self.indent = indent;
+    }
+
+    fn pattern<Id: AsRef<str>>(&mut self, pattern: &SpannedPattern<Id>) {
+        match pattern.value {
+            Pattern::Constructor(ref id, ref args) => {
+                self.ident(id.name.as_ref());
+                for arg in args {
+                    self.push(" ");
+                    match arg.value {
+                        Pattern::Constructor(_, ref args) if !args.is_empty() => {
+                            self.push("(");
+                            self.pattern(arg);
+                            self.push(")");
+                        }
+                        _ => self.pattern(arg),
+                    }
+                }
+            }
+            Pattern::Record { ref types, ref fields, .. } => {
+                if types.is_empty() && fields.is_empty() {
+                    self.push("{}");
+                    return;
+                }
+                self.push("{ ");
+                for (i, &(ref name, ref alias)) in types.iter().chain(fields).enumerate() {
+                    if i != 0 {
+                        self.push(", ");
+                    }
+                    self.ident(name.as_ref());
+                    if let Some(ref alias) = *alias {
+                        self.push(" = ");
+                        self.ident(alias.as_ref());
+                    }
+                }
+                self.push(" }");
+            }
+            Pattern::Tuple { ref elems, .. } => {
+                self.push("(");
+                for (i, elem) in elems.iter().enumerate() {
+                    if i != 0 {
+                        self.push(", ");
+                    }
+                    self.pattern(elem);
+                }
+                self.push(")");
+            }
+            Pattern::Ident(ref id) => self.ident(id.name.as_ref()),
+            Pattern::Literal(ref literal) => self.literal(literal),
+            Pattern::Wildcard(_) => self.push("_"),
+        }
+    }
+
+    /// Prints `typ` in source syntax. `prec` is 0 at the top level, 1 for the argument of a
+    /// function type and 2 for the argument of a type application.
+    fn typ<Id: AsRef<str>>(&mut self, typ: &ArcType<Id>, prec: u8) {
+        match **typ {
+            Type::Hole | Type::Variable(_) => self.push("_"),
+            Type::Generic(ref gen) => self.push(gen.id.as_ref()),
+            Type::Builtin(BuiltinType::Function) => self.push("(->)"),
+            Type::Builtin(builtin) => self.push(builtin.to_str()),
+            Type::Ident(ref id) => self.push(id.as_ref()),
+            Type::Alias(ref alias) => self.push(alias.name.as_ref()),
+            Type::Forall(ref params, ref body) => {
+                if prec > 0 {
+                    self.push("(");
+                    self.typ(typ, 0);
+                    self.push(")");
+                } else {
+                    self.push("forall");
+                    for param in params {
+                        self.push(" ");
+                        self.push(param.id.as_ref());
+                    }
+                    self.push(" . ");
+                    self.typ(body, 0);
+                }
+            }
+            Type::App(ref func, ref args) => {
+                if prec > 1 || (prec > 0 && typ.as_any_function().is_some()) {
+                    self.push("(");
+                    self.typ(typ, 0);
+                    self.push(")");
+                } else if let Some((arg, ret)) = typ.as_implicit_function() {
+                    self.push("[");
+                    self.typ(arg, 0);
+                    self.push("] -> ");
+                    self.typ(ret, 0);
+                } else if let Some((arg, ret)) = typ.as_function() {
+                    self.typ(arg, 1);
+                    self.push(" -> ");
+                    self.typ(ret, 0);
+                } else {
+                    self.typ(func, 2);
+                    for arg in args {
+                        self.push(" ");
+                        self.typ(arg, 2);
+                    }
+                }
+            }
+            Type::Variants(ref variants) => {
+                for (i, variant) in variants.iter().enumerate() {
+                    if i != 0 {
+                        self.push(" ");
+                    }
+                    self.variant(variant);
+                }
+            }
+            Type::Record(ref row) => self.record_type(row),
+            Type::EmptyRow | Type::ExtendRow { .. } => self.record_type(typ),
+        }
+    }
+
+    /// Prints each variant of `typ` on its own line
+    fn variants<Id: AsRef<str>>(&mut self, typ: &ArcType<Id>) {
+        if let Type::Variants(ref variants) = **typ {
+            for variant in variants {
+                self.newline();
+                self.variant(variant);
+            }
+        }
+    }
+
+    fn variant<Id: AsRef<str>>(&mut self, variant: &(Id, ArcType<Id>)) {
+        self.push("| ");
+        self.push(variant.0.as_ref());
+        if types::is_gadt_variant(&variant.1) {
+            self.push(" : ");
+            self.typ(&variant.1, 0);
+            return;
+        }
+        for arg in types::arg_iter(&variant.1) {
+            self.push(" ");
+            self.typ(arg, 2);
+        }
+    }
+
+    fn record_type<Id: AsRef<str>>(&mut self, row: &ArcType<Id>) {
+        let mut associated = Vec::new();
+        let mut fields = Vec::new();
+        let mut row = row;
+        while let Type::ExtendRow { ref types, fields: ref row_fields, ref rest } = **row {
+            associated.extend(types);
+            fields.extend(row_fields);
+            row = rest;
+        }
+        let is_tuple = associated.is_empty() && fields.len() > 1 &&
+                       fields.iter()
+            .enumerate()
+            .all(|(i, field)| field.name.as_ref() == format!("_{}", i));
+        if is_tuple {
+            self.push("(");
+            for (i, field) in fields.iter().enumerate() {
+                if i != 0 {
+                    self.push(", ");
+                }
+                self.typ(&field.typ, 0);
+            }
+            self.push(")");
+            return;
+        }
+        if associated.is_empty() && fields.is_empty() {
+            self.push("{}");
+            return;
+        }
+        let positions = vec![None; associated.len() + fields.len()];
+        self.sequence("{ ", " }", &positions, true, |p: &mut Printer<'a>, i| {
+            if i < associated.len() {
+                let alias = &associated[i].typ;
+                p.push(alias.name.as_ref());
+                if let Some(ref typ) = alias.typ {
+                    // A bare `Name` is parsed as an alias of itself, any other type field is
+                    // written with its definition
+                    let is_bare = match **typ {
+                        Type::Ident(ref id) => {
+                            alias.args.is_empty() && id.as_ref() == alias.name.as_ref()
+                        }
+                        _ => false,
+                    };
+                    if !is_bare {
+                        for arg in &alias.args {
+                            p.push(" ");
+                            p.push(arg.id.as_ref());
+                        }
+                        p.push(" = ");
+                        p.typ(typ, 0);
+                    }
+                }
+            } else {
+                let field = fields[i - associated.len()];
+                p.ident(field.name.as_ref());
+                p.push(" : ");
+                p.typ(&field.typ, 0);
+            }
+        });
+    }
+}
@@ -2,9 +2,10 @@ use std::cmp::Ordering;
 use std::fmt;
 
 use base::ast::is_operator_char;
-use base::pos::{BytePos, Column, Line, Location, Span, Spanned};
+use base::error::Errors;
+use base::pos::{self, BytePos, Column, Line, Location, Span, Spanned};
 
-use combine::primitives::{Consumed, Error as CombineError, RangeStream};
+use combine::primitives::{Consumed, Error as CombineError, Info, RangeStream};
 use combine::combinator::EnvParser;
 use combine::range::{take, take_while};
 use combine::*;
@@ -128,6 +129,7 @@ pub enum Token<Id> {
     If,
     Then,
     Else,
+    Forall,
     Open(Delimiter),
     Close(Delimiter),
     Lambda,
@@ -165,6 +167,7 @@ impl<Id> fmt::Display for Token<Id> {
             If => "If",
             Then => "Then",
             Else => "Else",
+            Forall => "Forall",
             Open(Brace) => "OpenBrace",
             Close(Brace) => "CloseBrace",
             Open(Paren) => "OpenParen",
@@ -210,6 +213,7 @@ impl<Id> Token<Id> {
             If => If,
             Then => Then,
             Else => Else,
+            Forall => Forall,
             Open(d) => Open(d),
             Close(d) => Close(d),
             Lambda => Lambda,
@@ -229,6 +233,73 @@ impl<Id> Token<Id> {
 
 pub type SpannedToken<Id> = Spanned<Token<Id>, Location>;
 
+/// Source text which is skipped by the lexer and is therefore not part of any token
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Trivia {
+    Whitespace,
+    /// A `//` comment, not including the line break which ends it
+    LineComment,
+    /// A `/* */` comment
+    BlockComment,
+}
+
+/// A token together with the trivia which precede it
+#[derive(Clone, PartialEq, Debug)]
+pub struct TriviaToken<Id> {
+    pub leading_trivia: Vec<Spanned<Trivia, BytePos>>,
+    pub token: Spanned<Token<Id>, BytePos>,
+}
+
+/// Side table which maps the start of each token to the trivia in front of it. As every node in
+/// the AST starts at a token this can be used to find the comments before any expression,
+/// pattern or binding.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TriviaTable {
+    /// The start of each token and the range of `trivia` which precedes it
+    tokens: Vec<(BytePos, usize, usize)>,
+    trivia: Vec<Spanned<Trivia, BytePos>>,
+}
+
+impl TriviaTable {
+    pub fn new<Id>(tokens: &[TriviaToken<Id>]) -> TriviaTable {
+        let mut table = TriviaTable::default();
+        for token in tokens {
+            let start = table.trivia.len();
+            table.trivia.extend(token.leading_trivia.iter().cloned());
+            table.tokens.push((token.token.span.start, start, table.trivia.len()));
+        }
+        table
+    }
+
+    /// Returns the trivia directly in front of the token which starts at `pos`
+    pub fn leading(&self, pos: BytePos) -> &[Spanned<Trivia, BytePos>] {
+        match self.tokens.binary_search_by(|token| token.0.cmp(&pos)) {
+            Ok(i) => {
+                let (_, start, end) = self.tokens[i];
+                &self.trivia[start..end]
+            }
+            Err(_) => &[],
+        }
+    }
+
+    /// Returns all trivia which lie inside of `span`
+    pub fn within(&self, span: Span<BytePos>) -> &[Spanned<Trivia, BytePos>] {
+        let start = self.trivia
+            .binary_search_by(|trivia| trivia.span.start.cmp(&span.start))
+            .unwrap_or_else(|i| i);
+        let len = self.trivia[start..]
+            .iter()
+            .take_while(|trivia| trivia.span.end <= span.end)
+            .count();
+        &self.trivia[start..start + len]
+    }
+
+    /// Returns all trivia in the order they appear in the source
+    pub fn trivia(&self) -> &[Spanned<Trivia, BytePos>] {
+        &self.trivia
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Offside {
     pub location: Location,
@@ -319,6 +390,10 @@ pub struct Lexer<'input, I>
     /// token so the span can be returned for it
     next_token: Option<SpannedToken<&'input str>>,
     end_span: Option<Span<Location>>,
+    /// Whitespace and comments which have been skipped, only recorded when set to `Some`
+    trivia: Option<Vec<Spanned<Trivia, BytePos>>>,
+    /// The error which stopped tokenization of the input
+    error: Option<Spanned<::ParseError, BytePos>>,
 }
 
 impl<'input, I> Lexer<'input, I>
@@ -326,6 +401,13 @@ impl<'input, I> Lexer<'input, I>
           I::Range: fmt::Debug + 'input,
 {
     pub fn new(input: I) -> Lexer<'input, I> {
+        let mut lexer = Lexer::raw(input);
+        lexer.next_token = lexer.uncons_next().ok();
+        lexer
+    }
+
+    /// Creates a lexer which has not yet lexed its first token
+    fn raw(input: I) -> Lexer<'input, I> {
         let env = LanguageEnv::new(LanguageDef {
             ident: Identifier {
                 start: letter().or(char('_')),
@@ -348,7 +430,7 @@ impl<'input, I> Lexer<'input, I>
             comment_line: satisfy(|_| false).map(|_| ()),
         });
 
-        let mut lexer = Lexer {
+        Lexer {
             env: env,
             input: Some(LocatedStream {
                 location: Location {
@@ -362,9 +444,9 @@ impl<'input, I> Lexer<'input, I>
             indent_levels: Contexts { stack: Vec::new() },
             next_token: None,
             end_span: None,
-        };
-        lexer.next_token = lexer.uncons_next().ok();
-        lexer
+            trivia: None,
+            error: None,
+        }
     }
 
     fn parser<'a, T>(&'a self,
@@ -437,6 +519,7 @@ impl<'input, I> Lexer<'input, I>
                     "if" => Some(Token::If),
                     "then" => Some(Token::Then),
                     "else" => Some(Token::Else),
+                    "forall" => Some(Token::Forall),
                     _ => None,
                 }
             }
@@ -479,6 +562,7 @@ impl<'input, I> Lexer<'input, I>
                     Ok(((), input)) => input.into_inner(),
                     Err(_) => input,
                 };
+                self.record_trivia(Trivia::Whitespace, end, input.position());
                 self.input = Some(input);
                 SpannedToken {
                     span: Span {
@@ -491,6 +575,10 @@ impl<'input, I> Lexer<'input, I>
             Err(err) => {
                 let err = err.into_inner();
                 debug!("Error tokenizing: {:?}", err);
+                let errors = err.errors.into_iter().map(static_char_error).collect();
+                self.error = Some(pos::spanned2(start.absolute,
+                                                start.absolute,
+                                                ::ParseError { errors: errors }));
                 let span = Span {
                     start: start,
                     end: start,
@@ -510,9 +598,11 @@ impl<'input, I> Lexer<'input, I>
                    -> ParseResult<Token<&'input str>, LocatedStream<I>> {
         loop {
             // Skip all whitespace before the token
+            let before = input.position();
             let (_, new_input) = try!(spaces().parse_lazy(input).into());
             input = new_input.into_inner();
             *location = input.position();
+            self.record_trivia(Trivia::Whitespace, before, *location);
             let (first, one_char_consumed) = try!(any().parse_stream(input.clone()));
 
             // Decide how to tokenize depending on what the first char is
@@ -527,7 +617,11 @@ impl<'input, I> Lexer<'input, I>
                     _ => {
                         if op.starts_with("///") {
                             let mut comment = String::new();
-                            let ((), new_input) = try!(spaces().parse_stream(input));
+                            // Only skip spaces on the current line as the comment may consist of
+                            // an empty line
+                            let ((), new_input) =
+                                try!(skip_many(satisfy(|c| c == ' ' || c == '\t'))
+                                    .parse_stream(input));
                             input = new_input.into_inner();
                             // Merge consecutive line comments
                             loop {
@@ -536,7 +630,9 @@ impl<'input, I> Lexer<'input, I>
                                 comment.push('\n');
                                 let ((), new_input) = try!(line.into_result(()));
                                 input = new_input.into_inner();
-                                let mut p = spaces().with(try(string("///"))).skip(spaces());
+                                let mut p = spaces()
+                                    .with(try(string("///")))
+                                    .skip(skip_many(satisfy(|c| c == ' ' || c == '\t')));
                                 match p.parse_stream(input.clone()) {
                                     Ok((_, new_input)) => input = new_input.into_inner(),
                                     Err(_) => break,
@@ -552,11 +648,15 @@ impl<'input, I> Lexer<'input, I>
                                     .parse_lazy(input)
                                     .into());
                             input = new_input.into_inner();
+                            let end = input.position();
+                            self.record_trivia(Trivia::LineComment, *location, end);
                             continue;
                         } else if op.starts_with("/*") {
                             // Skip over normal comments and try to parse a new token
                             let ((), new_input) = try!(self.skip_block_comment(input));
                             input = new_input.into_inner();
+                            let end = input.position();
+                            self.record_trivia(Trivia::BlockComment, *location, end);
                             continue;
                         } else {
                             Token::Operator(op)
@@ -608,6 +708,14 @@ impl<'input, I> Lexer<'input, I>
         }
     }
 
+    fn record_trivia(&mut self, kind: Trivia, start: Location, end: Location) {
+        if let Some(ref mut trivia) = self.trivia {
+            if start.absolute != end.absolute {
+                trivia.push(pos::spanned2(start.absolute, end.absolute, kind));
+            }
+        }
+    }
+
     fn skip_block_comment(&self, input: LocatedStream<I>) -> ParseResult<(), LocatedStream<I>> {
         let mut block_doc_comment = parser(|input| {
             let mut input = Consumed::Empty(input);
@@ -688,6 +796,71 @@ impl<'input, I> Lexer<'input, I>
     }
 }
 
+/// Splits `input` into its tokens while keeping the whitespace and comments between them.
+///
+/// Unlike the tokens seen by the parser no layout tokens (`OpenBlock`, `Semi`, ...) are
+/// inserted. The last token is always `Token::EOF` which holds the trivia at the end of `input`,
+/// so concatenating the source text of every trivia and token reproduces `input` exactly.
+pub fn tokenize<'input>(input: &'input str) -> Result<Vec<TriviaToken<&'input str>>, ::Error> {
+    let end_of_input = BytePos::from(input.len());
+    let mut lexer = Lexer::raw(input);
+    lexer.trivia = Some(Vec::new());
+    let mut tokens = Vec::new();
+    loop {
+        let token = lexer.next_token();
+        let start = token.span.start.absolute;
+        let leading_trivia = match lexer.trivia {
+            Some(ref mut trivia) => {
+                // Whitespace after the token has already been recorded as well
+                let len = trivia.iter().take_while(|trivia| trivia.span.end <= start).count();
+                trivia.drain(..len).collect()
+            }
+            None => Vec::new(),
+        };
+        match token.value {
+            // The lexer returns `CloseBlock` if it can't lex any more tokens
+            Token::CloseBlock => {
+                if start != end_of_input {
+                    if let Some(err) = lexer.error.take() {
+                        let mut errors = Errors::new();
+                        errors.error(err);
+                        return Err(errors);
+                    }
+                }
+                tokens.push(TriviaToken {
+                    leading_trivia: leading_trivia,
+                    token: pos::spanned2(end_of_input, end_of_input, Token::EOF),
+                });
+                return Ok(tokens);
+            }
+            value => {
+                tokens.push(TriviaToken {
+                    leading_trivia: leading_trivia,
+                    token: pos::spanned2(start, token.span.end.absolute, value),
+                })
+            }
+        }
+    }
+}
+
+// Converts an error from lexing the characters of the input into an error of the parser
+fn static_char_error(e: CombineError<char, &str>) -> CombineError<Token<String>, Token<String>> {
+    let static_info = |i: Info<char, &str>| {
+        match i {
+            Info::Token(c) => Info::Owned(c.to_string()),
+            Info::Range(s) => Info::Owned(String::from(s)),
+            Info::Borrowed(s) => Info::Borrowed(s),
+            Info::Owned(s) => Info::Owned(s),
+        }
+    };
+    match e {
+        CombineError::Unexpected(t) => CombineError::Unexpected(static_info(t)),
+        CombineError::Expected(t) => CombineError::Expected(static_info(t)),
+        CombineError::Message(t) => CombineError::Message(static_info(t)),
+        CombineError::Other(t) => CombineError::Other(t),
+    }
+}
+
 fn layout<'input, I>(lexer: &mut Lexer<'input, I>,
                      mut token: SpannedToken<&'input str>)
                      -> Result<SpannedToken<&'input str>, Error<&'input str>>
@@ -719,6 +892,12 @@ fn layout<'input, I>(lexer: &mut Lexer<'input, I>,
         debug!("--------\n{:?}\n{:?}", token, offside);
         let ordering = token.span.start.column.cmp(&offside.location.column);
 
+        // An `if` which is followed by `->` instead of `else` is the guard of a match alternative
+        if token.value == Token::RightArrow && offside.context == Context::If {
+            lexer.indent_levels.pop();
+            continue;
+        }
+
         // If it is closing token we remove contexts until a context for that token is found
         if [Token::In,
             Token::CloseBlock,
@@ -732,7 +911,8 @@ fn layout<'input, I>(lexer: &mut Lexer<'input, I>,
 
             if token.value == Token::Comma &&
                (offside.context == Context::Delimiter(Delimiter::Brace) ||
-                offside.context == Context::Delimiter(Delimiter::Bracket)) {
+                offside.context == Context::Delimiter(Delimiter::Bracket) ||
+                offside.context == Context::Delimiter(Delimiter::Paren)) {
                 return Ok(token);
             }
             lexer.indent_levels.pop();
@@ -8,6 +8,7 @@ extern crate combine;
 extern crate combine_language;
 
 // pub mod grammar;
+pub mod format;
 pub mod lexer;
 
 use std::cell::RefCell;
@@ -17,6 +18,7 @@ use std::marker::PhantomData;
 use std::rc::Rc;
 
 use base::ast::*;
+use base::diagnostic::{AsDiagnostic, Diagnostic};
 use base::error::Errors;
 use base::pos::{self, BytePos, Span, Spanned};
 use base::types::{Alias, ArcType, Generic, Field, Kind, Type};
@@ -26,11 +28,12 @@ use combine::primitives::{Consumed, Stream, StreamOnce, Error as CombineError, I
                           BufferedStream};
 use combine::primitives::FastResult::*;
 use combine::combinator::EnvParser;
-use combine::{between, choice, env_parser, many, many1, optional, parser, satisfy, sep_by1,
-              sep_end_by, token, try, value, ParseError as CombineParseError, ParseResult, Parser};
+use combine::{between, choice, env_parser, many, many1, optional, parser, satisfy, sep_by,
+              sep_by1, sep_end_by, token, try, ParseError as CombineParseError,
+              ParseResult, Parser};
 use combine_language::{Assoc, Fixity, expression_parser};
 
-use lexer::{Lexer, Delimiter, Token, IdentType};
+use lexer::{Lexer, Delimiter, Token, IdentType, TriviaTable};
 
 #[derive(Debug, PartialEq)]
 pub struct ParseError {
@@ -43,6 +46,20 @@ impl fmt::Display for ParseError {
     }
 }
 
+impl AsDiagnostic for ParseError {
+    fn as_diagnostic(&self, span: Span<BytePos>) -> Diagnostic {
+        // The first line describes the unexpected token, the rest what was expected instead
+        let message = self.to_string();
+        let mut lines = message.lines().filter(|line| !line.is_empty());
+        let mut diagnostic = Diagnostic::new(lines.next().unwrap_or("Parse error"))
+            .primary(span, "");
+        for line in lines {
+            diagnostic = diagnostic.note(line);
+        }
+        diagnostic
+    }
+}
+
 pub type Error = Errors<Spanned<ParseError, BytePos>>;
 
 // Dummy type for ParseError which has the correct associated types
@@ -77,6 +94,8 @@ struct ParserEnv<I, F>
     hole_typ: ArcType<F::Ident>,
     make_ident: Rc<RefCell<F>>,
     errors: RefCell<Error>,
+    /// Whether expressions with syntax errors are replaced by `Expr::Error` instead of failing
+    recover: bool,
     env: PhantomData<I>,
 }
 
@@ -139,6 +158,42 @@ macro_rules! match_parser {
     }
 }
 
+/// Returns the precedence of the binary operator `s`
+fn precedence(s: &str) -> i32 {
+    match s {
+        "*" | "/" | "%" => 7,
+        "+" | "-" => 6,
+        ":" | "++" => 5,
+        "&&" => 3,
+        "||" => 2,
+        "$" => 0,
+        "==" | "/=" | "<" | ">" | "<=" | ">=" => 4,
+        // Primitive operators starts with # and has the op at the end
+        _ if s.starts_with("#") => {
+            let op = s[1..].trim_left_matches(|c: char| c.is_alphanumeric());
+            precedence(op)
+        }
+        // Hack for some library operators
+        "<<" | ">>" => 9,
+        "<|" | "|>" => 0,
+        // User-defined operators
+        _ => 9,
+    }
+}
+
+/// Returns the fixity of the binary operator `i`
+fn fixity(i: &str) -> Fixity {
+    match i {
+        "*" | "/" | "%" | "+" | "-" | "==" | "/=" | "<" | ">" | "<=" | ">=" => Fixity::Left,
+        ":" | "++" | "&&" | "||" | "$" => Fixity::Right,
+        // Hack for some library operators
+        ">>" | "|>" => Fixity::Left,
+        "<<" | "<|" => Fixity::Right,
+        // User-defined operators
+        _ => Fixity::Left,
+    }
+}
+
 fn as_trait<P: Parser>(p: &mut P) -> &mut Parser<Input = P::Input, Output = P::Output> {
     p
 }
@@ -159,40 +214,6 @@ impl<'input, I, Id, F> ParserEnv<I, F>
         env_parser(self, parser)
     }
 
-    fn precedence(&self, s: &str) -> i32 {
-        match s {
-            "*" | "/" | "%" => 7,
-            "+" | "-" => 6,
-            ":" | "++" => 5,
-            "&&" => 3,
-            "||" => 2,
-            "$" => 0,
-            "==" | "/=" | "<" | ">" | "<=" | ">=" => 4,
-            // Primitive operators starts with # and has the op at the end
-            _ if s.starts_with("#") => {
-                let op = s[1..].trim_left_matches(|c: char| c.is_alphanumeric());
-                self.precedence(op)
-            }
-            // Hack for some library operators
-            "<<" | ">>" => 9,
-            "<|" | "|>" => 0,
-            // User-defined operators
-            _ => 9,
-        }
-    }
-
-    fn fixity(&self, i: &str) -> Fixity {
-        match i {
-            "*" | "/" | "%" | "+" | "-" | "==" | "/=" | "<" | ">" | "<=" | ">=" => Fixity::Left,
-            ":" | "++" | "&&" | "||" | "$" => Fixity::Right,
-            // Hack for some library operators
-            ">>" | "|>" => Fixity::Left,
-            "<<" | "<|" => Fixity::Right,
-            // User-defined operators
-            _ => Fixity::Left,
-        }
-    }
-
     fn ident<'a>(&'a self) -> LanguageParser<'a, I, F, Id> {
         self.parser(ParserEnv::<I, F>::parse_ident)
     }
@@ -260,19 +281,55 @@ impl<'input, I, Id, F> ParserEnv<I, F>
         self.parser(ParserEnv::<I, F>::parse_type)
     }
 
-    fn parse_adt(&self, return_type: &ArcType<Id>, input: I) -> ParseResult<ArcType<Id>, I> {
-        let variant = (token(Token::Pipe),
-                       self.ident(),
-                       many(self.parser(ParserEnv::<I, F>::type_arg)))
-            .map(|(_, id, args): (_, _, Vec<_>)| (id, Type::function(args, return_type.clone())));
+    /// Parses the variants of a type, returning the variant type together with the name of each
+    /// constructor
+    fn parse_adt(&self,
+                 return_type: &ArcType<Id>,
+                 input: I)
+                 -> ParseResult<(ArcType<Id>, Vec<Spanned<Id, BytePos>>), I> {
+        let constructor = parser(|input: I| {
+            let span = input.position();
+            self.ident().map(move |id| pos::spanned(span, id)).parse_stream(input)
+        });
+        // `| Cons a (List a)` or, for a constructor which refines the result type,
+        // `| IntLit : Int -> Expr Int`
+        let gadt_variant = token(Token::Colon).with(self.typ());
+        let args = many(self.parser(ParserEnv::<I, F>::type_arg))
+            .map(|args: Vec<_>| Type::function(args, return_type.clone()));
+        let variant = (token(Token::Pipe), constructor, gadt_variant.or(args))
+            .map(|(_, id, typ)| (id, typ));
         many1(variant)
-            .map(Type::variants)
+            .map(|variants: Vec<(Spanned<Id, BytePos>, ArcType<Id>)>| {
+                let constructors = variants.iter().map(|variant| variant.0.clone()).collect();
+                let variants = variants.into_iter().map(|(id, typ)| (id.value, typ)).collect();
+                (Type::variants(variants), constructors)
+            })
             .parse_stream(input)
     }
 
     fn parse_type(&self, input: I) -> ParseResult<ArcType<Id>, I> {
-        (many1(self.parser(ParserEnv::<I, F>::type_arg)),
-         optional(token(Token::RightArrow).with(self.typ())))
+        // `forall a b . (a -> b) -> List a -> List b`
+        let forall = (token(Token::Forall), many1(self.ident()), token(Token::Dot), self.typ())
+            .map(|(_, params, _, typ): (_, Vec<_>, _, _)| {
+                let params = params.into_iter()
+                    .map(|id| {
+                        Generic {
+                            kind: Kind::variable(0),
+                            id: id,
+                        }
+                    })
+                    .collect();
+                Type::forall(params, typ)
+            });
+        // `[Eq a] -> a -> a -> Bool` where `Eq a` is passed implicitly
+        let implicit_function = (between(token(Token::Open(Delimiter::Bracket)),
+                                         token(Token::Close(Delimiter::Bracket)),
+                                         self.typ()),
+                                 token(Token::RightArrow),
+                                 self.typ())
+            .map(|(arg, _, ret)| Type::implicit_function(vec![arg], ret));
+        let function = (many1(self.parser(ParserEnv::<I, F>::type_arg)),
+                        optional(token(Token::RightArrow).with(self.typ())))
             .map(|(mut arg, ret): (Vec<_>, _)| {
                 let arg = if arg.len() == 1 {
                     arg.pop().unwrap()
@@ -285,53 +342,90 @@ impl<'input, I, Id, F> ParserEnv<I, F>
                     Some(ret) => Type::function(vec![arg], ret),
                     None => arg,
                 }
-            })
-            .parse_stream(input)
+            });
+        forall.or(implicit_function).or(function).parse_stream(input)
     }
 
     fn record_type(&self, input: I) -> ParseResult<ArcType<Id>, I> {
-        let field = self.parser(ParserEnv::<I, F>::parse_ident2)
-            .then(|(id, typ)| {
-                parser(move |input| {
-                    if typ == IdentType::Constructor {
-                        value((id.clone(), None)).parse_stream(input)
-                    } else {
-                        token(Token::Colon)
-                            .with(self.typ())
-                            .map(|typ| (id.clone(), Some(typ)))
-                            .parse_stream(input)
-                    }
-                })
-            });
+        self.parser(ParserEnv::<I, F>::signature)
+            .map(|(typ, _)| typ)
+            .parse_stream(input)
+    }
+
+    /// Parses a record type, returning it together with the name of each field, spanning the
+    /// location of the field. Used for the record types which declare the signature of a module.
+    fn signature(&self, input: I) -> ParseResult<(ArcType<Id>, Vec<Spanned<Id, BytePos>>), I> {
+        let name = parser(|input: I| {
+            let span = input.position();
+            self.parser(ParserEnv::<I, F>::parse_ident2)
+                .map(move |(id, typ)| (pos::spanned(span, id), typ))
+                .parse_stream(input)
+        });
+        let field = name.then(|(id, typ)| {
+            parser(move |input| {
+                if typ == IdentType::Constructor {
+                    // `Stack a = List a` gives the definition of the type while a bare
+                    // `Stack` leaves it unspecified
+                    let definition = (many(self.ident()), token(Token::Equal), self.typ())
+                        .map(|(args, _, typ): (Vec<_>, _, _)| (args, typ));
+                    optional(definition)
+                        .map(|definition| (id.clone(), Err(definition)))
+                        .parse_stream(input)
+                } else {
+                    token(Token::Colon)
+                        .with(self.typ())
+                        .map(|typ| (id.clone(), Ok(typ)))
+                        .parse_stream(input)
+                }
+            })
+        });
         between(token(Token::Open(Delimiter::Brace)),
                 token(Token::Close(Delimiter::Brace)),
                 sep_end_by(field, token(Token::Comma)))
-            .map(|fields: Vec<(Id, _)>| {
+            .map(|fields: Vec<(Spanned<Id, BytePos>, _)>| {
                 let mut associated = Vec::new();
                 let mut types = Vec::new();
+                let mut names = Vec::new();
                 let mut ids = self.make_ident.borrow_mut();
                 for (id, field) in fields {
+                    let Spanned { span, value: id } = id;
                     let untyped_id = id.clone();
                     match field {
-                        Some(typ) => {
+                        Ok(typ) => {
+                            names.push(pos::spanned(span, untyped_id.clone()));
                             types.push(Field {
                                 name: untyped_id,
                                 typ: typ,
                             })
                         }
-                        None => {
-                            let typ = Type::ident(untyped_id.clone());
+                        Err(definition) => {
+                            let (args, typ) = match definition {
+                                Some((args, typ)) => {
+                                    let args = args.into_iter()
+                                        .map(|id| {
+                                            Generic {
+                                                kind: Kind::variable(0),
+                                                id: id,
+                                            }
+                                        })
+                                        .collect();
+                                    (args, typ)
+                                }
+                                None => (vec![], Type::ident(untyped_id.clone())),
+                            };
                             let short_name = String::from(Name::new(ids.string(&id))
                                 .name()
                                 .as_str());
+                            let name = ids.from_str(&short_name);
+                            names.push(pos::spanned(span, name.clone()));
                             associated.push(Field {
-                                name: ids.from_str(&short_name),
-                                typ: Alias::new(untyped_id, vec![], typ),
+                                name: name,
+                                typ: Alias::new(untyped_id, args, typ),
                             });
                         }
                     }
                 }
-                Type::record(associated, types)
+                (Type::record(associated, types), names)
             })
             .parse_stream(input)
     }
@@ -341,11 +435,23 @@ impl<'input, I, Id, F> ParserEnv<I, F>
                  _>([&mut self.parser(ParserEnv::<I, F>::record_type),
                      &mut between(token(Token::Open(Delimiter::Paren)),
                                   token(Token::Close(Delimiter::Paren)),
-                                  optional(self.typ()))
-                         .map(|typ| {
-                             match typ {
-                                 Some(typ) => typ,
-                                 None => Type::unit(),
+                                  sep_by(self.typ(), token(Token::Comma)))
+                         .map(|mut elems: Vec<_>| {
+                             match elems.len() {
+                                 0 => Type::unit(),
+                                 1 => elems.pop().unwrap(),
+                                 _ => {
+                                     let fields = elems.into_iter()
+                                         .enumerate()
+                                         .map(|(i, typ)| {
+                                             Field {
+                                                 name: self.tuple_field(i),
+                                                 typ: typ,
+                                             }
+                                         })
+                                         .collect();
+                                     Type::record(vec![], fields)
+                                 }
                              }
                          }),
                      &mut self.ident_type()])
@@ -390,8 +496,9 @@ impl<'input, I, Id, F> ParserEnv<I, F>
                 };
                 token(Token::Equal)
                     .with(self.typ()
+                        .map(|typ| (typ, Vec::new()))
                         .or(parser(move |input| self.parse_adt(&return_type, input))))
-                    .map(move |rhs_type| {
+                    .map(move |(rhs_type, constructors)| {
                         TypeBinding {
                             comment: None,
                             name: name.clone(),
@@ -405,6 +512,7 @@ impl<'input, I, Id, F> ParserEnv<I, F>
                                                   })
                                                   .collect(),
                                               rhs_type),
+                            constructors: constructors,
                         }
                     })
             })
@@ -501,12 +609,17 @@ impl<'input, I, Id, F> ParserEnv<I, F>
                      &mut self.parser(ParserEnv::<I, F>::record).map(&loc),
                      &mut between(token(Token::Open(Delimiter::Paren)),
                                   token(Token::Close(Delimiter::Paren)),
-                                  optional(self.expr()).map(|expr| {
-                                      match expr {
-                                          Some(expr) => expr,
-                                          None => loc(Expr::Tuple(vec![])),
-                                      }
-                                  })),
+                                  sep_by(self.expr(), token(Token::Comma)))
+                         .map(|mut elems: Vec<_>| {
+                             if elems.len() == 1 {
+                                 elems.pop().unwrap()
+                             } else {
+                                 loc(Expr::Tuple {
+                                     typ: self.hole_typ.clone(),
+                                     elems: elems,
+                                 })
+                             }
+                         }),
                      &mut self.string_literal()
                          .map(|s| loc(Expr::Literal(Literal::String(s)))),
                      &mut self.char_literal()
@@ -591,25 +704,10 @@ impl<'input, I, Id, F> ParserEnv<I, F>
 
     /// Parses any sort of expression
     fn top_expr(&self, input: I) -> ParseResult<SpannedExpr<Id>, I> {
-        let term = self.parser(ParserEnv::<I, F>::parse_expr);
-        let op = self.op()
-            .map(|op| {
-                let assoc = Assoc {
-                    precedence: self.precedence(&op),
-                    fixity: self.fixity(&op),
-                };
-                (op, assoc)
-            });
         between(token(Token::OpenBlock),
                 token(Token::CloseBlock),
                 self.expr())
-            .or(sep_by1(expression_parser(term, op, |l, op, r| {
-                pos::spanned2(l.span.start,
-                              r.span.end,
-                              Expr::Infix(Box::new(l),
-                                          TypedIdent::new(self.intern(&op)),
-                                          Box::new(r)))
-            }),
+            .or(sep_by1(self.parser(ParserEnv::<I, F>::recovering_infix_expr),
                         token(Token::Semi))
                 .map(|mut exprs: Vec<SpannedExpr<Id>>| {
                     if exprs.len() == 1 {
@@ -622,6 +720,49 @@ impl<'input, I, Id, F> ParserEnv<I, F>
             .parse_stream(input)
     }
 
+    /// Parses an infix expression, recovering if it contains a syntax error.
+    ///
+    /// If the expression fails after consuming some input the error is recorded and the
+    /// expression is replaced by `Expr::Error`. Parsing then resumes at the first token after the
+    /// error which can end the expression at the same level of nesting (see `skip_erroneous`).
+    fn recovering_infix_expr(&self, input: I) -> ParseResult<SpannedExpr<Id>, I> {
+        if !self.recover {
+            return self.infix_expr(input);
+        }
+        let start = input.position().start;
+        match self.parser(ParserEnv::<I, F>::infix_expr).parse_stream(input.clone()) {
+            Err(Consumed::Consumed(err)) => {
+                match skip_erroneous(input, err.position.start) {
+                    Some((end, input)) => {
+                        debug!("Recovered from parse error {:?}", err);
+                        self.errors.borrow_mut().error(static_error(err));
+                        Ok((pos::spanned2(start, end, Expr::Error), Consumed::Consumed(input)))
+                    }
+                    None => Err(Consumed::Consumed(err)),
+                }
+            }
+            result => result,
+        }
+    }
+
+    fn infix_expr(&self, input: I) -> ParseResult<SpannedExpr<Id>, I> {
+        let term = self.parser(ParserEnv::<I, F>::parse_expr);
+        let op = self.op()
+            .map(|op| {
+                let assoc = Assoc {
+                    precedence: precedence(&op),
+                    fixity: fixity(&op),
+                };
+                (op, assoc)
+            });
+        expression_parser(term, op, |l, op, r| {
+            pos::spanned2(l.span.start,
+                          r.span.end,
+                          Expr::Infix(Box::new(l), TypedIdent::new(self.intern(&op)), Box::new(r)))
+        })
+            .parse_stream(input)
+    }
+
     fn lambda(&self, input: I) -> ParseResult<SpannedExpr<Id>, I> {
         let start = input.position().start;
         (token(Token::Lambda), many(self.ident()), token(Token::RightArrow), self.expr())
@@ -641,10 +782,15 @@ impl<'input, I, Id, F> ParserEnv<I, F>
     }
 
     fn case_of(&self, input: I) -> ParseResult<SpannedExpr<Id>, I> {
-        let alt = (token(Token::Pipe), self.pattern(), token(Token::RightArrow), self.expr())
-            .map(|(_, pattern, _, expr)| {
+        let alt = (token(Token::Pipe),
+                   self.pattern(),
+                   optional(token(Token::If).with(self.expr())),
+                   token(Token::RightArrow),
+                   self.expr())
+            .map(|(_, pattern, guard, _, expr)| {
                 Alternative {
                     pattern: pattern,
+                    guard: guard,
                     expr: expr,
                 }
             });
@@ -662,30 +808,43 @@ impl<'input, I, Id, F> ParserEnv<I, F>
         self.parser(ParserEnv::<I, F>::parse_pattern)
     }
 
+    /// Parses a pattern, eg. `Cons x (Cons 1 Nil)`
     fn parse_pattern(&self, input: I) -> ParseResult<SpannedPattern<Id>, I> {
+        let span = input.position();
+
+        self.parser(ParserEnv::<I, F>::parse_ident2)
+            .then(|(id, typ)| {
+                parser(move |input| {
+                    if typ == IdentType::Constructor {
+                        many(self.parser(ParserEnv::<I, F>::pattern_arg))
+                            .parse_stream(input)
+                            .map(|(args, input)| {
+                                (Pattern::Constructor(TypedIdent::new(id.clone()), args), input)
+                            })
+                    } else {
+                        Ok((self.ident_pattern(id.clone()), Consumed::Empty(input)))
+                    }
+                })
+            })
+            .map(|p| pos::spanned(span, p))
+            .or(self.parser(ParserEnv::<I, F>::pattern_arg))
+            .parse_stream(input)
+    }
+
+    /// Parses a pattern which can be an argument of a constructor pattern without being
+    /// surrounded by parentheses
+    fn pattern_arg(&self, input: I) -> ParseResult<SpannedPattern<Id>, I> {
         self.record_parser(self.ident(), self.ident(), |record| {
             let span = input.position();
+            let loc = |pattern| pos::spanned(span, pattern);
 
             self.parser(ParserEnv::<I, F>::parse_ident2)
-                .then(|(id, typ)| {
-                    parser(move |input| {
-                        if typ == IdentType::Constructor {
-                            many(self.ident())
-                                .parse_stream(input)
-                                .map(|(args, input): (Vec<Id>, _)| {
-                                    (Pattern::Constructor(TypedIdent::new(id.clone()),
-                                                          args.iter()
-                                                              .map(|arg| {
-                                                                  TypedIdent::new(arg.clone())
-                                                              })
-                                                              .collect()),
-                                     input)
-                                })
-                        } else {
-                            Ok((Pattern::Ident(TypedIdent::new(id.clone())),
-                                Consumed::Empty(input)))
-                        }
-                    })
+                .map(|(id, typ)| {
+                    if typ == IdentType::Constructor {
+                        Pattern::Constructor(TypedIdent::new(id), vec![])
+                    } else {
+                        self.ident_pattern(id)
+                    }
                 })
                 .or(record.map(|fields: Vec<_>| {
                     let mut types = Vec::new();
@@ -702,14 +861,44 @@ impl<'input, I, Id, F> ParserEnv<I, F>
                         fields: patterns,
                     }
                 }))
-                .map(|p| pos::spanned(span, p))
+                .or(self.int().map(|i| Pattern::Literal(Literal::Int(i))))
+                .or(self.byte().map(|b| Pattern::Literal(Literal::Byte(b))))
+                .or(self.float().map(|f| Pattern::Literal(Literal::Float(f))))
+                .or(self.string_literal().map(|s| Pattern::Literal(Literal::String(s))))
+                .or(self.char_literal().map(|c| Pattern::Literal(Literal::Char(c))))
+                .map(&loc)
                 .or(between(token(Token::Open(Delimiter::Paren)),
                             token(Token::Close(Delimiter::Paren)),
-                            self.pattern()))
+                            sep_by(self.pattern(), token(Token::Comma)))
+                    .map(|mut elems: Vec<_>| {
+                        if elems.len() == 1 {
+                            elems.pop().unwrap()
+                        } else {
+                            loc(Pattern::Tuple {
+                                typ: self.hole_typ.clone(),
+                                elems: elems,
+                            })
+                        }
+                    }))
                 .parse_stream(input)
         })
     }
 
+    /// Creates the pattern for a lowercase identifier, `_` being the wildcard pattern
+    fn ident_pattern(&self, id: Id) -> Pattern<Id> {
+        let is_wildcard = self.make_ident.borrow().string(&id) == "_";
+        if is_wildcard {
+            Pattern::Wildcard(self.hole_typ.clone())
+        } else {
+            Pattern::Ident(TypedIdent::new(id))
+        }
+    }
+
+    /// Returns the name of the field which holds the element at `index` of a tuple
+    fn tuple_field(&self, index: usize) -> Id {
+        self.intern(&format!("_{}", index))
+    }
+
     fn if_else(&self, input: I) -> ParseResult<SpannedExpr<Id>, I> {
         let start = input.position().start;
         (token(Token::If),
@@ -844,6 +1033,30 @@ pub fn parse_expr_<'env, 'input, Id>(make_ident: &'env mut IdentEnv<Ident = Id>,
                                      input: &'input str)
                                      -> Result<SpannedExpr<Id>, (Option<SpannedExpr<Id>>, Error)>
     where Id: Clone + PartialEq + fmt::Debug,
+{
+    // Most expressions do not contain any syntax errors so they are first parsed without error
+    // recovery, which only needs a few tokens of lookahead
+    match parse_expr_with(&mut *make_ident, input, LOOKAHEAD, false) {
+        Ok(expr) => Ok(expr),
+        Err(_) => parse_expr_with(make_ident, input, RECOVERY_LOOKAHEAD, true),
+    }
+}
+
+/// The number of tokens which are buffered while parsing
+const LOOKAHEAD: usize = 10;
+
+/// The number of tokens which are buffered while parsing with error recovery. Recovering from an
+/// error requires the tokens of the erroneous expression to be read again from its start (see
+/// `skip_erroneous`) so syntax errors which occur more than this many tokens into an expression
+/// are not recovered from and end the parse instead.
+const RECOVERY_LOOKAHEAD: usize = 256;
+
+fn parse_expr_with<'env, 'input, Id>(make_ident: &'env mut IdentEnv<Ident = Id>,
+                                     input: &'input str,
+                                     lookahead: usize,
+                                     recover: bool)
+                                     -> Result<SpannedExpr<Id>, (Option<SpannedExpr<Id>>, Error)>
+    where Id: Clone + PartialEq + fmt::Debug,
 {
     let make_ident = Rc::new(RefCell::new(make_ident));
     let lexer = Lexer::new(input);
@@ -852,9 +1065,10 @@ pub fn parse_expr_<'env, 'input, Id>(make_ident: &'env mut IdentEnv<Ident = Id>,
         hole_typ: Type::hole(),
         make_ident: make_ident.clone(),
         errors: RefCell::new(Errors::new()),
+        recover: recover,
         env: PhantomData,
     };
-    let buffer = BufferedStream::new(lexer, 10);
+    let buffer = BufferedStream::new(lexer, lookahead);
     let stream = Wrapper { stream: buffer.as_stream() };
 
     let result = env.expr()
@@ -877,6 +1091,71 @@ pub fn parse_expr_<'env, 'input, Id>(make_ident: &'env mut IdentEnv<Ident = Id>,
     }
 }
 
+/// Parses the record type which declares the signature of a module, returning it together with
+/// the name of each field, spanning the location of the field in `input`
+pub fn parse_signature(symbols: &mut IdentEnv<Ident = Symbol>,
+                       input: &str)
+                       -> Result<(ArcType<Symbol>, Vec<Spanned<Symbol, BytePos>>), Error> {
+    parse_signature_(symbols, input)
+}
+
+/// Parses the record type which declares the signature of a module
+pub fn parse_signature_<'env, 'input, Id>
+    (make_ident: &'env mut IdentEnv<Ident = Id>,
+     input: &'input str)
+     -> Result<(ArcType<Id>, Vec<Spanned<Id, BytePos>>), Error>
+    where Id: Clone + PartialEq + fmt::Debug,
+{
+    let make_ident = Rc::new(RefCell::new(make_ident));
+    let lexer = Lexer::new(input);
+    let env = ParserEnv {
+        empty_id: make_ident.borrow_mut().from_str(""),
+        hole_typ: Type::hole(),
+        make_ident: make_ident.clone(),
+        errors: RefCell::new(Errors::new()),
+        recover: false,
+        env: PhantomData,
+    };
+    let buffer = BufferedStream::new(lexer, LOOKAHEAD);
+    let stream = Wrapper { stream: buffer.as_stream() };
+
+    let result = between(token(Token::OpenBlock),
+                         token(Token::CloseBlock),
+                         env.parser(ParserEnv::signature))
+        .parse(stream)
+        .map(|t| t.0);
+
+    let mut errors = env.errors.into_inner();
+    match result {
+        Ok(signature) => {
+            if !errors.has_errors() {
+                Ok(signature)
+            } else {
+                Err(errors)
+            }
+        }
+        Err(err) => {
+            errors.errors.push(static_error(err));
+            Err(errors)
+        }
+    }
+}
+
+/// Parses a gluon expression and collects the whitespace and comments of `input` into a
+/// `TriviaTable` which can be used together with the spans of the returned expression
+pub fn parse_expr_with_trivia<'env, 'input, Id>
+    (make_ident: &'env mut IdentEnv<Ident = Id>,
+     input: &'input str)
+     -> Result<(SpannedExpr<Id>, TriviaTable), (Option<SpannedExpr<Id>>, Error)>
+    where Id: Clone + PartialEq + fmt::Debug,
+{
+    let expr = try!(parse_expr_(make_ident, input));
+    match lexer::tokenize(input) {
+        Ok(tokens) => Ok((expr, TriviaTable::new(&tokens))),
+        Err(err) => Err((Some(expr), err)),
+    }
+}
+
 fn static_error<'input, I>(error: CombineParseError<I>) -> Spanned<ParseError, BytePos>
     where I: Stream<Item = Token<&'input str>, Range = Token<&'input str>, Position = Span<BytePos>>,
 {
@@ -887,6 +1166,94 @@ fn static_error<'input, I>(error: CombineParseError<I>) -> Spanned<ParseError, B
     pos::spanned(error.position, ParseError { errors: errors })
 }
 
+/// Skips the tokens of an expression which failed to parse at `error_pos`, starting from the
+/// beginning of the expression. Tokens after the error are also skipped until a `;`, `,`, `in`,
+/// `then`, `else` or `with` which does not belong to a skipped expression is found or until a
+/// token closes the block or delimiter which contains the expression. Since the layout algorithm
+/// closes all blocks before a `let` which starts a new binding this also resumes parsing at that
+/// `let`.
+///
+/// Returns the end of the last skipped token and the input at the token where parsing should
+/// resume, or `None` if no tokens could be skipped.
+fn skip_erroneous<'input, I>(mut input: I, error_pos: BytePos) -> Option<(BytePos, I)>
+    where I: Stream<Item = Token<&'input str>, Position = Span<BytePos>>,
+{
+    // Blocks, delimiters and keywords which have been skipped but not yet closed
+    let mut open = Vec::new();
+    let mut end = None;
+    loop {
+        let span = input.position();
+        let token = match input.clone().uncons() {
+            Ok(token) => token,
+            // Failing before the error means that the start of the expression has already been
+            // dropped from the token buffer
+            Err(_) => {
+                return if span.start >= error_pos {
+                    end.map(|end| (end, input))
+                } else {
+                    None
+                }
+            }
+        };
+        let is_block = |token: &Token<&'input str>| {
+            match *token {
+                Token::OpenBlock | Token::Open(_) => true,
+                _ => false,
+            }
+        };
+        let can_resume = span.start >= error_pos && !open.iter().any(&is_block);
+        let resume = match token {
+            Token::OpenBlock | Token::Open(_) | Token::Let | Token::Type | Token::If |
+            Token::Match => {
+                open.push(token);
+                false
+            }
+            Token::CloseBlock | Token::Close(_) => {
+                match open.iter().rposition(&is_block) {
+                    Some(i) => {
+                        open.truncate(i);
+                        false
+                    }
+                    None => true,
+                }
+            }
+            Token::In => {
+                match open.last() {
+                    Some(&Token::Let) | Some(&Token::Type) => {
+                        open.pop();
+                        false
+                    }
+                    _ => can_resume,
+                }
+            }
+            Token::Then => open.last() != Some(&Token::If) && can_resume,
+            Token::Else => {
+                if open.last() == Some(&Token::If) {
+                    open.pop();
+                    false
+                } else {
+                    can_resume
+                }
+            }
+            Token::With => {
+                if open.last() == Some(&Token::Match) {
+                    open.pop();
+                    false
+                } else {
+                    can_resume
+                }
+            }
+            Token::Semi | Token::Comma => can_resume,
+            _ => false,
+        };
+        if resume {
+            return end.map(|end| (end, input));
+        }
+        end = Some(span.end);
+        let _ = input.uncons();
+    }
+}
+
 // Converts an error into a static error by transforming any range arguments into strings
 fn static_error_<'input>(e: CombineError<Token<&'input str>, Token<&'input str>>)
                          -> CombineError<Token<String>, Token<String>> {
@@ -9,7 +9,7 @@ mod support;
 use base::ast::*;
 use base::pos::{self, BytePos, Span, Spanned};
 use base::types::{Alias, ArcType, Field, Generic, Kind, Type};
-use parser::{parse_string, Error};
+use parser::{parse_string, parse_signature_, Error};
 use support::MockEnv;
 
 pub fn intern(s: &str) -> String {
@@ -96,12 +96,21 @@ fn case(e: SpExpr, alts: Vec<(Pattern<String>, SpExpr)>) -> SpExpr {
                            .map(|(p, e)| {
                                Alternative {
                                    pattern: no_loc(p),
+                                   guard: None,
                                    expr: e,
                                }
                            })
                            .collect()))
 }
 
+fn ident_pattern(s: &str) -> SpannedPattern<String> {
+    no_loc(Pattern::Ident(TypedIdent::new(intern(s))))
+}
+
+fn constructor_pattern(s: &str, args: Vec<SpannedPattern<String>>) -> SpannedPattern<String> {
+    no_loc(Pattern::Constructor(TypedIdent::new(intern(s)), args))
+}
+
 fn lambda(name: &str, args: Vec<String>, body: SpExpr) -> SpExpr {
     no_loc(Expr::Lambda(Lambda {
         id: TypedIdent::new(intern(name)),
@@ -118,11 +127,22 @@ fn type_decl(name: String,
     type_decls(vec![TypeBinding {
                         comment: None,
                         name: name.clone(),
+                        constructors: constructors(&typ),
                         alias: Alias::new(name, args, typ),
                     }],
                body)
 }
 
+/// Returns the constructors of `typ` if it is a variant type
+fn constructors(typ: &ArcType<String>) -> Vec<Spanned<String, BytePos>> {
+    match **typ {
+        Type::Variants(ref variants) => {
+            variants.iter().map(|variant| no_loc(variant.0.clone())).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
 fn type_decls(binds: Vec<TypeBinding<String>>, body: SpExpr) -> SpExpr {
     no_loc(Expr::TypeBindings(binds, Box::new(body)))
 }
@@ -259,12 +279,14 @@ fn type_mutually_recursive() {
         TypeBinding {
             comment: None,
             name: intern("Test"),
+            constructors: constructors(&test),
             alias: Alias::new(intern("Test"), Vec::new(), test),
         },
         TypeBinding {
             comment: None,
             name: intern("Test2"),
             alias: Alias::new(intern("Test2"), Vec::new(), test2),
+            constructors: Vec::new(),
         },
         ];
     assert_eq!(e, type_decls(binds, int(1)));
@@ -320,11 +342,66 @@ match None with
     assert_eq!(e,
                Ok(case(id("None"),
                        vec![(Pattern::Constructor(TypedIdent::new(intern("Some")),
-                                                  vec![TypedIdent::new(intern("x"))]),
+                                                  vec![ident_pattern("x")]),
                              id("x")),
                             (Pattern::Constructor(TypedIdent::new(intern("None")), vec![]),
                              int(0))])));
 }
+
+#[test]
+fn nested_patterns() {
+    let _ = ::env_logger::init();
+    let text = r#"
+match x with
+    | Cons 1 (Cons y Nil) -> y
+    | (a, _) -> a
+    | Some "a" -> 0"#;
+    let e = parse_new!(text);
+    let list = constructor_pattern("Cons",
+                                   vec![ident_pattern("y"), constructor_pattern("Nil", vec![])]);
+    let list = Pattern::Constructor(TypedIdent::new(intern("Cons")),
+                                    vec![no_loc(Pattern::Literal(Literal::Int(1))), list]);
+    let tuple = Pattern::Tuple {
+        typ: Type::hole(),
+        elems: vec![ident_pattern("a"), no_loc(Pattern::Wildcard(Type::hole()))],
+    };
+    let string = no_loc(Pattern::Literal(Literal::String("a".into())));
+    let some = Pattern::Constructor(TypedIdent::new(intern("Some")), vec![string]);
+    assert_eq!(e,
+               case(id("x"),
+                    vec![(list, id("y")), (tuple, id("a")), (some, int(0))]));
+}
+
+#[test]
+fn match_guard() {
+    let _ = ::env_logger::init();
+    let text = r#"
+match x with
+    | Some y if y #Int< 0 -> 0
+    | y -> 1"#;
+    let e = parse_new!(text);
+    let mut expected = case(id("x"),
+                            vec![(Pattern::Constructor(TypedIdent::new(intern("Some")),
+                                                       vec![ident_pattern("y")]),
+                                  int(0)),
+                                 (Pattern::Ident(TypedIdent::new(intern("y"))), int(1))]);
+    if let Expr::Match(_, ref mut alts) = expected.value {
+        alts[0].guard = Some(binop(id("y"), "#Int<", int(0)));
+    }
+    assert_eq!(e, expected);
+}
+
+#[test]
+fn tuple_expr() {
+    let _ = ::env_logger::init();
+    let e = parse_new!("(1, a)");
+    assert_eq!(e,
+               no_loc(Expr::Tuple {
+                   typ: Type::hole(),
+                   elems: vec![int(1), id("a")],
+               }));
+}
+
 #[test]
 fn array_expr() {
     let _ = ::env_logger::init();
@@ -558,6 +635,7 @@ id
                                    comment: Some("Test type ".into()),
                                    name: intern("Test"),
                                    alias: Alias::new(intern("Test"), Vec::new(), typ("Int")),
+                                   constructors: Vec::new(),
                                }],
                           id("id")));
 }
@@ -581,6 +659,7 @@ id
                                          comment: Some("Test type ".into()),
                                          name: intern("Test"),
                                          alias: Alias::new(intern("Test"), Vec::new(), typ("Int")),
+                                         constructors: Vec::new(),
                                      }],
                                 id("id"))));
 }
@@ -601,6 +680,28 @@ id
                                    comment: Some("Merge\nconsecutive\nline comments.".into()),
                                    name: intern("Test"),
                                    alias: Alias::new(intern("Test"), Vec::new(), typ("Int")),
+                                   constructors: Vec::new(),
+                               }],
+                          id("id")));
+}
+
+#[test]
+fn line_comment_with_empty_line() {
+    let _ = ::env_logger::init();
+    let text = r#"
+/// Paragraph
+///
+/// Another paragraph
+type Test = Int
+id
+"#;
+    let e = parse_new!(text);
+    assert_eq!(e,
+               type_decls(vec![TypeBinding {
+                                   comment: Some("Paragraph\n\nAnother paragraph".into()),
+                                   name: intern("Test"),
+                                   alias: Alias::new(intern("Test"), Vec::new(), typ("Int")),
+                                   constructors: Vec::new(),
                                }],
                           id("id")));
 }
@@ -680,3 +781,96 @@ fn quote_in_identifier() {
                 app(id("f'"), vec![int(1), int(2)]));
     assert_eq!(e, a);
 }
+
+#[test]
+fn implicit_function_type() {
+    let _ = ::env_logger::init();
+    let e = parse_new!("let f x : [Eq a] -> a -> Bool = x in f");
+    let eq_a = Type::app(typ("Eq"), vec![typ("a")]);
+    match e.value {
+        Expr::LetBindings(bind, _) => {
+            assert_eq!(bind[0].typ,
+                       Type::implicit_function(vec![eq_a],
+                                               Type::function(vec![typ("a")], typ("Bool"))))
+        }
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn gadt_variant_type() {
+    let _ = ::env_logger::init();
+    let e = parse_new!("type Expr a = | IntLit : Int -> Expr Int | If Bool (Expr a) in 1");
+    let expr = |arg| Type::app(typ("Expr"), vec![arg]);
+    let int_lit = Type::function(vec![typ("Int")], expr(typ("Int")));
+    let if_ = Type::function(vec![typ("Bool"), expr(typ("a"))], expr(typ("a")));
+    assert_eq!(e,
+               type_decl(intern("Expr"),
+                         vec![generic("a")],
+                         Type::variants(vec![(intern("IntLit"), int_lit), (intern("If"), if_)]),
+                         int(1)));
+}
+
+#[test]
+fn forall_type() {
+    let _ = ::env_logger::init();
+    let e = parse_new!("let f x : (forall a . a -> a) -> Int = x 1 in f");
+    let id_type = Type::forall(vec![generic("a")], Type::function(vec![typ("a")], typ("a")));
+    match e.value {
+        Expr::LetBindings(bind, _) => {
+            assert_eq!(bind[0].typ, Type::function(vec![id_type], typ("Int")))
+        }
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn forall_type_in_record_field() {
+    let _ = ::env_logger::init();
+    let e = parse_new!("type Functor f = { map : forall a b . (a -> b) -> f a -> f b } in 1");
+    let map_type = Type::function(vec![Type::function(vec![typ("a")], typ("b")),
+                                       Type::app(typ("f"), vec![typ("a")])],
+                                  Type::app(typ("f"), vec![typ("b")]));
+    let record = Type::record(vec![],
+                              vec![field("map",
+                                         Type::forall(vec![generic("a"), generic("b")],
+                                                      map_type))]);
+    assert_eq!(e, type_decl(intern("Functor"), vec![generic("f")], record, int(1)));
+}
+
+#[test]
+fn module_signature_type() {
+    let _ = ::env_logger::init();
+    let text = r#"
+{
+    Stack,
+    Pair a = (a, a),
+    empty : Stack a,
+    push : a -> Stack a -> Stack a,
+}
+"#;
+    let (typ_, fields) = parse_signature_(&mut MockEnv::<String>::new(), text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let stack = |arg| Type::app(typ("Stack"), vec![arg]);
+    let pair = Type::record(vec![], vec![field("_0", typ("a")), field("_1", typ("a"))]);
+    let expected = Type::record(vec![Field {
+                                         name: intern("Stack"),
+                                         typ: Alias::new(intern("Stack"), vec![], typ("Stack")),
+                                     },
+                                     Field {
+                                         name: intern("Pair"),
+                                         typ: Alias::new(intern("Pair"), vec![generic("a")], pair),
+                                     }],
+                                vec![field("empty", stack(typ("a"))),
+                                     field("push",
+                                           Type::function(vec![typ("a"), stack(typ("a"))],
+                                                          stack(typ("a"))))]);
+    assert_eq!(typ_, expected);
+
+    let span_text = |span: Span<BytePos>| &text[span.start.to_usize()..span.end.to_usize()];
+    let fields: Vec<_> = fields.iter()
+        .map(|field| (&field.value[..], span_text(field.span)))
+        .collect();
+    assert_eq!(fields,
+               vec![("Stack", "Stack"), ("Pair", "Pair"), ("empty", "empty"), ("push", "push")]);
+}
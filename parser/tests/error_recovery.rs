@@ -0,0 +1,119 @@
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+
+mod support;
+
+use base::ast::*;
+use parser::parse_string;
+use support::MockEnv;
+
+fn parse(text: &str) -> (Option<SpannedExpr<String>>, usize) {
+    match parse_string(&mut MockEnv::new(), text) {
+        Ok(expr) => (Some(expr), 0),
+        Err((expr, err)) => (expr, err.errors.len()),
+    }
+}
+
+fn span_text<'a>(text: &'a str, expr: &SpannedExpr<String>) -> &'a str {
+    &text[expr.span.start.to_usize()..expr.span.end.to_usize()]
+}
+
+#[test]
+fn errors_in_multiple_let_bindings() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let x = 1 +
+let y = 2 *
+x
+"#;
+    let (expr, errors) = parse(text);
+    assert_eq!(errors, 2);
+
+    let expr = expr.expect("Expression");
+    match expr.value {
+        Expr::LetBindings(ref binds, ref body) => {
+            assert_eq!(binds[0].expr.value, Expr::Error);
+            assert_eq!(span_text(text, &binds[0].expr), "1 +");
+            match body.value {
+                Expr::LetBindings(ref binds, ref body) => {
+                    assert_eq!(binds[0].expr.value, Expr::Error);
+                    assert_eq!(span_text(text, &binds[0].expr), "2 *");
+                    assert_eq!(span_text(text, body), "x");
+                }
+                _ => panic!("Expected let bindings, found {:?}", body),
+            }
+        }
+        _ => panic!("Expected let bindings, found {:?}", expr),
+    }
+}
+
+#[test]
+fn error_inside_parentheses() {
+    let _ = ::env_logger::init();
+    let text = "f (1 +) 2";
+    let (expr, errors) = parse(text);
+    assert_eq!(errors, 1);
+
+    let expr = expr.expect("Expression");
+    match expr.value {
+        Expr::App(_, ref args) => {
+            assert_eq!(args.len(), 2);
+            assert_eq!(args[0].value, Expr::Error);
+            assert_eq!(span_text(text, &args[1]), "2");
+        }
+        _ => panic!("Expected application, found {:?}", expr),
+    }
+}
+
+#[test]
+fn error_in_array_element() {
+    let _ = ::env_logger::init();
+    let text = "[1, 2 +, 3]";
+    let (expr, errors) = parse(text);
+    assert_eq!(errors, 1);
+
+    let expr = expr.expect("Expression");
+    match expr.value {
+        Expr::Array(ref array) => {
+            assert_eq!(array.exprs.iter().map(|e| span_text(text, e)).collect::<Vec<_>>(),
+                       ["1", "2 +", "3"]);
+        }
+        _ => panic!("Expected array, found {:?}", expr),
+    }
+}
+
+#[test]
+fn error_in_block_expression() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let f x =
+    let y = x +
+    y
+f 1
+"#;
+    let (expr, errors) = parse(text);
+    assert_eq!(errors, 1);
+
+    let expr = expr.expect("Expression");
+    match expr.value {
+        Expr::LetBindings(_, ref body) => assert_eq!(span_text(text, body), "f 1"),
+        _ => panic!("Expected let bindings, found {:?}", expr),
+    }
+}
+
+#[test]
+fn error_after_more_tokens_than_can_be_buffered_is_not_recovered() {
+    let _ = ::env_logger::init();
+    let long_expr = vec!["1"; 200].join(" + ");
+    let text = format!("let x = {} +\nlet y = 2\ny", long_expr);
+    let (expr, errors) = parse(&text);
+    assert_eq!(errors, 1);
+    assert!(expr.is_none(), "{:?}", expr);
+
+    let text = format!("let x = {}\nx", long_expr);
+    let (expr, errors) = parse(&text);
+    assert_eq!(errors, 0);
+    assert!(expr.is_some());
+}
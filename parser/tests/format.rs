@@ -0,0 +1,149 @@
+extern crate gluon_parser as parser;
+
+use std::fs::{self, File};
+use std::io::Read;
+
+use parser::format::format_expr;
+
+fn format(input: &str) -> String {
+    format_expr(input).unwrap_or_else(|err| panic!("{}", err))
+}
+
+#[test]
+fn let_bindings() {
+    let input = r#"let   x=1
+let f x y = x #Int+ y
+f x 2"#;
+    assert_eq!(format(input),
+               r#"let x = 1
+let f x y = x #Int+ y
+f x 2
+"#);
+}
+
+#[test]
+fn match_expr() {
+    let input = r#"match x with
+| Some  y->y
+| None ->   0"#;
+    assert_eq!(format(input),
+               r#"match x with
+| Some y -> y
+| None -> 0
+"#);
+}
+
+#[test]
+fn redundant_parentheses() {
+    let input = r#"let x = (1 + 2) * 3 - (4 - 5)
+let y = (x * 2) + (f (x))
+y"#;
+    assert_eq!(format(input),
+               r#"let x = (1 + 2) * 3 - (4 - 5)
+let y = x * 2 + f x
+y
+"#);
+}
+
+#[test]
+fn short_record() {
+    let input = r#"let r = {a=1,b="x",c}
+r"#;
+    assert_eq!(format(input),
+               r#"let r = { a = 1, b = "x", c }
+r
+"#);
+}
+
+#[test]
+fn long_record() {
+    let input = "let record = { field_number_0 = 1000000000, field_number_1 = 1000000001, \
+                 field_number_2 = 1000000002, field_number_3 = 1000000003 }\nrecord";
+    let expected = r#"let record = {
+    field_number_0 = 1000000000,
+    field_number_1 = 1000000001,
+    field_number_2 = 1000000002,
+    field_number_3 = 1000000003,
+}
+record
+"#;
+    assert_eq!(format(input), expected);
+    assert_eq!(format(expected), expected);
+}
+
+#[test]
+fn long_if_else() {
+    let input = "let f = \\x -> if x then \"this string is long enough that both branches will \
+                 not fit\" else \"this string is long enough that both branches will not fit\"\nf";
+    let expected = r#"let f = \x ->
+    if x then
+        "this string is long enough that both branches will not fit"
+    else
+        "this string is long enough that both branches will not fit"
+f
+"#;
+    assert_eq!(format(input), expected);
+    assert_eq!(format(expected), expected);
+}
+
+#[test]
+fn comments_are_kept() {
+    let input = r#"// header
+let x = 1 // one
+
+// before y
+let y = 2
+/* block */
+x
+"#;
+    assert_eq!(format(input), input);
+}
+
+#[test]
+fn doc_comments() {
+    let input = r#"/// Adds one
+///   to a number
+let add1 x = x #Int+ 1
+add1"#;
+    assert_eq!(format(input),
+               r#"/// Adds one
+/// to a number
+let add1 x = x #Int+ 1
+add1
+"#);
+}
+
+#[test]
+fn gadt_variants() {
+    let input = r#"type Expr a =   | IntLit :Int->Expr Int
+    | If (Expr Bool) (Expr a) (Expr a)
+1"#;
+    assert_eq!(format(input),
+               r#"type Expr a = | IntLit : Int -> Expr Int | If (Expr Bool) (Expr a) (Expr a)
+1
+"#);
+}
+
+#[test]
+fn record_type_fields() {
+    let input = r#"type Module a = {Stack,Pair b=(b,b),x:a}
+1"#;
+    assert_eq!(format(input),
+               r#"type Module a = { Stack, Pair b = (b, b), x : a }
+1
+"#);
+}
+
+#[test]
+fn std_library_is_formatted_idempotently() {
+    for entry in fs::read_dir("../std").unwrap() {
+        let path = entry.unwrap().path();
+        let mut source = String::new();
+        File::open(&path).and_then(|mut file| file.read_to_string(&mut source)).unwrap();
+        let formatted = format_expr(&source)
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        let formatted_again = format_expr(&formatted)
+            .unwrap_or_else(|err| panic!("{}: {}\n{}", path.display(), err, formatted));
+        assert_eq!(formatted, formatted_again, "{}", path.display());
+    }
+}
@@ -0,0 +1,104 @@
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+
+mod support;
+
+use std::fs::{self, File};
+use std::io::Read;
+
+use base::ast::Expr;
+use base::pos::{BytePos, Span, Spanned};
+use parser::lexer::{tokenize, IdentType, Token, Trivia, TriviaToken};
+use parser::parse_expr_with_trivia;
+use support::MockEnv;
+
+fn text(source: &str, span: Span<BytePos>) -> &str {
+    &source[span.start.to_usize()..span.end.to_usize()]
+}
+
+fn trivia<'a>(source: &'a str, trivia: &[Spanned<Trivia, BytePos>]) -> Vec<(Trivia, &'a str)> {
+    trivia.iter().map(|trivia| (trivia.value, text(source, trivia.span))).collect()
+}
+
+fn concat(source: &str, tokens: &[TriviaToken<&str>]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        for trivia in &token.leading_trivia {
+            out.push_str(text(source, trivia.span));
+        }
+        out.push_str(text(source, token.token.span));
+    }
+    out
+}
+
+#[test]
+fn tokens_keep_comments_and_whitespace() {
+    let source = "let x = 1 // one\n/* two */ x\n";
+    let tokens = tokenize(source).unwrap();
+
+    assert_eq!(tokens.iter().map(|token| token.token.value.clone()).collect::<Vec<_>>(),
+               vec![Token::Let,
+                    Token::Ident("x", IdentType::Variable),
+                    Token::Equal,
+                    Token::Int(1),
+                    Token::Ident("x", IdentType::Variable),
+                    Token::EOF]);
+    assert_eq!(trivia(source, &tokens[4].leading_trivia),
+               vec![(Trivia::Whitespace, " "),
+                    (Trivia::LineComment, "// one"),
+                    (Trivia::Whitespace, "\n"),
+                    (Trivia::BlockComment, "/* two */"),
+                    (Trivia::Whitespace, " ")]);
+    assert_eq!(trivia(source, &tokens[5].leading_trivia),
+               vec![(Trivia::Whitespace, "\n")]);
+    assert_eq!(concat(source, &tokens), source);
+}
+
+#[test]
+fn doc_comments_are_tokens() {
+    let source = "/// Doc\nlet x = 1\nx";
+    let tokens = tokenize(source).unwrap();
+    assert_eq!(tokens[0].token.value, Token::DocComment("Doc".to_string()));
+    assert_eq!(concat(source, &tokens), source);
+}
+
+#[test]
+fn tokenize_error() {
+    assert!(tokenize("let x = \"abc").is_err());
+}
+
+#[test]
+fn std_library_is_tokenized_losslessly() {
+    for entry in fs::read_dir("../std").unwrap() {
+        let path = entry.unwrap().path();
+        let mut source = String::new();
+        File::open(&path).and_then(|mut file| file.read_to_string(&mut source)).unwrap();
+        let tokens = tokenize(&source).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assert_eq!(concat(&source, &tokens), source, "{}", path.display());
+    }
+}
+
+#[test]
+fn trivia_table_finds_comments_before_expressions() {
+    let source = r#"
+let x = 1 /* inside */ #Int+ 2
+// Body
+x
+"#;
+    let (expr, table) = parse_expr_with_trivia(&mut MockEnv::<String>::new(), source)
+        .unwrap_or_else(|(_, err)| panic!("{}", err));
+    let (binding, body) = match expr.value {
+        Expr::LetBindings(ref bindings, ref body) => (&bindings[0], body),
+        _ => panic!("Expected let bindings, found {:?}", expr),
+    };
+
+    assert_eq!(trivia(source, table.leading(body.span.start)),
+               vec![(Trivia::Whitespace, "\n"),
+                    (Trivia::LineComment, "// Body"),
+                    (Trivia::Whitespace, "\n")]);
+    assert_eq!(trivia(source, table.within(binding.expr.span)),
+               vec![(Trivia::Whitespace, " "),
+                    (Trivia::BlockComment, "/* inside */"),
+                    (Trivia::Whitespace, " "),
+                    (Trivia::Whitespace, " ")]);
+}
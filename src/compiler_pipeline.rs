@@ -9,17 +9,22 @@
 //! difficult to forget a stage.
 
 use std::borrow::{Borrow, BorrowMut};
+use std::mem;
+use std::sync::Arc;
 
-use base::ast::SpannedExpr;
+use base::ast::{Expr, Pattern, SpannedExpr, TypedIdent, ValueBinding};
+use base::diagnostic::Severity;
 use base::error::InFile;
+use base::pos;
 use base::types::ArcType;
 use base::symbol::{Name, NameBuf, Symbol, SymbolModule};
 
-use vm::compiler::CompiledFunction;
+use vm::compiler::{CompiledFunction, SourceFile};
 use vm::internal::ClosureDataDef;
 use vm::macros::MacroExpander;
 use vm::thread::{RootedValue, Thread, ThreadInternal};
 
+use import::Signature;
 use {Compiler, Result};
 
 /// Result type of successful macro expansion
@@ -78,7 +83,12 @@ impl<'s> MacroExpandable for &'s mut SpannedExpr<Symbol> {
         if compiler.implicit_prelude {
             compiler.include_implicit_prelude(file, self);
         }
+        // Modules imported by `self` are compiled with the lint settings of `compiler`
+        let start = ::import_lints(compiler, macros).warnings.len();
         macros.run(self);
+        // Pass on the warnings found in the modules which `self` imported
+        let warnings: Vec<_> = ::import_lints(compiler, macros).warnings.drain(start..).collect();
+        compiler.warnings.extend(warnings);
         Ok(MacroValue { expr: self })
     }
 }
@@ -144,9 +154,20 @@ impl<E> Typecheckable for MacroValue<E>
 
         let env = thread.get_env();
         let mut tc = Typecheck::new(file.into(), &mut compiler.symbols, &*env);
+        for (&lint, &level) in &compiler.lint_levels {
+            tc.set_lint_level(lint, level);
+        }
 
         let typ = try!(tc.typecheck_expr_expected(self.expr.borrow_mut(), expected_type)
             .map_err(|err| InFile::new(file, expr_str, err)));
+        let warnings = tc.take_warnings();
+        if warnings.has_errors() {
+            let warnings = InFile::new(file, expr_str, warnings);
+            if compiler.deny_warnings {
+                return Err(warnings.into());
+            }
+            compiler.warnings.push(warnings.severity(Severity::Warning));
+        }
 
         Ok(TypecheckValue {
             expr: self.expr,
@@ -155,6 +176,79 @@ impl<E> Typecheckable for MacroValue<E>
     }
 }
 
+impl<E> TypecheckValue<E>
+    where E: BorrowMut<SpannedExpr<Symbol>>,
+{
+    /// Checks that the module which was typechecked provides the types and values declared in
+    /// `signature`, reporting errors in the interface file which declares it. If successful the
+    /// module is changed to evaluate to a record of only the declared fields and the type is
+    /// replaced with the type the module is exported as, see `Typecheck::check_signature`.
+    pub fn check_signature(mut self,
+                           compiler: &mut Compiler,
+                           thread: &Thread,
+                           file: &str,
+                           signature: &Signature)
+                           -> Result<TypecheckValue<E>> {
+        use check::typecheck::Typecheck;
+
+        let typ = {
+            let env = thread.get_env();
+            let mut tc = Typecheck::new(file.into(), &mut compiler.symbols, &*env);
+            try!(tc.check_signature(&signature.typ, &signature.fields, &self.typ)
+                .map_err(|err| InFile::new(&signature.file, &signature.source, err)))
+        };
+        export_signature(self.expr.borrow_mut(), &self.typ, &typ);
+        Ok(TypecheckValue {
+            expr: self.expr,
+            typ: typ,
+        })
+    }
+}
+
+/// Replaces the value of the module `expr`, whose type is `actual`, with a record of the fields
+/// in `exported`. The fields are stored in the order they are declared in, as the compiler
+/// accesses the fields of records by their position in the type.
+///
+/// The value `{ Stack, push, unused }` becomes `let module = { Stack, push, unused } in { Stack,
+/// push = module.push }`. It is replaced inside the module's bindings so that the metadata of its
+/// types can still be found.
+fn export_signature(expr: &mut SpannedExpr<Symbol>, actual: &ArcType, exported: &ArcType) {
+    match expr.value {
+        Expr::LetBindings(_, ref mut body) |
+        Expr::TypeBindings(_, ref mut body) => return export_signature(body, actual, exported),
+        _ => (),
+    }
+    let span = expr.span;
+    let module = TypedIdent {
+        name: Symbol::from("module"),
+        typ: actual.clone(),
+    };
+    let types = exported.type_field_iter()
+        .map(|field| (field.typ.name.clone(), None))
+        .collect();
+    let exprs = exported.field_iter()
+        .map(|field| {
+            let module = Box::new(pos::spanned(span, Expr::Ident(module.clone())));
+            let projection = Expr::Projection(module, field.name.clone(), field.typ.clone());
+            (field.name.clone(), Some(pos::spanned(span, projection)))
+        })
+        .collect();
+    let record = Expr::Record {
+        typ: exported.clone(),
+        types: types,
+        exprs: exprs,
+    };
+    let value = mem::replace(&mut expr.value, Expr::Error);
+    let bind = ValueBinding {
+        comment: None,
+        name: pos::spanned(span, Pattern::Ident(module.clone())),
+        typ: actual.clone(),
+        args: Vec::new(),
+        expr: pos::spanned(span, value),
+    };
+    expr.value = Expr::LetBindings(vec![bind], Box::new(pos::spanned(span, record)));
+}
+
 /// Result of successful compilation
 pub struct CompileValue<E> {
     pub expr: E,
@@ -183,8 +277,11 @@ impl<'a, 'b, T> Compileable<(&'a str, Option<&'b ArcType>)> for T
                file: &str,
                (expr_str, expected_type): (&'a str, Option<&'b ArcType>))
                -> Result<CompileValue<Self::Expr>> {
-        self.typecheck_expected(compiler, thread, file, expr_str, expected_type)
-            .and_then(|tc_value| tc_value.compile(compiler, thread, file, ()))
+        let tc_value =
+            try!(self.typecheck_expected(compiler, thread, file, expr_str, expected_type));
+        let mut value = try!(tc_value.compile(compiler, thread, file, ()));
+        value.function.set_source(Arc::new(SourceFile::new(file, expr_str)));
+        Ok(value)
     }
 }
 impl<E, Extra> Compileable<Extra> for TypecheckValue<E>
@@ -206,7 +303,8 @@ impl<E, Extra> Compileable<Extra> for TypecheckValue<E>
             let name = NameBuf::from(name.module());
             let symbols = SymbolModule::new(String::from(AsRef::<str>::as_ref(&name)),
                                             &mut compiler.symbols);
-            let mut compiler = Compiler::new(&*env, thread.global_env(), symbols);
+            let mut compiler = Compiler::new(&*env, thread.global_env(), symbols)
+                .optimization_level(compiler.optimization_level);
             try!(compiler.compile_expr(self.expr.borrow()))
         };
         function.id = Symbol::from(filename);
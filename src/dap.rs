@@ -0,0 +1,767 @@
+//! Debug Adapter Protocol server which is started with `gluon dap`.
+//!
+//! Requests are read from stdin and responses and events are written to stdout. The debugged
+//! program runs on its own OS thread and while it is paused, requests which inspect or resume it
+//! are forwarded to that thread which answers them from inside the debugger hook. Such requests
+//! are answered with an error while the program is not paused.
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use rustc_serialize::json::Json;
+
+use base::instantiate::remove_aliases;
+use base::pos::Line;
+use base::types::{arg_iter, ArcType, BuiltinType, Type};
+use vm::Error as VmError;
+use vm::api::ValueRef;
+use vm::debugger::{Action, Breakpoint, DebugInfo, Debugger, DebuggerHandle, StackInfo};
+use vm::internal::Value;
+use vm::thread::{InterruptHandle, RootedValue, Thread};
+use vm::types::VmTag;
+
+use gluon::{filename_to_module, new_vm, Compiler, Error};
+use gluon::compiler_pipeline::{Compileable, Executable};
+use gluon::io::redirect_output;
+
+use protocol::{object, read_message, string, write_message};
+
+/// The id of the only thread which is reported to the client
+const THREAD_ID: i64 = 1;
+
+/// Writes responses and events to the client
+pub struct Output {
+    seq: i64,
+    writer: Box<Write + Send>,
+}
+
+type SharedOutput = Arc<Mutex<Output>>;
+
+impl Output {
+    pub fn new(writer: Box<Write + Send>) -> Output {
+        Output {
+            seq: 0,
+            writer: writer,
+        }
+    }
+
+    fn send(&mut self, mut fields: Vec<(&str, Json)>) {
+        self.seq += 1;
+        fields.push(("seq", Json::I64(self.seq)));
+        let result = write_message(&mut self.writer, &object(fields));
+        // There is no way to report the error to the client
+        if let Err(err) = result {
+            error!("Unable to write message: {}", err);
+        }
+    }
+
+    fn respond(&mut self, request: &Request, result: Result<Json, String>) {
+        let mut fields = vec![("type", string("response")),
+                              ("request_seq", Json::I64(request.seq)),
+                              ("command", string(&request.command[..]))];
+        match result {
+            Ok(body) => {
+                fields.push(("success", Json::Boolean(true)));
+                fields.push(("body", body));
+            }
+            Err(message) => {
+                fields.push(("success", Json::Boolean(false)));
+                fields.push(("message", string(message)));
+            }
+        }
+        self.send(fields)
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(vec![("type", string("event")), ("event", string(event)), ("body", body)])
+    }
+}
+
+struct Request {
+    seq: i64,
+    command: String,
+    arguments: Json,
+}
+
+impl Request {
+    fn from_json(json: &Json) -> Option<Request> {
+        let seq = json.find("seq").and_then(Json::as_i64);
+        let command = json.find("command").and_then(Json::as_string);
+        match (seq, command) {
+            (Some(seq), Some(command)) => {
+                Some(Request {
+                    seq: seq,
+                    command: command.into(),
+                    arguments: json.find("arguments").cloned().unwrap_or(Json::Null),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn argument(&self, name: &str) -> Option<&Json> {
+        self.arguments.find(name)
+    }
+}
+
+/// Handles which are sent back from the program thread once the program has been compiled
+type Started = Result<(DebuggerHandle, InterruptHandle), String>;
+
+/// The state of the server on the thread which reads the requests
+struct Session {
+    output: SharedOutput,
+    commands: Option<Sender<Request>>,
+    receiver: Option<Receiver<Request>>,
+    program: Option<(DebuggerHandle, InterruptHandle, thread::JoinHandle<()>)>,
+    /// Set by the program thread when the program is paused and cleared when the session forwards
+    /// a request which resumes it
+    paused: Arc<AtomicBool>,
+    /// Breakpoints which were set before the program were launched
+    breakpoints: Vec<(String, Vec<Line>)>,
+}
+
+impl Session {
+    /// Handles `request`, returning `false` if the client has disconnected
+    fn handle_request(&mut self, request: Request) -> bool {
+        let command = request.command.clone();
+        let result = match &command[..] {
+            "initialize" => {
+                let capabilities = object(vec![
+                    ("supportsConfigurationDoneRequest", Json::Boolean(true)),
+                    ("supportsEvaluateForHovers", Json::Boolean(true)),
+                ]);
+                let mut output = self.output.lock().unwrap();
+                output.respond(&request, Ok(capabilities));
+                output.event("initialized", object(vec![]));
+                return true;
+            }
+            "launch" => self.launch(&request),
+            "setBreakpoints" => self.set_breakpoints(&request),
+            "threads" => {
+                let thread = object(vec![("id", Json::I64(THREAD_ID)), ("name", string("main"))]);
+                Ok(object(vec![("threads", Json::Array(vec![thread]))]))
+            }
+            "pause" => {
+                match self.program {
+                    Some((ref debugger, _, _)) => {
+                        debugger.pause();
+                        Ok(object(vec![]))
+                    }
+                    None => Err("The program is not running".into()),
+                }
+            }
+            "configurationDone" => {
+                // The program thread waits for this request before starting the program
+                let _ = self.forward(Request {
+                    seq: request.seq,
+                    command: request.command.clone(),
+                    arguments: Json::Null,
+                });
+                Ok(object(vec![]))
+            }
+            "continue" | "next" | "stepIn" | "stepOut" | "stackTrace" | "scopes" |
+            "variables" | "evaluate" => {
+                // The program thread only reads requests while it is paused so any request sent
+                // while it is running would not be answered until it is paused again
+                if self.program.is_some() && !self.paused.load(Ordering::SeqCst) {
+                    Err("The program is not paused".into())
+                } else {
+                    match &command[..] {
+                        "continue" | "next" | "stepIn" | "stepOut" => {
+                            self.paused.store(false, Ordering::SeqCst)
+                        }
+                        _ => (),
+                    }
+                    return match self.forward(request) {
+                        Ok(()) => true,
+                        Err(request) => {
+                            let mut output = self.output.lock().unwrap();
+                            output.respond(&request, Err("The program is not running".into()));
+                            true
+                        }
+                    };
+                }
+            }
+            "disconnect" => {
+                self.output.lock().unwrap().respond(&request, Ok(object(vec![])));
+                return false;
+            }
+            command => Err(format!("Unsupported request `{}`", command)),
+        };
+        self.output.lock().unwrap().respond(&request, result);
+        true
+    }
+
+    fn forward(&self, request: Request) -> Result<(), Request> {
+        match self.commands {
+            Some(ref commands) => commands.send(request).map_err(|err| err.0),
+            None => Err(request),
+        }
+    }
+
+    fn launch(&mut self, request: &Request) -> Result<Json, String> {
+        let path = try!(request.argument("program")
+            .and_then(Json::as_string)
+            .ok_or_else(|| "Expected a `program` to launch".to_string()));
+        let stop_on_entry = request.argument("stopOnEntry")
+            .and_then(Json::as_boolean)
+            .unwrap_or(false);
+        let receiver = try!(self.receiver
+            .take()
+            .ok_or_else(|| "The program has already been launched".to_string()));
+
+        let (started_sender, started) = channel();
+        let output = self.output.clone();
+        let paused = self.paused.clone();
+        let path = path.to_string();
+        let join_handle = try!(thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || {
+                run_program(path, stop_on_entry, output, paused, receiver, started_sender)
+            })
+            .map_err(|err| err.to_string()));
+        let (debugger, interrupt) = match started.recv() {
+            Ok(Ok(handles)) => handles,
+            Ok(Err(message)) => {
+                let _ = join_handle.join();
+                return Err(message);
+            }
+            Err(_) => return Err("The program thread stopped unexpectedly".into()),
+        };
+        for (source_name, lines) in self.breakpoints.drain(..) {
+            debugger.set_breakpoints(&source_name, &lines);
+        }
+        self.program = Some((debugger, interrupt, join_handle));
+        Ok(object(vec![]))
+    }
+
+    fn set_breakpoints(&mut self, request: &Request) -> Result<Json, String> {
+        let path = try!(request.argument("source")
+            .and_then(|source| source.find("path"))
+            .and_then(Json::as_string)
+            .ok_or_else(|| "Expected a source path".to_string()));
+        let lines: Vec<Line> = request.argument("breakpoints")
+            .and_then(Json::as_array)
+            .map_or(Vec::new(), |breakpoints| {
+                breakpoints.iter()
+                    .filter_map(|breakpoint| breakpoint.find("line").and_then(Json::as_i64))
+                    .filter(|&line| line > 0)
+                    .map(|line| Line::from(line as usize - 1))
+                    .collect()
+            });
+        let source_name = filename_to_module(path);
+        match self.program {
+            Some((ref debugger, _, _)) => debugger.set_breakpoints(&source_name, &lines),
+            None => {
+                self.breakpoints.retain(|&(ref name, _)| *name != source_name);
+                self.breakpoints.push((source_name, lines.clone()));
+            }
+        }
+        let breakpoints = lines.iter()
+            .map(|line| {
+                object(vec![("verified", Json::Boolean(true)),
+                            ("line", Json::I64(line.to_usize() as i64 + 1))])
+            })
+            .collect();
+        Ok(object(vec![("breakpoints", Json::Array(breakpoints))]))
+    }
+
+    /// Waits for the program thread to exit. If `interrupt` is `true` the program is stopped,
+    /// otherwise it is only stopped if it is paused.
+    fn shutdown(&mut self, interrupt: bool) {
+        // Dropping the sender makes a paused program return an error from the debugger
+        self.commands = None;
+        if let Some((_, interrupt_handle, join_handle)) = self.program.take() {
+            if interrupt {
+                interrupt_handle.interrupt();
+            }
+            let _ = join_handle.join();
+        }
+    }
+}
+
+/// Runs the server, reading requests from stdin and writing responses to stdout
+#[cfg(not(test))]
+pub fn run() -> Result<(), Box<StdError + Send + Sync>> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    serve(&mut input, Box::new(io::stdout()))
+}
+
+/// Runs the server, reading requests from `input` until it is closed or the client disconnects
+pub fn serve<R: BufRead>(input: &mut R,
+                         writer: Box<Write + Send>)
+                         -> Result<(), Box<StdError + Send + Sync>> {
+    let (sender, receiver) = channel();
+    let mut session = Session {
+        output: Arc::new(Mutex::new(Output::new(writer))),
+        commands: Some(sender),
+        receiver: Some(receiver),
+        program: None,
+        paused: Arc::new(AtomicBool::new(false)),
+        breakpoints: Vec::new(),
+    };
+    loop {
+        let message = match read_message(input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            // The message was skipped so the next one can still be read
+            Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {
+                warn!("Ignoring invalid message: {}", err);
+                continue;
+            }
+            Err(err) => {
+                session.shutdown(true);
+                return Err(err.into());
+            }
+        };
+        match Request::from_json(&message) {
+            Some(request) => {
+                if !session.handle_request(request) {
+                    session.shutdown(true);
+                    return Ok(());
+                }
+            }
+            None => warn!("Ignoring message which is not a request: {}", message),
+        }
+    }
+    session.shutdown(false);
+    Ok(())
+}
+
+/// Compiles and runs the program at `path` on the current thread
+fn run_program(path: String,
+               stop_on_entry: bool,
+               output: SharedOutput,
+               paused: Arc<AtomicBool>,
+               commands: Receiver<Request>,
+               started: Sender<Started>) {
+    let mut text = String::new();
+    if let Err(err) = File::open(&path).and_then(|mut file| file.read_to_string(&mut text)) {
+        let _ = started.send(Err(format!("Unable to read `{}`: {}", path, err)));
+        return;
+    }
+    let module = filename_to_module(&path);
+
+    let vm = new_vm();
+    // Expressions are evaluated on a separate thread as `vm` is locked while it is paused
+    let eval_thread = match vm.new_thread() {
+        Ok(eval_thread) => eval_thread,
+        Err(err) => {
+            let _ = started.send(Err(err.to_string()));
+            return;
+        }
+    };
+    let mut compiler = Compiler::new();
+    let compiled = match (&text[..]).compile(&mut compiler, &vm, &module, (&text[..], None)) {
+        Ok(compiled) => compiled,
+        Err(err) => {
+            let _ = started.send(Err(err.to_string()));
+            return;
+        }
+    };
+
+    let commands = Arc::new(Mutex::new(commands));
+    let mut debugger = {
+        let commands = commands.clone();
+        let output = output.clone();
+        let program = Program {
+            module: module.clone(),
+            path: path.clone(),
+        };
+        let mut last_action = None;
+        Debugger::new(Box::new(move |thread: &Thread,
+                                     info: &DebugInfo,
+                                     breakpoints: &mut Vec<Breakpoint>| {
+            let reason = stop_reason(last_action, stop_on_entry, info, breakpoints);
+            let stopped = object(vec![("reason", string(reason)),
+                                      ("threadId", Json::I64(THREAD_ID)),
+                                      ("allThreadsStopped", Json::Boolean(true))]);
+            // Set before the event is sent as the client may send requests as soon as it is
+            // received
+            paused.store(true, Ordering::SeqCst);
+            output.lock().unwrap().event("stopped", stopped);
+            let action = try!(pause(thread,
+                                    info,
+                                    &commands.lock().unwrap(),
+                                    &output,
+                                    &mut compiler,
+                                    &eval_thread,
+                                    &program));
+            last_action = Some(action);
+            Ok(action)
+        }))
+    };
+    if stop_on_entry {
+        debugger.pause_on_start();
+    }
+    if started.send(Ok((debugger.handle(), vm.interrupt_handle()))).is_err() {
+        return;
+    }
+
+    // Wait until the client has set all breakpoints before starting
+    loop {
+        match commands.lock().unwrap().recv() {
+            Ok(ref request) if request.command == "configurationDone" => break,
+            Ok(request) => {
+                let mut output = output.lock().unwrap();
+                output.respond(&request, Err("The program has not started".into()));
+            }
+            Err(_) => return,
+        }
+    }
+    debugger.attach(&mut vm.context());
+
+    {
+        let output = output.clone();
+        redirect_output(Some(Box::new(move |s: &str| {
+            let body = object(vec![("category", string("stdout")), ("output", string(s))]);
+            output.lock().unwrap().event("output", body);
+        })));
+    }
+    let result = compiled.load_script(&mut Compiler::new(), &vm, &module, ());
+    redirect_output(None);
+
+    let exit_code = match result {
+        Ok(()) => 0,
+        Err(Error::VM(VmError::DebuggerQuit)) => 0,
+        Err(Error::VM(VmError::Interrupted(_))) => 0,
+        Err(err) => {
+            let body = object(vec![("category", string("stderr")),
+                                   ("output", string(format!("{}\n", err)))]);
+            output.lock().unwrap().event("output", body);
+            1
+        }
+    };
+    let mut output = output.lock().unwrap();
+    output.event("exited", object(vec![("exitCode", Json::I64(exit_code))]));
+    output.event("terminated", object(vec![]));
+}
+
+/// The module name and path of the program which is debugged
+struct Program {
+    module: String,
+    path: String,
+}
+
+fn stop_reason(last_action: Option<Action>,
+               stop_on_entry: bool,
+               info: &DebugInfo,
+               breakpoints: &[Breakpoint])
+               -> &'static str {
+    let at_breakpoint = info.stack_info(0).map_or(false, |frame| {
+        breakpoints.iter().any(|breakpoint| {
+            frame.source_name() == Some(&breakpoint.source_name[..]) &&
+            frame.line() == Some(breakpoint.line)
+        })
+    });
+    match last_action {
+        None if stop_on_entry => "entry",
+        _ if at_breakpoint => "breakpoint",
+        Some(Action::Continue) | None => "pause",
+        Some(_) => "step",
+    }
+}
+
+/// Answers requests until one of them resumes the program
+fn pause(thread: &Thread,
+         info: &DebugInfo,
+         commands: &Receiver<Request>,
+         output: &SharedOutput,
+         compiler: &mut Compiler,
+         eval_thread: &Thread,
+         program: &Program)
+         -> Result<Action, VmError> {
+    let mut variables = Variables::new(thread);
+    loop {
+        let request = try!(commands.recv().map_err(|_| VmError::DebuggerQuit));
+        let action = match &request.command[..] {
+            "continue" => Some(Action::Continue),
+            "next" => Some(Action::StepOver),
+            "stepIn" => Some(Action::StepIn),
+            "stepOut" => Some(Action::StepOut),
+            _ => None,
+        };
+        if let Some(action) = action {
+            output.lock().unwrap().respond(&request, Ok(object(vec![])));
+            return Ok(action);
+        }
+        let result = match &request.command[..] {
+            "stackTrace" => Ok(stack_trace(info, program)),
+            "scopes" => scopes(info, &mut variables, &request),
+            "variables" => variables.variables(info, &request),
+            "evaluate" => evaluate(info, &mut variables, compiler, eval_thread, &request),
+            command => Err(format!("Unsupported request `{}`", command)),
+        };
+        output.lock().unwrap().respond(&request, result);
+    }
+}
+
+fn frame_argument<'a>(info: &DebugInfo<'a>,
+                      request: &Request)
+                      -> Result<(usize, StackInfo<'a>), String> {
+    // Frame ids are the level of the frame plus one
+    let level = request.argument("frameId")
+        .and_then(Json::as_i64)
+        .map_or(0, |id| (id as usize).saturating_sub(1));
+    info.stack_info(level)
+        .map(|frame| (level, frame))
+        .ok_or_else(|| format!("No frame at level {}", level))
+}
+
+fn stack_trace(info: &DebugInfo, program: &Program) -> Json {
+    let mut frames = Vec::new();
+    while let Some(frame) = info.stack_info(frames.len()) {
+        let (line, column) = frame.location()
+            .map_or((0, 0), |location| {
+                (location.line.to_usize() as i64 + 1, location.column.to_usize() as i64 + 1)
+            });
+        let mut fields = vec![("id", Json::I64(frames.len() as i64 + 1)),
+                              ("name", string(frame.function_name().declared_name())),
+                              ("line", Json::I64(line)),
+                              ("column", Json::I64(column))];
+        if let Some(source_name) = frame.source_name() {
+            // Only the path of the launched program is known
+            let mut source = vec![("name", string(source_name))];
+            if source_name == program.module {
+                source.push(("path", string(&program.path[..])));
+            }
+            fields.push(("source", object(source)));
+        }
+        frames.push(object(fields));
+    }
+    let total = frames.len() as i64;
+    object(vec![("stackFrames", Json::Array(frames)), ("totalFrames", Json::I64(total))])
+}
+
+fn scopes(info: &DebugInfo,
+          variables: &mut Variables,
+          request: &Request)
+          -> Result<Json, String> {
+    let (level, _) = try!(frame_argument(info, request));
+    let scope = |name: &str, reference: i64| {
+        object(vec![("name", string(name)),
+                    ("variablesReference", Json::I64(reference)),
+                    ("expensive", Json::Boolean(false))])
+    };
+    let locals = scope("Locals", variables.add(Container::Locals(level)));
+    let upvars = scope("Upvars", variables.add(Container::Upvars(level)));
+    Ok(object(vec![("scopes", Json::Array(vec![locals, upvars]))]))
+}
+
+fn evaluate<'a>(info: &DebugInfo,
+                variables: &mut Variables<'a>,
+                compiler: &mut Compiler,
+                eval_thread: &'a Thread,
+                request: &Request)
+                -> Result<Json, String> {
+    let expr = try!(request.argument("expression")
+        .and_then(Json::as_string)
+        .ok_or_else(|| "Expected an expression".to_string()));
+    let (_, frame) = try!(frame_argument(info, request));
+    // Variables can be shown directly without compiling anything
+    let (value, typ) = match frame.get(expr) {
+        Some(var) => (var.value, var.typ.clone()),
+        None => {
+            let (value, typ) = try!(compiler.run_expr_in_frame(eval_thread, &frame, "<debug>", expr)
+                .map_err(|err| err.to_string()));
+            let result = *value;
+            variables.roots.push(value);
+            (result, typ)
+        }
+    };
+    let (text, reference) = variables.render(value, &typ);
+    Ok(object(vec![("result", string(text)),
+                   ("type", string(typ.to_string())),
+                   ("variablesReference", Json::I64(reference))]))
+}
+
+#[derive(Clone)]
+enum Container {
+    Locals(usize),
+    Upvars(usize),
+    Value(Value, ArcType),
+}
+
+/// The values which the client can request the variables of while the program is paused.
+/// `variablesReference` is the index of the container plus one.
+struct Variables<'a> {
+    thread: &'a Thread,
+    containers: Vec<Container>,
+    /// Keeps the results of evaluated expressions alive until the program is resumed
+    roots: Vec<RootedValue<&'a Thread>>,
+}
+
+impl<'a> Variables<'a> {
+    fn new(thread: &'a Thread) -> Variables<'a> {
+        Variables {
+            thread: thread,
+            containers: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, container: Container) -> i64 {
+        self.containers.push(container);
+        self.containers.len() as i64
+    }
+
+    fn variables(&mut self,
+                 info: &DebugInfo,
+                 request: &Request)
+                 -> Result<Json, String> {
+        let reference = request.argument("variablesReference").and_then(Json::as_i64).unwrap_or(0);
+        let container = try!((reference as usize)
+            .checked_sub(1)
+            .and_then(|index| self.containers.get(index))
+            .cloned()
+            .ok_or_else(|| format!("Unknown variables reference {}", reference)));
+        let frame_variables = |level: usize, upvars: bool| {
+            let frame = info.stack_info(level).expect("Frame");
+            let variables = if upvars {
+                frame.upvars()
+            } else {
+                frame.locals()
+            };
+            variables.into_iter()
+                .map(|var| (var.name.declared_name().to_string(), var.value, var.typ.clone()))
+                .collect::<Vec<_>>()
+        };
+        let children = match container {
+            Container::Locals(level) => frame_variables(level, false),
+            Container::Upvars(level) => frame_variables(level, true),
+            Container::Value(value, typ) => self.children(value, &typ),
+        };
+        let variables = children.into_iter()
+            .map(|(name, value, typ)| {
+                let (text, reference) = self.render(value, &typ);
+                object(vec![("name", string(name)),
+                            ("value", string(text)),
+                            ("type", string(typ.to_string())),
+                            ("variablesReference", Json::I64(reference))])
+            })
+            .collect();
+        Ok(object(vec![("variables", Json::Array(variables))]))
+    }
+
+    fn resolve(&self, typ: &ArcType) -> ArcType {
+        remove_aliases(&*self.thread.get_env(), typ.clone())
+    }
+
+    /// Renders `value` as a string, returning a reference to its fields if it has any
+    fn render(&mut self, value: Value, typ: &ArcType) -> (String, i64) {
+        let typ = self.resolve(typ);
+        let text = match ValueRef::new(&value) {
+            ValueRef::Byte(b) => b.to_string(),
+            ValueRef::Int(i) => i.to_string(),
+            ValueRef::Float(f) => f.to_string(),
+            ValueRef::String(s) => format!("{:?}", s),
+            ValueRef::Tag(tag) => constructor_name(&typ, tag).unwrap_or_else(|| tag.to_string()),
+            ValueRef::Data(data) => {
+                match *typ {
+                    Type::Record(_) => {
+                        let fields: Vec<_> = typ.field_iter()
+                            .map(|field| field.name.declared_name())
+                            .collect();
+                        format!("{{ {} }}", fields.join(", "))
+                    }
+                    _ => {
+                        constructor_name(&typ, data.tag())
+                            .unwrap_or_else(|| format!("<data {}>", data.tag()))
+                    }
+                }
+            }
+            ValueRef::Userdata(_) => "<userdata>".into(),
+            ValueRef::Internal => {
+                match value {
+                    Value::Array(array) => format!("[..] ({} elements)", array.len()),
+                    Value::Closure(closure) => {
+                        format!("<function {}>", closure.function.name.declared_name())
+                    }
+                    Value::Function(function) => {
+                        format!("<function {}>", function.id.declared_name())
+                    }
+                    Value::Thread(_) => "<thread>".into(),
+                    _ => "<function>".into(),
+                }
+            }
+        };
+        let has_children = match value {
+            Value::Data(data) => !data.fields.is_empty(),
+            Value::Array(array) => array.len() != 0,
+            _ => false,
+        };
+        let reference = if has_children {
+            self.add(Container::Value(value, typ))
+        } else {
+            0
+        };
+        (text, reference)
+    }
+
+    /// Returns the fields of a record, variant or array together with their names and types
+    fn children(&self, value: Value, typ: &ArcType) -> Vec<(String, Value, ArcType)> {
+        match value {
+            Value::Data(data) => {
+                let field_types: Vec<(String, ArcType)> = match **typ {
+                    Type::Record(_) => {
+                        typ.field_iter()
+                            .map(|field| {
+                                (field.name.declared_name().to_string(), field.typ.clone())
+                            })
+                            .collect()
+                    }
+                    Type::Variants(ref variants) => {
+                        variants.get(data.tag as usize)
+                            .map_or(Vec::new(), |&(_, ref constructor)| {
+                                arg_iter(constructor)
+                                    .enumerate()
+                                    .map(|(i, arg)| (i.to_string(), arg.clone()))
+                                    .collect()
+                            })
+                    }
+                    _ => Vec::new(),
+                };
+                data.fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &field)| {
+                        let (name, typ) = field_types.get(i)
+                            .cloned()
+                            .unwrap_or_else(|| (i.to_string(), Type::hole()));
+                        (name, field, typ)
+                    })
+                    .collect()
+            }
+            Value::Array(array) => {
+                let element_type = match **typ {
+                    Type::App(ref array_type, ref args) if args.len() == 1 => {
+                        match **array_type {
+                            Type::Builtin(BuiltinType::Array) => args[0].clone(),
+                            _ => Type::hole(),
+                        }
+                    }
+                    _ => Type::hole(),
+                };
+                array.iter()
+                    .enumerate()
+                    .map(|(i, element)| (format!("[{}]", i), element, element_type.clone()))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn constructor_name(typ: &ArcType, tag: VmTag) -> Option<String> {
+    match **typ {
+        Type::Variants(ref variants) => {
+            variants.get(tag as usize).map(|&(ref name, _)| name.declared_name().to_string())
+        }
+        _ => None,
+    }
+}
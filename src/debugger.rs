@@ -0,0 +1,203 @@
+//! Command line debugger which is started with `gluon debug <file>`
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+
+use base::pos::Line;
+use base::source::Source;
+use vm::Error as VmError;
+use vm::debugger::{Action, Breakpoint, DebugInfo, Debugger, StackInfo};
+use vm::thread::{Thread, ThreadInternal};
+
+use gluon::{filename_to_module, new_vm, Compiler};
+use gluon::compiler_pipeline::{Compileable, Executable};
+
+const HELP: &'static str = r#"Commands:
+    c, continue          Continue running until the next breakpoint
+    s, step              Step to the next line, entering any called function
+    n, next              Step to the next line of the current function
+    f, finish            Step out of the current function
+    b, break <line>      Set a breakpoint at <line>
+    d, delete <line>     Remove the breakpoint at <line>
+    bt, backtrace        Show the call stack
+    frame <level>        Select the frame at <level> in the call stack
+    locals               Show the local variables of the selected frame
+    upvars               Show the captured variables of the selected frame
+    p, print <expr>      Evaluate <expr> in the selected frame
+    q, quit              Stop the program
+    h, help              Show this message"#;
+
+fn print_location(module: &str, source: &Source, frame: &StackInfo) {
+    match (frame.source_name(), frame.line()) {
+        (Some(source_name), Some(line)) => {
+            // Only the source of the debugged module is available
+            let text = if source_name == module {
+                source.line(line).map_or("", |(_, text)| text)
+            } else {
+                ""
+            };
+            println!("{}:{}: {}", source_name, line, text);
+        }
+        _ => println!("{}", frame.function_name()),
+    }
+}
+
+fn print_backtrace(info: &DebugInfo) {
+    let mut level = 0;
+    while let Some(frame) = info.stack_info(level) {
+        match (frame.source_name(), frame.line()) {
+            (Some(source_name), Some(line)) => {
+                println!("#{} {} at {}:{}",
+                         level,
+                         frame.function_name(),
+                         source_name,
+                         line)
+            }
+            _ => println!("#{} {}", level, frame.function_name()),
+        }
+        level += 1;
+    }
+}
+
+fn parse_line(arg: &str) -> Option<Line> {
+    match arg.trim().parse::<usize>() {
+        Ok(line) if line > 0 => Some(Line::from(line - 1)),
+        _ => {
+            println!("Expected a line number, got `{}`", arg);
+            None
+        }
+    }
+}
+
+/// Reads and runs commands until one of them resumes the execution of the thread
+fn pause(compiler: &mut Compiler,
+         eval_thread: &Thread,
+         module: &str,
+         source: &Source,
+         info: &DebugInfo,
+         breakpoints: &mut Vec<Breakpoint>)
+         -> Result<Action, VmError> {
+    let mut level = 0;
+    if let Some(frame) = info.stack_info(level) {
+        print_location(module, source, &frame);
+    }
+    let stdin = io::stdin();
+    loop {
+        print!("(debug) ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return Err(VmError::DebuggerQuit),
+            Ok(_) => (),
+        }
+        let line = line.trim();
+        let (command, arg) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        let frame = info.stack_info(level);
+        match command {
+            "c" | "continue" => return Ok(Action::Continue),
+            "s" | "step" => return Ok(Action::StepIn),
+            "n" | "next" => return Ok(Action::StepOver),
+            "f" | "finish" => return Ok(Action::StepOut),
+            "q" | "quit" => return Err(VmError::DebuggerQuit),
+            "b" | "break" => {
+                if let Some(line) = parse_line(arg) {
+                    breakpoints.push(Breakpoint {
+                        source_name: module.into(),
+                        line: line,
+                    });
+                }
+            }
+            "d" | "delete" => {
+                if let Some(line) = parse_line(arg) {
+                    breakpoints.retain(|breakpoint| {
+                        !(breakpoint.source_name == module && breakpoint.line == line)
+                    });
+                }
+            }
+            "bt" | "backtrace" => print_backtrace(info),
+            "frame" => {
+                match arg.parse::<usize>() {
+                    Ok(new_level) if info.stack_info(new_level).is_some() => {
+                        level = new_level;
+                        if let Some(frame) = info.stack_info(level) {
+                            print_location(module, source, &frame);
+                        }
+                    }
+                    _ => println!("No frame at `{}`", arg),
+                }
+            }
+            "locals" => {
+                if let Some(frame) = frame {
+                    for var in frame.locals() {
+                        println!("{:?}", var);
+                    }
+                }
+            }
+            "upvars" => {
+                if let Some(frame) = frame {
+                    for var in frame.upvars() {
+                        println!("{:?}", var);
+                    }
+                }
+            }
+            "p" | "print" => {
+                let frame = match frame {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                match frame.get(arg) {
+                    Some(var) => println!("{:?}", var),
+                    None => {
+                        match compiler.run_expr_in_frame(eval_thread, &frame, "<debug>", arg) {
+                            Ok((value, typ)) => println!("{:?} : {}", value, typ),
+                            Err(err) => println!("{}", err),
+                        }
+                    }
+                }
+            }
+            "h" | "help" => println!("{}", HELP),
+            "" => (),
+            _ => println!("Unknown command `{}`, type `help` for a list of commands", command),
+        }
+    }
+}
+
+/// Runs the gluon program in `filename`, pausing at its first line to let the user set
+/// breakpoints and step through the program
+pub fn run(filename: &str) -> Result<(), Box<StdError + Send + Sync>> {
+    let mut text = String::new();
+    try!(try!(File::open(filename)).read_to_string(&mut text));
+    let module = filename_to_module(filename);
+
+    let vm = new_vm();
+    // Expressions are evaluated on a separate thread as `vm` is locked while it is paused
+    let eval_thread = try!(vm.new_thread());
+
+    // Compile the program before attaching the debugger so that modules which are imported while
+    // compiling do not trigger it
+    let mut compiler = Compiler::new();
+    let compiled = try!((&text[..]).compile(&mut compiler, &vm, &module, (&text[..], None)));
+
+    let mut debugger = {
+        let module = module.clone();
+        let text = text.clone();
+        Debugger::new(Box::new(move |_: &Thread,
+                                     info: &DebugInfo,
+                                     breakpoints: &mut Vec<Breakpoint>| {
+            let source = Source::new(&text);
+            pause(&mut compiler, &eval_thread, &module, &source, info, breakpoints)
+        }))
+    };
+    debugger.pause_on_start();
+    debugger.attach(&mut vm.context());
+
+    let result = compiled.load_script(&mut Compiler::new(), &vm, &module, ());
+    match result {
+        Ok(()) => Ok(()),
+        Err(::gluon::Error::VM(VmError::DebuggerQuit)) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
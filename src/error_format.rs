@@ -0,0 +1,274 @@
+//! Conversion of errors into JSON for the `--error-format=json` flag. Each error is written as a
+//! JSON object on its own line, for example:
+//!
+//! ```text
+//! {"code":"E0006","file":"test","kind":"type",
+//!  "message":"Expected the following types to be equal","notes":["Expected: Int","Found: String"],
+//!  "related":[{"file":"test","message":"expected because of this annotation","span":{..}}],
+//!  "rendered":"error[E0006]: ...","severity":"error",
+//!  "span":{"start":{"byte":14,"line":1,"column":15},"end":{"byte":19,"line":1,"column":20}}}
+//! ```
+//!
+//! Lines and columns start at 1 and columns are counted in characters. Errors which do not refer
+//! to a location in a file, such as IO errors, have a `null` `file` and `span`.
+//!
+//! Errors which are shown to a person are rendered by `render_error` instead.
+use std::fmt::{self, Write};
+
+use rustc_serialize::json::Json;
+
+use base::diagnostic::{self, AsDiagnostic, Diagnostic, LabelStyle, Renderer};
+use base::error::InFile;
+use base::pos::{BytePos, Location, Span};
+use base::source::Source;
+use base::symbol::Symbol;
+use check::typecheck::TypeError;
+use gluon::Error;
+
+use protocol::{object, string};
+
+/// Returns a JSON object for each error in `err`
+pub fn errors_to_json(err: &Error) -> Vec<Json> {
+    let mut errors = Vec::new();
+    collect_errors(err, &mut errors);
+    errors
+}
+
+fn collect_errors(err: &Error, errors: &mut Vec<Json>) {
+    match *err {
+        Error::Parse(ref err) => in_file_errors(err, |_| "parse", errors),
+        Error::Typecheck(ref err) => in_file_errors(err, type_error_kind, errors),
+        Error::IO(ref err) => errors.push(error_without_file("io", err)),
+        Error::VM(ref err) => errors.push(error_without_file("vm", err)),
+        Error::Macro(ref err) => {
+            // Errors in imported modules are returned by the `import` macro
+            match err.downcast_ref::<Error>() {
+                Some(err) => collect_errors(err, errors),
+                None => errors.push(error_without_file("macro", err)),
+            }
+        }
+        Error::Serialization(ref err) => errors.push(error_without_file("serialization", err)),
+        Error::Multiple(ref multiple) => {
+            for err in &multiple.errors {
+                collect_errors(err, errors);
+            }
+        }
+    }
+}
+
+/// Renders `err` in the same way as its `Display` implementation, with the diagnostics using ANSI
+/// colour codes if `color` is set
+pub fn render_error(err: &Error, color: bool) -> String {
+    let mut rendered = String::new();
+    render_error_(err, color, &mut rendered);
+    rendered
+}
+
+fn render_error_(err: &Error, color: bool, out: &mut String) {
+    match *err {
+        Error::Parse(ref err) => render_in_file(err, color, out),
+        Error::Typecheck(ref err) => render_in_file(err, color, out),
+        Error::Macro(ref err) => {
+            match err.downcast_ref::<Error>() {
+                Some(err) => render_error_(err, color, out),
+                None => {
+                    let _ = write!(out, "{}", err);
+                }
+            }
+        }
+        Error::Multiple(ref multiple) => {
+            for err in &multiple.errors {
+                render_error_(err, color, out);
+                out.push('\n');
+            }
+        }
+        _ => {
+            let _ = write!(out, "{}", err);
+        }
+    }
+}
+
+fn render_in_file<E: AsDiagnostic>(err: &InFile<E>, color: bool, out: &mut String) {
+    let source = Source::new(err.source());
+    let renderer = Renderer::new(err.source_name(), &source).color(color);
+    for (i, diagnostic) in err.diagnostics().iter().enumerate() {
+        if i != 0 {
+            out.push('\n');
+        }
+        let _ = renderer.render(out, diagnostic);
+    }
+}
+
+fn type_error_kind(err: &TypeError<Symbol>) -> &'static str {
+    match *err {
+        TypeError::KindError(_) => "kind",
+        TypeError::Rename(_) => "rename",
+        _ => "type",
+    }
+}
+
+fn in_file_errors<E, F>(err: &InFile<E>, kind: F, errors: &mut Vec<Json>)
+    where E: AsDiagnostic + fmt::Display,
+          F: Fn(&E) -> &'static str,
+{
+    let source = Source::new(err.source());
+    for (error, diagnostic) in err.errors().into_iter().zip(err.diagnostics()) {
+        errors.push(diagnostic_to_json(kind(&error.value),
+                                       Some((err.source_name(), &source)),
+                                       &diagnostic));
+    }
+}
+
+fn error_without_file<E: ToString>(kind: &str, err: &E) -> Json {
+    diagnostic_to_json(kind, None, &Diagnostic::new(err.to_string()))
+}
+
+fn diagnostic_to_json(kind: &str, file: Option<(&str, &Source)>, diagnostic: &Diagnostic) -> Json {
+    let primary = diagnostic.labels.iter().position(|label| label.style == LabelStyle::Primary);
+    let (name, span) = match (file, primary) {
+        (Some((name, source)), Some(i)) => {
+            (string(name), span_to_json(source, diagnostic.labels[i].span))
+        }
+        (Some((name, _)), None) => (string(name), Json::Null),
+        (None, _) => (Json::Null, Json::Null),
+    };
+    let related = diagnostic.labels
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| Some(i) != primary)
+        .map(|(_, label)| {
+            object(vec![("file", name.clone()),
+                        ("message", string(&label.message[..])),
+                        ("span",
+                         file.map_or(Json::Null, |(_, source)| span_to_json(source, label.span)))])
+        })
+        .collect();
+    let mut rendered = String::new();
+    match file {
+        Some((name, source)) => {
+            let _ = Renderer::new(name, source).render(&mut rendered, diagnostic);
+        }
+        None => rendered = format!("{}: {}", diagnostic.severity, diagnostic.message),
+    }
+    object(vec![("kind", string(kind)),
+                ("code", diagnostic.code.as_ref().map_or(Json::Null, |code| string(&code[..]))),
+                ("severity", string(diagnostic.severity.to_string())),
+                ("message", string(&diagnostic.message[..])),
+                ("file", name),
+                ("span", span),
+                ("related", Json::Array(related)),
+                ("notes",
+                 Json::Array(diagnostic.notes.iter().map(|note| string(&note[..])).collect())),
+                ("rendered", string(rendered))])
+}
+
+fn span_to_json(source: &Source, span: Span<BytePos>) -> Json {
+    match (diagnostic::location(source, span.start), diagnostic::location(source, span.end)) {
+        (Some(start), Some(end)) => {
+            object(vec![("start", location_to_json(start)), ("end", location_to_json(end))])
+        }
+        _ => Json::Null,
+    }
+}
+
+fn location_to_json(location: Location) -> Json {
+    object(vec![("byte", Json::U64(location.absolute.to_usize() as u64)),
+                ("line", Json::U64(location.line.to_usize() as u64 + 1)),
+                ("column", Json::U64(location.column.to_usize() as u64 + 1))])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use rustc_serialize::json::Json;
+
+    use base::error::Errors;
+    use gluon::{new_vm, Compiler, Error};
+
+    use super::{errors_to_json, render_error};
+
+    fn find<'a>(json: &'a Json, path: &[&str]) -> &'a Json {
+        json.find_path(path).unwrap_or_else(|| panic!("{:?} is missing in {}", path, json))
+    }
+
+    #[test]
+    fn type_error_with_annotation() {
+        let vm = new_vm();
+        let text = "let x : Int = \"abc\"\nx";
+        let err = Compiler::new()
+            .implicit_prelude(false)
+            .typecheck_str(&vm, "test", text, None)
+            .unwrap_err();
+        let errors = errors_to_json(&err);
+        assert_eq!(errors.len(), 1);
+
+        let error = &errors[0];
+        assert_eq!(find(error, &["kind"]).as_string(), Some("type"));
+        assert_eq!(find(error, &["code"]).as_string(), Some("E0006"));
+        assert_eq!(find(error, &["severity"]).as_string(), Some("error"));
+        assert_eq!(find(error, &["file"]).as_string(), Some("test"));
+        assert_eq!(find(error, &["span", "start", "byte"]).as_u64(), Some(14));
+        assert_eq!(find(error, &["span", "start", "line"]).as_u64(), Some(1));
+        assert_eq!(find(error, &["span", "start", "column"]).as_u64(), Some(15));
+        assert_eq!(find(error, &["span", "end", "byte"]).as_u64(), Some(19));
+
+        let related = find(error, &["related"]).as_array().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(find(&related[0], &["message"]).as_string(),
+                   Some("expected because of this annotation"));
+        assert_eq!(find(&related[0], &["span", "start", "column"]).as_u64(), Some(5));
+    }
+
+    #[test]
+    fn multiple_errors() {
+        let mut compiler = Compiler::new().implicit_prelude(false);
+        let parse_error = compiler.parse_expr("parse", "let x = 1 +\nx").unwrap_err();
+        let io_error = ::std::io::Error::new(::std::io::ErrorKind::NotFound, "missing");
+        let err = Error::Multiple(Errors {
+            errors: vec![Error::from(parse_error), Error::from(io_error)],
+        });
+
+        let errors = errors_to_json(&err);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(find(&errors[0], &["kind"]).as_string(), Some("parse"));
+        assert!(find(&errors[0], &["span", "start", "byte"]).is_u64());
+        assert_eq!(find(&errors[1], &["kind"]).as_string(), Some("io"));
+        assert_eq!(find(&errors[1], &["file"]), &Json::Null);
+        assert_eq!(find(&errors[1], &["message"]).as_string(), Some("missing"));
+    }
+
+    #[test]
+    fn error_in_imported_module() {
+        let path = ::std::env::temp_dir().join("gluon_error_format_import.glu");
+        File::create(&path)
+            .and_then(|mut file| file.write_all(b"let x : Int = \"abc\"\nx"))
+            .unwrap();
+
+        let vm = new_vm();
+        let text = format!("import {:?}", path.to_str().unwrap());
+        let err = Compiler::new()
+            .implicit_prelude(false)
+            .typecheck_str(&vm, "test", &text, None)
+            .unwrap_err();
+        let errors = errors_to_json(&err);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(find(&errors[0], &["kind"]).as_string(), Some("type"));
+        assert_eq!(find(&errors[0], &["span", "start", "byte"]).as_u64(), Some(14));
+        assert_eq!(render_error(&err, false), err.to_string());
+    }
+
+    #[test]
+    fn render_with_and_without_color() {
+        let vm = new_vm();
+        let err = Compiler::new()
+            .implicit_prelude(false)
+            .typecheck_str(&vm, "test", "let x : Int = \"abc\"\nx", None)
+            .unwrap_err();
+
+        assert_eq!(render_error(&err, false), err.to_string());
+        let colored = render_error(&err, true);
+        assert!(colored.contains("\x1b["), "{}", colored);
+    }
+}
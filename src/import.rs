@@ -8,9 +8,11 @@ use std::io::Read;
 use std::path::{Path, PathBuf};
 
 use base::ast::{Expr, Literal, SpannedExpr, TypedIdent};
-use base::metadata::Metadata;
-use base::pos;
+use base::pos::{self, BytePos, Spanned};
 use base::symbol::Symbol;
+use base::types::ArcType;
+use check::metadata;
+use vm::compiler::SourceFile;
 use vm::macros::{Macro, MacroExpander, Error as MacroError};
 use vm::thread::{Thread, ThreadInternal};
 use vm::internal::Value;
@@ -55,13 +57,29 @@ static STD_LIBS: [(&'static str, &'static str); 8] = std_libs!("prelude",
                                                                "test",
                                                                "writer");
 
+/// The signature of a module, declared as a record type in an interface file next to the module
+/// (`stack.glui` for `stack.glu`)
+pub struct Signature {
+    /// The name of the interface file
+    pub file: String,
+    /// The contents of the interface file
+    pub source: String,
+    /// The record type which declares the types and values the module exports
+    pub typ: ArcType,
+    /// The name of each field of `typ`, spanning its location in `source`
+    pub fields: Vec<Spanned<Symbol, BytePos>>,
+}
+
 pub trait Importer: Any + Clone + Sync + Send {
+    /// Loads the module `modulename`. If the module has an interface file `signature` is the
+    /// signature declared in it.
     fn import(&self,
               compiler: &mut Compiler,
               vm: &Thread,
               modulename: &str,
               input: &str,
-              expr: SpannedExpr<Symbol>)
+              expr: SpannedExpr<Symbol>,
+              signature: Option<&Signature>)
               -> Result<(), MacroError>;
 }
 
@@ -73,11 +91,18 @@ impl Importer for DefaultImporter {
               vm: &Thread,
               modulename: &str,
               input: &str,
-              expr: SpannedExpr<Symbol>)
+              expr: SpannedExpr<Symbol>,
+              signature: Option<&Signature>)
               -> Result<(), MacroError> {
         use compiler_pipeline::*;
 
-        try!(MacroValue { expr: expr }.load_script(compiler, vm, &modulename, (input, None)));
+        let mut value = try!(MacroValue { expr: expr }.typecheck(compiler, vm, modulename, input));
+        if let Some(signature) = signature {
+            value = try!(value.check_signature(compiler, vm, modulename, signature));
+        }
+        let mut value = try!(value.compile(compiler, vm, modulename, ()));
+        value.function.set_source(Arc::new(SourceFile::new(modulename, input)));
+        try!(value.load_script(compiler, vm, modulename, ()));
         Ok(())
     }
 }
@@ -95,15 +120,19 @@ impl Importer for CheckImporter {
               vm: &Thread,
               module_name: &str,
               input: &str,
-              expr: SpannedExpr<Symbol>)
+              expr: SpannedExpr<Symbol>,
+              signature: Option<&Signature>)
               -> Result<(), MacroError> {
         use compiler_pipeline::*;
 
         let macro_value = MacroValue { expr: expr };
-        let TypecheckValue { expr, typ } =
-            try!(macro_value.typecheck(compiler, vm, module_name, input));
+        let mut value = try!(macro_value.typecheck(compiler, vm, module_name, input));
+        if let Some(signature) = signature {
+            value = try!(value.check_signature(compiler, vm, module_name, signature));
+        }
+        let TypecheckValue { mut expr, typ } = value;
+        let metadata = metadata::metadata(&*vm.get_env(), &mut expr);
         self.0.lock().unwrap().insert(module_name.into(), expr);
-        let metadata = Metadata::default();
         // Insert a global to ensure the globals type can be looked up
         try!(vm.global_env().set_global(Symbol::from(module_name), typ, metadata, Value::Int(0)));
         Ok(())
@@ -111,7 +140,13 @@ impl Importer for CheckImporter {
 }
 
 /// Macro which rewrites occurances of `import "filename"` to a load of that file if it is not
-/// already loaded and then a global access to the loaded module
+/// already loaded and then a global access to the loaded module.
+///
+/// If an interface file exists next to the imported file (`stack.glui` for `stack.glu`) it is
+/// parsed as a record type which declares the types and values the module exports. The module is
+/// checked against it and only the declared fields can be used by the importer. Types declared
+/// without a definition (`{ Stack, empty : Stack a }`) are abstract and the importer can not see
+/// their representation.
 pub struct Import<I = DefaultImporter> {
     pub paths: RwLock<Vec<PathBuf>>,
     pub importer: I,
@@ -130,6 +165,23 @@ impl<I> Import<I> {
     pub fn add_path<P: Into<PathBuf>>(&self, path: P) {
         self.paths.write().unwrap().push(path.into());
     }
+
+    /// Opens the first file found at `path` relative to one of the import paths
+    fn find_file(&self, path: &Path) -> Option<File> {
+        self.paths
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|p| {
+                let mut base = p.clone();
+                base.push(path);
+                match File::open(&base) {
+                    Ok(file) => Some(file),
+                    Err(_) => None,
+                }
+            })
+            .next()
+    }
 }
 
 fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
@@ -175,34 +227,48 @@ impl<I> Macro for Import<I>
                         state.visited.push(filename.clone());
                     }
                     let mut buffer = String::new();
+                    let mut signature_buffer = String::new();
 
                     // Retrieve the source, first looking in the standard library included in the
                     // binary
-                    let file_contents = match STD_LIBS.iter().find(|tup| tup.0 == filename) {
-                        Some(tup) => tup.1,
+                    let std_lib = STD_LIBS.iter().find(|tup| tup.0 == filename);
+                    let (file_contents, signature_file) = match std_lib {
+                        Some(tup) => (tup.1, None),
                         None => {
-                            let file = self.paths
-                                .read()
-                                .unwrap()
-                                .iter()
-                                .filter_map(|p| {
-                                    let mut base = p.clone();
-                                    base.push(path);
-                                    match File::open(&base) {
-                                        Ok(file) => Some(file),
-                                        Err(_) => None,
-                                    }
-                                })
-                                .next();
-                            let mut file = try!(file.ok_or_else(|| {
+                            let mut file = try!(self.find_file(path).ok_or_else(|| {
                                 Error::String(format!("Could not find file '{}'", filename))
                             }));
                             try!(file.read_to_string(&mut buffer));
-                            &*buffer
+                            // The types and values the module exports may be declared in an
+                            // interface file next to it (`stack.glui` for `stack.glu`)
+                            let signature_path = path.with_extension("glui");
+                            let signature_file = match self.find_file(&signature_path) {
+                                Some(mut file) => {
+                                    try!(file.read_to_string(&mut signature_buffer));
+                                    Some(signature_path)
+                                }
+                                None => None,
+                            };
+                            (&*buffer, signature_file)
                         }
                     };
 
                     let mut compiler = Compiler::new().implicit_prelude(modulename != "std.types");
+                    // The standard library is compiled with the default lint settings and its
+                    // warnings are only logged as the user can not do anything about them
+                    if std_lib.is_none() {
+                        let lints = ::import_lints(&compiler, macros);
+                        compiler.lint_levels = lints.lint_levels.clone();
+                        compiler.deny_warnings = lints.deny_warnings;
+                    }
+                    let signature = match signature_file {
+                        Some(signature_file) => {
+                            let signature_file = signature_file.to_string_lossy();
+                            Some(try!(compiler.parse_signature(&signature_file, &signature_buffer)
+                                .map_err(::Error::from)))
+                        }
+                        None => None,
+                    };
                     let errors = macros.errors.errors.len();
                     let macro_result =
                         try!(file_contents.expand_macro_with(&mut compiler, macros, &modulename));
@@ -221,7 +287,12 @@ impl<I> Macro for Import<I>
                                 vm,
                                 &modulename,
                                 &file_contents,
-                                macro_result.expr));
+                                macro_result.expr,
+                                signature.as_ref()));
+                    if std_lib.is_none() {
+                        let warnings = compiler.take_warnings();
+                        ::import_lints(&compiler, macros).warnings.extend(warnings);
+                    }
                 }
                 // FIXME Does not handle shadowing
                 Ok(pos::spanned(args[0].span, Expr::Ident(TypedIdent::new(name))))
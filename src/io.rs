@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::string::String as StdString;
 use std::io::{Read, stdin};
 use std::fmt;
@@ -17,13 +18,38 @@ use vm::internal::Value;
 
 use super::{Compiler, Error};
 
+/// Function which receives the output of `io.print` and `io.println`
+pub type OutputFn = Box<FnMut(&str)>;
+
+thread_local!(static OUTPUT: RefCell<Option<OutputFn>> = RefCell::new(None));
+
+/// Redirects the output of `io.print` and `io.println` which are called on the current OS thread
+/// to `output`. Passing `None` makes them write to stdout again.
+pub fn redirect_output(output: Option<OutputFn>) {
+    OUTPUT.with(|current| *current.borrow_mut() = output);
+}
+
+fn write_output(s: &str) -> bool {
+    OUTPUT.with(|output| match *output.borrow_mut() {
+        Some(ref mut output) => {
+            output(s);
+            true
+        }
+        None => false,
+    })
+}
+
 fn print(s: &str) -> IO<()> {
-    print!("{}", s);
+    if !write_output(s) {
+        print!("{}", s);
+    }
     IO::Value(())
 }
 
 fn println(s: &str) -> IO<()> {
-    println!("{}", s);
+    if !write_output(&format!("{}\n", s)) {
+        println!("{}", s);
+    }
     IO::Value(())
 }
 
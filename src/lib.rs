@@ -24,21 +24,30 @@ pub use vm::thread::{RootedThread, Thread};
 use std::result::Result as StdResult;
 use std::string::String as StdString;
 use std::env;
+use std::sync::Arc;
 
-use base::ast::{self, SpannedExpr};
+use base::ast::{self, SpannedExpr, TypedIdent};
 use base::error::{Errors, InFile};
+use base::fnv::FnvMap;
 use base::metadata::Metadata;
+use base::pos;
 use base::symbol::{Symbol, Symbols, SymbolModule};
 use base::types::{ArcType, Type};
 use parser::ParseError;
+use check::lint::{Lint, LintLevel};
 use check::typecheck::TypeError;
 use vm::Variants;
 use vm::api::{Getable, Hole, VmType, OpaqueValue};
 use vm::Error as VmError;
-use vm::compiler::CompiledFunction;
-use vm::thread::ThreadInternal;
+use vm::compiler::{CompiledFunction, SourceFile};
+use vm::debugger::StackInfo;
+use vm::thread::{RootedValue, ThreadInternal};
+use vm::types::VmIndex;
 use vm::macros;
+use vm::optimize::OptimizationLevel;
+use vm::serialization;
 use compiler_pipeline::*;
+use import::Signature;
 
 quick_error! {
     /// Error type wrapping all possible errors that can be generated from gluon
@@ -74,6 +83,12 @@ quick_error! {
             display("{}", err)
             from()
         }
+        /// Error found when reading or writing a compiled function
+        Serialization(err: serialization::Error) {
+            description(err.description())
+            display("{}", err)
+            from()
+        }
         /// Multiple errors where found
         Multiple(err: Errors<Error>) {
             description(err.description())
@@ -122,6 +137,11 @@ pub type Result<T> = StdResult<T, Error>;
 pub struct Compiler {
     symbols: Symbols,
     implicit_prelude: bool,
+    optimization_level: OptimizationLevel,
+    lint_levels: FnvMap<Lint, LintLevel>,
+    deny_warnings: bool,
+    /// Warnings found when typechecking which have not been taken yet
+    warnings: Vec<InFile<TypeError<Symbol>>>,
 }
 
 impl Compiler {
@@ -130,6 +150,10 @@ impl Compiler {
         Compiler {
             symbols: Symbols::new(),
             implicit_prelude: true,
+            optimization_level: OptimizationLevel::default(),
+            lint_levels: FnvMap::default(),
+            deny_warnings: false,
+            warnings: Vec::new(),
         }
     }
 
@@ -140,6 +164,32 @@ impl Compiler {
         self
     }
 
+    /// Sets how much the compiled code should be optimized (default: `OptimizationLevel::None`)
+    pub fn optimization_level(mut self, optimization_level: OptimizationLevel) -> Compiler {
+        self.optimization_level = optimization_level;
+        self
+    }
+
+    /// Sets how the problems found by `lint` are reported when typechecking (default:
+    /// `LintLevel::Warn`)
+    pub fn lint_level(mut self, lint: Lint, level: LintLevel) -> Compiler {
+        self.lint_levels.insert(lint, level);
+        self
+    }
+
+    /// Sets whether warnings found when typechecking are reported as errors instead, causing
+    /// compilation to fail (default: false)
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Compiler {
+        self.deny_warnings = deny_warnings;
+        self
+    }
+
+    /// Returns the warnings which were found when typechecking since the last call to this
+    /// function
+    pub fn take_warnings(&mut self) -> Vec<InFile<TypeError<Symbol>>> {
+        ::std::mem::replace(&mut self.warnings, Vec::new())
+    }
+
     /// Parse `expr_str`, returning an expression if successful
     pub fn parse_expr(&mut self,
                       file: &str,
@@ -160,6 +210,24 @@ impl Compiler {
             .map_err(|(expr, err)| (expr, InFile::new(file, expr_str, err)))))
     }
 
+    /// Parse `input`, the interface file `file` which declares the signature of a module,
+    /// returning the signature if successful
+    pub fn parse_signature(&mut self,
+                           file: &str,
+                           input: &str)
+                           -> StdResult<Signature, InFile<ParseError>> {
+        let (typ, fields) =
+            try!(parser::parse_signature(&mut SymbolModule::new(file.into(), &mut self.symbols),
+                                         input)
+                .map_err(|err| InFile::new(file, input, err)));
+        Ok(Signature {
+            file: file.into(),
+            source: input.into(),
+            typ: typ,
+            fields: fields,
+        })
+    }
+
     /// Parse and typecheck `expr_str` returning the typechecked expression and type of the
     /// expression
     pub fn typecheck_expr(&mut self,
@@ -232,6 +300,61 @@ impl Compiler {
         self.load_script(vm, &name, &buffer)
     }
 
+    /// Compiles `input` and writes the resulting bytecode to `writer` so that it can later be loaded
+    /// with `load_compiled` without needing to parse, typecheck and compile `input` again.
+    pub fn write_compiled<W>(&mut self,
+                             vm: &Thread,
+                             filename: &str,
+                             input: &str,
+                             writer: &mut W)
+                             -> Result<()>
+        where W: ::std::io::Write,
+    {
+        let CompileValue { function, .. } = try!(input.compile(self, vm, filename, (input, None)));
+        try!(serialization::write_compiled(writer,
+                                           vm.global_env(),
+                                           input,
+                                           self.optimization_level,
+                                           &function));
+        Ok(())
+    }
+
+    /// Reads bytecode written by `write_compiled`, runs it and stores the resulting value in the
+    /// vm, just as `load_script` would have done with `input`.
+    ///
+    /// `input` is only used to check that the bytecode were compiled from the same source. If it
+    /// were not, or if the bytecode were compiled with a different optimization level or against
+    /// imported modules with different types, `Error::Serialization(serialization::Error::Stale)`
+    /// is returned and the caller should fall back to `load_script`. Metadata such as documentation
+    /// comments are not stored in the bytecode so the loaded value will not have any.
+    pub fn load_compiled<R>(&mut self,
+                            vm: &Thread,
+                            filename: &str,
+                            input: &str,
+                            reader: &mut R)
+                            -> Result<()>
+        where R: ::std::io::Read,
+    {
+        use vm::internal::ClosureDataDef;
+
+        let mut function = {
+            let mut symbols = SymbolModule::new(filename.into(), &mut self.symbols);
+            try!(serialization::read_compiled(reader,
+                                              vm.global_env(),
+                                              &mut symbols,
+                                              input,
+                                              self.optimization_level))
+        };
+        function.set_source(Arc::new(SourceFile::new(filename, input)));
+        let typ = function.typ.clone();
+        let function = try!(vm.global_env().new_function(function));
+        let closure = try!(vm.context().alloc(ClosureDataDef(function, &[])));
+        let value = try!(vm.call_thunk(closure));
+        try!(vm.global_env().set_global(function.name.clone(), typ, Metadata::default(), value));
+        info!("Loaded compiled module `{}`", filename);
+        Ok(())
+    }
+
     /// Compiles and runs the expression in `expr_str`. If successful the value from running the
     /// expression is returned
     pub fn run_expr<'vm, T>(&mut self,
@@ -288,6 +411,86 @@ impl Compiler {
         }
     }
 
+    /// Compiles and runs `expr_str` as if it were written at the current position of the
+    /// function described by `frame`, allowing it to refer to the variables of that function.
+    ///
+    /// `frame` usually describes a thread which is paused by a debugger. As that thread is
+    /// locked while it is paused the expression is run on `vm` which should be a thread created
+    /// from the paused thread with `new_thread` before the paused thread started executing.
+    pub fn run_expr_in_frame<'vm>(&mut self,
+                                  vm: &'vm Thread,
+                                  frame: &StackInfo,
+                                  name: &str,
+                                  expr_str: &str)
+                                  -> Result<(RootedValue<&'vm Thread>, ArcType)> {
+        let variables: Vec<_> = frame.visible_variables()
+            .into_iter()
+            .filter(|var| {
+                let is_ident = var.name
+                    .declared_name()
+                    .chars()
+                    .next()
+                    .map_or(false, |c| c.is_alphabetic() || c == '_');
+                let has_type = match **var.typ {
+                    Type::Hole => false,
+                    _ => true,
+                };
+                is_ident && has_type
+            })
+            .collect();
+        if variables.is_empty() {
+            let ExecuteValue { typ, value, .. } =
+                try!(expr_str.run_expr(self, vm, name, (expr_str, None)));
+            return Ok((value, typ));
+        }
+
+        // Compile the expression as a function which takes the variables of the frame as
+        // arguments and then call it with their current values
+        let body = try!(self.parse_expr(name, expr_str));
+        let args = variables.iter()
+            .map(|var| {
+                TypedIdent {
+                    name: self.symbols.symbol(var.name.declared_name()),
+                    typ: var.typ.clone(),
+                }
+            })
+            .collect();
+        let mut lambda = pos::spanned(body.span,
+                                      ast::Expr::Lambda(ast::Lambda {
+                                          id: TypedIdent::new(self.symbols.symbol(name)),
+                                          args: args,
+                                          body: Box::new(body),
+                                      }));
+        let expected = Type::function(variables.iter().map(|var| var.typ.clone()).collect(),
+                                      Type::hole());
+        let ExecuteValue { typ, value: function, .. } =
+            try!((&mut lambda).run_expr(self, vm, name, (expr_str, Some(&expected))));
+
+        let value = {
+            let mut context = vm.context();
+            context.stack.push(*function);
+            for var in &variables {
+                context.stack.push(var.value);
+            }
+            match try!(vm.call_function(context, variables.len() as VmIndex)) {
+                Some(mut context) => context.stack.pop(),
+                // The expression yielded, for instance by waiting on a channel
+                None => {
+                    let msg = format!("`{}` yielded instead of returning a value", expr_str);
+                    return Err(VmError::Message(msg).into());
+                }
+            }
+        };
+        let mut typ = typ;
+        for _ in &variables {
+            typ = match typ.as_function() {
+                Some((_, ret)) => ret.clone(),
+                None => break,
+            };
+        }
+        Ok((vm.root_value_ref(value), typ))
+    }
+
     fn include_implicit_prelude(&mut self, name: &str, expr: &mut SpannedExpr<Symbol>) {
         use std::mem;
         if name == "std.prelude" {
@@ -311,7 +514,31 @@ impl Compiler {
 
     in 0
     "#;
-        let prelude_expr = self.parse_expr("", prelude_import).unwrap();
+        let mut prelude_expr = self.parse_expr("", prelude_import).unwrap();
+
+        // The spans of the prelude refer to `prelude_import` so they are replaced by an empty
+        // span to prevent them from overlapping with the spans of `expr`
+        struct ClearSpans(pos::Span<pos::BytePos>);
+        impl ast::MutVisitor for ClearSpans {
+            type Ident = Symbol;
+
+            fn visit_expr(&mut self, e: &mut SpannedExpr<Symbol>) {
+                e.span = self.0;
+                ast::walk_mut_expr(self, e);
+            }
+
+            fn visit_pattern(&mut self, p: &mut ast::SpannedPattern<Symbol>) {
+                p.span = self.0;
+                ast::walk_mut_pattern(self, &mut p.value);
+            }
+        }
+        let start = expr.span.start;
+        ast::MutVisitor::visit_expr(&mut ClearSpans(pos::Span {
+                                        start: start,
+                                        end: start,
+                                    }),
+                                    &mut prelude_expr);
+
         let original_expr = mem::replace(expr, prelude_expr);
         fn assign_last_body(l: &mut SpannedExpr<Symbol>, original_expr: SpannedExpr<Symbol>) {
             match l.value {
@@ -325,6 +552,43 @@ impl Compiler {
     }
 }
 
+impl Drop for Compiler {
+    fn drop(&mut self) {
+        // Not every user of a compiler takes its warnings so those which were not taken are
+        // logged instead of being lost
+        for warning in &self.warnings {
+            warn!("{}", warning);
+        }
+    }
+}
+
+/// The lint settings which modules imported while expanding macros are compiled with, along with
+/// the warnings found in those modules. Kept in the state of the `MacroExpander` so that the
+/// `import` macro can pass the warnings on to the compiler which expands the macros.
+struct ImportLints {
+    lint_levels: FnvMap<Lint, LintLevel>,
+    deny_warnings: bool,
+    warnings: Vec<InFile<TypeError<Symbol>>>,
+}
+
+/// Returns the lint settings of the modules imported while expanding `macros`. They are taken
+/// from `compiler` unless an earlier compiler using `macros` has already set them.
+fn import_lints<'m>(compiler: &Compiler,
+                    macros: &'m mut macros::MacroExpander)
+                    -> &'m mut ImportLints {
+    macros.state
+        .entry(String::from("lints"))
+        .or_insert_with(|| {
+            Box::new(ImportLints {
+                lint_levels: compiler.lint_levels.clone(),
+                deny_warnings: compiler.deny_warnings,
+                warnings: Vec::new(),
+            })
+        })
+        .downcast_mut::<ImportLints>()
+        .unwrap()
+}
+
 pub fn filename_to_module(filename: &str) -> StdString {
     use std::path::Path;
     let path = Path::new(filename);
@@ -0,0 +1,835 @@
+//! Language Server Protocol server which is started with `gluon lsp`.
+//!
+//! Requests are read from stdin and responses and notifications are written to stdout. Each
+//! document is parsed and typechecked whenever it is opened or changed and the resulting
+//! expression is used to answer queries about it. Modules which are imported are only
+//! typechecked, never run.
+use std::env;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::json::Json;
+
+use base::ast::{Expr, Pattern, SpannedExpr};
+use base::diagnostic::AsDiagnostic;
+use base::error::InFile;
+use base::fnv::FnvMap;
+use base::metadata::{Metadata, MetadataEnv};
+use base::pos::{BytePos, Span};
+use base::symbol::Symbol;
+use check::{completion, query};
+use check::metadata::metadata_with_bindings;
+use vm::thread::{RootedThread, Thread};
+
+use gluon::{filename_to_module, new_vm, Compiler, Error};
+use gluon::compiler_pipeline::{MacroExpandable, MacroValue, Typecheckable};
+use gluon::import::{CheckImporter, Import};
+
+use protocol::{object, read_message, string, write_message};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_PARAMS: i64 = -32602;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// `SymbolKind` values from the protocol
+const KIND_CLASS: i64 = 5;
+const KIND_FUNCTION: i64 = 12;
+const KIND_VARIABLE: i64 = 13;
+
+/// `CompletionItemKind` values from the protocol
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+
+/// Error which is returned to the client in response to a request
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl ResponseError {
+    fn new<S: Into<String>>(code: i64, message: S) -> ResponseError {
+        ResponseError {
+            code: code,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_params<S: Into<String>>(message: S) -> ResponseError {
+        ResponseError::new(INVALID_PARAMS, message)
+    }
+}
+
+type RequestResult = Result<Json, ResponseError>;
+
+/// Converts a `file://` URI into a path, decoding any percent encoded characters
+fn uri_to_path(uri: &str) -> String {
+    let path = if uri.starts_with("file://") {
+        &uri["file://".len()..]
+    } else {
+        uri
+    };
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            ::std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Converts an absolute path into a `file://` URI
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Converts a byte offset into `text` to a protocol position, which counts characters in UTF-16
+/// code units
+fn to_position(text: &str, offset: usize) -> Json {
+    let mut offset = ::std::cmp::min(offset, text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..line_start].matches('\n').count();
+    let character = text[line_start..offset].encode_utf16().count();
+    object(vec![("line", Json::U64(line as u64)), ("character", Json::U64(character as u64))])
+}
+
+/// Converts a protocol position to a byte offset into `text`
+fn to_offset(text: &str, position: &Json) -> Option<usize> {
+    let line = position.find("line").and_then(Json::as_u64);
+    let character = position.find("character").and_then(Json::as_u64);
+    let (line, character) = match (line, character) {
+        (Some(line), Some(character)) => (line as usize, character as usize),
+        _ => return None,
+    };
+    let mut line_start = 0;
+    for _ in 0..line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return None,
+        }
+    }
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+fn range(text: &str, span: Span<BytePos>) -> Json {
+    object(vec![("start", to_position(text, span.start.to_usize())),
+                ("end", to_position(text, span.end.to_usize()))])
+}
+
+fn diagnostic<M: fmt::Display>(text: &str, span: Span<BytePos>, message: M) -> Json {
+    object(vec![("range", range(text, span)),
+                ("severity", Json::U64(1)),
+                ("source", string("gluon")),
+                ("message", string(message.to_string()))])
+}
+
+fn in_file_diagnostics<E>(module: &str, text: &str, err: &InFile<E>, diagnostics: &mut Vec<Json>)
+    where E: AsDiagnostic + fmt::Display,
+{
+    if err.source_name() != module {
+        // The error is in an imported module so it is reported at the start of the document
+        let start = BytePos::from(0);
+        let span = Span {
+            start: start,
+            end: start,
+        };
+        diagnostics.push(diagnostic(text, span, err));
+        return;
+    }
+    for error in err.errors() {
+        let span = Span {
+            start: error.span.start.absolute,
+            end: error.span.end.absolute,
+        };
+        diagnostics.push(diagnostic(text, span, &error.value));
+    }
+}
+
+fn error_diagnostics(module: &str, text: &str, err: &Error, diagnostics: &mut Vec<Json>) {
+    match *err {
+        Error::Parse(ref err) => in_file_diagnostics(module, text, err, diagnostics),
+        Error::Typecheck(ref err) => in_file_diagnostics(module, text, err, diagnostics),
+        Error::Multiple(ref errors) => {
+            for err in &errors.errors {
+                error_diagnostics(module, text, err, diagnostics);
+            }
+        }
+        ref err => {
+            let start = BytePos::from(0);
+            let span = Span {
+                start: start,
+                end: start,
+            };
+            diagnostics.push(diagnostic(text, span, err));
+        }
+    }
+}
+
+/// Looks up metadata from the bindings in a document before looking at the globals
+struct Metadatas<'a> {
+    bindings: &'a FnvMap<Symbol, Metadata>,
+    globals: &'a MetadataEnv,
+}
+
+impl<'a> MetadataEnv for Metadatas<'a> {
+    fn get_metadata(&self, id: &Symbol) -> Option<&Metadata> {
+        self.bindings.get(id).or_else(|| self.globals.get_metadata(id))
+    }
+}
+
+struct Document {
+    module: String,
+    text: String,
+    /// The typechecked expression, if the document could be parsed
+    expr: Option<SpannedExpr<Symbol>>,
+    bindings: FnvMap<Symbol, Metadata>,
+}
+
+impl Document {
+    /// Parses and typechecks `text`, returning the document and the errors which were found
+    fn analyze(vm: &Thread, module: &str, text: String) -> (Document, Vec<Json>) {
+        let mut compiler = Compiler::new();
+        let mut diagnostics = Vec::new();
+        // The parser may find parse errors but still produce an expression which can be
+        // typechecked
+        let expr = match compiler.parse_partial_expr(module, &text) {
+            Ok(expr) => Some(expr),
+            Err((expr, err)) => {
+                error_diagnostics(module, &text, &err.into(), &mut diagnostics);
+                expr
+            }
+        };
+        let mut bindings = FnvMap::default();
+        let expr = expr.map(|mut expr| {
+            if let Err(err) = (&mut expr).expand_macro(&mut compiler, vm, module) {
+                error_diagnostics(module, &text, &err, &mut diagnostics);
+            }
+            // Typecheck even if some imports failed so that as much as possible is inferred
+            let result = MacroValue { expr: &mut expr }.typecheck(&mut compiler, vm, module, &text);
+            if let Err(err) = result {
+                error_diagnostics(module, &text, &err, &mut diagnostics);
+            }
+            bindings = metadata_with_bindings(&*vm.get_env(), &mut expr).1;
+            expr
+        });
+        let document = Document {
+            module: module.into(),
+            text: text,
+            expr: expr,
+            bindings: bindings,
+        };
+        (document, diagnostics)
+    }
+}
+
+/// Returns the bindings and type bindings at the top level of `expr`
+fn document_symbols(uri: &str, text: &str, expr: &SpannedExpr<Symbol>) -> Vec<Json> {
+    let symbol = |name: &Symbol, kind: i64, span: Span<BytePos>| {
+        let location = object(vec![("uri", string(uri)), ("range", range(text, span))]);
+        object(vec![("name", string(name.declared_name())),
+                    ("kind", Json::I64(kind)),
+                    ("location", location)])
+    };
+    let mut symbols = Vec::new();
+    let mut current = expr;
+    loop {
+        match current.value {
+            Expr::LetBindings(ref bindings, ref body) => {
+                for bind in bindings {
+                    // Bindings from the implicit prelude have empty spans
+                    if bind.name.span.start == bind.name.span.end {
+                        continue;
+                    }
+                    if let Pattern::Ident(ref id) = bind.name.value {
+                        let kind = if bind.args.is_empty() && id.typ.as_function().is_none() {
+                            KIND_VARIABLE
+                        } else {
+                            KIND_FUNCTION
+                        };
+                        symbols.push(symbol(&id.name, kind, bind.name.span));
+                    }
+                }
+                current = body;
+            }
+            Expr::TypeBindings(ref bindings, ref body) => {
+                let span = Span {
+                    start: current.span.start,
+                    end: body.span.start,
+                };
+                for bind in bindings {
+                    symbols.push(symbol(&bind.name, KIND_CLASS, span));
+                }
+                current = body;
+            }
+            _ => break,
+        }
+    }
+    symbols
+}
+
+struct Server<W> {
+    output: W,
+    vm: Option<RootedThread>,
+    documents: FnvMap<String, Document>,
+    /// The expressions of the modules which have been imported
+    modules: CheckImporter,
+    /// The paths which are searched for imported modules
+    paths: Vec<PathBuf>,
+}
+
+impl<W: Write> Server<W> {
+    /// Handles a message from the client. Returns `false` once the client has sent `exit`.
+    fn handle_message(&mut self, message: &Json) -> io::Result<bool> {
+        let method = message.find("method").and_then(Json::as_string).unwrap_or("");
+        let params = message.find("params").unwrap_or(&Json::Null);
+        match message.find("id") {
+            Some(id) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => {
+                        object(vec![("jsonrpc", string("2.0")),
+                                    ("id", id.clone()),
+                                    ("result", result)])
+                    }
+                    Err(err) => {
+                        let error = object(vec![("code", Json::I64(err.code)),
+                                                ("message", string(err.message))]);
+                        object(vec![("jsonrpc", string("2.0")),
+                                    ("id", id.clone()),
+                                    ("error", error)])
+                    }
+                };
+                try!(write_message(&mut self.output, &response));
+            }
+            None => {
+                if method == "exit" {
+                    return Ok(false);
+                }
+                try!(self.notification(method, params));
+            }
+        }
+        Ok(true)
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> RequestResult {
+        if method == "initialize" {
+            return Ok(self.initialize(params));
+        }
+        if self.vm.is_none() {
+            return Err(ResponseError::new(SERVER_NOT_INITIALIZED,
+                                          "The server has not been initialized"));
+        }
+        match method {
+            "shutdown" => Ok(Json::Null),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/documentSymbol" => {
+                let uri = try!(text_document_uri(params));
+                let document = try!(self.document(uri));
+                Ok(Json::Array(document.expr
+                    .as_ref()
+                    .map_or(Vec::new(), |expr| document_symbols(uri, &document.text, expr))))
+            }
+            _ => {
+                Err(ResponseError::new(METHOD_NOT_FOUND, format!("Unknown method `{}`", method)))
+            }
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let text_document = params.find("textDocument");
+        let uri = text_document.and_then(|doc| doc.find("uri")).and_then(Json::as_string);
+        match (method, uri) {
+            ("textDocument/didOpen", Some(uri)) => {
+                let text = text_document.and_then(|doc| doc.find("text")).and_then(Json::as_string);
+                if let Some(text) = text {
+                    try!(self.update_document(uri, text.into()));
+                }
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // Only full document updates are requested in `initialize`
+                let text = params.find("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.find("text"))
+                    .and_then(Json::as_string);
+                if let Some(text) = text {
+                    try!(self.update_document(uri, text.into()));
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(uri);
+                try!(self.publish_diagnostics(uri, Vec::new()));
+            }
+            _ => debug!("Ignoring notification `{}`", method),
+        }
+        Ok(())
+    }
+
+    fn initialize(&mut self, params: &Json) -> Json {
+        let root = params.find("rootUri")
+            .and_then(Json::as_string)
+            .map(uri_to_path)
+            .or_else(|| params.find("rootPath").and_then(Json::as_string).map(String::from));
+
+        let vm = new_vm();
+        // Imported modules are only typechecked
+        let import = Import::new(self.modules.clone());
+        if let Ok(path) = env::var("GLUON_PATH") {
+            import.add_path(path);
+        }
+        if let Some(root) = root {
+            import.add_path(root);
+        }
+        self.paths = import.paths.read().unwrap().clone();
+        vm.get_macros().insert(String::from("import"), import);
+        self.vm = Some(vm);
+
+        let completion_options = object(vec![
+            ("resolveProvider", Json::Boolean(false)),
+            ("triggerCharacters", Json::Array(vec![string(".")])),
+        ]);
+        let capabilities = object(vec![
+            // Full documents are sent on each change
+            ("textDocumentSync", Json::I64(1)),
+            ("hoverProvider", Json::Boolean(true)),
+            ("completionProvider", completion_options),
+            ("definitionProvider", Json::Boolean(true)),
+            ("referencesProvider", Json::Boolean(true)),
+            ("documentSymbolProvider", Json::Boolean(true)),
+        ]);
+        object(vec![("capabilities", capabilities)])
+    }
+
+    fn update_document(&mut self, uri: &str, text: String) -> io::Result<()> {
+        let (document, diagnostics) = match self.vm {
+            Some(ref vm) => {
+                let module = filename_to_module(&uri_to_path(uri));
+                Document::analyze(vm, &module, text)
+            }
+            None => return Ok(()),
+        };
+        self.documents.insert(uri.into(), document);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+        let params = object(vec![("uri", string(uri)), ("diagnostics", Json::Array(diagnostics))]);
+        let notification = object(vec![("jsonrpc", string("2.0")),
+                                       ("method", string("textDocument/publishDiagnostics")),
+                                       ("params", params)]);
+        write_message(&mut self.output, &notification)
+    }
+
+    fn document(&self, uri: &str) -> Result<&Document, ResponseError> {
+        self.documents
+            .get(uri)
+            .ok_or_else(|| ResponseError::invalid_params(format!("Unknown document `{}`", uri)))
+    }
+
+    /// Returns the document, its expression and the position which `params` refer to
+    fn document_position<'a>
+        (&'a self,
+         params: &'a Json)
+         -> Result<Option<(&'a str, &'a Document, &'a SpannedExpr<Symbol>, BytePos)>,
+                   ResponseError> {
+        let uri = try!(text_document_uri(params));
+        let document = try!(self.document(uri));
+        let offset = try!(params.find("position")
+            .and_then(|position| to_offset(&document.text, position))
+            .ok_or_else(|| ResponseError::invalid_params("Expected a position")));
+        Ok(document.expr
+            .as_ref()
+            .map(|expr| (uri, document, expr, BytePos::from(offset))))
+    }
+
+    fn hover(&self, params: &Json) -> RequestResult {
+        let (_, document, expr, pos) = match try!(self.document_position(params)) {
+            Some(x) => x,
+            None => return Ok(Json::Null),
+        };
+        let vm = self.vm.as_ref().expect("Initialized vm");
+        let env = vm.get_env();
+        let mut contents = Vec::new();
+        if let Ok(typ) = completion::find(&*env, expr, pos) {
+            contents.push(object(vec![("language", string("gluon")),
+                                      ("value", string(typ.to_string()))]));
+        }
+        let metadatas = Metadatas {
+            bindings: &document.bindings,
+            globals: &*env,
+        };
+        let comment = completion::get_metadata(&metadatas, expr, pos)
+            .and_then(|metadata| metadata.comment.as_ref());
+        if let Some(comment) = comment {
+            contents.push(string(&comment[..]));
+        }
+        if contents.is_empty() {
+            Ok(Json::Null)
+        } else {
+            Ok(object(vec![("contents", Json::Array(contents))]))
+        }
+    }
+
+    fn completion(&self, params: &Json) -> RequestResult {
+        let (_, _, expr, pos) = match try!(self.document_position(params)) {
+            Some(x) => x,
+            None => return Ok(Json::Array(Vec::new())),
+        };
+        let vm = self.vm.as_ref().expect("Initialized vm");
+        let items = completion::suggest(&*vm.get_env(), expr, pos)
+            .into_iter()
+            .map(|suggestion| {
+                let kind = if suggestion.typ.as_function().is_some() {
+                    COMPLETION_FUNCTION
+                } else {
+                    COMPLETION_VARIABLE
+                };
+                object(vec![("label", string(suggestion.name)),
+                            ("kind", Json::I64(kind)),
+                            ("detail", string(suggestion.typ.to_string()))])
+            })
+            .collect();
+        Ok(Json::Array(items))
+    }
+
+    fn definition(&self, params: &Json) -> RequestResult {
+        let (uri, document, expr, pos) = match try!(self.document_position(params)) {
+            Some(x) => x,
+            None => return Ok(Json::Null),
+        };
+        let modules = self.modules.0.lock().unwrap();
+        let location = query::definition(&*modules, &document.module, expr, pos)
+            .and_then(|location| self.location(uri, document, &location));
+        Ok(location.unwrap_or(Json::Null))
+    }
+
+    fn references(&self, params: &Json) -> RequestResult {
+        let (uri, document, expr, pos) = match try!(self.document_position(params)) {
+            Some(x) => x,
+            None => return Ok(Json::Array(Vec::new())),
+        };
+        let mut locations = Vec::new();
+        let include_declaration = params.find_path(&["context", "includeDeclaration"])
+            .and_then(Json::as_boolean)
+            .unwrap_or(false);
+        if include_declaration {
+            // Only declarations in this document are included as references are not searched
+            // for in other modules
+            let declaration = query::definition(&(), &document.module, expr, pos)
+                .and_then(|location| self.location(uri, document, &location));
+            locations.extend(declaration);
+        }
+        locations.extend(query::references(expr, pos).into_iter().map(|span| {
+            object(vec![("uri", string(uri)), ("range", range(&document.text, span))])
+        }));
+        Ok(Json::Array(locations))
+    }
+
+    /// Converts `location` into a protocol location. `document` is the document at `uri` which
+    /// the query was made in.
+    fn location(&self, uri: &str, document: &Document, location: &query::Location) -> Option<Json> {
+        let span = location.span;
+        let to_location = |uri: &str, text: &str| {
+            object(vec![("uri", string(uri)), ("range", range(text, span))])
+        };
+        if location.source_name == document.module {
+            // Bindings from the implicit prelude have empty spans
+            return if span.start == span.end {
+                None
+            } else {
+                Some(to_location(uri, &document.text))
+            };
+        }
+        let open_document = self.documents
+            .iter()
+            .find(|&(_, document)| document.module == location.source_name);
+        if let Some((uri, document)) = open_document {
+            return Some(to_location(uri, &document.text));
+        }
+        // Look for the file in the same way as the `import` macro
+        let file_name = format!("{}.glu", location.source_name.replace('.', "/"));
+        self.paths
+            .iter()
+            .filter_map(|path| {
+                let path = path.join(&file_name);
+                let mut text = String::new();
+                match File::open(&path).and_then(|mut file| file.read_to_string(&mut text)) {
+                    Ok(_) => {
+                        path.canonicalize()
+                            .ok()
+                            .map(|path| to_location(&path_to_uri(&path), &text))
+                    }
+                    Err(_) => None,
+                }
+            })
+            .next()
+    }
+}
+
+fn text_document_uri(params: &Json) -> Result<&str, ResponseError> {
+    params.find_path(&["textDocument", "uri"])
+        .and_then(Json::as_string)
+        .ok_or_else(|| ResponseError::invalid_params("Expected a text document"))
+}
+
+/// Runs the server, reading messages from stdin and writing to stdout
+#[cfg(not(test))]
+pub fn run() -> Result<(), Box<StdError + Send + Sync>> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    serve(&mut input, io::stdout())
+}
+
+/// Runs the server, reading messages from `input` until it is closed or the client exits
+pub fn serve<R, W>(input: &mut R, output: W) -> Result<(), Box<StdError + Send + Sync>>
+    where R: BufRead,
+          W: Write,
+{
+    let mut server = Server {
+        output: output,
+        vm: None,
+        documents: FnvMap::default(),
+        modules: CheckImporter::new(),
+        paths: Vec::new(),
+    };
+    loop {
+        let message = match read_message(input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return Ok(()),
+            Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {
+                let error = object(vec![("code", Json::I64(PARSE_ERROR)),
+                                        ("message", string(err.to_string()))]);
+                let response = object(vec![("jsonrpc", string("2.0")),
+                                           ("id", Json::Null),
+                                           ("error", error)]);
+                try!(write_message(&mut server.output, &response));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if !try!(server.handle_message(&message)) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use rustc_serialize::json::Json;
+
+    use protocol::{object, read_message, string};
+    use super::serve;
+
+    const TEXT: &'static str = r#"/// The answer
+let answer = 42
+let add x = x #Int+ answer
+add 1
+"#;
+
+    fn message(id: Option<i64>, method: &str, params: Json) -> String {
+        let id = id.map_or(String::new(), |id| format!(r#""id":{},"#, id));
+        let body = format!(r#"{{"jsonrpc":"2.0",{}"method":"{}","params":{}}}"#,
+                           id,
+                           method,
+                           params);
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    fn text_document(uri: &str, line: u64, character: u64) -> Json {
+        Json::from_str(&format!(r#"{{"textDocument":{{"uri":"{}"}},
+                                     "position":{{"line":{},"character":{}}}}}"#,
+                                uri,
+                                line,
+                                character))
+            .unwrap()
+    }
+
+    fn run_session(messages: &[String]) -> Vec<Json> {
+        let mut input = Cursor::new(messages.concat().into_bytes());
+        let mut output = Vec::new();
+        serve(&mut input, &mut output).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    fn result(messages: &[Json], id: i64) -> &Json {
+        let response = messages.iter()
+            .find(|message| message.find("id").and_then(Json::as_i64) == Some(id))
+            .unwrap_or_else(|| panic!("No response to {} in {:?}", id, messages));
+        response.find("result").unwrap_or_else(|| panic!("Error response {}", response))
+    }
+
+    fn diagnostics<'a>(messages: &'a [Json], uri: &str) -> &'a [Json] {
+        messages.iter()
+            .filter(|message| {
+                message.find("method").and_then(Json::as_string) ==
+                Some("textDocument/publishDiagnostics")
+            })
+            .filter_map(|message| message.find("params"))
+            .find(|params| params.find("uri").and_then(Json::as_string) == Some(uri))
+            .and_then(|params| params.find("diagnostics"))
+            .and_then(Json::as_array)
+            .unwrap_or_else(|| panic!("No diagnostics for `{}` in {:?}", uri, messages))
+    }
+
+    fn did_open(uri: &str, text: &str) -> String {
+        let document = object(vec![("uri", string(uri)), ("text", string(text))]);
+        message(None, "textDocument/didOpen", object(vec![("textDocument", document)]))
+    }
+
+    #[test]
+    fn query_document() {
+        let _ = ::env_logger::init();
+
+        let uri = "file:///lsp_test.glu";
+        let error_uri = "file:///lsp_error.glu";
+        let mut references = text_document(uri, 2, 21);
+        if let Json::Object(ref mut params) = references {
+            let context = Json::from_str(r#"{"includeDeclaration":true}"#).unwrap();
+            params.insert("context".to_string(), context);
+        }
+        let messages = run_session(&[message(Some(1), "initialize", Json::from_str("{}").unwrap()),
+                                     did_open(uri, TEXT),
+                                     did_open(error_uri, "1 #Int+ \"\""),
+                                     message(Some(2),
+                                             "textDocument/hover",
+                                             text_document(uri, 2, 21)),
+                                     message(Some(3),
+                                             "textDocument/definition",
+                                             text_document(uri, 2, 21)),
+                                     message(Some(4),
+                                             "textDocument/completion",
+                                             text_document(uri, 3, 2)),
+                                     message(Some(5),
+                                             "textDocument/documentSymbol",
+                                             text_document(uri, 0, 0)),
+                                     message(Some(6), "textDocument/references", references),
+                                     message(Some(7), "shutdown", Json::Null),
+                                     message(None, "exit", Json::Null)]);
+
+        let capabilities = result(&messages, 1).find("capabilities").expect("Capabilities");
+        assert_eq!(capabilities.find("hoverProvider"), Some(&Json::Boolean(true)));
+
+        assert!(diagnostics(&messages, uri).is_empty());
+        let errors = diagnostics(&messages, error_uri);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].find_path(&["range", "start", "character"]),
+                   Some(&Json::U64(8)));
+
+        let hover = result(&messages, 2).find("contents").and_then(Json::as_array).unwrap();
+        assert_eq!(hover[0].find("value").and_then(Json::as_string), Some("Int"));
+        assert_eq!(hover[1].as_string(), Some("The answer"));
+
+        let definition = result(&messages, 3);
+        assert_eq!(definition.find("uri").and_then(Json::as_string), Some(uri));
+        assert_eq!(definition.find_path(&["range", "start"]),
+                   Some(&Json::from_str(r#"{"line":1,"character":4}"#).unwrap()));
+
+        let completions = result(&messages, 4).as_array().unwrap();
+        assert!(completions.iter()
+                    .any(|item| item.find("label").and_then(Json::as_string) == Some("add")),
+                "{:?}",
+                completions);
+
+        let symbols = result(&messages, 5)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| {
+                (symbol.find("name").and_then(Json::as_string).unwrap().to_string(),
+                 symbol.find("kind").and_then(Json::as_i64).unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, vec![("answer".to_string(), 13), ("add".to_string(), 12)]);
+
+        let references = result(&messages, 6)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|location| {
+                let start = location.find_path(&["range", "start"]).unwrap();
+                (start.find("line").and_then(Json::as_u64).unwrap(),
+                 start.find("character").and_then(Json::as_u64).unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(references, vec![(1, 4), (2, 20)]);
+
+        assert_eq!(result(&messages, 7), &Json::Null);
+    }
+
+    #[test]
+    fn query_document_with_syntax_errors() {
+        let _ = ::env_logger::init();
+
+        let uri = "file:///lsp_syntax_error.glu";
+        let text = "let x = 1\nlet y = x #Int+ )\nlet z = []\nx #Int+ \n";
+        let mut requests = vec![message(Some(1), "initialize", Json::from_str("{}").unwrap()),
+                                did_open(uri, text)];
+        let positions = [(0, 4), (1, 8), (1, 10), (2, 9), (3, 0), (3, 7)];
+        for (i, &(line, character)) in positions.iter().enumerate() {
+            let id = 2 + 2 * i as i64;
+            requests.push(message(Some(id),
+                                  "textDocument/hover",
+                                  text_document(uri, line, character)));
+            requests.push(message(Some(id + 1),
+                                  "textDocument/completion",
+                                  text_document(uri, line, character)));
+        }
+        requests.push(message(Some(100), "shutdown", Json::Null));
+        requests.push(message(None, "exit", Json::Null));
+        let messages = run_session(&requests);
+
+        assert!(!diagnostics(&messages, uri).is_empty());
+        for id in 2..(2 + 2 * positions.len() as i64) {
+            result(&messages, id);
+        }
+        assert_eq!(result(&messages, 100), &Json::Null);
+    }
+}
@@ -7,6 +7,8 @@ extern crate clap;
 extern crate quick_error;
 #[macro_use]
 extern crate lazy_static;
+extern crate rustc_serialize;
+extern crate atty;
 
 extern crate gluon_base as base;
 extern crate gluon;
@@ -17,28 +19,103 @@ extern crate gluon_vm as vm;
 
 
 #[cfg(not(test))]
-use std::error::Error as StdError;
+use std::fs::File;
 #[cfg(not(test))]
-use gluon::{new_vm, Compiler};
+use std::io::{Read, Write};
 #[cfg(not(test))]
-use clap::{Arg, App};
+use gluon::{new_vm, Compiler, Error};
+#[cfg(not(test))]
+use clap::{Arg, App, SubCommand};
+#[cfg(not(test))]
+use check::lint::{Lint, LintLevel};
 
+#[cfg(not(test))]
+mod dap;
+#[cfg(not(test))]
+mod debugger;
+mod error_format;
+mod lsp;
+mod protocol;
 mod repl;
 
 
+/// Runs each file in `files`, writing the warnings found in a file before running the next one
 #[cfg(not(test))]
-fn run_files<'s, I>(files: I) -> Result<(), Box<StdError + Send + Sync>>
+fn run_files<'s, I>(compiler: &mut Compiler,
+                    files: I,
+                    json: bool,
+                    color: bool)
+                    -> Result<(), Error>
     where I: Iterator<Item = &'s str>,
 {
     let vm = new_vm();
-    let mut compiler = Compiler::new();
     for file in files {
-        try!(compiler.load_file(&vm, file));
+        let result = compiler.load_file(&vm, file);
+        for warning in compiler.take_warnings() {
+            write_error(&Error::from(warning), json, color);
+        }
+        try!(result);
     }
     Ok(())
 }
 
 
+/// Formats each file in `files`, rewriting the files which are not already formatted. If `check`
+/// is set the files are left untouched. Returns `false` if any file was not formatted.
+#[cfg(not(test))]
+fn fmt_files<'s, I>(files: I, check: bool) -> Result<bool, Error>
+    where I: Iterator<Item = &'s str>,
+{
+    use base::error::InFile;
+    use parser::format::format_expr;
+
+    let mut formatted = true;
+    for file in files {
+        let mut text = String::new();
+        try!(File::open(file).and_then(|mut f| f.read_to_string(&mut text)));
+        let output = try!(format_expr(&text).map_err(|err| InFile::new(file, &text, err)));
+        if output != text {
+            formatted = false;
+            if check {
+                println!("{} is not formatted", file);
+            } else {
+                try!(File::create(file).and_then(|mut f| f.write_all(output.as_bytes())));
+            }
+        }
+    }
+    Ok(formatted)
+}
+
+/// Writes `err` to stderr, as a JSON object per line for each contained error if `json` is set.
+/// Otherwise the diagnostics are colored if `color` is set. Errors and warnings are written to the
+/// same stream in both formats so that they are not interleaved with the output of the program.
+#[cfg(not(test))]
+fn write_error(err: &Error, json: bool, color: bool) {
+    let stderr = ::std::io::stderr();
+    let mut out = stderr.lock();
+    if json {
+        for error in error_format::errors_to_json(err) {
+            let _ = writeln!(out, "{}", error);
+        }
+    } else {
+        let _ = writeln!(out, "{}", error_format::render_error(err, color));
+    }
+}
+
+/// Returns whether diagnostics should be colored for the `--color` argument, `auto` colors them
+/// if stderr is a terminal and `TERM` is set to a terminal with color support
+#[cfg(not(test))]
+fn use_color(color: Option<&str>) -> bool {
+    match color {
+        Some("always") => true,
+        Some("never") => false,
+        _ => {
+            atty::is(atty::Stream::Stderr) &&
+            ::std::env::var("TERM").map(|term| term != "dumb").unwrap_or(false)
+        }
+    }
+}
+
 #[cfg(all(not(test), feature = "env_logger"))]
 fn init_env_logger() {
     ::env_logger::init().unwrap();
@@ -55,6 +132,7 @@ fn main() {
         .spawn(|| {
             init_env_logger();
 
+            let lint_names: Vec<_> = Lint::all().iter().map(|lint| lint.name()).collect();
             let matches = App::new("gluon")
                 .about("Executes gluon programs")
                 .arg(Arg::with_name("INPUT")
@@ -65,15 +143,120 @@ fn main() {
                     .long("interactive")
                     .help("Starts the repl")
                     .takes_value(false))
+                .arg(Arg::with_name("COLOR")
+                    .long("color")
+                    .takes_value(true)
+                    .possible_values(&["auto", "always", "never"])
+                    .help("Whether errors are printed with colors (defaults to auto)"))
+                .arg(Arg::with_name("EXPLAIN")
+                    .long("explain")
+                    .takes_value(true)
+                    .value_name("CODE")
+                    .help("Prints a detailed explanation of the error with the code CODE"))
+                .arg(Arg::with_name("ERROR_FORMAT")
+                    .long("error-format")
+                    .takes_value(true)
+                    .possible_values(&["human", "json"])
+                    .help("Prints errors as text or as one JSON object per line (defaults to \
+                           human)"))
+                .arg(Arg::with_name("DENY_WARNINGS")
+                    .long("deny-warnings")
+                    .takes_value(false)
+                    .help("Reports warnings as errors"))
+                .arg(Arg::with_name("ALLOW")
+                    .long("allow")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("LINT")
+                    .possible_values(&lint_names)
+                    .help("Does not report the problems found by the lint LINT"))
+                .arg(Arg::with_name("DENY")
+                    .long("deny")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .value_name("LINT")
+                    .possible_values(&lint_names)
+                    .help("Reports the problems found by the lint LINT as errors"))
+                .subcommand(SubCommand::with_name("debug")
+                    .about("Runs a gluon program in the debugger")
+                    .arg(Arg::with_name("FILE")
+                        .required(true)
+                        .help("The program to debug")))
+                .subcommand(SubCommand::with_name("dap")
+                    .about("Starts a Debug Adapter Protocol server which communicates over \
+                            stdin and stdout"))
+                .subcommand(SubCommand::with_name("lsp")
+                    .about("Starts a Language Server Protocol server which communicates over \
+                            stdin and stdout"))
+                .subcommand(SubCommand::with_name("fmt")
+                    .about("Formats gluon source files")
+                    .arg(Arg::with_name("FILES")
+                        .multiple(true)
+                        .required(true)
+                        .help("The files to format"))
+                    .arg(Arg::with_name("CHECK")
+                        .long("check")
+                        .help("Checks that the files are formatted without modifying them. \
+                               Exits with status 1 if any file is not formatted.")
+                        .takes_value(false)))
                 .get_matches();
-            if matches.is_present("REPL") {
+            let color = use_color(matches.value_of("COLOR"));
+            let json_errors = matches.value_of("ERROR_FORMAT") == Some("json");
+            if let Some(code) = matches.value_of("EXPLAIN") {
+                match check::explain::explain(code) {
+                    Some(explanation) => println!("{}", explanation),
+                    None => {
+                        let _ = writeln!(::std::io::stderr(), "No error has the code `{}`", code);
+                        ::std::process::exit(1);
+                    }
+                }
+            } else if let Some(debug_matches) = matches.subcommand_matches("debug") {
+                let file = debug_matches.value_of("FILE").expect("FILE argument");
+                if let Err(err) = debugger::run(file) {
+                    println!("{}", err);
+                }
+            } else if matches.subcommand_matches("dap").is_some() {
+                if let Err(err) = dap::run() {
+                    // stdout is used for the protocol
+                    let _ = writeln!(::std::io::stderr(), "{}", err);
+                }
+            } else if matches.subcommand_matches("lsp").is_some() {
+                if let Err(err) = lsp::run() {
+                    // stdout is used for the protocol
+                    let _ = writeln!(::std::io::stderr(), "{}", err);
+                }
+            } else if let Some(fmt_matches) = matches.subcommand_matches("fmt") {
+                let files = fmt_matches.values_of("FILES").expect("FILES argument");
+                match fmt_files(files, fmt_matches.is_present("CHECK")) {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        if fmt_matches.is_present("CHECK") {
+                            ::std::process::exit(1);
+                        }
+                    }
+                    Err(err) => {
+                        write_error(&err, json_errors, color);
+                        ::std::process::exit(1);
+                    }
+                }
+            } else if matches.is_present("REPL") {
                 if let Err(err) = repl::run() {
                     println!("{}", err);
                 }
             } else if let Some(args) = matches.values_of("INPUT") {
-                match run_files(args) {
-                    Ok(()) => (),
-                    Err(msg) => println!("{}", msg),
+                let mut compiler = Compiler::new()
+                    .deny_warnings(matches.is_present("DENY_WARNINGS"));
+                for &(arg, level) in &[("ALLOW", LintLevel::Allow), ("DENY", LintLevel::Deny)] {
+                    for name in matches.values_of(arg).into_iter().flat_map(|names| names) {
+                        let lint = Lint::from_name(name).expect("LINT argument");
+                        compiler = compiler.lint_level(lint, level);
+                    }
+                }
+                if let Err(err) = run_files(&mut compiler, args, json_errors, color) {
+                    write_error(&err, json_errors, color);
+                    ::std::process::exit(1);
                 }
             } else {
                 println!("{}", matches.usage());
@@ -0,0 +1,91 @@
+//! Reading and writing of the messages used by the Debug Adapter Protocol and the Language Server
+//! Protocol. Both protocols send JSON messages which are prefixed by a `Content-Length` header.
+use std::error::Error as StdError;
+use std::io::{self, BufRead, Read, Write};
+
+use rustc_serialize::json::Json;
+
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect())
+}
+
+pub fn string<S: Into<String>>(s: S) -> Json {
+    Json::String(s.into())
+}
+
+/// The largest message body which is read. The `Content-Length` of a message comes from the client
+/// so it is limited to avoid allocating arbitrarily large buffers.
+const MAX_CONTENT_LENGTH: usize = 64 * 1024 * 1024;
+
+fn invalid_data<E>(err: E) -> io::Error
+    where E: Into<Box<StdError + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Reads a message which is prefixed by a `Content-Length` header. Returns `None` if the input
+/// has been closed. Bodies larger than `MAX_CONTENT_LENGTH` are skipped and reported as
+/// `InvalidData` errors so that the next message can still be read.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if try!(input.read_line(&mut header)) == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        let mut parts = header.splitn(2, ':');
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.trim().to_lowercase() == "content-length" {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = try!(length.ok_or_else(|| invalid_data("Missing Content-Length header")));
+    if length > MAX_CONTENT_LENGTH {
+        try!(io::copy(&mut input.by_ref().take(length as u64), &mut io::sink()));
+        return Err(invalid_data(format!("Content-Length {} exceeds the limit of {} bytes",
+                                        length,
+                                        MAX_CONTENT_LENGTH)));
+    }
+    let mut body = vec![0; length];
+    try!(input.read_exact(&mut body));
+    let body = try!(String::from_utf8(body).map_err(invalid_data));
+    Json::from_str(&body).map(Some).map_err(invalid_data)
+}
+
+/// Writes `message` prefixed by a `Content-Length` header
+pub fn write_message<W: ?Sized + Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    try!(write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body));
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn reject_content_length_above_limit() {
+        let length = MAX_CONTENT_LENGTH + 1;
+        let mut input = format!("Content-Length: {}\r\n\r\n", length).into_bytes();
+        input.extend(vec![b' '; length]);
+        input.extend_from_slice(b"Content-Length: 2\r\n\r\n{}");
+        let mut input = Cursor::new(input);
+        let err = read_message(&mut input).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // The body of the rejected message is skipped
+        assert_eq!(read_message(&mut input).unwrap(),
+                   Some(Json::Object(Default::default())));
+    }
+}
@@ -26,6 +26,30 @@ fn type_of_expr(args: WithVM<RootStr>) -> IO<Result<String, String>> {
     })
 }
 
+fn disassemble(args: WithVM<RootStr>) -> IO<Result<String, String>> {
+    use base::source::Source;
+    use gluon::compiler_pipeline::*;
+    use vm::disassembler;
+
+    let WithVM { vm, value: args } = args;
+    let input: &str = &args;
+    let mut compiler = Compiler::new();
+    IO::Value(match input.compile(&mut compiler, vm, "<repl>", (input, None)) {
+        Ok(CompileValue { function, .. }) => {
+            let source = Source::new(input);
+            Ok(format!("{}", disassembler::disassemble(&function, Some(&source))))
+        }
+        Err(msg) => Err(format!("{}", msg)),
+    })
+}
+
+fn explain(code: &str) -> IO<Result<String, String>> {
+    let code = code.trim();
+    IO::Value(::check::explain::explain(code)
+        .map(String::from)
+        .ok_or_else(|| format!("No error has the code `{}`", code)))
+}
+
 fn find_kind(args: WithVM<RootStr>) -> IO<Result<String, String>> {
     let vm = args.vm;
     let args = args.value.trim();
@@ -178,7 +202,9 @@ fn compile_repl(vm: &Thread) -> Result<(), Box<StdError + Send + Sync>> {
                           record!(
         type_of_expr => primitive!(1 type_of_expr),
         find_info => primitive!(1 find_info),
-        find_kind => primitive!(1 find_kind)
+        find_kind => primitive!(1 find_kind),
+        explain => primitive!(1 explain),
+        disassemble => primitive!(1 disassemble)
     )));
     let mut compiler = Compiler::new();
     try!(compiler.load_file(vm, "std/prelude.glu"));
@@ -221,6 +247,19 @@ mod tests {
         assert_eq!(type_of.call("123"), Ok(IO::Value(Ok("Int".into()))));
     }
 
+    #[test]
+    fn disassemble() {
+        let _ = ::env_logger::init();
+        let vm = new_vm();
+        compile_repl(&vm).unwrap_or_else(|err| panic!("{}", err));
+        let mut disassemble: FunctionRef<QueryFn> = vm.get_global("repl_prim.disassemble")
+            .unwrap();
+        match disassemble.call("1 #Int+ 2") {
+            Ok(IO::Value(Ok(ref listing))) if listing.contains("AddInt") => (),
+            x => assert!(false, "{:?}", x),
+        }
+    }
+
     #[test]
     fn find_kind() {
         let _ = ::env_logger::init();
@@ -231,6 +270,22 @@ mod tests {
                    Ok(IO::Value(Ok("Type -> Type".into()))));
     }
 
+    #[test]
+    fn explain() {
+        let _ = ::env_logger::init();
+        let vm = new_vm();
+        compile_repl(&vm).unwrap_or_else(|err| panic!("{}", err));
+        let mut explain: FunctionRef<QueryFn> = vm.get_global("repl_prim.explain").unwrap();
+        match explain.call("E0006") {
+            Ok(IO::Value(Ok(ref explanation))) if explanation.contains("let x : Int") => (),
+            x => assert!(false, "{:?}", x),
+        }
+        match explain.call("E9999") {
+            Ok(IO::Value(Err(_))) => (),
+            x => assert!(false, "{:?}", x),
+        }
+    }
+
     #[test]
     fn find_info() {
         let _ = ::env_logger::init();
@@ -0,0 +1,227 @@
+//! Tests of the Debug Adapter Protocol server which run `repl dap` and communicate with it over
+//! stdin and stdout
+#![cfg(feature = "repl")]
+extern crate env_logger;
+extern crate rustc_serialize;
+
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{ChildStdout, Command, Stdio};
+
+use rustc_serialize::json::Json;
+
+/// Returns the path of the `repl` executable which is built next to the test executable
+fn repl_path() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join(format!("repl{}", env::consts::EXE_SUFFIX))
+}
+
+fn request(seq: i64, command: &str, arguments: &str) -> String {
+    let body = format!(r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#,
+                       seq,
+                       command,
+                       arguments);
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+/// Reads a message which is prefixed by a `Content-Length` header
+fn read_message(output: &mut BufReader<ChildStdout>) -> Option<Json> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if output.read_line(&mut header).unwrap() == 0 {
+            return None;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if header.to_lowercase().starts_with("content-length:") {
+            length = header["content-length:".len()..].trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length.unwrap()];
+    output.read_exact(&mut body).unwrap();
+    Some(Json::from_str(&String::from_utf8(body).unwrap()).unwrap())
+}
+
+fn is_event(message: &Json, event: &str) -> bool {
+    message.find("event").and_then(Json::as_string) == Some(event)
+}
+
+/// Runs a session with `groups` of requests as input and returns the messages sent to the client.
+/// Each group after the first is sent once the program has stopped, like a client which waits for
+/// the `stopped` event.
+fn run_session(groups: &[&[String]]) -> Vec<Json> {
+    let mut child = Command::new(repl_path())
+        .arg("dap")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("Unable to start `{}`: {}", repl_path().display(), err));
+    let mut input = child.stdin.take().unwrap();
+    let mut output = BufReader::new(child.stdout.take().unwrap());
+
+    let mut messages = Vec::new();
+    for (i, requests) in groups.iter().enumerate() {
+        if i != 0 {
+            loop {
+                let message = read_message(&mut output).expect("A `stopped` event");
+                let stopped = is_event(&message, "stopped");
+                messages.push(message);
+                if stopped {
+                    break;
+                }
+            }
+        }
+        input.write_all(requests.concat().as_bytes()).unwrap();
+        input.flush().unwrap();
+    }
+    drop(input);
+    while let Some(message) = read_message(&mut output) {
+        messages.push(message);
+    }
+    child.wait().unwrap();
+    messages
+}
+
+fn response(messages: &[Json], request_seq: i64) -> &Json {
+    messages.iter()
+        .find(|message| message.find("request_seq").and_then(Json::as_i64) == Some(request_seq))
+        .unwrap_or_else(|| panic!("No response to {} in {:?}", request_seq, messages))
+}
+
+fn response_body(messages: &[Json], request_seq: i64) -> &Json {
+    let response = response(messages, request_seq);
+    assert_eq!(response.find("success"), Some(&Json::Boolean(true)), "{}", response);
+    response.find("body").expect("Response body")
+}
+
+fn event<'a>(messages: &'a [Json], event: &str) -> &'a Json {
+    messages.iter()
+        .find(|message| is_event(message, event))
+        .and_then(|message| message.find("body"))
+        .unwrap_or_else(|| panic!("No `{}` event in {:?}", event, messages))
+}
+
+fn variables(body: &Json) -> Vec<(String, String)> {
+    body.find("variables")
+        .and_then(Json::as_array)
+        .expect("Variables")
+        .iter()
+        .map(|var| {
+            (var.find("name").and_then(Json::as_string).unwrap().to_string(),
+             var.find("value").and_then(Json::as_string).unwrap().to_string())
+        })
+        .collect()
+}
+
+/// Writes `text` to a temporary file called `name`, returning its path both as is and as a JSON
+/// string
+fn write_program(name: &str, text: &str) -> (String, String) {
+    let path = env::temp_dir().join(name);
+    let path = path.to_str().unwrap().to_string();
+    File::create(&path).and_then(|mut file| file.write_all(text.as_bytes())).unwrap();
+    let json = Json::String(path.clone()).to_string();
+    (path, json)
+}
+
+#[test]
+fn stop_at_breakpoint_and_inspect_variables() {
+    let _ = ::env_logger::init();
+
+    let (path, program) = write_program("gluon_dap_breakpoint.glu",
+                                        r#"
+let f x =
+    let y = { a = x, b = [1, 2] }
+    y.a
+f 10
+"#);
+
+    let messages = run_session(&[
+        &[
+            request(1, "initialize", r#"{"adapterID":"gluon"}"#),
+            request(2, "launch", &format!(r#"{{"program":{}}}"#, program)),
+            request(3,
+                    "setBreakpoints",
+                    &format!(r#"{{"source":{{"path":{}}},"breakpoints":[{{"line":4}}]}}"#,
+                             program)),
+            request(4, "configurationDone", "{}"),
+        ],
+        &[
+            request(5, "stackTrace", r#"{"threadId":1}"#),
+            request(6, "scopes", r#"{"frameId":1}"#),
+            request(7, "variables", r#"{"variablesReference":1}"#),
+            request(8, "variables", r#"{"variablesReference":3}"#),
+            request(9, "evaluate", r#"{"expression":"x #Int+ 1","frameId":1}"#),
+            request(10, "continue", r#"{"threadId":1}"#),
+        ],
+    ]);
+
+    assert_eq!(event(&messages, "stopped").find("reason"),
+               Some(&Json::String("breakpoint".into())));
+
+    let frames = response_body(&messages, 5).find("stackFrames").and_then(Json::as_array).unwrap();
+    let frame = &frames[0];
+    assert_eq!(frame.find("line"), Some(&Json::I64(4)));
+    assert_eq!(frame.find_path(&["source", "path"]), Some(&Json::String(path)));
+
+    assert_eq!(variables(response_body(&messages, 7)),
+               vec![("x".to_string(), "10".to_string()),
+                    ("y".to_string(), "{ a, b }".to_string())]);
+    assert_eq!(variables(response_body(&messages, 8)),
+               vec![("a".to_string(), "10".to_string()),
+                    ("b".to_string(), "[..] (2 elements)".to_string())]);
+    assert_eq!(response_body(&messages, 9).find("result"),
+               Some(&Json::String("11".into())));
+
+    assert_eq!(event(&messages, "exited").find("exitCode"), Some(&Json::I64(0)));
+}
+
+#[test]
+fn launch_reports_compile_errors() {
+    let _ = ::env_logger::init();
+
+    let (_, program) = write_program("gluon_dap_error.glu", "1 #Int+ \"\"");
+
+    let messages = run_session(&[&[
+        request(1, "launch", &format!(r#"{{"program":{}}}"#, program)),
+    ]]);
+    assert_eq!(response(&messages, 1).find("success"), Some(&Json::Boolean(false)));
+}
+
+#[test]
+fn requests_are_rejected_while_the_program_is_running() {
+    let _ = ::env_logger::init();
+
+    let (_, program) = write_program("gluon_dap_running.glu", "let f x : Int -> Int = f x\nf 0");
+
+    // The program never stops so it is interrupted by the `disconnect` request
+    let messages = run_session(&[&[
+        request(1, "launch", &format!(r#"{{"program":{}}}"#, program)),
+        request(2, "configurationDone", "{}"),
+        request(3, "evaluate", r#"{"expression":"1","frameId":1}"#),
+        request(4, "disconnect", "{}"),
+    ]]);
+    assert_eq!(response(&messages, 3).find("success"), Some(&Json::Boolean(false)));
+}
+
+#[test]
+fn invalid_message_does_not_end_the_session() {
+    let _ = ::env_logger::init();
+
+    let messages = run_session(&[&[
+        "Content-Length: 3\r\n\r\nabc".to_string(),
+        request(1, "initialize", r#"{"adapterID":"gluon"}"#),
+    ]]);
+    assert_eq!(response(&messages, 1).find("success"), Some(&Json::Boolean(true)));
+}
@@ -0,0 +1,193 @@
+extern crate env_logger;
+extern crate gluon;
+
+mod support;
+
+use std::sync::{Arc, Mutex};
+
+use gluon::{Compiler, Thread};
+use gluon::base::pos::Line;
+use gluon::vm::Error as VMError;
+use gluon::vm::api::{Hole, OpaqueValue};
+use gluon::vm::debugger::{Action, Breakpoint, DebugInfo, Debugger, HookFlags};
+use gluon::vm::thread::ThreadInternal;
+
+use support::make_vm;
+
+const PROGRAM: &'static str = r#"
+let f x =
+    let y = x #Int+ 1
+    y #Int* 2
+f 10
+"#;
+
+fn run(vm: &Thread) -> Result<i32, gluon::Error> {
+    Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(vm, "test", PROGRAM)
+        .map(|(value, _)| value)
+}
+
+#[test]
+fn line_hook_can_inspect_locals() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    {
+        let events = events.clone();
+        let mut context = vm.context();
+        context.set_hook_flags(HookFlags {
+            call: false,
+            line: true,
+        });
+        context.set_debug_hook(Some(Box::new(move |_: &Thread, info: DebugInfo| {
+            let frame = info.stack_info(0).expect("Frame");
+            let locals = frame.locals()
+                .iter()
+                .map(|var| format!("{:?}", var))
+                .collect::<Vec<_>>();
+            events.lock().unwrap().push((frame.line(), locals));
+            Ok(())
+        })));
+    }
+
+    assert_eq!(run(&vm).unwrap(), 22);
+
+    let events = events.lock().unwrap();
+    let last_line = events.iter()
+        .find(|event| event.0 == Some(Line::from(3)))
+        .expect("Line event for the last line of `f`");
+    assert_eq!(last_line.1,
+               vec!["x : Int = 10".to_string(), "y : Int = 11".to_string()]);
+}
+
+#[test]
+fn hook_is_called_when_functions_are_entered() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let calls = Arc::new(Mutex::new(0));
+    {
+        let calls = calls.clone();
+        let mut context = vm.context();
+        context.set_hook(Some(Box::new(move |_: &Thread| {
+            *calls.lock().unwrap() += 1;
+            Ok(())
+        })));
+    }
+
+    assert_eq!(run(&vm).unwrap(), 22);
+    assert!(*calls.lock().unwrap() > 0);
+}
+
+#[test]
+fn breakpoints_and_stepping() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let paused = Arc::new(Mutex::new(Vec::new()));
+    let mut debugger = {
+        let paused = paused.clone();
+        Debugger::new(Box::new(move |_: &Thread,
+                                     info: &DebugInfo,
+                                     _: &mut Vec<Breakpoint>| {
+            let frame = info.stack_info(0).expect("Frame");
+            let mut paused = paused.lock().unwrap();
+            paused.push((frame.line().map(|line| line.to_usize()), info.stack_depth()));
+            // Step over the first line of `f` and then out of `f`
+            Ok(if paused.len() == 1 {
+                Action::StepOver
+            } else if paused.len() == 2 {
+                Action::StepOut
+            } else {
+                Action::Continue
+            })
+        }))
+    };
+    debugger.add_breakpoint("test", Line::from(2));
+    debugger.attach(&mut vm.context());
+
+    assert_eq!(run(&vm).unwrap(), 22);
+
+    let paused = paused.lock().unwrap();
+    assert_eq!(paused.len(), 3, "{:?}", *paused);
+    assert_eq!(paused[0].0, Some(2));
+    assert_eq!(paused[1].0, Some(3));
+    // Stepping out returns to the top level function
+    assert_eq!(paused[2].1, paused[0].1 - 1);
+}
+
+#[test]
+fn evaluate_expression_in_paused_frame() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let eval_thread = vm.new_thread().unwrap();
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let mut debugger = {
+        let results = results.clone();
+        let mut compiler = Compiler::new().implicit_prelude(false);
+        Debugger::new(Box::new(move |_: &Thread,
+                                     info: &DebugInfo,
+                                     _: &mut Vec<Breakpoint>| {
+            let frame = info.stack_info(0).expect("Frame");
+            let result = compiler.run_expr_in_frame(&eval_thread, &frame, "<debug>", "y #Int+ x")
+                .map(|(value, typ)| format!("{:?} : {}", value, typ))
+                .map_err(|err| err.to_string());
+            results.lock().unwrap().push(result);
+            Ok(Action::Continue)
+        }))
+    };
+    debugger.add_breakpoint("test", Line::from(3));
+    debugger.attach(&mut vm.context());
+
+    assert_eq!(run(&vm).unwrap(), 22);
+    assert_eq!(*results.lock().unwrap(), vec![Ok("21 : Int".to_string())]);
+}
+
+#[test]
+fn pause_function_can_stop_execution() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let mut debugger = Debugger::new(Box::new(|_: &Thread, _: &DebugInfo, _: &mut Vec<Breakpoint>| {
+        Err(VMError::Message("Stopped".into()))
+    }));
+    debugger.pause_on_start();
+    debugger.attach(&mut vm.context());
+
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&vm, "test", PROGRAM);
+    match result {
+        Err(gluon::Error::VM(VMError::Message(ref msg))) if msg == "Stopped" => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn set_breakpoints_through_handle() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let paused = Arc::new(Mutex::new(Vec::new()));
+    let debugger = {
+        let paused = paused.clone();
+        Debugger::new(Box::new(move |_: &Thread,
+                                     info: &DebugInfo,
+                                     _: &mut Vec<Breakpoint>| {
+            let frame = info.stack_info(0).expect("Frame");
+            paused.lock().unwrap().push(frame.line().map(|line| line.to_usize()));
+            Ok(Action::Continue)
+        }))
+    };
+    let handle = debugger.handle();
+    handle.set_breakpoints("test", &[Line::from(2), Line::from(4)]);
+    handle.set_breakpoints("test", &[Line::from(3)]);
+    debugger.attach(&mut vm.context());
+
+    assert_eq!(run(&vm).unwrap(), 22);
+    assert_eq!(*paused.lock().unwrap(), vec![Some(3)]);
+}
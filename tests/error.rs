@@ -13,3 +13,66 @@ fn dont_panic_when_error_span_is_at_eof() {
     let result = Compiler::new().load_script(&vm, "test", text);
     assert!(result.is_err());
 }
+
+#[test]
+fn unused_import_is_reported_as_a_warning() {
+    let _ = ::env_logger::init();
+    let vm = support::make_vm();
+    let text = r#"
+let prelude = import "std/prelude.glu"
+1
+"#;
+    let mut compiler = Compiler::new();
+    compiler.load_script(&vm, "test", text).unwrap_or_else(|err| panic!("{}", err));
+    let warnings = compiler.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    let warning = warnings[0].to_string();
+    assert!(warning.contains("Unused import `prelude`"), "{}", warning);
+    assert!(compiler.take_warnings().is_empty());
+}
+
+#[test]
+fn deny_warnings_reports_warnings_as_errors() {
+    let _ = ::env_logger::init();
+    let vm = support::make_vm();
+    let text = r#"
+let x = 1
+2
+"#;
+    let result = Compiler::new().deny_warnings(true).load_script(&vm, "test", text);
+    match result {
+        Ok(()) => panic!("Expected an error"),
+        Err(err) => assert!(err.to_string().contains("Unused variable `x`"), "{}", err),
+    }
+}
+
+#[test]
+fn warnings_of_imported_modules_are_reported() {
+    let _ = ::env_logger::init();
+    let vm = support::make_vm();
+    let text = r#"
+let { x } = import "tests/pass/deps/unused_variable.glu"
+x
+"#;
+    let mut compiler = Compiler::new();
+    compiler.load_script(&vm, "test", text).unwrap_or_else(|err| panic!("{}", err));
+    let warnings = compiler.take_warnings();
+    assert_eq!(warnings.len(), 1);
+    let warning = warnings[0].to_string();
+    assert!(warning.contains("Unused variable `unused`"), "{}", warning);
+}
+
+#[test]
+fn deny_warnings_applies_to_imported_modules() {
+    let _ = ::env_logger::init();
+    let vm = support::make_vm();
+    let text = r#"
+let { x } = import "tests/pass/deps/unused_variable.glu"
+x
+"#;
+    let result = Compiler::new().deny_warnings(true).load_script(&vm, "test", text);
+    match result {
+        Ok(()) => panic!("Expected an error"),
+        Err(err) => assert!(err.to_string().contains("Unused variable `unused`"), "{}", err),
+    }
+}
@@ -3,9 +3,13 @@ extern crate gluon;
 
 mod support;
 
+use std::thread;
+use std::time::{Duration, Instant};
+
 use gluon::{Compiler, Error, Thread};
 use gluon::vm::Error as VMError;
 use gluon::vm::api::{Hole, OpaqueValue};
+use gluon::vm::internal::Value;
 use gluon::vm::thread::ThreadInternal;
 
 use support::make_vm;
@@ -44,3 +48,112 @@ fn stack_overflow() {
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+#[test]
+fn out_of_fuel() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    vm.context().set_fuel(Some(1000));
+
+    let expr = r#"
+let loop x = loop x
+loop 0
+"#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&vm, "example", expr);
+
+    match result {
+        Err(Error::VM(VMError::OutOfFuel)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+    assert_eq!(vm.context().fuel(), Some(0));
+}
+
+#[test]
+fn resume_after_out_of_fuel() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    vm.context().set_fuel(Some(10));
+
+    let expr = r#"
+let sum n acc = if n #Int== 0 then acc else sum (n #Int- 1) (acc #Int+ n)
+sum 100 0
+"#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(&vm, "example", expr);
+
+    match result {
+        Err(Error::VM(VMError::OutOfFuel)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+
+    vm.context().set_fuel(None);
+    assert_eq!(vm.resume(), Ok(()));
+    assert_eq!(vm.context().stack.pop(), Value::Int(5050));
+}
+
+#[test]
+fn timeout() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    vm.context().set_deadline(Some(Instant::now() + Duration::from_millis(10)));
+
+    let expr = r#"
+let loop x = loop x
+loop 0
+"#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&vm, "example", expr);
+
+    match result {
+        Err(Error::VM(VMError::Timeout)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn interrupt_from_another_thread() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let handle = vm.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(10));
+        handle.interrupt();
+    });
+
+    let expr = r#"
+let loop x = loop x
+loop 0
+"#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<OpaqueValue<&Thread, Hole>>(&vm, "example", expr);
+    interrupter.join().unwrap();
+
+    match result {
+        Err(Error::VM(VMError::Interrupted(ref trace))) => {
+            assert!(trace.to_string().contains("loop"), "{}", trace);
+        }
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+
+    // The thread is unwound after being interrupted and can be used again
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(&vm, "example", "1 #Int+ 2");
+    match result {
+        Ok((value, _)) => assert_eq!(value, 3),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+    }
+}
@@ -52,6 +52,20 @@ fn test_files(path: &str) -> Result<Vec<PathBuf>, Box<Error>> {
     Ok(paths)
 }
 
+/// Returns the message of the error which a test in `tests/fail` is expected to fail with. It is
+/// given in a comment on the first line of the test, such as `// ERROR: Undefined variable`.
+fn expected_error(text: &str) -> Option<&str> {
+    text.lines()
+        .next()
+        .and_then(|line| {
+            if line.starts_with("// ERROR: ") {
+                Some(line["// ERROR: ".len()..].trim())
+            } else {
+                None
+            }
+        })
+}
+
 fn main_() -> Result<(), Box<Error>> {
     let vm = new_vm();
     let mut compiler = Compiler::new();
@@ -79,7 +93,20 @@ fn main_() -> Result<(), Box<Error>> {
                                                x))
                     .into())
             }
-            Err(er) => println!("{}", er),
+            Err(er) => {
+                println!("{}", er);
+                if let Some(expected) = expected_error(&text) {
+                    let er = er.to_string();
+                    if !er.contains(expected) {
+                        return Err(StringError(format!("Expected test '{}' to fail with `{}` \
+                                                        got {}",
+                                                       filename.to_str().unwrap(),
+                                                       expected,
+                                                       er))
+                            .into());
+                    }
+                }
+            }
         }
     }
     Ok(())
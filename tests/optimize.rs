@@ -0,0 +1,73 @@
+extern crate env_logger;
+extern crate gluon;
+
+mod support;
+
+use gluon::Compiler;
+use gluon::vm::optimize::OptimizationLevel;
+
+use support::make_vm;
+
+fn run_optimized(expr: &str) -> i32 {
+    run_with_level(OptimizationLevel::Peephole, expr)
+}
+
+fn run_with_level(level: OptimizationLevel, expr: &str) -> i32 {
+    let vm = make_vm();
+    Compiler::new()
+        .implicit_prelude(false)
+        .optimization_level(level)
+        .run_expr(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .0
+}
+
+#[test]
+fn constant_arithmetic() {
+    let _ = ::env_logger::init();
+    assert_eq!(run_optimized("(1 #Int+ 2) #Int* 3 #Int- 4"), 5);
+}
+
+#[test]
+fn constant_condition() {
+    let _ = ::env_logger::init();
+    assert_eq!(run_optimized("if 1 #Int< 2 then 10 else 20"), 10);
+    assert_eq!(run_optimized("if 2.0 #Float== 3.0 then 10 else 20"), 20);
+}
+
+#[test]
+fn recursive_function() {
+    let _ = ::env_logger::init();
+    let expr = r#"
+let fac n : Int -> Int = if n #Int== 0 then 1 else n #Int* fac (n #Int- 1)
+fac (2 #Int+ 3)
+"#;
+    assert_eq!(run_optimized(expr), 120);
+}
+
+#[test]
+fn inline_functions_in_records() {
+    let _ = ::env_logger::init();
+    let expr = r#"
+let add_Int l r : Int -> Int -> Int = l #Int+ r
+let unused x : Int -> Int = x
+let num_Int = { add = add_Int, zero = 0 }
+let sum n acc : Int -> Int -> Int =
+    if n #Int== 0 then acc else sum (n #Int- 1) (num_Int.add acc n)
+sum 10 num_Int.zero
+"#;
+    assert_eq!(run_with_level(OptimizationLevel::Inline, expr), 55);
+}
+
+#[test]
+fn inline_does_not_capture_shadowed_variables() {
+    let _ = ::env_logger::init();
+    let expr = r#"
+let y = 1
+let add_y x : Int -> Int = x #Int+ y
+let y = 10
+let x = 100
+add_y (x #Int+ y)
+"#;
+    assert_eq!(run_with_level(OptimizationLevel::Inline, expr), 111);
+}
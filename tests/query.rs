@@ -0,0 +1,54 @@
+extern crate env_logger;
+extern crate gluon;
+
+use std::fs::File;
+use std::io::Read;
+
+use gluon::base::pos::BytePos;
+use gluon::check::query;
+use gluon::compiler_pipeline::Typecheckable;
+use gluon::import::{CheckImporter, Import};
+use gluon::{new_vm, Compiler};
+
+const TEXT: &'static str = r#"
+let prelude = import "std/prelude.glu"
+let { id } = prelude
+id (prelude.const 1 2)
+"#;
+
+#[test]
+fn definition_in_imported_module() {
+    let _ = ::env_logger::init();
+
+    let vm = new_vm();
+    let importer = CheckImporter::new();
+    vm.get_macros().insert(String::from("import"), Import::new(importer.clone()));
+
+    let mut compiler = Compiler::new().implicit_prelude(false);
+    let mut expr = compiler.parse_expr("test", TEXT).unwrap();
+    (&mut expr).typecheck(&mut compiler, &vm, "test", TEXT).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut prelude = String::new();
+    File::open("std/prelude.glu").and_then(|mut file| file.read_to_string(&mut prelude)).unwrap();
+
+    let modules = importer.0.lock().unwrap();
+    let definition = |pos: usize| {
+        let location = query::definition(&*modules, "test", &expr, BytePos::from(pos))
+            .expect("Definition");
+        assert_eq!(location.source_name, "std.prelude");
+        let start = location.span.start.to_usize();
+        let line_start = prelude[..start].rfind('\n').map_or(0, |i| i + 1);
+        prelude[line_start..].lines().next().unwrap().to_string()
+    };
+
+    assert_eq!(definition(TEXT.rfind("id (").unwrap()), "let id : a -> a =");
+    assert_eq!(definition(TEXT.rfind("const").unwrap()), "let const : a -> b -> a =");
+
+    // Bindings which are not imported are found in the module itself
+    let location = query::definition(&*modules,
+                                     "test",
+                                     &expr,
+                                     BytePos::from(TEXT.rfind("prelude.const").unwrap()));
+    assert_eq!(location.map(|location| (location.source_name, location.span.start)),
+               Some(("test".to_string(), BytePos::from(TEXT.find("prelude").unwrap()))));
+}
@@ -0,0 +1,55 @@
+extern crate env_logger;
+extern crate gluon;
+
+mod support;
+
+use gluon::{Compiler, Error};
+use gluon::vm::serialization;
+
+use support::make_vm;
+
+#[test]
+fn load_compiled_module() {
+    let _ = ::env_logger::init();
+
+    let input = r#"
+type List a = | Cons a (List a) | Nil
+let sum xs : List Int -> Int =
+    match xs with
+        | Cons x ys -> x #Int+ sum ys
+        | Nil -> 0
+{ sum, ten = sum (Cons 1 (Cons 2 (Cons 7 Nil))) }
+"#;
+    let mut buffer = Vec::new();
+    {
+        let vm = make_vm();
+        Compiler::new()
+            .write_compiled(&vm, "test", input, &mut buffer)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    let vm = make_vm();
+    Compiler::new()
+        .load_compiled(&vm, "test", input, &mut &buffer[..])
+        .unwrap_or_else(|err| panic!("{}", err));
+    let ten: i32 = vm.get_global("test.ten").unwrap();
+    assert_eq!(ten, 10);
+}
+
+#[test]
+fn reject_stale_compiled_module() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let mut buffer = Vec::new();
+    Compiler::new()
+        .write_compiled(&vm, "test", "1 #Int+ 2", &mut buffer)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let result = Compiler::new().load_compiled(&vm, "test", "1 #Int+ 3", &mut &buffer[..]);
+    match result {
+        Err(Error::Serialization(serialization::Error::Stale)) => (),
+        Err(err) => panic!("Unexpected error `{}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
@@ -315,6 +315,20 @@ match A with
     assert!(result.is_err());
 }
 
+#[test]
+fn non_exhaustive_pattern_with_two_alternatives() {
+    let _ = ::env_logger::init();
+    let text = r"
+type ABC = | A | B | C in
+match C with
+| A -> True
+| B -> False
+";
+    let mut vm = make_vm();
+    let result = Compiler::new().run_expr::<bool>(&mut vm, "<top>", text);
+    assert!(result.is_err());
+}
+
 test_expr!{ record_pattern,
 r#"
 match { x = 1, y = "abc" } with
@@ -345,6 +359,94 @@ in match x with
 2.0f64
 }
 
+test_expr!{ nested_constructor_pattern,
+r#"
+type List a = | Cons a (List a) | Nil in
+let second xs =
+    match xs with
+    | Cons _ (Cons y _) -> y
+    | _ -> 0
+in second (Cons 1 (Cons 2 Nil)) #Int+ second (Cons 10 Nil)
+"#,
+2i32
+}
+
+test_expr!{ literal_pattern,
+r#"
+type Option a = | None | Some a in
+let string x =
+    match x with
+    | Some "abc" -> 1
+    | _ -> 2
+in
+let int x =
+    match x with
+    | Some 1 -> 10
+    | _ -> 20
+in
+let char c =
+    match c with
+    | 'a' -> 100
+    | _ -> 200
+in
+let float f =
+    match f with
+    | 2.0 -> 1000
+    | _ -> 2000
+in string (Some "abc") #Int+ int (Some 1) #Int+ int None #Int+ char 'a' #Int+ float 2.0
+"#,
+1131i32
+}
+
+test_expr!{ string_pattern_with_shadowed_string_prim,
+r#"
+let string_prim = 1 in
+match "abc" with
+| "abc" -> string_prim
+| _ -> 2
+"#,
+1i32
+}
+
+test_expr!{ tuple_pattern,
+r#"
+let (a, b) = (1, (2, 3))
+in
+match b with
+| (2, c) -> a #Int+ c
+| _ -> 0
+"#,
+4i32
+}
+
+test_expr!{ match_guard,
+r#"
+type Option a = | None | Some a in
+let f x =
+    match x with
+    | Some y if y #Int< 0 -> 0 #Int- y
+    | Some y -> y
+    | None -> 100
+in f (Some (0 #Int- 5)) #Int+ f (Some 6) #Int+ f None
+"#,
+111i32
+}
+
+#[test]
+fn non_exhaustive_nested_pattern() {
+    let _ = ::env_logger::init();
+    let text = r"
+type AB = | A | B in
+type Option a = | None | Some a in
+match Some A with
+| Some B -> True
+| None -> False
+";
+    let mut vm = make_vm();
+    let result = Compiler::new().run_expr::<bool>(&mut vm, "<top>", text);
+    assert!(result.is_err());
+}
+
 test_expr!{ record_let_adjust,
 r#"
 let x = \z -> let { x, y } = { x = 1, y = 2 } in z in
@@ -563,6 +665,87 @@ in id 1
 0i32
 }
 
+test_expr!{ implicit_argument,
+r#"
+type Num a = { add : a -> a -> a }
+let num_Int : Num Int = { add = \x y -> x #Int+ y }
+let num_Float : Num Float = { add = \x y -> x #Float+ y }
+let (+) num x y : [Num a] -> a -> a -> a = num.add x y
+let z = 1.0 + 2.0
+1 + 2
+"#,
+3i32
+}
+
+test_expr!{ implicit_argument_from_implicit_function,
+r#"
+type Size a = { size : a -> Int }
+type List a = | Nil | Cons a (List a)
+let size_Int : Size Int = { size = \_ -> 1 }
+let size_List s : [Size a] -> Size (List a) =
+    let size xs =
+        match xs with
+        | Nil -> 0
+        | Cons x ys -> s.size x #Int+ size ys
+    { size }
+let size s x : [Size a] -> a -> Int = s.size x
+size (Cons 1 (Cons 2 Nil))
+"#,
+2i32
+}
+
+test_expr!{ higher_rank_function_argument,
+r#"
+type Pair a b = { first : a, second : b }
+let both f : (forall a . a -> a) -> Pair String Int = { first = f "a", second = f 1 }
+(both (\x -> x)).second
+"#,
+1i32
+}
+
+test_expr!{ gadt_typed_interpreter,
+r#"
+type Expr a =
+    | IntLit : Int -> Expr Int
+    | IsZero : Expr Int -> Expr Bool
+    | Add : Expr Int -> Expr Int -> Expr Int
+    | If : Expr Bool -> Expr a -> Expr a -> Expr a
+let eval e : Expr a -> a =
+    match e with
+    | IntLit x -> x
+    | IsZero x -> eval x #Int== 0
+    | Add l r -> eval l #Int+ eval r
+    | If p t f -> if eval p then eval t else eval f
+eval (If (IsZero (IntLit 0)) (Add (IntLit 1) (IntLit 2)) (IntLit 10))
+"#,
+3i32
+}
+
+test_expr!{ mutually_recursive_gadts,
+r#"
+type Expr a =
+    | IntLit : Int -> Expr Int
+    | IsZero : Expr Int -> Expr Bool
+    | If : Expr Bool -> Expr a -> Expr a -> Expr a
+    | Force : Thunk a -> Expr a
+and Thunk a =
+    | Delay : Expr a -> Thunk a
+    | Now : Int -> Thunk Int
+let eval e : Expr a -> a =
+    match e with
+    | IntLit x -> x
+    | IsZero x -> eval x #Int== 0
+    | If p t f -> if eval p then eval t else eval f
+    | Force t -> force t
+and force t : Thunk a -> a =
+    match t with
+    | Delay e -> eval e
+    | Now x -> x
+eval (If (IsZero (Force (Now 0))) (Force (Delay (IntLit 1))) (IntLit 2))
+"#,
+1i32
+}
+
 #[test]
 fn run_expr_int() {
     let _ = ::env_logger::init();
@@ -579,6 +762,23 @@ fn run_expr_int() {
     }
 }
 
+#[test]
+fn stacktrace_has_line_information() {
+    let _ = ::env_logger::init();
+
+    let text = r#"io.run_expr "let f x : Int -> Int = (error \"boom\") #Int+ x\nf 1 #Int+ 0" "#;
+    let mut vm = make_vm();
+    let (result, _) = Compiler::new().run_io_expr::<IO<String>>(&mut vm, "<top>", text).unwrap();
+    match result {
+        IO::Value(result) => panic!("Expected an error, got `{}`", result),
+        IO::Exception(err) => {
+            assert!(err.contains("boom"), "{}", err);
+            assert!(err.contains("at <top>:1:"), "{}", err);
+            assert!(err.contains("at <top>:2:1"), "{}", err);
+        }
+    }
+}
+
 test_expr!{ io run_expr_io,
 r#"io_flat_map (\x -> io_pure 100) (io.run_expr "io.print \"123\" ") "#,
 100i32
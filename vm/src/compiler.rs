@@ -1,12 +1,15 @@
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::usize;
 use interner::InternedStr;
-use base::ast::{Literal, Pattern, TypedIdent};
-use base::instantiate;
-use base::symbol::{Symbol, SymbolRef, SymbolModule};
-use base::ast::{Typed, DisplayEnv, SpannedExpr, Expr};
-use base::types;
-use base::types::{Alias, KindEnv, ArcType, Type, TypeEnv};
-use base::scoped_map::ScopedMap;
+use base::ast::{Literal, TypedIdent};
+use base::pos::{BytePos, Column, Line, Location, Span};
+use base::symbol::{Symbol, SymbolModule};
+use base::ast::{Typed, DisplayEnv, SpannedExpr};
+use base::types::{ArcType, Type, TypeEnv};
+use core::{self, Alternative, Closure, DataKind, Field, Named, Pattern, Translator};
+use inline;
+use optimize::{self, OptimizationLevel};
 use types::*;
 use vm::GlobalVmState;
 use self::Variable::*;
@@ -23,11 +26,179 @@ pub enum Variable<G> {
     UpVar(VmIndex),
 }
 
-/// Field accesses on records can either be by name in the case of polymorphic records or by offset
-/// when the record is non-polymorphic (which is faster)
-enum FieldAccess {
-    Name,
-    Index(VmIndex),
+/// Maps instructions to the span of the expression which emitted them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    /// `(instruction_index, span)` pairs sorted on the instruction index. Each span is valid from
+    /// its instruction index until the index of the next pair.
+    map: Vec<(usize, Span<BytePos>)>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { map: Vec::new() }
+    }
+
+    /// Records that the instructions starting from `instruction_index` were emitted for the
+    /// expression at `span`
+    pub fn emit(&mut self, instruction_index: usize, span: Span<BytePos>) {
+        if let Some(&mut (index, ref mut last_span)) = self.map.last_mut() {
+            if *last_span == span {
+                return;
+            }
+            if index == instruction_index {
+                *last_span = span;
+                return;
+            }
+        }
+        self.map.push((instruction_index, span));
+    }
+
+    /// Returns the span of the expression which emitted the instruction at `instruction_index`
+    pub fn span(&self, instruction_index: usize) -> Option<Span<BytePos>> {
+        let i = match self.map.binary_search_by(|&(index, _)| index.cmp(&instruction_index)) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        Some(self.map[i].1)
+    }
+
+    /// Iterates over all `(instruction_index, span)` pairs
+    pub fn iter(&self) -> ::std::slice::Iter<(usize, Span<BytePos>)> {
+        self.map.iter()
+    }
+}
+
+/// A variable which is stored on the stack of a function
+#[derive(Clone, Debug, PartialEq)]
+pub struct Local {
+    /// The index of the first instruction where the variable is in scope
+    pub start: usize,
+    /// The index of the instruction where the variable goes out of scope
+    pub end: usize,
+    /// The stack slot, relative to the function's frame, which stores the variable
+    pub index: VmIndex,
+    pub name: Symbol,
+    pub typ: ArcType,
+}
+
+/// Maps instructions to the variables which are in scope at each instruction
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocalMap {
+    /// Locals sorted on the instruction where they enter scope
+    map: Vec<Local>,
+}
+
+impl LocalMap {
+    pub fn new() -> LocalMap {
+        LocalMap { map: Vec::new() }
+    }
+
+    /// Records that `name` is stored at `index` from the instruction at `instruction_index`
+    /// until `close` is called for `index`
+    pub fn emit(&mut self, instruction_index: usize, index: VmIndex, name: Symbol, typ: ArcType) {
+        self.map.push(Local {
+            start: instruction_index,
+            end: usize::MAX,
+            index: index,
+            name: name,
+            typ: typ,
+        });
+    }
+
+    /// Records that the variable stored at `index` goes out of scope at `instruction_index`
+    pub fn close(&mut self, index: VmIndex, instruction_index: usize) {
+        if let Some(local) = self.map
+            .iter_mut()
+            .rev()
+            .find(|local| local.index == index && local.end == usize::MAX) {
+            local.end = instruction_index;
+        }
+    }
+
+    /// Returns the variable called `name` which is currently in scope
+    pub fn open(&self, name: &Symbol) -> Option<&Local> {
+        self.map.iter().rev().find(|local| local.name == *name && local.end == usize::MAX)
+    }
+
+    /// Returns the variables which are in scope at `instruction_index`, with the innermost
+    /// variables last
+    pub fn locals(&self, instruction_index: usize) -> Vec<&Local> {
+        self.map
+            .iter()
+            .filter(|local| local.start <= instruction_index && instruction_index < local.end)
+            .collect()
+    }
+
+    /// Updates the instruction indexes after the instructions were rewritten where
+    /// `new_index[i]` is the index of the instruction which replaced the old instruction `i`
+    pub fn remap(&mut self, new_index: &[usize]) {
+        for local in &mut self.map {
+            local.start = new_index[local.start];
+            if local.end != usize::MAX {
+                local.end = new_index[local.end];
+            }
+        }
+    }
+
+    /// Iterates over all locals of the function
+    pub fn iter(&self) -> ::std::slice::Iter<Local> {
+        self.map.iter()
+    }
+}
+
+/// A variable which a closure has captured from an enclosing function
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpvarInfo {
+    pub name: Symbol,
+    pub typ: ArcType,
+}
+
+/// The name and contents of the file which a function were compiled from
+#[derive(Debug, PartialEq)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+    /// The starting byte position of each line in `text`
+    lines: Vec<BytePos>,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, text: &str) -> SourceFile {
+        let lines = Some(BytePos::from(0))
+            .into_iter()
+            .chain(text.bytes()
+                .enumerate()
+                .filter(|&(_, b)| b == b'\n')
+                .map(|(i, _)| BytePos::from(i + 1)))
+            .collect();
+        SourceFile {
+            name: name.into(),
+            text: text.into(),
+            lines: lines,
+        }
+    }
+
+    /// Returns the line which `pos` is on
+    pub fn line(&self, pos: BytePos) -> Line {
+        match self.lines.binary_search(&pos) {
+            Ok(i) => Line::from(i),
+            Err(i) => Line::from(i - 1),
+        }
+    }
+
+    /// Returns the line and column of `pos`
+    pub fn location(&self, pos: BytePos) -> Location {
+        let line = self.line(pos);
+        let line_start = self.lines[line.to_usize()];
+        let column = self.text[line_start.to_usize()..pos.to_usize()].chars().count();
+        Location {
+            line: line,
+            column: Column::from(column),
+            absolute: pos,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -43,6 +214,14 @@ pub struct CompiledFunction {
     /// Storage for globals which are needed by the module which is currently being compiled
     pub module_globals: Vec<Symbol>,
     pub records: Vec<Vec<Symbol>>,
+    pub source_map: SourceMap,
+    /// The file which this function were compiled from, used to translate the spans in
+    /// `source_map` into lines and columns
+    pub source: Option<Arc<SourceFile>>,
+    /// The variables stored on the stack of this function, used when debugging
+    pub locals: LocalMap,
+    /// The variables captured by this function, in the order they are stored in the closure
+    pub upvars: Vec<UpvarInfo>,
 }
 
 impl CompiledFunction {
@@ -57,8 +236,20 @@ impl CompiledFunction {
             strings: Vec::new(),
             module_globals: Vec::new(),
             records: Vec::new(),
+            source_map: SourceMap::new(),
+            source: None,
+            locals: LocalMap::new(),
+            upvars: Vec::new(),
         }
     }
+
+    /// Sets the file which this function and all its inner functions were compiled from
+    pub fn set_source(&mut self, source: Arc<SourceFile>) {
+        for inner in &mut self.inner_functions {
+            inner.set_source(source.clone());
+        }
+        self.source = Some(source);
+    }
 }
 
 struct FunctionEnv {
@@ -66,6 +257,8 @@ struct FunctionEnv {
     stack_size: VmIndex,
     free_vars: Vec<Symbol>,
     function: CompiledFunction,
+    /// The span of the expression which is currently being compiled
+    current_span: Span<BytePos>,
 }
 
 struct FunctionEnvs {
@@ -90,19 +283,11 @@ impl FunctionEnvs {
         FunctionEnvs { envs: vec![] }
     }
 
-    fn start_function(&mut self,
-                      compiler: &mut Compiler,
-                      args: VmIndex,
-                      id: Symbol,
-                      typ: ArcType) {
-        compiler.stack_types.enter_scope();
-        compiler.stack_constructors.enter_scope();
+    fn start_function(&mut self, args: VmIndex, id: Symbol, typ: ArcType) {
         self.envs.push(FunctionEnv::new(args, id, typ));
     }
 
-    fn end_function(&mut self, compiler: &mut Compiler) -> FunctionEnv {
-        compiler.stack_types.exit_scope();
-        compiler.stack_constructors.exit_scope();
+    fn end_function(&mut self) -> FunctionEnv {
         self.envs.pop().expect("FunctionEnv in scope")
     }
 }
@@ -114,6 +299,10 @@ impl FunctionEnv {
             stack: Vec::new(),
             stack_size: 0,
             function: CompiledFunction::new(args, id, typ),
+            current_span: Span {
+                start: BytePos::from(0),
+                end: BytePos::from(0),
+            },
         }
     }
 
@@ -130,6 +319,8 @@ impl FunctionEnv {
             self.stack_size -= -adjustment as VmIndex;
         }
 
+        let index = self.function.instructions.len();
+        self.function.source_map.emit(index, self.current_span);
         self.function.instructions.push(instruction);
     }
 
@@ -149,13 +340,11 @@ impl FunctionEnv {
         self.emit(i);
     }
 
-    fn emit_field(&mut self, compiler: &mut Compiler, typ: &ArcType, field: &Symbol) -> Result<()> {
-        let field_index = compiler.find_field(typ, field)
-            .expect("ICE: Undefined field in field access");
-        match field_index {
-            FieldAccess::Index(i) => self.emit(GetOffset(i)),
-            FieldAccess::Name => {
-                let interned = try!(compiler.intern(field.as_ref()));
+    fn emit_field(&mut self, compiler: &mut Compiler, field: &Field) -> Result<()> {
+        match *field {
+            Field::Offset(i) => self.emit(GetOffset(i)),
+            Field::Name(ref name) => {
+                let interned = try!(compiler.intern(name.as_ref()));
                 let index = self.add_string_constant(interned);
                 self.emit(GetField(index));
             }
@@ -163,7 +352,6 @@ impl FunctionEnv {
         Ok(())
     }
 
-
     fn add_record_map(&mut self, fields: Vec<Symbol>) -> VmIndex {
         match self.function.records.iter().position(|t| *t == fields) {
             Some(i) => i as VmIndex,
@@ -189,11 +377,15 @@ impl FunctionEnv {
         self.emit(PushString(index as VmIndex));
     }
 
-    fn upvar(&mut self, s: &Symbol) -> VmIndex {
+    fn upvar(&mut self, s: &Symbol, typ: ArcType) -> VmIndex {
         match self.free_vars.iter().position(|t| t == s) {
             Some(index) => index as VmIndex,
             None => {
                 self.free_vars.push(s.clone());
+                self.function.upvars.push(UpvarInfo {
+                    name: s.clone(),
+                    typ: typ,
+                });
                 (self.free_vars.len() - 1) as VmIndex
             }
         }
@@ -203,41 +395,30 @@ impl FunctionEnv {
         (self.stack_size - 1) as VmIndex
     }
 
-    fn push_stack_var(&mut self, s: Symbol) {
+    fn push_stack_var(&mut self, s: Symbol, typ: ArcType) {
         self.increase_stack(1);
-        self.new_stack_var(s)
+        self.new_stack_var(s, typ)
     }
 
-    fn new_stack_var(&mut self, s: Symbol) {
+    fn new_stack_var(&mut self, s: Symbol, typ: ArcType) {
         debug!("Push var: {:?} at {}", s, self.stack_size - 1);
-        self.stack.push((self.stack_size - 1, s));
+        let index = self.stack_size - 1;
+        if !s.as_ref().is_empty() {
+            let instruction_index = self.function.instructions.len();
+            self.function.locals.emit(instruction_index, index, s.clone(), typ);
+        }
+        self.stack.push((index, s));
     }
 
     fn pop_var(&mut self) {
         let x = self.stack.pop();
         debug!("Pop var: {:?}", x);
-    }
-
-    fn pop_pattern(&mut self, pattern: &Pattern<Symbol>) -> VmIndex {
-        match *pattern {
-            Pattern::Constructor(_, ref args) => {
-                for _ in 0..args.len() {
-                    self.pop_var();
-                }
-                args.len() as VmIndex
-            }
-            Pattern::Record { ref fields, .. } => {
-                for _ in fields {
-                    self.pop_var();
-                }
-                fields.len() as VmIndex
-            }
-            Pattern::Ident(_) => {
-                self.pop_var();
-                1
-            }
+        if let Some((index, _)) = x {
+            let instruction_index = self.function.instructions.len();
+            self.function.locals.close(index, instruction_index);
         }
     }
+
 }
 
 pub trait CompilerEnv: TypeEnv {
@@ -278,29 +459,7 @@ pub struct Compiler<'a> {
     globals: &'a (CompilerEnv + 'a),
     vm: &'a GlobalVmState,
     symbols: SymbolModule<'a>,
-    stack_constructors: ScopedMap<Symbol, ArcType>,
-    stack_types: ScopedMap<Symbol, Alias<Symbol, ArcType>>,
-}
-
-impl<'a> KindEnv for Compiler<'a> {
-    fn find_kind(&self, _type_name: &SymbolRef) -> Option<types::ArcKind> {
-        None
-    }
-}
-
-impl<'a> TypeEnv for Compiler<'a> {
-    fn find_type(&self, _id: &SymbolRef) -> Option<&ArcType> {
-        None
-    }
-
-    fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
-        self.stack_types
-            .get(id)
-    }
-
-    fn find_record(&self, _fields: &[Symbol]) -> Option<(&ArcType, &ArcType)> {
-        None
-    }
+    optimization_level: OptimizationLevel,
 }
 
 impl<'a, T: CompilerEnv> CompilerEnv for &'a T {
@@ -318,54 +477,47 @@ impl<'a> Compiler<'a> {
             globals: globals,
             vm: vm,
             symbols: symbols,
-            stack_constructors: ScopedMap::new(),
-            stack_types: ScopedMap::new(),
+            optimization_level: OptimizationLevel::default(),
         }
     }
 
+    /// Sets how much the compiled code should be optimized (default: `OptimizationLevel::None`)
+    pub fn optimization_level(mut self, optimization_level: OptimizationLevel) -> Compiler<'a> {
+        self.optimization_level = optimization_level;
+        self
+    }
+
     fn intern(&mut self, s: &str) -> Result<InternedStr> {
         self.vm.intern(s)
     }
 
     fn find(&self, id: &Symbol, current: &mut FunctionEnvs) -> Option<Variable<VmIndex>> {
-        let variable = self.stack_constructors
+        let variable = current.stack
             .iter()
-            .filter_map(|(_, typ)| {
-                match **typ {
-                    Type::Variants(ref variants) => {
-                        variants.iter()
-                            .enumerate()
-                            .find(|&(_, v)| v.0 == *id)
-                    }
-                    _ => None,
-                }
-            })
-            .next()
-            .map(|(tag, &(_, ref typ))| {
-                Constructor(tag as VmIndex, types::arg_iter(typ).count() as VmIndex)
-            })
+            .rev()
+            .cloned()
+            .find(|&(_, ref var)| var == id)
+            .map(|(index, _)| Stack(index))
             .or_else(|| {
-                current.stack
-                    .iter()
+                let i = current.envs.len() - 1;
+                let (rest, current) = current.envs.split_at_mut(i);
+                rest.iter()
                     .rev()
-                    .cloned()
-                    .find(|&(_, ref var)| var == id)
-                    .map(|(index, _)| Stack(index))
-                    .or_else(|| {
-                        let i = current.envs.len() - 1;
-                        let (rest, current) = current.envs.split_at_mut(i);
-                        rest.iter()
+                    .filter_map(|env| {
+                        env.stack
+                            .iter()
                             .rev()
-                            .filter_map(|env| {
-                                env.stack
-                                    .iter()
-                                    .rev()
-                                    .cloned()
-                                    .find(|&(_, ref var)| var == id)
-                                    .map(|_| UpVar(current[0].upvar(id)))
+                            .cloned()
+                            .find(|&(_, ref var)| var == id)
+                            .map(|_| {
+                                let typ = env.function
+                                    .locals
+                                    .open(id)
+                                    .map_or_else(Type::hole, |local| local.typ.clone());
+                                UpVar(current[0].upvar(id, typ))
                             })
-                            .next()
                     })
+                    .next()
             })
             .or_else(|| self.globals.find_var(&id));
         variable.map(|variable| {
@@ -387,46 +539,29 @@ impl<'a> Compiler<'a> {
         })
     }
 
-    fn find_field(&self, typ: &ArcType, field: &Symbol) -> Option<FieldAccess> {
-        // Remove all type aliases to get the actual record type
-        let typ = instantiate::remove_aliases_cow(self, typ);
-        let mut iter = typ.field_iter();
-        match iter.by_ref().position(|f| f.name.name_eq(field)) {
-            Some(index) => {
-                for _ in iter.by_ref() {}
-                Some(if **iter.current_type() == Type::EmptyRow {
-                    // Non-polymorphic record, access by index
-                    FieldAccess::Index(index as VmIndex)
-                } else {
-                    FieldAccess::Name
-                })
-            }
-            None => None,
-        }
-    }
-
-    fn find_tag(&self, typ: &ArcType, constructor: &Symbol) -> Option<VmTag> {
-        match **instantiate::remove_aliases_cow(self, typ) {
-            Type::Variants(ref variants) => {
-                variants.iter()
-                    .enumerate()
-                    .find(|&(_, v)| v.0 == *constructor)
-                    .map(|(tag, _)| tag as VmTag)
-            }
-            _ => None,
-        }
-    }
-
     /// Compiles an expression to a zero argument function which can be directly fed to the
     /// interpreter
     pub fn compile_expr(&mut self, expr: &CExpr) -> Result<CompiledFunction> {
+        let mut core_expr = try!(Translator::new(&mut self.symbols).translate(expr));
+        if self.optimization_level >= OptimizationLevel::Inline {
+            core_expr = inline::eliminate_dead_bindings(inline::inline(core_expr));
+        }
+        let typ = ArcType::from(expr.env_type_of(&self.globals).clone());
+        self.compile_core_expr(&core_expr, typ)
+    }
+
+    /// Compiles an expression which has already been translated into the core language. `typ`
+    /// is the type of the value the expression evaluates to.
+    pub fn compile_core_expr(&mut self,
+                             expr: &core::Expr,
+                             typ: ArcType)
+                             -> Result<CompiledFunction> {
         let mut env = FunctionEnvs::new();
         let id = self.symbols.symbol("");
-        let typ = Type::function(vec![],
-                                 ArcType::from(expr.env_type_of(&self.globals).clone()));
-        env.start_function(self, 0, id, typ);
+        env.start_function(0, id, Type::function(vec![], typ));
         try!(self.compile(expr, &mut env, true));
-        let FunctionEnv { function, .. } = env.end_function(self);
+        let FunctionEnv { mut function, .. } = env.end_function();
+        optimize::optimize(&mut function, self.optimization_level);
         Ok(function)
     }
 
@@ -448,38 +583,40 @@ impl<'a> Compiler<'a> {
     }
 
     fn compile(&mut self,
-               mut expr: &CExpr,
+               mut expr: &core::Expr,
                function: &mut FunctionEnvs,
                tail_position: bool)
                -> Result<()> {
-        // Store a stack of expressions which need to be cleaned up after this "tailcall" loop is
-        // done
-        let mut exprs = Vec::new();
-        exprs.push(expr);
-        while let Some(next) = try!(self.compile_(expr, function, tail_position)) {
-            exprs.push(next);
+        // Store the number of variables which each expression in this "tailcall" loop bound so
+        // that they can be cleaned up after the loop is done
+        let mut bound = Vec::new();
+        let previous_span = function.current_span;
+        function.current_span = expr.span();
+        while let Some((next, count)) = try!(self.compile_(expr, function, tail_position)) {
+            bound.push(count);
             expr = next;
+            function.current_span = expr.span();
         }
-        for expr in exprs.iter().rev() {
-            let mut count = 0;
-            if let Expr::LetBindings(ref bindings, _) = expr.value {
-                for binding in bindings {
-                    count += function.pop_pattern(&binding.name.value);
-                }
-                self.stack_constructors.exit_scope();
+        for &count in bound.iter().rev() {
+            for _ in 0..count {
+                function.pop_var();
             }
             function.emit(Slide(count));
         }
+        function.current_span = previous_span;
         Ok(())
     }
 
+    /// Compiles `expr`. If `expr` binds variables which scope over another expression, that
+    /// expression is returned together with the number of variables which were bound instead of
+    /// being compiled.
     fn compile_<'e>(&mut self,
-                    expr: &'e CExpr,
+                    expr: &'e core::Expr,
                     function: &mut FunctionEnvs,
                     tail_position: bool)
-                    -> Result<Option<&'e CExpr>> {
-        match expr.value {
-            Expr::Literal(ref lit) => {
+                    -> Result<Option<(&'e core::Expr, VmIndex)>> {
+        match *expr {
+            core::Expr::Const(ref lit, _) => {
                 match *lit {
                     Literal::Int(i) => function.emit(PushInt(i as isize)),
                     Literal::Byte(b) => function.emit(PushByte(b)),
@@ -488,243 +625,36 @@ impl<'a> Compiler<'a> {
                     Literal::Char(c) => function.emit(PushInt(c as isize)),
                 }
             }
-            Expr::Ident(ref id) => self.load_identifier(&id.name, function),
-            Expr::IfElse(ref pred, ref if_true, ref if_false) => {
-                try!(self.compile(&**pred, function, false));
-                let jump_index = function.function.instructions.len();
-                function.emit(CJump(0));
-
-                try!(self.compile(&**if_false, function, tail_position));
-                // The stack size of the true branch should not be increased by the false branch
-                function.stack_size -= 1;
-                let false_jump_index = function.function.instructions.len();
-                function.emit(Jump(0));
-
-                function.function.instructions[jump_index] =
-                    CJump(function.function.instructions.len() as VmIndex);
-                try!(self.compile(&**if_true, function, tail_position));
-                function.function.instructions[false_jump_index] =
-                    Jump(function.function.instructions.len() as VmIndex);
-            }
-            Expr::Infix(ref lhs, ref op, ref rhs) => {
-                if op.name.as_ref() == "&&" {
-                    try!(self.compile(&**lhs, function, false));
-                    let lhs_end = function.function.instructions.len();
-                    function.emit(CJump(lhs_end as VmIndex + 3));//Jump to rhs evaluation
-                    function.emit(Construct { tag: 0, args: 0 });
-                    function.emit(Jump(0));//lhs false, jump to after rhs
-                    // Dont count the integer added added above as the next part of the code never
-                    // pushed it
-                    function.stack_size -= 1;
-                    try!(self.compile(&**rhs, function, tail_position));
-                    // replace jump instruction
-                    function.function.instructions[lhs_end + 2] =
-                        Jump(function.function.instructions.len() as VmIndex);
-                } else if op.name.as_ref() == "||" {
-                    try!(self.compile(&**lhs, function, false));
-                    let lhs_end = function.function.instructions.len();
-                    function.emit(CJump(0));
-                    try!(self.compile(&**rhs, function, tail_position));
-                    function.emit(Jump(0));
-                    function.function.instructions[lhs_end] =
-                        CJump(function.function.instructions.len() as VmIndex);
-                    function.emit(Construct { tag: 1, args: 0 });
-                    // Dont count the integer above
-                    function.stack_size -= 1;
-                    let end = function.function.instructions.len();
-                    function.function.instructions[end - 2] = Jump(end as VmIndex);
-                } else {
-                    let instr = match self.symbols.string(&op.name) {
-                        "#Int+" => AddInt,
-                        "#Int-" => SubtractInt,
-                        "#Int*" => MultiplyInt,
-                        "#Int/" => DivideInt,
-                        "#Int<" | "#Char<" => IntLT,
-                        "#Int==" | "#Char==" => IntEQ,
-                        "#Byte+" => AddByte,
-                        "#Byte-" => SubtractByte,
-                        "#Byte*" => MultiplyByte,
-                        "#Byte/" => DivideByte,
-                        "#Byte<" => ByteLT,
-                        "#Byte==" => ByteEQ,
-                        "#Float+" => AddFloat,
-                        "#Float-" => SubtractFloat,
-                        "#Float*" => MultiplyFloat,
-                        "#Float/" => DivideFloat,
-                        "#Float<" => FloatLT,
-                        "#Float==" => FloatEQ,
-                        _ => {
-                            self.load_identifier(&op.name, function);
-                            Call(2)
-                        }
-                    };
-                    try!(self.compile(&**lhs, function, false));
-                    try!(self.compile(&**rhs, function, false));
-                    function.emit(instr);
-                }
-            }
-            Expr::LetBindings(ref bindings, ref body) => {
-                self.stack_constructors.enter_scope();
-                let stack_start = function.stack_size;
-                // Index where the instruction to create the first closure should be at
-                let first_index = function.function.instructions.len();
-                let is_recursive = bindings.iter().all(|bind| bind.args.len() > 0);
-                if is_recursive {
-                    for bind in bindings.iter() {
-                        // Add the NewClosure instruction before hand
-                        // it will be fixed later
-                        function.emit(NewClosure {
-                            function_index: 0,
-                            upvars: 0,
-                        });
-                        match bind.name.value {
-                            Pattern::Ident(ref name) => {
-                                function.new_stack_var(name.name.clone());
-                            }
-                            _ => panic!("ICE: Unexpected non identifer pattern"),
+            core::Expr::Ident(ref id, _) => self.load_identifier(&id.name, function),
+            core::Expr::Call(ref func, ref args, _) => {
+                if let core::Expr::Ident(ref id, _) = **func {
+                    let primitive = primitive_instruction(self.symbols.string(&id.name));
+                    if let Some(instruction) = primitive {
+                        for arg in args {
+                            try!(self.compile(arg, function, false));
                         }
+                        function.emit(instruction);
+                        return Ok(None);
                     }
-                }
-                for (i, bind) in bindings.iter().enumerate() {
-
-                    if is_recursive {
-                        function.emit(Push(stack_start + i as VmIndex));
-                        let name = match bind.name.value {
-                            Pattern::Ident(ref name) => name,
-                            _ => panic!("Lambda binds to non identifer pattern"),
-                        };
-                        let (function_index, vars, cf) =
-                            try!(self.compile_lambda(name, &bind.args, &bind.expr, function));
-                        let offset = first_index + i;
-                        function.function.instructions[offset] = NewClosure {
-                            function_index: function_index,
-                            upvars: vars,
-                        };
-                        function.emit(CloseClosure(vars));
-                        function.stack_size -= vars;
-                        function.function.inner_functions.push(cf);
-                    } else {
-                        try!(self.compile(&bind.expr, function, false));
-                        let typ = bind.expr.env_type_of(self);
-                        try!(self.compile_let_pattern(&bind.name.value, &typ, function));
-                    }
-                }
-                return Ok(Some(body));
-            }
-            Expr::App(ref func, ref args) => {
-                if let Expr::Ident(ref id) = func.value {
-                    if let Some(Constructor(tag, num_args)) = self.find(&id.name, function) {
-                        for arg in args.iter() {
+                    if let Some(Constructor(tag, _)) = self.find(&id.name, function) {
+                        for arg in args {
                             try!(self.compile(arg, function, false));
                         }
                         function.emit(Construct {
                             tag: tag,
-                            args: num_args,
+                            args: args.len() as VmIndex,
                         });
                         return Ok(None);
                     }
                 }
-                try!(self.compile(&**func, function, false));
-                for arg in args.iter() {
+                try!(self.compile(func, function, false));
+                for arg in args {
                     try!(self.compile(arg, function, false));
                 }
                 function.emit_call(args.len() as VmIndex, tail_position);
             }
-            Expr::Projection(ref expr, ref id, ref typ) => {
-                try!(self.compile(&**expr, function, false));
-                debug!("{:?} {:?} {:?}", expr, id, typ);
-                let typ = expr.env_type_of(self);
-                debug!("Projection {}", types::display_type(&self.symbols, &typ));
-
-                try!(function.emit_field(self, &typ, id));
-            }
-            Expr::Match(ref expr, ref alts) => {
-                try!(self.compile(&**expr, function, false));
-                // Indexes for each alternative for a successful match to the alternatives code
-                let mut start_jumps = Vec::new();
-                let typ = expr.env_type_of(self);
-                let mut catch_all = false;
-                // Emit a TestTag + Jump instuction for each alternative which jumps to the
-                // alternatives code if TestTag is sucessesful
-                for alt in alts.iter() {
-                    match alt.pattern.value {
-                        Pattern::Constructor(ref id, _) => {
-                            let tag = self.find_tag(&typ, &id.name)
-                                .unwrap_or_else(|| {
-                                    panic!("Could not find tag for {}::{}",
-                                           types::display_type(&self.symbols, &typ),
-                                           self.symbols.string(&id.name))
-                                });
-                            function.emit(TestTag(tag));
-                            start_jumps.push(function.function.instructions.len());
-                            function.emit(CJump(0));
-                        }
-                        Pattern::Record { .. } => {
-                            catch_all = true;
-                            start_jumps.push(function.function.instructions.len());
-                        }
-                        _ => {
-                            catch_all = true;
-                            start_jumps.push(function.function.instructions.len());
-                            function.emit(Jump(0));
-                        }
-                    }
-                }
-                // Create a catch all to prevent us from running into undefined behaviour
-                if !catch_all {
-                    let error_fn = self.symbols.symbol("#error");
-                    self.load_identifier(&error_fn, function);
-                    function.emit_string(try!(self.intern("Non-exhaustive pattern")));
-                    function.emit(Call(1));
-                    // The stack has been increased by 1 here but it should not affect compiling the
-                    // alternatives
-                    function.stack_size -= 1;
-                }
-                // Indexes for each alternative from the end of the alternatives code to code
-                // after the alternative
-                let mut end_jumps = Vec::new();
-                for (alt, &start_index) in alts.iter().zip(start_jumps.iter()) {
-                    self.stack_constructors.enter_scope();
-                    match alt.pattern.value {
-                        Pattern::Constructor(_, ref args) => {
-                            function.function.instructions[start_index] =
-                                CJump(function.function.instructions.len() as VmIndex);
-                            function.emit(Split);
-                            for arg in args.iter() {
-                                function.push_stack_var(arg.name.clone());
-                            }
-                        }
-                        Pattern::Record { .. } => {
-                            let typ = &expr.env_type_of(self);
-                            try!(self.compile_let_pattern(&alt.pattern.value, typ, function));
-                        }
-                        Pattern::Ident(ref id) => {
-                            function.function.instructions[start_index] =
-                                Jump(function.function.instructions.len() as VmIndex);
-                            function.new_stack_var(id.name.clone());
-                        }
-                    }
-                    try!(self.compile(&alt.expr, function, tail_position));
-                    let count = function.pop_pattern(&alt.pattern.value);
-                    self.stack_constructors.exit_scope();
-                    function.emit(Slide(count));
-                    end_jumps.push(function.function.instructions.len());
-                    function.emit(Jump(0));
-                }
-                for &index in end_jumps.iter() {
-                    function.function.instructions[index] =
-                        Jump(function.function.instructions.len() as VmIndex);
-                }
-            }
-            Expr::Array(ref a) => {
-                for expr in a.exprs.iter() {
-                    try!(self.compile(expr, function, false));
-                }
-                function.emit(ConstructArray(a.exprs.len() as VmIndex));
-            }
-            Expr::Lambda(ref lambda) => {
-                let (function_index, vars, cf) =
-                    try!(self.compile_lambda(&lambda.id, &lambda.args, &lambda.body, function));
+            core::Expr::Lambda(ref closure, _) => {
+                let (function_index, vars, cf) = try!(self.compile_lambda(closure, function));
                 function.emit(MakeClosure {
                     function_index: function_index,
                     upvars: vars,
@@ -732,137 +662,265 @@ impl<'a> Compiler<'a> {
                 function.stack_size -= vars;
                 function.function.inner_functions.push(cf);
             }
-            Expr::TypeBindings(ref type_bindings, ref expr) => {
-                for bind in type_bindings {
-                    self.stack_types.insert(bind.alias.name.clone(), bind.alias.clone());
-                    let typ = bind.alias.typ.as_ref().expect("TypeBinding type").clone();
-                    self.stack_constructors.insert(bind.name.clone(), typ);
+            core::Expr::Data(ref kind, ref exprs, _) => {
+                for expr in exprs {
+                    try!(self.compile(expr, function, false));
                 }
-                return Ok(Some(expr));
-            }
-            Expr::Record { exprs: ref fields, .. } => {
-                for field in fields {
-                    match field.1 {
-                        Some(ref field_expr) => try!(self.compile(field_expr, function, false)),
-                        None => self.load_identifier(&field.0, function),
+                let args = exprs.len() as VmIndex;
+                match *kind {
+                    DataKind::Tag(tag) => {
+                        function.emit(Construct {
+                            tag: tag,
+                            args: args,
+                        })
+                    }
+                    DataKind::Record(ref fields) => {
+                        let index = function.add_record_map(fields.clone());
+                        function.emit(ConstructRecord {
+                            record: index,
+                            args: args,
+                        });
                     }
+                    DataKind::Array => function.emit(ConstructArray(args)),
                 }
-                let index =
-                    function.add_record_map(fields.iter().map(|field| field.0.clone()).collect());
-                function.emit(ConstructRecord {
-                    record: index,
-                    args: fields.len() as u32,
-                });
             }
-            Expr::Tuple(ref exprs) => {
-                for expr in exprs {
-                    try!(self.compile(expr, function, false));
+            core::Expr::Let(ref named, ref body, _) => {
+                match *named {
+                    Named::Expr(ref id, ref value) => {
+                        try!(self.compile(value, function, false));
+                        function.new_stack_var(id.name.clone(), id.typ.clone());
+                        return Ok(Some((body, 1)));
+                    }
+                    Named::Recursive(ref closures) => {
+                        let stack_start = function.stack_size;
+                        // Index where the instruction to create the first closure should be at
+                        let first_index = function.function.instructions.len();
+                        for closure in closures {
+                            // Add the NewClosure instruction before hand
+                            // it will be fixed later
+                            function.emit(NewClosure {
+                                function_index: 0,
+                                upvars: 0,
+                            });
+                            function.new_stack_var(closure.id.name.clone(),
+                                                   closure.id.typ.clone());
+                        }
+                        for (i, closure) in closures.iter().enumerate() {
+                            function.emit(Push(stack_start + i as VmIndex));
+                            let (function_index, vars, cf) =
+                                try!(self.compile_lambda(closure, function));
+                            let offset = first_index + i;
+                            function.function.instructions[offset] = NewClosure {
+                                function_index: function_index,
+                                upvars: vars,
+                            };
+                            function.emit(CloseClosure(vars));
+                            function.stack_size -= vars;
+                            function.function.inner_functions.push(cf);
+                        }
+                        return Ok(Some((body, closures.len() as VmIndex)));
+                    }
                 }
-                function.emit(Construct {
-                    tag: 0,
-                    args: exprs.len() as u32,
-                });
             }
-            Expr::Block(ref exprs) => {
-                let (last, exprs) = exprs.split_last().expect("Expr in block");
-                for expr in exprs {
-                    try!(self.compile(expr, function, false));
-                }
-                try!(self.compile(last, function, tail_position));
-                function.emit(Slide(exprs.len() as u32 - 1));
+            core::Expr::Match(ref scrutinee, ref alts, _) => {
+                return self.compile_match(scrutinee, alts, function, tail_position);
             }
         }
         Ok(None)
     }
 
-    fn compile_let_pattern(&mut self,
-                           pattern: &Pattern<Symbol>,
-                           pattern_type: &ArcType,
-                           function: &mut FunctionEnvs)
-                           -> Result<()> {
-        match *pattern {
-            Pattern::Ident(ref name) => {
-                function.new_stack_var(name.name.clone());
+    fn compile_match<'e>(&mut self,
+                         scrutinee: &core::Expr,
+                         alts: &'e [Alternative],
+                         function: &mut FunctionEnvs,
+                         tail_position: bool)
+                         -> Result<Option<(&'e core::Expr, VmIndex)>> {
+        if let Some(field) = as_projection(alts) {
+            try!(self.compile(scrutinee, function, false));
+            try!(function.emit_field(self, field));
+            return Ok(None);
+        }
+        try!(self.compile(scrutinee, function, false));
+        if let Some((if_true, if_false)) = as_bool_match(alts) {
+            let jump_index = function.function.instructions.len();
+            function.emit(CJump(0));
+
+            try!(self.compile(if_false, function, tail_position));
+            // The stack size of the true branch should not be increased by the false branch
+            function.stack_size -= 1;
+            let false_jump_index = function.function.instructions.len();
+            function.emit(Jump(0));
+
+            function.function.instructions[jump_index] =
+                CJump(function.function.instructions.len() as VmIndex);
+            try!(self.compile(if_true, function, tail_position));
+            function.function.instructions[false_jump_index] =
+                Jump(function.function.instructions.len() as VmIndex);
+            return Ok(None);
+        }
+        match alts.first() {
+            Some(alt) if alts.len() == 1 && is_irrefutable(&alt.pattern) => {
+                // A single irrefutable pattern only binds variables so the body can be compiled
+                // as part of the tailcall loop in `compile`
+                let count = try!(self.compile_pattern(&alt.pattern, function));
+                return Ok(Some((&alt.expr, count)));
+            }
+            _ => (),
+        }
+
+        let decision = Decision::new(alts);
+        try!(self.compile_decision(&decision, function, tail_position));
+        Ok(None)
+    }
+
+    /// Compiles `decision` with the value being matched at the top of the stack
+    fn compile_decision(&mut self,
+                        decision: &Decision,
+                        function: &mut FunctionEnvs,
+                        tail_position: bool)
+                        -> Result<()> {
+        match *decision {
+            Decision::Fail => {
+                let error_fn = self.symbols.symbol("#error");
+                self.load_identifier(&error_fn, function);
+                function.emit_string(try!(self.intern("Non-exhaustive pattern")));
+                function.emit(Call(1));
+                // The stack has been increased by 1 here but it should not affect compiling the
+                // alternatives
+                function.stack_size -= 1;
             }
-            Pattern::Record { ref types, ref fields, .. } => {
-                let typ = instantiate::remove_aliases(self, pattern_type.clone());
-                // Insert all variant constructor into scope
-                with_pattern_types(types, &typ, |name, alias| {
-                    // FIXME: Workaround so that both the types name in this module and its global
-                    // name are imported. Without this aliases may not be traversed properly
-                    self.stack_types.insert(alias.name.clone(), alias.clone());
-                    self.stack_types.insert(name.clone(), alias.clone());
-                    if let Some(ref typ) = alias.typ {
-                        self.stack_constructors.insert(alias.name.clone(), typ.clone());
-                        self.stack_constructors.insert(name.clone(), typ.clone());
+            Decision::Leaf(alt) => {
+                let count = try!(self.compile_pattern(&alt.pattern, function));
+                try!(self.compile(&alt.expr, function, tail_position));
+                for _ in 0..count {
+                    function.pop_var();
+                }
+                function.emit(Slide(count));
+            }
+            Decision::Switch { ref cases, ref default } => {
+                // If every tag has a case the last tag does not need to be tested as it is the
+                // only one left
+                let (tested, fallthrough) = match *default {
+                    Some(ref default) => (&cases[..], &**default),
+                    None => {
+                        let (last, rest) = cases.split_last().expect("Switch without cases");
+                        (rest, &last.1)
                     }
-                });
-                match *typ {
-                    Type::Record(_) => {
-                        let mut field_iter = typ.field_iter();
-                        let number_of_fields = field_iter.by_ref().count();
-                        let is_polymorphic = **field_iter.current_type() != Type::EmptyRow;
-                        if fields.len() == 0 ||
-                           (number_of_fields > 4 && number_of_fields / fields.len() >= 4) ||
-                           is_polymorphic {
-                            // For pattern matches on large records where only a few of the fields
-                            // are used we instead emit a series of GetOffset instructions to avoid
-                            // pushing a lot of unnecessary fields to the stack
-                            // Polymorphic records also needs to generate field accesses as `Split`
-                            // would push the fields in a different order depending on the record
-                            let record_index = function.stack_size();
-                            for pattern_field in fields {
-                                function.emit(Push(record_index));
-                                try!(function.emit_field(self, &typ, &pattern_field.0));
-                                function.new_stack_var(pattern_field.1
-                                    .as_ref()
-                                    .unwrap_or(&pattern_field.0)
-                                    .clone());
-                            }
-                        } else {
-                            function.emit(Split);
-                            for field in typ.field_iter() {
-                                let name = match fields.iter()
-                                    .find(|tup| tup.0.name_eq(&field.name)) {
-                                    Some(&(ref name, ref bind)) => {
-                                        bind.as_ref().unwrap_or(name).clone()
-                                    }
-                                    None => self.symbols.symbol(""),
-                                };
-                                function.push_stack_var(name);
-                            }
-                        }
+                };
+                // Emit a TestTag + CJump instuction for each case which jumps to the
+                // code of the case if TestTag is sucessesful
+                let mut start_jumps = Vec::new();
+                for &(tag, _) in tested {
+                    function.emit(TestTag(tag));
+                    start_jumps.push(function.function.instructions.len());
+                    function.emit(CJump(0));
+                }
+                try!(self.compile_decision(fallthrough, function, tail_position));
+                // Indexes for each case from the end of its code to the code after the match
+                let mut end_jumps = Vec::new();
+                for (i, (&(_, ref case), start_index)) in tested.iter()
+                    .zip(start_jumps)
+                    .enumerate() {
+                    if i == 0 {
+                        end_jumps.push(function.function.instructions.len());
+                        function.emit(Jump(0));
                     }
-                    _ => {
-                        panic!("Expected record, got {} at {:?}",
-                               types::display_type(&self.symbols, &typ),
-                               pattern)
+                    function.function.instructions[start_index] =
+                        CJump(function.function.instructions.len() as VmIndex);
+                    try!(self.compile_decision(case, function, tail_position));
+                    if i + 1 != tested.len() {
+                        end_jumps.push(function.function.instructions.len());
+                        function.emit(Jump(0));
                     }
                 }
+                for &index in end_jumps.iter() {
+                    function.function.instructions[index] =
+                        Jump(function.function.instructions.len() as VmIndex);
+                }
             }
-            Pattern::Constructor(..) => panic!("constructor pattern in let"),
         }
         Ok(())
     }
 
+    /// Binds the variables of `pattern` to the matched value which is at the top of the stack.
+    /// Returns the number of stack slots which need to be removed once the variables go out of
+    /// scope.
+    fn compile_pattern(&mut self,
+                       pattern: &Pattern,
+                       function: &mut FunctionEnvs)
+                       -> Result<VmIndex> {
+        match *pattern {
+            Pattern::Constructor(_, _, ref args) => {
+                function.emit(Split);
+                for arg in args {
+                    function.push_stack_var(arg.name.clone(), arg.typ.clone());
+                }
+                Ok(args.len() as VmIndex)
+            }
+            Pattern::Ident(ref id) => {
+                function.new_stack_var(id.name.clone(), id.typ.clone());
+                Ok(1)
+            }
+            Pattern::Record { ref fields, size } => {
+                self.compile_record_pattern(fields, size, function)
+            }
+        }
+    }
+
+    fn compile_record_pattern(&mut self,
+                              fields: &[(Field, TypedIdent)],
+                              size: Option<VmIndex>,
+                              function: &mut FunctionEnvs)
+                              -> Result<VmIndex> {
+        match size {
+            Some(size) if !fields.is_empty() &&
+                          !(size > 4 && size / fields.len() as VmIndex >= 4) => {
+                function.emit(Split);
+                for offset in 0..size {
+                    let (name, typ) = fields.iter()
+                        .find(|field| field.0 == Field::Offset(offset))
+                        .map(|field| (field.1.name.clone(), field.1.typ.clone()))
+                        .unwrap_or_else(|| (self.symbols.symbol(""), Type::hole()));
+                    function.push_stack_var(name, typ);
+                }
+                Ok(size)
+            }
+            _ => {
+                // For pattern matches on large records where only a few of the fields
+                // are used we instead emit a series of GetOffset instructions to avoid
+                // pushing a lot of unnecessary fields to the stack
+                // Polymorphic records also needs to generate field accesses as `Split`
+                // would push the fields in a different order depending on the record
+                let record_index = function.stack_size();
+                function.new_stack_var(self.symbols.symbol(""), Type::hole());
+                for &(ref field, ref id) in fields {
+                    function.emit(Push(record_index));
+                    try!(function.emit_field(self, field));
+                    function.new_stack_var(id.name.clone(), id.typ.clone());
+                }
+                Ok(fields.len() as VmIndex + 1)
+            }
+        }
+    }
+
     fn compile_lambda(&mut self,
-                      id: &TypedIdent,
-                      args: &[TypedIdent],
-                      body: &SpannedExpr<Symbol>,
+                      closure: &Closure,
                       function: &mut FunctionEnvs)
                       -> Result<(VmIndex, VmIndex, CompiledFunction)> {
-        function.start_function(self, args.len() as VmIndex, id.name.clone(), id.typ.clone());
-        for arg in args {
-            function.push_stack_var(arg.name.clone());
+        function.start_function(closure.args.len() as VmIndex,
+                                closure.id.name.clone(),
+                                closure.id.typ.clone());
+        for arg in &closure.args {
+            function.push_stack_var(arg.name.clone(), arg.typ.clone());
         }
-        try!(self.compile(body, function, true));
+        try!(self.compile(&closure.body, function, true));
 
-        for _ in 0..args.len() {
+        for _ in 0..closure.args.len() {
             function.pop_var();
         }
         // Insert all free variables into the above globals free variables
         // if they arent in that lambdas scope
-        let f = function.end_function(self);
+        let f = function.end_function();
         for var in f.free_vars.iter() {
             match self.find(var, function).expect("free_vars: find") {
                 Stack(index) => function.emit(Push(index)),
@@ -877,13 +935,130 @@ impl<'a> Compiler<'a> {
     }
 }
 
-fn with_pattern_types<F>(types: &[(Symbol, Option<Symbol>)], typ: &ArcType, mut f: F)
-    where F: FnMut(&Symbol, &Alias<Symbol, ArcType>),
-{
-    for field in types {
-        let associated_type = typ.type_field_iter()
-            .find(|type_field| type_field.name.name_eq(&field.0))
-            .expect("Associated type to exist in record");
-        f(&field.0, &associated_type.typ);
+/// A decision tree which selects the alternative of a `match` expression to evaluate
+enum Decision<'a> {
+    /// None of the alternatives match the value
+    Fail,
+    /// The value matches the pattern of the alternative
+    Leaf(&'a Alternative),
+    /// Continues with the decision of the case with the same tag as the value
+    Switch {
+        cases: Vec<(VmTag, Decision<'a>)>,
+        /// The decision for values with a tag which has no case. `None` if every tag of the type
+        /// has a case.
+        default: Option<Box<Decision<'a>>>,
+    },
+}
+
+impl<'a> Decision<'a> {
+    /// Builds the decision tree for `alts`. Alternatives which can never be selected are left
+    /// out of the tree.
+    fn new(alts: &'a [Alternative]) -> Decision<'a> {
+        let mut cases: Vec<(VmTag, Decision)> = Vec::new();
+        let mut constructors = None;
+        for alt in alts {
+            match alt.pattern {
+                Pattern::Constructor(_, ref tag, _) => {
+                    constructors = Some(tag.constructors);
+                    if cases.iter().all(|case| case.0 != tag.tag) {
+                        cases.push((tag.tag, Decision::Leaf(alt)));
+                    }
+                }
+                Pattern::Record { .. } | Pattern::Ident(_) => {
+                    return Decision::switch(cases, Decision::Leaf(alt), constructors);
+                }
+            }
+        }
+        Decision::switch(cases, Decision::Fail, constructors)
+    }
+
+    fn switch(cases: Vec<(VmTag, Decision<'a>)>,
+              default: Decision<'a>,
+              constructors: Option<VmIndex>)
+              -> Decision<'a> {
+        if cases.is_empty() {
+            return default;
+        }
+        let is_exhaustive = constructors.map_or(false, |n| cases.len() == n as usize);
+        Decision::Switch {
+            cases: cases,
+            default: if is_exhaustive {
+                None
+            } else {
+                Some(Box::new(default))
+            },
+        }
+    }
+}
+
+/// Returns the instruction which implements the primitive operation `name`
+fn primitive_instruction(name: &str) -> Option<Instruction> {
+    Some(match name {
+        "#Int+" => AddInt,
+        "#Int-" => SubtractInt,
+        "#Int*" => MultiplyInt,
+        "#Int/" => DivideInt,
+        "#Int<" | "#Char<" => IntLT,
+        "#Int==" | "#Char==" => IntEQ,
+        "#Byte+" => AddByte,
+        "#Byte-" => SubtractByte,
+        "#Byte*" => MultiplyByte,
+        "#Byte/" => DivideByte,
+        "#Byte<" => ByteLT,
+        "#Byte==" => ByteEQ,
+        "#Float+" => AddFloat,
+        "#Float-" => SubtractFloat,
+        "#Float*" => MultiplyFloat,
+        "#Float/" => DivideFloat,
+        "#Float<" => FloatLT,
+        "#Float==" => FloatEQ,
+        _ => return None,
+    })
+}
+
+fn is_irrefutable(pattern: &Pattern) -> bool {
+    match *pattern {
+        Pattern::Constructor(..) => false,
+        Pattern::Record { .. } | Pattern::Ident(_) => true,
+    }
+}
+
+/// Returns the field being accessed if `alts` only retrieves a single field from a record
+fn as_projection(alts: &[Alternative]) -> Option<&Field> {
+    if alts.len() != 1 {
+        return None;
+    }
+    match (&alts[0].pattern, &alts[0].expr) {
+        (&Pattern::Record { ref fields, .. }, &core::Expr::Ident(ref id, _)) => {
+            match fields.first() {
+                Some(&(ref field, ref bind)) if fields.len() == 1 && bind.name == id.name => {
+                    Some(field)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns the expressions of the `True` and `False` alternatives if `alts` matches on a value
+/// whose type has exactly two constructors, tag 0 and tag 1, which do not take any arguments.
+/// Types with more constructors can not use `CJump` as any tag other than 1 would select the
+/// alternative for tag 0.
+fn as_bool_match(alts: &[Alternative]) -> Option<(&core::Expr, &core::Expr)> {
+    if alts.len() != 2 {
+        return None;
+    }
+    let tag = |alt: &Alternative| {
+        match alt.pattern {
+            Pattern::Constructor(_, ref tag, ref args) if args.is_empty() &&
+                                                          tag.constructors == 2 => Some(tag.tag),
+            _ => None,
+        }
+    };
+    match (tag(&alts[0]), tag(&alts[1])) {
+        (Some(1), Some(0)) => Some((&alts[0].expr, &alts[1].expr)),
+        (Some(0), Some(1)) => Some((&alts[1].expr, &alts[0].expr)),
+        _ => None,
     }
 }
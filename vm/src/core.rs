@@ -0,0 +1,943 @@
+//! The core language which typechecked expressions are translated into before any bytecode is
+//! generated.
+//!
+//! Compared to `base::ast::Expr` the core language has no nested patterns, literal patterns,
+//! guards, operators, `if` expressions, blocks or type bindings. Every decision which depends on
+//! types (the tag of a
+//! constructor, whether a field is accessed by offset or by name) has already been made during
+//! translation so passes over the core language and the bytecode generator only need to walk the
+//! expression.
+use base::ast::{self, Literal, SpannedExpr, Typed, TypedIdent};
+use base::instantiate;
+use base::pos::{BytePos, Span};
+use base::scoped_map::ScopedMap;
+use base::symbol::{Symbol, SymbolModule, SymbolRef};
+use base::types::{self, Alias, ArcType, KindEnv, Type, TypeEnv};
+
+use types::{VmIndex, VmTag};
+use {Error, Result};
+
+/// A function with its arguments and body
+#[derive(Clone, Debug, PartialEq)]
+pub struct Closure {
+    pub id: TypedIdent,
+    pub args: Vec<TypedIdent>,
+    pub body: Box<Expr>,
+}
+
+/// The value(s) bound by a `let`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Named {
+    /// A group of functions which may refer to each other
+    Recursive(Vec<Closure>),
+    Expr(TypedIdent, Box<Expr>),
+}
+
+/// Describes how a field of a record is retrieved
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    /// The field is at a known offset in a non-polymorphic record
+    Offset(VmIndex),
+    /// The field must be looked up by name as the record is polymorphic
+    Name(Symbol),
+}
+
+/// What kind of value a `Data` expression constructs
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataKind {
+    Tag(VmTag),
+    /// A record with the fields in order
+    Record(Vec<Symbol>),
+    Array,
+}
+
+/// Identifies a constructor of a variant type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tag {
+    pub tag: VmTag,
+    /// The number of constructors the type has
+    pub constructors: VmIndex,
+}
+
+/// A pattern which only inspects a single level of a value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    /// Matches values tagged with `tag`, binding each of the arguments
+    Constructor(TypedIdent, Tag, Vec<TypedIdent>),
+    /// Binds fields of a record. `size` is the number of fields in the record if it is not
+    /// polymorphic.
+    Record {
+        fields: Vec<(Field, TypedIdent)>,
+        size: Option<VmIndex>,
+    },
+    Ident(TypedIdent),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alternative {
+    pub pattern: Pattern,
+    pub expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Const(Literal, Span<BytePos>),
+    Ident(TypedIdent, Span<BytePos>),
+    /// Calls a function. Primitive operations such as `#Int+` are represented as calls to an
+    /// identifier with the name of the operation.
+    Call(Box<Expr>, Vec<Expr>, Span<BytePos>),
+    Lambda(Closure, Span<BytePos>),
+    /// Constructs a value from the values of the expressions
+    Data(DataKind, Vec<Expr>, Span<BytePos>),
+    Let(Named, Box<Expr>, Span<BytePos>),
+    Match(Box<Expr>, Vec<Alternative>, Span<BytePos>),
+}
+
+impl Expr {
+    pub fn span(&self) -> Span<BytePos> {
+        match *self {
+            Expr::Const(_, span) |
+            Expr::Ident(_, span) |
+            Expr::Call(_, _, span) |
+            Expr::Lambda(_, span) |
+            Expr::Data(_, _, span) |
+            Expr::Let(_, _, span) |
+            Expr::Match(_, _, span) => span,
+        }
+    }
+}
+
+/// A binding which is waiting for the expression it scopes over to be translated
+enum Binding<'p> {
+    Let(Named, Span<BytePos>),
+    Pattern(Expr, Pattern, Span<BytePos>),
+    /// A binding to a pattern which needs to be translated into multiple matches
+    Nested(Expr, &'p ast::SpannedPattern<Symbol>, ArcType, Span<BytePos>),
+}
+
+/// An alternative whose pattern may be nested and whose guard and body have been translated
+struct NestedAlternative<'p> {
+    pattern: &'p ast::SpannedPattern<Symbol>,
+    guard: Option<Expr>,
+    expr: Expr,
+}
+
+/// Translates typechecked expressions into the core language
+pub struct Translator<'t, 'a: 't> {
+    symbols: &'t mut SymbolModule<'a>,
+    stack_constructors: ScopedMap<Symbol, ArcType>,
+    stack_types: ScopedMap<Symbol, Alias<Symbol, ArcType>>,
+    /// The span of the first `Expr::Error` which was found
+    syntax_error: Option<Span<BytePos>>,
+}
+
+impl<'t, 'a> KindEnv for Translator<'t, 'a> {
+    fn find_kind(&self, _type_name: &SymbolRef) -> Option<types::ArcKind> {
+        None
+    }
+}
+
+impl<'t, 'a> TypeEnv for Translator<'t, 'a> {
+    fn find_type(&self, _id: &SymbolRef) -> Option<&ArcType> {
+        None
+    }
+
+    fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
+        self.stack_types
+            .get(id)
+    }
+
+    fn find_record(&self, _fields: &[Symbol]) -> Option<(&ArcType, &ArcType)> {
+        None
+    }
+}
+
+impl<'t, 'a> Translator<'t, 'a> {
+    pub fn new(symbols: &'t mut SymbolModule<'a>) -> Translator<'t, 'a> {
+        Translator {
+            symbols: symbols,
+            stack_constructors: ScopedMap::new(),
+            stack_types: ScopedMap::new(),
+            syntax_error: None,
+        }
+    }
+
+    /// Translates `expr` into the core language. Expressions which still contain the errors that
+    /// the parser recovered from can not be translated and return an error.
+    pub fn translate(&mut self, expr: &SpannedExpr<Symbol>) -> Result<Expr> {
+        let expr = self.translate_expr(expr);
+        match self.syntax_error.take() {
+            Some(span) => {
+                Err(Error::Message(format!("Unable to compile an expression with a syntax error \
+                                            at {}",
+                                           span.start)))
+            }
+            None => Ok(expr),
+        }
+    }
+
+    fn translate_expr(&mut self, expr: &SpannedExpr<Symbol>) -> Expr {
+        let span = expr.span;
+        match expr.value {
+            ast::Expr::Ident(ref id) => {
+                match self.find_constructor(&id.name) {
+                    Some((tag, 0)) => Expr::Data(DataKind::Tag(tag), vec![], span),
+                    Some(_) => panic!("Constructor {:?} is not fully applied", id.name),
+                    None => Expr::Ident(id.clone(), span),
+                }
+            }
+            ast::Expr::Literal(ref lit) => Expr::Const(lit.clone(), span),
+            ast::Expr::App(ref func, ref args) => {
+                let args = args.iter().map(|arg| self.translate_expr(arg)).collect();
+                if let ast::Expr::Ident(ref id) = func.value {
+                    if let Some((tag, _)) = self.find_constructor(&id.name) {
+                        return Expr::Data(DataKind::Tag(tag), args, span);
+                    }
+                }
+                Expr::Call(Box::new(self.translate_expr(func)), args, span)
+            }
+            ast::Expr::Lambda(ref lambda) => {
+                Expr::Lambda(self.translate_closure(&lambda.id, &lambda.args, &lambda.body),
+                             span)
+            }
+            ast::Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+                let typ = pred.env_type_of(self);
+                let alts = vec![Alternative {
+                                    pattern: self.bool_pattern(&typ, true),
+                                    expr: self.translate_expr(if_true),
+                                },
+                                Alternative {
+                                    pattern: self.bool_pattern(&typ, false),
+                                    expr: self.translate_expr(if_false),
+                                }];
+                Expr::Match(Box::new(self.translate_expr(pred)), alts, span)
+            }
+            ast::Expr::Infix(ref lhs, ref op, ref rhs) => {
+                let is_and = op.name.as_ref() == "&&";
+                if is_and || op.name.as_ref() == "||" {
+                    // `a && b` is translated to `if a then b else False` and `a || b` to
+                    // `if a then True else b`
+                    let typ = lhs.env_type_of(self);
+                    let (if_true, if_false) = if is_and {
+                        (self.translate_expr(rhs), Expr::Data(DataKind::Tag(0), vec![], span))
+                    } else {
+                        (Expr::Data(DataKind::Tag(1), vec![], span), self.translate_expr(rhs))
+                    };
+                    let alts = vec![Alternative {
+                                        pattern: self.bool_pattern(&typ, true),
+                                        expr: if_true,
+                                    },
+                                    Alternative {
+                                        pattern: self.bool_pattern(&typ, false),
+                                        expr: if_false,
+                                    }];
+                    Expr::Match(Box::new(self.translate_expr(lhs)), alts, span)
+                } else {
+                    let args = vec![self.translate_expr(lhs), self.translate_expr(rhs)];
+                    Expr::Call(Box::new(Expr::Ident(op.clone(), span)), args, span)
+                }
+            }
+            ast::Expr::Projection(ref record, ref field, ref typ) => {
+                let record_type = record.env_type_of(self);
+                let field_access = self.find_field(&record_type, field)
+                    .expect("ICE: Undefined field in field access");
+                let id = TypedIdent {
+                    name: Symbol::from(AsRef::<str>::as_ref(field)),
+                    typ: typ.clone(),
+                };
+                let alt = Alternative {
+                    pattern: Pattern::Record {
+                        fields: vec![(field_access, id.clone())],
+                        size: None,
+                    },
+                    expr: Expr::Ident(id, span),
+                };
+                Expr::Match(Box::new(self.translate_expr(record)), vec![alt], span)
+            }
+            ast::Expr::Match(ref scrutinee, ref alts) => {
+                let typ = scrutinee.env_type_of(self);
+                let is_flat = alts.iter()
+                    .all(|alt| alt.guard.is_none() && is_flat_pattern(&alt.pattern.value));
+                if is_flat {
+                    let alts = alts.iter()
+                        .map(|alt| {
+                            self.enter_scope();
+                            let pattern = self.translate_pattern(&alt.pattern.value, &typ);
+                            let expr = self.translate_expr(&alt.expr);
+                            self.exit_scope();
+                            Alternative {
+                                pattern: pattern,
+                                expr: expr,
+                            }
+                        })
+                        .collect();
+                    Expr::Match(Box::new(self.translate_expr(scrutinee)), alts, span)
+                } else {
+                    let alts = alts.iter()
+                        .map(|alt| {
+                            self.enter_scope();
+                            self.import_pattern_types(&alt.pattern.value, &typ);
+                            let guard = alt.guard.as_ref().map(|guard| self.translate_expr(guard));
+                            let expr = self.translate_expr(&alt.expr);
+                            self.exit_scope();
+                            NestedAlternative {
+                                pattern: &alt.pattern,
+                                guard: guard,
+                                expr: expr,
+                            }
+                        })
+                        .collect();
+                    let scrutinee = self.translate_expr(scrutinee);
+                    self.translate_nested_match(scrutinee, &typ, alts, span)
+                }
+            }
+            ast::Expr::Array(ref array) => {
+                let exprs = array.exprs.iter().map(|expr| self.translate_expr(expr)).collect();
+                Expr::Data(DataKind::Array, exprs, span)
+            }
+            ast::Expr::Record { ref typ, exprs: ref fields, .. } => {
+                let record_type = instantiate::remove_aliases(self, typ.clone());
+                let exprs = fields.iter()
+                    .map(|field| {
+                        match field.1 {
+                            Some(ref expr) => self.translate_expr(expr),
+                            None => {
+                                let typ = record_type.field_iter()
+                                    .find(|f| f.name.name_eq(&field.0))
+                                    .map_or_else(Type::hole, |f| f.typ.clone());
+                                let id = TypedIdent {
+                                    name: field.0.clone(),
+                                    typ: typ,
+                                };
+                                match self.find_constructor(&id.name) {
+                                    Some((tag, 0)) => Expr::Data(DataKind::Tag(tag), vec![], span),
+                                    _ => Expr::Ident(id, span),
+                                }
+                            }
+                        }
+                    })
+                    .collect();
+                let names = fields.iter().map(|field| field.0.clone()).collect();
+                Expr::Data(DataKind::Record(names), exprs, span)
+            }
+            ast::Expr::Tuple { ref elems, .. } => {
+                let exprs = elems.iter().map(|expr| self.translate_expr(expr)).collect();
+                if elems.is_empty() {
+                    Expr::Data(DataKind::Tag(0), exprs, span)
+                } else {
+                    let names = (0..elems.len()).map(|i| self.tuple_field(i)).collect();
+                    Expr::Data(DataKind::Record(names), exprs, span)
+                }
+            }
+            ast::Expr::Block(ref exprs) => {
+                let (last, exprs) = exprs.split_last().expect("Expr in block");
+                let mut result = self.translate_expr(last);
+                for expr in exprs.iter().rev() {
+                    let id = TypedIdent {
+                        name: self.symbols.symbol(""),
+                        typ: expr.env_type_of(self),
+                    };
+                    result = Expr::Let(Named::Expr(id, Box::new(self.translate_expr(expr))),
+                                       Box::new(result),
+                                       span);
+                }
+                result
+            }
+            ast::Expr::LetBindings(..) |
+            ast::Expr::TypeBindings(..) => self.translate_bindings(expr),
+            ast::Expr::Error => {
+                // Reported by `translate` once the rest of the expression has been translated
+                self.syntax_error = self.syntax_error.or(Some(span));
+                Expr::Data(DataKind::Tag(0), vec![], span)
+            }
+        }
+    }
+
+    /// Translates a chain of `let` and `type` bindings. This is done iteratively to avoid
+    /// recursing once for each binding in large modules.
+    fn translate_bindings(&mut self, mut expr: &SpannedExpr<Symbol>) -> Expr {
+        let mut bindings = Vec::new();
+        let mut scopes = 0;
+        loop {
+            match expr.value {
+                ast::Expr::LetBindings(ref binds, ref body) => {
+                    let is_recursive = binds.iter().all(|bind| bind.args.len() > 0);
+                    if is_recursive {
+                        let closures = binds.iter()
+                            .map(|bind| {
+                                let id = match bind.name.value {
+                                    ast::Pattern::Ident(ref id) => id,
+                                    _ => panic!("Lambda binds to non identifer pattern"),
+                                };
+                                self.translate_closure(id, &bind.args, &bind.expr)
+                            })
+                            .collect();
+                        bindings.push(Binding::Let(Named::Recursive(closures), expr.span));
+                    } else {
+                        for bind in binds {
+                            match bind.name.value {
+                                ast::Pattern::Ident(ref id) => {
+                                    let value = if bind.args.is_empty() {
+                                        self.translate_expr(&bind.expr)
+                                    } else {
+                                        Expr::Lambda(self.translate_closure(id,
+                                                                            &bind.args,
+                                                                            &bind.expr),
+                                                     bind.expr.span)
+                                    };
+                                    bindings.push(Binding::Let(Named::Expr(id.clone(),
+                                                                           Box::new(value)),
+                                                               expr.span));
+                                }
+                                ast::Pattern::Record { .. } => {
+                                    let value = self.translate_expr(&bind.expr);
+                                    let typ = bind.expr.env_type_of(self);
+                                    // Any types imported by the pattern are in scope until the
+                                    // end of the `let` expression
+                                    self.enter_scope();
+                                    scopes += 1;
+                                    let pattern = self.translate_pattern(&bind.name.value, &typ);
+                                    bindings.push(Binding::Pattern(value, pattern, expr.span));
+                                }
+                                _ => {
+                                    let value = self.translate_expr(&bind.expr);
+                                    let typ = bind.expr.env_type_of(self);
+                                    self.enter_scope();
+                                    scopes += 1;
+                                    self.import_pattern_types(&bind.name.value, &typ);
+                                    bindings.push(Binding::Nested(value,
+                                                                  &bind.name,
+                                                                  typ,
+                                                                  expr.span));
+                                }
+                            }
+                        }
+                    }
+                    expr = body;
+                }
+                ast::Expr::TypeBindings(ref binds, ref body) => {
+                    self.enter_scope();
+                    scopes += 1;
+                    for bind in binds {
+                        self.stack_types.insert(bind.alias.name.clone(), bind.alias.clone());
+                        let typ = bind.alias.typ.as_ref().expect("TypeBinding type").clone();
+                        self.stack_constructors.insert(bind.name.clone(), typ);
+                    }
+                    expr = body;
+                }
+                _ => break,
+            }
+        }
+        let mut result = self.translate_expr(expr);
+        for _ in 0..scopes {
+            self.exit_scope();
+        }
+        while let Some(binding) = bindings.pop() {
+            result = match binding {
+                Binding::Let(named, span) => Expr::Let(named, Box::new(result), span),
+                Binding::Pattern(value, pattern, span) => {
+                    let alt = Alternative {
+                        pattern: pattern,
+                        expr: result,
+                    };
+                    Expr::Match(Box::new(value), vec![alt], span)
+                }
+                Binding::Nested(value, pattern, typ, span) => {
+                    let alt = NestedAlternative {
+                        pattern: pattern,
+                        guard: None,
+                        expr: result,
+                    };
+                    self.translate_nested_match(value, &typ, vec![alt], span)
+                }
+            };
+        }
+        result
+    }
+
+    fn translate_closure(&mut self,
+                         id: &TypedIdent,
+                         args: &[TypedIdent],
+                         body: &SpannedExpr<Symbol>)
+                         -> Closure {
+        self.enter_scope();
+        let body = self.translate_expr(body);
+        self.exit_scope();
+        Closure {
+            id: id.clone(),
+            args: args.to_owned(),
+            body: Box::new(body),
+        }
+    }
+
+    fn translate_pattern(&mut self, pattern: &ast::Pattern<Symbol>, typ: &ArcType) -> Pattern {
+        match *pattern {
+            ast::Pattern::Constructor(ref id, ref args) => {
+                let tag = self.expect_tag(typ, &id.name);
+                let args = args.iter()
+                    .map(|arg| {
+                        match arg.value {
+                            ast::Pattern::Ident(ref id) => id.clone(),
+                            ast::Pattern::Wildcard(ref typ) => self.fresh_ident("_", typ.clone()),
+                            _ => panic!("ICE: Nested pattern in flat pattern: {:?}", arg),
+                        }
+                    })
+                    .collect();
+                Pattern::Constructor(id.clone(), tag, args)
+            }
+            ast::Pattern::Record { ref fields, .. } => {
+                self.import_pattern_types(pattern, typ);
+                let typ = instantiate::remove_aliases(self, typ.clone());
+                match *typ {
+                    Type::Record(_) => {
+                        let mut field_iter = typ.field_iter();
+                        let number_of_fields = field_iter.by_ref().count();
+                        let is_polymorphic = **field_iter.current_type() != Type::EmptyRow;
+                        let fields = fields.iter()
+                            .map(|&(ref name, ref bind)| {
+                                let field = self.find_field(&typ, name)
+                                    .expect("ICE: Undefined field in record pattern");
+                                let field_type = typ.field_iter()
+                                    .find(|f| f.name.name_eq(name))
+                                    .map_or_else(Type::hole, |f| f.typ.clone());
+                                let id = TypedIdent {
+                                    name: bind.as_ref().unwrap_or(name).clone(),
+                                    typ: field_type,
+                                };
+                                (field, id)
+                            })
+                            .collect();
+                        Pattern::Record {
+                            fields: fields,
+                            size: if is_polymorphic {
+                                None
+                            } else {
+                                Some(number_of_fields as VmIndex)
+                            },
+                        }
+                    }
+                    _ => {
+                        panic!("Expected record, got {} at {:?}",
+                               types::display_type(&*self.symbols, &typ),
+                               pattern)
+                    }
+                }
+            }
+            ast::Pattern::Ident(ref id) => Pattern::Ident(id.clone()),
+            ast::Pattern::Wildcard(ref typ) => Pattern::Ident(self.fresh_ident("_", typ.clone())),
+            ast::Pattern::Tuple { .. } |
+            ast::Pattern::Literal(_) => {
+                panic!("ICE: Nested pattern in flat pattern: {:?}", pattern)
+            }
+        }
+    }
+
+    /// Brings the types which record patterns in `pattern` import into scope
+    fn import_pattern_types(&mut self, pattern: &ast::Pattern<Symbol>, typ: &ArcType) {
+        match *pattern {
+            ast::Pattern::Record { ref types, .. } => {
+                let typ = instantiate::remove_aliases(self, typ.clone());
+                // Insert all variant constructor into scope
+                with_pattern_types(types, &typ, |name, alias| {
+                    // FIXME: Workaround so that both the types name in this module and its global
+                    // name are imported. Without this aliases may not be traversed properly
+                    self.stack_types.insert(alias.name.clone(), alias.clone());
+                    self.stack_types.insert(name.clone(), alias.clone());
+                    if let Some(ref typ) = alias.typ {
+                        self.stack_constructors.insert(alias.name.clone(), typ.clone());
+                        self.stack_constructors.insert(name.clone(), typ.clone());
+                    }
+                });
+            }
+            ast::Pattern::Constructor(_, ref args) |
+            ast::Pattern::Tuple { elems: ref args, .. } => {
+                for arg in args {
+                    let arg_type = arg.env_type_of(self);
+                    self.import_pattern_types(&arg.value, &arg_type);
+                }
+            }
+            ast::Pattern::Ident(_) |
+            ast::Pattern::Literal(_) |
+            ast::Pattern::Wildcard(_) => (),
+        }
+    }
+
+    /// Translates a match on patterns which may be nested or have guards. Each alternative is
+    /// translated into a sequence of flat matches, one for each level of its pattern, which
+    /// continue with the next alternative if any of them fail.
+    fn translate_nested_match(&mut self,
+                              scrutinee: Expr,
+                              typ: &ArcType,
+                              alts: Vec<NestedAlternative>,
+                              span: Span<BytePos>)
+                              -> Expr {
+        self.enter_scope();
+        let (var, scrutinee) = match scrutinee {
+            Expr::Ident(ref id, _) => {
+                let var = TypedIdent {
+                    name: id.name.clone(),
+                    typ: typ.clone(),
+                };
+                (var, None)
+            }
+            scrutinee => (self.fresh_ident("match_value", typ.clone()), Some(scrutinee)),
+        };
+        let mut result = self.non_exhaustive_error(span);
+        for alt in alts.into_iter().rev() {
+            let can_fail = alt.guard.is_some() || can_fail(&alt.pattern.value);
+            // The expression used on failure is used once for each part of the pattern which
+            // can fail so it is put into a function unless it is just a call
+            let (fail, fail_binding) = if !can_fail || is_call(&result) {
+                (result, None)
+            } else {
+                let id = self.fresh_ident("match_fail",
+                                          Type::function(vec![Type::unit()], Type::hole()));
+                let closure = Closure {
+                    id: id.clone(),
+                    args: vec![self.fresh_ident("_", Type::unit())],
+                    body: Box::new(result),
+                };
+                let call = Expr::Call(Box::new(Expr::Ident(id.clone(), span)),
+                                      vec![Expr::Data(DataKind::Tag(0), vec![], span)],
+                                      span);
+                let named = Named::Expr(id, Box::new(Expr::Lambda(closure, span)));
+                (call, Some(named))
+            };
+            let success = match alt.guard {
+                Some(guard) => self.bool_match(guard, alt.expr, fail.clone(), span),
+                None => alt.expr,
+            };
+            let expr = self.translate_nested_pattern(vec![(alt.pattern, var.clone())],
+                                                     success,
+                                                     &fail,
+                                                     span);
+            result = match fail_binding {
+                Some(named) => Expr::Let(named, Box::new(expr), span),
+                None => expr,
+            };
+        }
+        self.exit_scope();
+        match scrutinee {
+            Some(scrutinee) => {
+                Expr::Let(Named::Expr(var, Box::new(scrutinee)), Box::new(result), span)
+            }
+            None => result,
+        }
+    }
+
+    /// Matches each pattern in `pending` against the variable paired with it, evaluating
+    /// `success` if all of them match and `fail` otherwise. `pending` is used as a stack so the
+    /// last pattern is matched first.
+    fn translate_nested_pattern(&mut self,
+                                mut pending: Vec<(&ast::SpannedPattern<Symbol>, TypedIdent)>,
+                                success: Expr,
+                                fail: &Expr,
+                                span: Span<BytePos>)
+                                -> Expr {
+        let (pattern, var) = match pending.pop() {
+            Some(next) => next,
+            None => return success,
+        };
+        let value = Expr::Ident(var.clone(), span);
+        match pattern.value {
+            ast::Pattern::Ident(ref id) => {
+                let rest = self.translate_nested_pattern(pending, success, fail, span);
+                Expr::Let(Named::Expr(id.clone(), Box::new(value)), Box::new(rest), span)
+            }
+            ast::Pattern::Wildcard(_) => {
+                self.translate_nested_pattern(pending, success, fail, span)
+            }
+            ast::Pattern::Constructor(ref id, ref args) => {
+                let tag = self.expect_tag(&var.typ, &id.name);
+                let arg_vars = args.iter()
+                    .map(|arg| {
+                        let typ = arg.env_type_of(self);
+                        self.fresh_ident("arg", typ)
+                    })
+                    .collect::<Vec<_>>();
+                pending.extend(args.iter().zip(arg_vars.iter().cloned()).rev());
+                let rest = self.translate_nested_pattern(pending, success, fail, span);
+                let mut alts = vec![Alternative {
+                                        pattern: Pattern::Constructor(id.clone(), tag, arg_vars),
+                                        expr: rest,
+                                    }];
+                if tag.constructors > 1 {
+                    alts.push(Alternative {
+                        pattern: Pattern::Ident(self.fresh_ident("_", var.typ.clone())),
+                        expr: fail.clone(),
+                    });
+                }
+                Expr::Match(Box::new(value), alts, span)
+            }
+            ast::Pattern::Tuple { ref elems, .. } => {
+                if elems.is_empty() {
+                    return self.translate_nested_pattern(pending, success, fail, span);
+                }
+                let fields = elems.iter()
+                    .enumerate()
+                    .map(|(i, elem)| {
+                        let typ = elem.env_type_of(self);
+                        (Field::Offset(i as VmIndex), self.fresh_ident("elem", typ))
+                    })
+                    .collect::<Vec<_>>();
+                pending.extend(elems.iter().zip(fields.iter().map(|field| field.1.clone())).rev());
+                let rest = self.translate_nested_pattern(pending, success, fail, span);
+                let alt = Alternative {
+                    pattern: Pattern::Record {
+                        fields: fields,
+                        size: Some(elems.len() as VmIndex),
+                    },
+                    expr: rest,
+                };
+                Expr::Match(Box::new(value), vec![alt], span)
+            }
+            ast::Pattern::Record { .. } => {
+                let record_pattern = self.translate_pattern(&pattern.value, &var.typ);
+                let rest = self.translate_nested_pattern(pending, success, fail, span);
+                let alt = Alternative {
+                    pattern: record_pattern,
+                    expr: rest,
+                };
+                Expr::Match(Box::new(value), vec![alt], span)
+            }
+            ast::Pattern::Literal(ref literal) => {
+                let test = self.literal_eq(value, literal, span);
+                let rest = self.translate_nested_pattern(pending, success, fail, span);
+                self.bool_match(test, rest, fail.clone(), span)
+            }
+        }
+    }
+
+    /// Returns an expression which tests if `value` is equal to `literal`
+    fn literal_eq(&mut self, value: Expr, literal: &Literal, span: Span<BytePos>) -> Expr {
+        let literal_expr = Expr::Const(literal.clone(), span);
+        let op = match *literal {
+            Literal::Byte(_) => "#Byte==",
+            Literal::Int(_) => "#Int==",
+            Literal::Float(_) => "#Float==",
+            Literal::Char(_) => "#Char==",
+            // Strings have no primitive operator so a global function is used instead. It is
+            // prefixed with `#` like the operators so that it can not be shadowed
+            Literal::String(_) => "#string_eq",
+        };
+        let op = TypedIdent::new(self.symbols.symbol(op));
+        Expr::Call(Box::new(Expr::Ident(op, span)), vec![value, literal_expr], span)
+    }
+
+    /// Returns an expression which evaluates `if_true` or `if_false` depending on `test`
+    fn bool_match(&mut self,
+                  test: Expr,
+                  if_true: Expr,
+                  if_false: Expr,
+                  span: Span<BytePos>)
+                  -> Expr {
+        let alts = vec![Alternative {
+                            pattern: self.bool_pattern(&Type::hole(), true),
+                            expr: if_true,
+                        },
+                        Alternative {
+                            pattern: self.bool_pattern(&Type::hole(), false),
+                            expr: if_false,
+                        }];
+        Expr::Match(Box::new(test), alts, span)
+    }
+
+    /// Returns an expression which raises an error because no alternative matched
+    fn non_exhaustive_error(&mut self, span: Span<BytePos>) -> Expr {
+        let error = TypedIdent::new(self.symbols.symbol("#error"));
+        let message = Expr::Const(Literal::String("Non-exhaustive pattern".into()), span);
+        Expr::Call(Box::new(Expr::Ident(error, span)), vec![message], span)
+    }
+
+    /// Creates an identifier which is distinct from every other identifier
+    fn fresh_ident(&self, name: &str, typ: ArcType) -> TypedIdent {
+        TypedIdent {
+            name: Symbol::from(name),
+            typ: typ,
+        }
+    }
+
+    /// Returns the name of the field which holds the element at `index` of a tuple
+    fn tuple_field(&mut self, index: usize) -> Symbol {
+        self.symbols.symbol(format!("_{}", index))
+    }
+
+    /// Returns the pattern matching `True` or `False` of the boolean type `typ`
+    fn bool_pattern(&mut self, typ: &ArcType, value: bool) -> Pattern {
+        let tag = if value { 1 } else { 0 };
+        let typ = instantiate::remove_aliases(self, typ.clone());
+        let id = match *typ {
+            Type::Variants(ref variants) => {
+                variants.get(tag as usize)
+                    .map(|variant| {
+                        TypedIdent {
+                            name: variant.0.clone(),
+                            typ: variant.1.clone(),
+                        }
+                    })
+            }
+            _ => None,
+        };
+        let id = id.unwrap_or_else(|| {
+            TypedIdent::new(self.symbols.symbol(if value { "True" } else { "False" }))
+        });
+        let tag = Tag {
+            tag: tag,
+            constructors: 2,
+        };
+        Pattern::Constructor(id, tag, vec![])
+    }
+
+    fn enter_scope(&mut self) {
+        self.stack_types.enter_scope();
+        self.stack_constructors.enter_scope();
+    }
+
+    fn exit_scope(&mut self) {
+        self.stack_types.exit_scope();
+        self.stack_constructors.exit_scope();
+    }
+
+    /// Returns the tag and number of arguments of `id` if it is a constructor of a type defined
+    /// in the expression being translated
+    fn find_constructor(&self, id: &Symbol) -> Option<(VmTag, VmIndex)> {
+        self.stack_constructors
+            .iter()
+            .filter_map(|(_, typ)| {
+                match **typ {
+                    Type::Variants(ref variants) => {
+                        variants.iter()
+                            .enumerate()
+                            .find(|&(_, v)| v.0 == *id)
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+            .map(|(tag, &(_, ref typ))| {
+                (tag as VmTag, types::arg_iter(typ).count() as VmIndex)
+            })
+    }
+
+    fn find_field(&self, typ: &ArcType, field: &Symbol) -> Option<Field> {
+        // Remove all type aliases to get the actual record type
+        let typ = instantiate::remove_aliases_cow(self, typ);
+        let mut iter = typ.field_iter();
+        match iter.by_ref().position(|f| f.name.name_eq(field)) {
+            Some(index) => {
+                for _ in iter.by_ref() {}
+                Some(if **iter.current_type() == Type::EmptyRow {
+                    // Non-polymorphic record, access by index
+                    Field::Offset(index as VmIndex)
+                } else {
+                    Field::Name(field.clone())
+                })
+            }
+            None => None,
+        }
+    }
+
+    fn expect_tag(&self, typ: &ArcType, constructor: &Symbol) -> Tag {
+        self.find_tag(typ, constructor)
+            .unwrap_or_else(|| {
+                panic!("Could not find tag for {}::{}",
+                       types::display_type(&*self.symbols, typ),
+                       self.symbols.string(constructor))
+            })
+    }
+
+    fn find_tag(&self, typ: &ArcType, constructor: &Symbol) -> Option<Tag> {
+        match **instantiate::remove_aliases_cow(self, typ) {
+            Type::Variants(ref variants) => {
+                variants.iter()
+                    .enumerate()
+                    .find(|&(_, v)| v.0 == *constructor)
+                    .map(|(tag, _)| {
+                        Tag {
+                            tag: tag as VmTag,
+                            constructors: variants.len() as VmIndex,
+                        }
+                    })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn with_pattern_types<F>(types: &[(Symbol, Option<Symbol>)], typ: &ArcType, mut f: F)
+    where F: FnMut(&Symbol, &Alias<Symbol, ArcType>),
+{
+    for field in types {
+        let associated_type = typ.type_field_iter()
+            .find(|type_field| type_field.name.name_eq(&field.0))
+            .expect("Associated type to exist in record");
+        f(&field.0, &associated_type.typ);
+    }
+}
+
+/// Returns true if `pattern` only binds variables at the top level and in the arguments of a
+/// constructor, in which case it does not need to be translated into multiple matches
+fn is_flat_pattern(pattern: &ast::Pattern<Symbol>) -> bool {
+    let is_variable = |pattern: &ast::SpannedPattern<Symbol>| {
+        match pattern.value {
+            ast::Pattern::Ident(_) | ast::Pattern::Wildcard(_) => true,
+            _ => false,
+        }
+    };
+    match *pattern {
+        ast::Pattern::Constructor(_, ref args) => args.iter().all(is_variable),
+        ast::Pattern::Ident(_) |
+        ast::Pattern::Record { .. } |
+        ast::Pattern::Wildcard(_) => true,
+        ast::Pattern::Tuple { .. } |
+        ast::Pattern::Literal(_) => false,
+    }
+}
+
+/// Returns true if there may be values which `pattern` does not match
+fn can_fail(pattern: &ast::Pattern<Symbol>) -> bool {
+    match *pattern {
+        ast::Pattern::Constructor(..) |
+        ast::Pattern::Literal(_) => true,
+        ast::Pattern::Tuple { ref elems, .. } => elems.iter().any(|elem| can_fail(&elem.value)),
+        ast::Pattern::Ident(_) |
+        ast::Pattern::Record { .. } |
+        ast::Pattern::Wildcard(_) => false,
+    }
+}
+
+fn is_call(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Call(ref f, ref args, _) => {
+            let is_simple = |expr: &Expr| {
+                match *expr {
+                    Expr::Ident(..) | Expr::Const(..) => true,
+                    Expr::Data(_, ref args, _) => args.is_empty(),
+                    _ => false,
+                }
+            };
+            is_simple(f) && args.iter().all(is_simple)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::pos::spanned2;
+    use base::symbol::Symbols;
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let mut symbols = Symbols::new();
+        let mut symbols = SymbolModule::new("test".into(), &mut symbols);
+        let mut translator = Translator::new(&mut symbols);
+
+        let int = spanned2(BytePos::from(0), BytePos::from(1), ast::Expr::Literal(Literal::Int(1)));
+        let error = spanned2(BytePos::from(3), BytePos::from(4), ast::Expr::Error);
+        let expr = spanned2(BytePos::from(0), BytePos::from(4), ast::Expr::Block(vec![int, error]));
+        assert!(translator.translate(&expr).is_err());
+    }
+}
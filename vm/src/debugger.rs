@@ -0,0 +1,418 @@
+//! Debugger support for gluon threads.
+//!
+//! Functions compiled from source record which line each instruction belongs to and which
+//! variables are stored on the stack. When the `line` flag of `Context::set_hook_flags` is set the
+//! debug hook is called before each new line is executed and can inspect the running thread through
+//! `DebugInfo`. `Debugger` builds on this to support breakpoints and stepping.
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use base::pos::{Line, Location};
+use base::symbol::Symbol;
+use base::types::ArcType;
+
+use compiler::Local;
+use stack::{Frame, Stack, State};
+use thread::{Context, Thread};
+use value::Value;
+use Result;
+
+/// Selects which events cause the hook set through `Context::set_debug_hook` to be called
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HookFlags {
+    /// Call the hook each time a function is entered or returned to
+    pub call: bool,
+    /// Call the hook before starting to execute a new line
+    pub line: bool,
+}
+
+impl Default for HookFlags {
+    fn default() -> HookFlags {
+        HookFlags {
+            call: true,
+            line: false,
+        }
+    }
+}
+
+/// The event which caused a hook to be called
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookEvent {
+    Call,
+    Line,
+}
+
+/// Information about the thread which a hook were called for
+pub struct DebugInfo<'a> {
+    stack: &'a Stack,
+    event: HookEvent,
+}
+
+impl<'a> DebugInfo<'a> {
+    pub fn new(stack: &'a Stack, event: HookEvent) -> DebugInfo<'a> {
+        DebugInfo {
+            stack: stack,
+            event: event,
+        }
+    }
+
+    pub fn event(&self) -> HookEvent {
+        self.event
+    }
+
+    /// Returns the number of functions which are currently executing
+    pub fn stack_depth(&self) -> usize {
+        self.function_frames().count()
+    }
+
+    /// Returns information about the function `level` steps up the call stack where `0` is the
+    /// function which is currently executing
+    pub fn stack_info(&self, level: usize) -> Option<StackInfo<'a>> {
+        self.function_frames()
+            .nth(level)
+            .map(|frame| {
+                StackInfo {
+                    stack: self.stack,
+                    frame: frame,
+                    current: level == 0,
+                }
+            })
+    }
+
+    fn function_frames(&self) -> FunctionFrames<'a> {
+        FunctionFrames(self.stack.get_frames().iter().rev())
+    }
+}
+
+struct FunctionFrames<'a>(::std::iter::Rev<::std::slice::Iter<'a, Frame>>);
+
+impl<'a> Iterator for FunctionFrames<'a> {
+    type Item = &'a Frame;
+
+    fn next(&mut self) -> Option<&'a Frame> {
+        self.0
+            .by_ref()
+            .find(|frame| match frame.state {
+                State::Closure(_) | State::Extern(_) => true,
+                _ => false,
+            })
+    }
+}
+
+/// A variable which is visible in a stack frame
+pub struct VariableInfo<'a> {
+    pub name: &'a Symbol,
+    pub typ: &'a ArcType,
+    pub value: Value,
+}
+
+impl<'a> fmt::Debug for VariableInfo<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{} : {} = {:?}",
+               self.name.declared_name(),
+               self.typ,
+               self.value)
+    }
+}
+
+/// Information about a single function on the call stack
+pub struct StackInfo<'a> {
+    stack: &'a Stack,
+    frame: &'a Frame,
+    current: bool,
+}
+
+impl<'a> StackInfo<'a> {
+    /// Returns the name of the function
+    pub fn function_name(&self) -> &'a Symbol {
+        match self.frame.state {
+            State::Closure(ref closure) => &closure.function.name,
+            State::Extern(ref function) => &function.id,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the index of the instruction which is currently executing in this function
+    pub fn instruction_index(&self) -> usize {
+        if self.current {
+            self.frame.instruction_index
+        } else {
+            // Functions further up the stack point to the instruction after the call they
+            // are executing
+            self.frame.instruction_index.saturating_sub(1)
+        }
+    }
+
+    /// Returns the name of the source file the function were compiled from
+    pub fn source_name(&self) -> Option<&'a str> {
+        match self.frame.state {
+            State::Closure(ref closure) => {
+                closure.function.source.as_ref().map(|source| &source.name[..])
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the line which is currently executing
+    pub fn line(&self) -> Option<Line> {
+        self.location().map(|location| location.line)
+    }
+
+    /// Returns the line and column of the expression which is currently executing
+    pub fn location(&self) -> Option<Location> {
+        match self.frame.state {
+            State::Closure(ref closure) => {
+                let function = &closure.function;
+                function.source
+                    .as_ref()
+                    .and_then(|source| {
+                        function.source_map
+                            .span(self.instruction_index())
+                            .map(|span| source.location(span.start))
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the variables which are in scope, with the innermost variables last
+    pub fn locals(&self) -> Vec<VariableInfo<'a>> {
+        match self.frame.state {
+            State::Closure(ref closure) => {
+                let values = self.stack.get_values();
+                let offset = self.frame.offset;
+                closure.function
+                    .locals
+                    .locals(self.instruction_index())
+                    .into_iter()
+                    .filter_map(|local: &'a Local| {
+                        values.get((offset + local.index) as usize).map(|&value| {
+                            VariableInfo {
+                                name: &local.name,
+                                typ: &local.typ,
+                                value: value,
+                            }
+                        })
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the variables which the function has captured
+    pub fn upvars(&self) -> Vec<VariableInfo<'a>> {
+        match self.frame.state {
+            State::Closure(ref closure) => {
+                closure.function
+                    .upvars
+                    .iter()
+                    .zip(closure.upvars.iter())
+                    .map(|(info, &value)| {
+                        VariableInfo {
+                            name: &info.name,
+                            typ: &info.typ,
+                            value: value,
+                        }
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Looks up the variable called `name`, searching the locals before the upvariables
+    pub fn get(&self, name: &str) -> Option<VariableInfo<'a>> {
+        let mut locals = self.locals();
+        match locals.iter().rposition(|var| var.name.declared_name() == name) {
+            Some(i) => Some(locals.swap_remove(i)),
+            None => self.upvars().into_iter().find(|var| var.name.declared_name() == name),
+        }
+    }
+
+    /// Returns all variables which are visible in the function. Variables which are shadowed by
+    /// another variable with the same name are left out.
+    pub fn visible_variables(&self) -> Vec<VariableInfo<'a>> {
+        let mut variables: Vec<VariableInfo<'a>> = Vec::new();
+        for var in self.locals().into_iter().rev().chain(self.upvars()) {
+            let name = var.name.declared_name();
+            if !variables.iter().any(|existing| existing.name.declared_name() == name) {
+                variables.push(var);
+            }
+        }
+        variables
+    }
+}
+
+/// A breakpoint at a line in a source file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breakpoint {
+    pub source_name: String,
+    pub line: Line,
+}
+
+/// What the debugger should do after the thread has been paused
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// Run until the next breakpoint
+    Continue,
+    /// Pause at the next line, entering any function which is called
+    StepIn,
+    /// Pause at the next line of the current function or the function which called it
+    StepOver,
+    /// Pause once the current function has returned
+    StepOut,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Continue,
+    StepIn,
+    StepOver(usize),
+    StepOut(usize),
+}
+
+/// Function which is called each time the debugger pauses the thread. Returning an error stops
+/// the execution of the thread with that error, `Error::DebuggerQuit` should be returned when the
+/// user asks to stop the program.
+pub type PauseFn = Box<FnMut(&Thread, &DebugInfo, &mut Vec<Breakpoint>) -> Result<Action> +
+                       Send + Sync>;
+
+/// A debugger which pauses the execution of a thread at breakpoints or after stepping.
+///
+/// The thread is paused by calling the `PauseFn` which the debugger were created with. The
+/// function can inspect the thread, evaluate expressions in a different thread and modify the
+/// breakpoints before returning what the debugger should do next.
+pub struct Debugger {
+    breakpoints: Arc<Mutex<Vec<Breakpoint>>>,
+    pause_requested: Arc<AtomicBool>,
+    mode: Mode,
+    on_pause: PauseFn,
+}
+
+impl Debugger {
+    pub fn new(on_pause: PauseFn) -> Debugger {
+        Debugger {
+            breakpoints: Arc::new(Mutex::new(Vec::new())),
+            pause_requested: Arc::new(AtomicBool::new(false)),
+            mode: Mode::Continue,
+            on_pause: on_pause,
+        }
+    }
+
+    /// Adds a breakpoint at `line` in the source file `source_name`
+    pub fn add_breakpoint(&mut self, source_name: &str, line: Line) {
+        self.breakpoints.lock().unwrap().push(Breakpoint {
+            source_name: source_name.into(),
+            line: line,
+        });
+    }
+
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.lock().unwrap().clone()
+    }
+
+    /// Returns a handle which can be used to control the debugger from another OS thread after it
+    /// has been attached
+    pub fn handle(&self) -> DebuggerHandle {
+        DebuggerHandle {
+            breakpoints: self.breakpoints.clone(),
+            pause_requested: self.pause_requested.clone(),
+        }
+    }
+
+    /// Makes the debugger pause at the first line which is executed
+    pub fn pause_on_start(&mut self) {
+        self.mode = Mode::StepIn;
+    }
+
+    /// Installs the debugger as the debug hook of `context`, replacing any previous debug hook
+    pub fn attach(mut self, context: &mut Context) {
+        context.set_hook_flags(HookFlags {
+            call: false,
+            line: true,
+        });
+        context.set_debug_hook(Some(Box::new(move |thread: &Thread, info: DebugInfo| {
+            self.on_line(thread, &info)
+        })));
+    }
+
+    fn on_line(&mut self, thread: &Thread, info: &DebugInfo) -> Result<()> {
+        let depth = info.stack_depth();
+        let should_pause = match self.mode {
+            Mode::Continue => false,
+            Mode::StepIn => true,
+            Mode::StepOver(level) => depth <= level,
+            Mode::StepOut(level) => depth < level,
+        };
+        let should_pause = self.pause_requested.swap(false, Ordering::SeqCst) || should_pause;
+        if should_pause || self.is_at_breakpoint(info) {
+            // The lock is not held while paused so that the breakpoints can be changed through a
+            // `DebuggerHandle` in the meantime
+            let original = self.breakpoints();
+            let mut breakpoints = original.clone();
+            let action = try!((self.on_pause)(thread, info, &mut breakpoints));
+            if breakpoints != original {
+                *self.breakpoints.lock().unwrap() = breakpoints;
+            }
+            self.mode = match action {
+                Action::Continue => Mode::Continue,
+                Action::StepIn => Mode::StepIn,
+                Action::StepOver => Mode::StepOver(depth),
+                Action::StepOut => Mode::StepOut(depth),
+            };
+        }
+        Ok(())
+    }
+
+    fn is_at_breakpoint(&self, info: &DebugInfo) -> bool {
+        let frame = match info.stack_info(0) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        match (frame.source_name(), frame.line()) {
+            (Some(source_name), Some(line)) => {
+                self.breakpoints
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|breakpoint| {
+                        breakpoint.source_name == source_name && breakpoint.line == line
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Handle to a `Debugger` which can be sent to other OS threads
+#[derive(Clone)]
+pub struct DebuggerHandle {
+    breakpoints: Arc<Mutex<Vec<Breakpoint>>>,
+    pause_requested: Arc<AtomicBool>,
+}
+
+impl DebuggerHandle {
+    /// Replaces the breakpoints in the source file `source_name` with breakpoints at `lines`
+    pub fn set_breakpoints(&self, source_name: &str, lines: &[Line]) {
+        let mut breakpoints = self.breakpoints.lock().unwrap();
+        breakpoints.retain(|breakpoint| breakpoint.source_name != source_name);
+        breakpoints.extend(lines.iter().map(|&line| {
+            Breakpoint {
+                source_name: source_name.into(),
+                line: line,
+            }
+        }));
+    }
+
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.lock().unwrap().clone()
+    }
+
+    /// Makes the debugger pause before the next line is executed
+    pub fn pause(&self) {
+        self.pause_requested.store(true, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,282 @@
+//! Module which produces human readable listings of compiled bytecode
+use std::fmt;
+
+use base::pos::{BytePos, Span};
+use base::source::Source;
+use base::symbol::Symbol;
+
+use compiler::{CompiledFunction, SourceMap};
+use types::*;
+use value::BytecodeFunction;
+
+/// Trait which abstracts over the different representations of a compiled function so that they
+/// can be disassembled
+pub trait DisassembleFunction {
+    fn name(&self) -> &Symbol;
+    fn args(&self) -> VmIndex;
+    fn instructions(&self) -> &[Instruction];
+    /// Returns the string constant at `index`
+    fn string(&self, index: VmIndex) -> Option<&str>;
+    /// Returns the name of the global at `index`
+    fn global(&self, index: VmIndex) -> Option<&str>;
+    /// Returns the field names of the record at `index`
+    fn record(&self, index: VmIndex) -> Option<Vec<&str>>;
+    fn inner_functions(&self) -> Vec<&Self>;
+    fn source_map(&self) -> &SourceMap;
+}
+
+impl DisassembleFunction for CompiledFunction {
+    fn name(&self) -> &Symbol {
+        &self.id
+    }
+    fn args(&self) -> VmIndex {
+        self.args
+    }
+    fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+    fn string(&self, index: VmIndex) -> Option<&str> {
+        self.strings.get(index as usize).map(|s| &s[..])
+    }
+    fn global(&self, index: VmIndex) -> Option<&str> {
+        self.module_globals.get(index as usize).map(|s| s.as_ref())
+    }
+    fn record(&self, index: VmIndex) -> Option<Vec<&str>> {
+        self.records
+            .get(index as usize)
+            .map(|fields| fields.iter().map(|field| field.as_ref()).collect())
+    }
+    fn inner_functions(&self) -> Vec<&CompiledFunction> {
+        self.inner_functions.iter().collect()
+    }
+    fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+}
+
+impl DisassembleFunction for BytecodeFunction {
+    fn name(&self) -> &Symbol {
+        &self.name
+    }
+    fn args(&self) -> VmIndex {
+        self.args
+    }
+    fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+    fn string(&self, index: VmIndex) -> Option<&str> {
+        self.strings.get(index as usize).map(|s| &s[..])
+    }
+    fn global(&self, index: VmIndex) -> Option<&str> {
+        self.global_names.get(index as usize).map(|s| s.as_ref())
+    }
+    fn record(&self, index: VmIndex) -> Option<Vec<&str>> {
+        self.records
+            .get(index as usize)
+            .map(|fields| fields.iter().map(|field| &field[..]).collect())
+    }
+    fn inner_functions(&self) -> Vec<&BytecodeFunction> {
+        self.inner_functions.iter().map(|f| &**f).collect()
+    }
+    fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Depth {
+    Unreached,
+    /// The depth could not be determined statically (after a `Split` instruction)
+    Unknown,
+    Known(i32),
+}
+
+/// Calculates the depth of the stack before each instruction is executed by following the
+/// control flow of `instructions`
+fn stack_depths(args: VmIndex, instructions: &[Instruction]) -> Vec<Depth> {
+    let mut depths = vec![Depth::Unreached; instructions.len()];
+    let mut work = vec![(0, Depth::Known(args as i32))];
+    while let Some((index, depth)) = work.pop() {
+        if index >= instructions.len() || depths[index] != Depth::Unreached {
+            continue;
+        }
+        depths[index] = depth;
+        let instruction = instructions[index];
+        let next = match (instruction, depth) {
+            (Split, _) | (_, Depth::Unknown) => Depth::Unknown,
+            (_, Depth::Known(depth)) => Depth::Known(depth + instruction.adjust()),
+            (_, Depth::Unreached) => unreachable!(),
+        };
+        match instruction {
+            Jump(target) => work.push((target as usize, next)),
+            CJump(target) => {
+                work.push((target as usize, next));
+                work.push((index + 1, next));
+            }
+            _ => work.push((index + 1, next)),
+        }
+    }
+    depths
+}
+
+/// Displays a function and all of its inner functions as a listing of instructions, each
+/// annotated with the depth of the stack before it executes and with any constants, globals or
+/// source locations it refers to.
+pub struct Disassembly<'a, F: 'a> {
+    function: &'a F,
+    source: Option<&'a Source<'a>>,
+}
+
+/// Creates a value which displays the disassembly of `function`. If `source` is given, spans are
+/// displayed as line and column numbers instead of byte offsets.
+pub fn disassemble<'a, F>(function: &'a F, source: Option<&'a Source<'a>>) -> Disassembly<'a, F>
+    where F: DisassembleFunction,
+{
+    Disassembly {
+        function: function,
+        source: source,
+    }
+}
+
+impl<'a, F> fmt::Display for Disassembly<'a, F>
+    where F: DisassembleFunction,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_function(f, self.function, 0)
+    }
+}
+
+impl<'a, F> Disassembly<'a, F>
+    where F: DisassembleFunction,
+{
+    fn fmt_function(&self, f: &mut fmt::Formatter, function: &F, indent: usize) -> fmt::Result {
+        try!(writeln!(f,
+                      "{:indent$}function {} (args: {})",
+                      "",
+                      function.name(),
+                      function.args(),
+                      indent = indent));
+        let instructions = function.instructions();
+        let depths = stack_depths(function.args(), instructions);
+        let mut last_span = None;
+        for (i, (instruction, depth)) in instructions.iter().zip(&depths).enumerate() {
+            let depth = match *depth {
+                Depth::Known(depth) => depth.to_string(),
+                Depth::Unknown => "?".to_string(),
+                Depth::Unreached => "-".to_string(),
+            };
+            let mut comments = Vec::new();
+            if let Some(operand) = self.operand(function, instruction) {
+                comments.push(operand);
+            }
+            let span = function.source_map().span(i);
+            if span.is_some() && span != last_span {
+                comments.push(self.span(span.unwrap()));
+            }
+            last_span = span;
+            let instruction = format!("{:?}", instruction);
+            if comments.is_empty() {
+                try!(writeln!(f,
+                              "{:indent$}{:>4} [{:>3}] {}",
+                              "",
+                              i,
+                              depth,
+                              instruction,
+                              indent = indent + 4));
+            } else {
+                try!(writeln!(f,
+                              "{:indent$}{:>4} [{:>3}] {:<40} ; {}",
+                              "",
+                              i,
+                              depth,
+                              instruction,
+                              comments.join(", "),
+                              indent = indent + 4));
+            }
+        }
+        for inner in function.inner_functions() {
+            try!(self.fmt_function(f, inner, indent + 4));
+        }
+        Ok(())
+    }
+
+    fn operand(&self, function: &F, instruction: &Instruction) -> Option<String> {
+        match *instruction {
+            PushString(index) => function.string(index).map(|s| format!("{:?}", s)),
+            GetField(index) => function.string(index).map(|s| format!(".{}", s)),
+            PushGlobal(index) => function.global(index).map(|s| s.to_string()),
+            ConstructRecord { record, .. } => {
+                function.record(record).map(|fields| format!("{{ {} }}", fields.join(", ")))
+            }
+            MakeClosure { function_index, .. } |
+            NewClosure { function_index, .. } => {
+                function.inner_functions()
+                    .get(function_index as usize)
+                    .map(|inner| inner.name().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn span(&self, span: Span<BytePos>) -> String {
+        match self.source.and_then(|source| source.location(span.start)) {
+            Some(location) => format!("{}:{}", location.line, location.column),
+            None => format!("{}..{}", span.start, span.end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::pos::{BytePos, Span};
+    use base::source::Source;
+    use base::symbol::Symbol;
+    use base::types::{ArcType, Type};
+
+    use compiler::CompiledFunction;
+    use types::*;
+    use vm::GlobalVmState;
+
+    #[test]
+    fn disassemble_function() {
+        let vm = GlobalVmState::new();
+        let typ: ArcType = Type::int();
+        let mut function = CompiledFunction::new(1, Symbol::from("test"), typ.clone());
+        function.strings.push(vm.intern("x").unwrap());
+        function.instructions = vec![Push(0),
+                                     CJump(4),
+                                     PushString(0),
+                                     Jump(5),
+                                     PushInt(1),
+                                     MakeClosure {
+                                         function_index: 0,
+                                         upvars: 1,
+                                     }];
+        function.inner_functions.push(CompiledFunction::new(0, Symbol::from("inner"), typ));
+        function.source_map.emit(0,
+                                 Span {
+                                     start: BytePos::from(0),
+                                     end: BytePos::from(1),
+                                 });
+        function.source_map.emit(4,
+                                 Span {
+                                     start: BytePos::from(4),
+                                     end: BytePos::from(5),
+                                 });
+
+        let source = Source::new("a\nb\nc");
+        let listing = disassemble(&function, Some(&source)).to_string();
+        let lines: Vec<_> = listing.lines().map(|line| line.trim_right()).collect();
+        assert_eq!(lines,
+                   vec!["function test (args: 1)",
+                        "       0 [  1] Push(0)                                  ; 1:1",
+                        "       1 [  2] CJump(4)",
+                        "       2 [  1] PushString(0)                            ; \"x\"",
+                        "       3 [  2] Jump(5)",
+                        "       4 [  1] PushInt(1)                               ; 3:1",
+                        "       5 [  2] MakeClosure { function_index: 0, upvars: 1 } ; inner",
+                        "    function inner (args: 0)"]);
+    }
+}
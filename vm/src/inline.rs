@@ -0,0 +1,756 @@
+//! Optimization passes over the core language which inline calls to small functions whose
+//! definitions are known at compile time and remove bindings which are never used.
+use base::ast::TypedIdent;
+use base::fnv::FnvSet;
+use base::pos::{BytePos, Span};
+use base::scoped_map::ScopedMap;
+use base::symbol::Symbol;
+
+use core::{Alternative, Closure, DataKind, Expr, Field, Named, Pattern};
+
+/// Functions whose body are larger than this (as counted by `size`) are never inlined
+const INLINE_THRESHOLD: usize = 20;
+
+/// Inlines calls to known functions and projections from known records in `expr`
+pub fn inline(expr: Expr) -> Expr {
+    let mut inliner = Inliner {
+        scope: ScopedMap::new(),
+        next_binding: 0,
+    };
+    inliner.inline(expr)
+}
+
+/// Removes all bindings in `expr` which are unused and whose values can be dropped without
+/// changing what the expression does
+pub fn eliminate_dead_bindings(expr: Expr) -> Expr {
+    eliminate(expr).0
+}
+
+#[derive(Clone, Debug)]
+enum Known {
+    /// A function which is small enough to be inlined
+    Function(Closure),
+    /// A constant or a variable which can be used directly in place of the binding
+    Expr(Expr),
+    /// A record literal together with what is known about each of its fields
+    Record(Vec<Symbol>, Vec<Option<KnownValue>>),
+}
+
+#[derive(Clone, Debug)]
+struct KnownValue {
+    known: Known,
+    /// The variables which `known` refers to, each paired with the binding it referred to where
+    /// `known` were defined. `known` may only be used where all of these still refer to the same
+    /// bindings.
+    free_vars: Vec<(Symbol, Option<usize>)>,
+}
+
+struct Inliner {
+    /// Maps each variable in scope to a unique id for its binding and to its value if it is
+    /// known
+    scope: ScopedMap<Symbol, (usize, Option<KnownValue>)>,
+    next_binding: usize,
+}
+
+impl Inliner {
+    fn bind(&mut self, name: &Symbol, known: Option<KnownValue>) {
+        let id = self.next_binding;
+        self.next_binding += 1;
+        self.scope.insert(name.clone(), (id, known));
+    }
+
+    fn binding_id(&self, name: &Symbol) -> Option<usize> {
+        self.scope.get(name).map(|binding| binding.0)
+    }
+
+    fn known_value(&self, known: Known, free_vars: FnvSet<Symbol>) -> KnownValue {
+        KnownValue {
+            known: known,
+            free_vars: free_vars.into_iter()
+                .map(|name| {
+                    let id = self.binding_id(&name);
+                    (name, id)
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns what is known about `expr`, if it would be evaluated in the current scope
+    fn known_of(&self, expr: &Expr) -> Option<KnownValue> {
+        match *expr {
+            Expr::Const(..) => {
+                Some(self.known_value(Known::Expr(expr.clone()), FnvSet::default()))
+            }
+            Expr::Ident(ref id, _) => {
+                let mut free_vars = FnvSet::default();
+                free_vars.insert(id.name.clone());
+                Some(self.known_value(Known::Expr(expr.clone()), free_vars))
+            }
+            Expr::Lambda(ref closure, _) => self.known_closure(closure),
+            Expr::Data(DataKind::Record(ref names), ref exprs, _) => {
+                let fields = exprs.iter().map(|expr| self.known_of(expr)).collect();
+                Some(self.known_value(Known::Record(names.clone(), fields), FnvSet::default()))
+            }
+            _ => None,
+        }
+    }
+
+    fn known_closure(&self, closure: &Closure) -> Option<KnownValue> {
+        if size(&closure.body) > INLINE_THRESHOLD {
+            return None;
+        }
+        let free_vars = closure_free_variables(closure);
+        Some(self.known_value(Known::Function(closure.clone()), free_vars))
+    }
+
+    fn is_visible(&self, value: &KnownValue) -> bool {
+        value.free_vars.iter().all(|&(ref name, id)| self.binding_id(name) == id)
+    }
+
+    /// Returns what is known about the value `expr` evaluates to
+    fn resolve(&self, expr: &Expr) -> Option<Known> {
+        let value = match *expr {
+            Expr::Ident(ref id, _) => {
+                match self.scope.get(&id.name) {
+                    Some(&(_, Some(ref value))) => value,
+                    _ => return None,
+                }
+            }
+            Expr::Match(ref scrutinee, ref alts, _) => {
+                return projection(alts).and_then(|field| {
+                    match self.resolve(scrutinee) {
+                        Some(Known::Record(ref names, ref fields)) => {
+                            field_value(names, fields, field)
+                                .and_then(|value| if self.is_visible(value) {
+                                    self.resolve_known(&value.known)
+                                } else {
+                                    None
+                                })
+                        }
+                        _ => None,
+                    }
+                })
+            }
+            _ => return None,
+        };
+        if self.is_visible(value) {
+            self.resolve_known(&value.known)
+        } else {
+            None
+        }
+    }
+
+    fn resolve_known(&self, known: &Known) -> Option<Known> {
+        match *known {
+            // Follow aliases such as `let f = g` to the value of `g`
+            Known::Expr(ref expr @ Expr::Ident(..)) => {
+                Some(self.resolve(expr).unwrap_or_else(|| known.clone()))
+            }
+            _ => Some(known.clone()),
+        }
+    }
+
+    fn inline(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Const(..) => expr,
+            Expr::Ident(id, span) => {
+                match self.resolve_ident(&id.name) {
+                    Some(expr) => with_span(expr, span),
+                    None => Expr::Ident(id, span),
+                }
+            }
+            Expr::Call(f, args, span) => {
+                let args = args.into_iter().map(|arg| self.inline(arg)).collect::<Vec<_>>();
+                match self.resolve(&f) {
+                    Some(Known::Function(ref closure)) if args.len() >= closure.args.len() => {
+                        inline_call(closure, args, span)
+                    }
+                    _ => Expr::Call(Box::new(self.inline(*f)), args, span),
+                }
+            }
+            Expr::Lambda(closure, span) => Expr::Lambda(self.inline_closure(closure), span),
+            Expr::Data(kind, exprs, span) => {
+                Expr::Data(kind,
+                           exprs.into_iter().map(|expr| self.inline(expr)).collect(),
+                           span)
+            }
+            Expr::Let(Named::Expr(id, value), body, span) => {
+                let value = self.inline(*value);
+                let known = self.known_of(&value);
+                self.scope.enter_scope();
+                self.bind(&id.name, known);
+                let body = self.inline(*body);
+                self.scope.exit_scope();
+                Expr::Let(Named::Expr(id, Box::new(value)), Box::new(body), span)
+            }
+            Expr::Let(Named::Recursive(closures), body, span) => {
+                self.scope.enter_scope();
+                for closure in &closures {
+                    self.bind(&closure.id.name, None);
+                }
+                let closures = closures.into_iter()
+                    .map(|closure| self.inline_closure(closure))
+                    .collect::<Vec<_>>();
+                self.scope.exit_scope();
+
+                // A single function which does not call itself can be inlined like any other
+                let known = match closures.first() {
+                    Some(closure) if closures.len() == 1 &&
+                                     !closure_free_variables(closure)
+                        .contains(&closure.id.name) => self.known_closure(closure),
+                    _ => None,
+                };
+                self.scope.enter_scope();
+                for closure in &closures {
+                    self.bind(&closure.id.name, known.clone());
+                }
+                let body = self.inline(*body);
+                self.scope.exit_scope();
+                Expr::Let(Named::Recursive(closures), Box::new(body), span)
+            }
+            Expr::Match(scrutinee, alts, span) => {
+                let known = self.resolve(&scrutinee);
+                if let Some(field) = projection(&alts) {
+                    if let Some(Known::Record(ref names, ref fields)) = known {
+                        let value = field_value(names, fields, field)
+                            .and_then(|value| match value.known {
+                                Known::Expr(ref expr) if self.is_visible(value) => {
+                                    Some(expr.clone())
+                                }
+                                _ => None,
+                            });
+                        if let Some(expr) = value {
+                            return with_span(expr, span);
+                        }
+                    }
+                }
+                let scrutinee = self.inline(*scrutinee);
+                let alts = alts.into_iter()
+                    .map(|alt| {
+                        self.scope.enter_scope();
+                        match (&alt.pattern, &known) {
+                            (&Pattern::Record { ref fields, .. },
+                             &Some(Known::Record(ref names, ref values))) => {
+                                for &(ref field, ref id) in fields {
+                                    let value = field_value(names, values, field).cloned();
+                                    self.bind(&id.name, value);
+                                }
+                            }
+                            _ => {
+                                for id in pattern_binders(&alt.pattern) {
+                                    self.bind(&id.name, None);
+                                }
+                            }
+                        }
+                        let expr = self.inline(alt.expr);
+                        self.scope.exit_scope();
+                        Alternative {
+                            pattern: alt.pattern,
+                            expr: expr,
+                        }
+                    })
+                    .collect();
+                Expr::Match(Box::new(scrutinee), alts, span)
+            }
+        }
+    }
+
+    /// Returns the expression which can be used in place of the variable `name`
+    fn resolve_ident(&self, name: &Symbol) -> Option<Expr> {
+        match self.scope.get(name) {
+            Some(&(_, Some(ref value))) if self.is_visible(value) => {
+                match value.known {
+                    Known::Expr(ref expr) => Some(expr.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn inline_closure(&mut self, closure: Closure) -> Closure {
+        self.scope.enter_scope();
+        for arg in &closure.args {
+            self.bind(&arg.name, None);
+        }
+        let body = self.inline(*closure.body);
+        self.scope.exit_scope();
+        Closure {
+            id: closure.id,
+            args: closure.args,
+            body: Box::new(body),
+        }
+    }
+}
+
+/// Replaces a call to `closure` with its body
+fn inline_call(closure: &Closure, args: Vec<Expr>, span: Span<BytePos>) -> Expr {
+    let mut renamer = Renamer {
+        names: ScopedMap::new(),
+        span: span,
+    };
+    let mut args = args.into_iter();
+    // Arguments which are not constants or variables are bound to a variable before the body so
+    // that they are evaluated exactly once and in the same order as before
+    let mut bindings = Vec::new();
+    for (param, arg) in closure.args.iter().zip(args.by_ref()) {
+        match arg {
+            Expr::Const(..) | Expr::Ident(..) => {
+                renamer.names.insert(param.name.clone(), arg);
+            }
+            _ => {
+                let id = fresh(param);
+                renamer.names.insert(param.name.clone(), Expr::Ident(id.clone(), span));
+                bindings.push((id, arg));
+            }
+        }
+    }
+    let mut result = renamer.rename((*closure.body).clone());
+    for (id, arg) in bindings.into_iter().rev() {
+        result = Expr::Let(Named::Expr(id, Box::new(arg)), Box::new(result), span);
+    }
+    let rest = args.collect::<Vec<_>>();
+    if rest.is_empty() {
+        result
+    } else {
+        Expr::Call(Box::new(result), rest, span)
+    }
+}
+
+/// Gives every variable bound in an expression a new, unique name so that the expression can be
+/// moved into a different scope without any of its variables capturing variables in that scope
+struct Renamer {
+    names: ScopedMap<Symbol, Expr>,
+    /// The span of the expression being replaced
+    span: Span<BytePos>,
+}
+
+impl Renamer {
+    fn bind(&mut self, id: &TypedIdent) -> TypedIdent {
+        let new = fresh(id);
+        self.names.insert(id.name.clone(), Expr::Ident(new.clone(), self.span));
+        new
+    }
+
+    fn rename(&mut self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Const(..) => expr,
+            Expr::Ident(id, span) => {
+                match self.names.get(&id.name) {
+                    Some(expr) => with_span(expr.clone(), span),
+                    None => Expr::Ident(id, span),
+                }
+            }
+            Expr::Call(f, args, span) => {
+                let f = self.rename(*f);
+                Expr::Call(Box::new(f),
+                           args.into_iter().map(|arg| self.rename(arg)).collect(),
+                           span)
+            }
+            Expr::Lambda(closure, span) => {
+                let id = closure.id.clone();
+                self.names.enter_scope();
+                let closure = self.rename_closure(closure, id);
+                self.names.exit_scope();
+                Expr::Lambda(closure, span)
+            }
+            Expr::Data(kind, exprs, span) => {
+                Expr::Data(kind,
+                           exprs.into_iter().map(|expr| self.rename(expr)).collect(),
+                           span)
+            }
+            Expr::Let(Named::Expr(id, value), body, span) => {
+                let value = self.rename(*value);
+                self.names.enter_scope();
+                let id = self.bind(&id);
+                let body = self.rename(*body);
+                self.names.exit_scope();
+                Expr::Let(Named::Expr(id, Box::new(value)), Box::new(body), span)
+            }
+            Expr::Let(Named::Recursive(closures), body, span) => {
+                self.names.enter_scope();
+                let ids = closures.iter()
+                    .map(|closure| self.bind(&closure.id))
+                    .collect::<Vec<_>>();
+                let closures = closures.into_iter()
+                    .zip(ids)
+                    .map(|(closure, id)| {
+                        self.names.enter_scope();
+                        let closure = self.rename_closure(closure, id);
+                        self.names.exit_scope();
+                        closure
+                    })
+                    .collect();
+                let body = self.rename(*body);
+                self.names.exit_scope();
+                Expr::Let(Named::Recursive(closures), Box::new(body), span)
+            }
+            Expr::Match(scrutinee, alts, span) => {
+                let scrutinee = self.rename(*scrutinee);
+                let alts = alts.into_iter()
+                    .map(|alt| {
+                        self.names.enter_scope();
+                        let pattern = match alt.pattern {
+                            Pattern::Constructor(id, tag, args) => {
+                                let args = args.iter().map(|arg| self.bind(arg)).collect();
+                                Pattern::Constructor(id, tag, args)
+                            }
+                            Pattern::Record { fields, size } => {
+                                Pattern::Record {
+                                    fields: fields.into_iter()
+                                        .map(|(field, id)| (field, self.bind(&id)))
+                                        .collect(),
+                                    size: size,
+                                }
+                            }
+                            Pattern::Ident(id) => Pattern::Ident(self.bind(&id)),
+                        };
+                        let expr = self.rename(alt.expr);
+                        self.names.exit_scope();
+                        Alternative {
+                            pattern: pattern,
+                            expr: expr,
+                        }
+                    })
+                    .collect();
+                Expr::Match(Box::new(scrutinee), alts, span)
+            }
+        }
+    }
+
+    fn rename_closure(&mut self, closure: Closure, id: TypedIdent) -> Closure {
+        let args = closure.args.iter().map(|arg| self.bind(arg)).collect();
+        Closure {
+            id: id,
+            args: args,
+            body: Box::new(self.rename(*closure.body)),
+        }
+    }
+}
+
+fn fresh(id: &TypedIdent) -> TypedIdent {
+    TypedIdent {
+        name: Symbol::from(AsRef::<str>::as_ref(&id.name)),
+        typ: id.typ.clone(),
+    }
+}
+
+fn with_span(expr: Expr, span: Span<BytePos>) -> Expr {
+    match expr {
+        Expr::Const(lit, _) => Expr::Const(lit, span),
+        Expr::Ident(id, _) => Expr::Ident(id, span),
+        expr => expr,
+    }
+}
+
+/// Returns the field being accessed if `alts` only retrieves a single field from a record
+fn projection(alts: &[Alternative]) -> Option<&Field> {
+    if alts.len() != 1 {
+        return None;
+    }
+    match (&alts[0].pattern, &alts[0].expr) {
+        (&Pattern::Record { ref fields, .. }, &Expr::Ident(ref id, _)) => {
+            match fields.first() {
+                Some(&(ref field, ref bind)) if fields.len() == 1 && bind.name == id.name => {
+                    Some(field)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn field_value<'a>(names: &[Symbol],
+                   values: &'a [Option<KnownValue>],
+                   field: &Field)
+                   -> Option<&'a KnownValue> {
+    let index = match *field {
+        Field::Offset(index) => Some(index as usize),
+        Field::Name(ref name) => names.iter().position(|field| field.name_eq(name)),
+    };
+    index.and_then(|index| values.get(index)).and_then(|value| value.as_ref())
+}
+
+fn pattern_binders(pattern: &Pattern) -> Vec<&TypedIdent> {
+    match *pattern {
+        Pattern::Constructor(_, _, ref args) => args.iter().collect(),
+        Pattern::Record { ref fields, .. } => fields.iter().map(|field| &field.1).collect(),
+        Pattern::Ident(ref id) => vec![id],
+    }
+}
+
+/// Returns the number of expressions in `expr`
+fn size(expr: &Expr) -> usize {
+    1 +
+    match *expr {
+        Expr::Const(..) | Expr::Ident(..) => 0,
+        Expr::Call(ref f, ref args, _) => size(f) + args.iter().map(size).sum::<usize>(),
+        Expr::Lambda(ref closure, _) => size(&closure.body),
+        Expr::Data(_, ref exprs, _) => exprs.iter().map(size).sum(),
+        Expr::Let(Named::Expr(_, ref value), ref body, _) => size(value) + size(body),
+        Expr::Let(Named::Recursive(ref closures), ref body, _) => {
+            closures.iter().map(|closure| size(&closure.body)).sum::<usize>() + size(body)
+        }
+        Expr::Match(ref scrutinee, ref alts, _) => {
+            size(scrutinee) + alts.iter().map(|alt| size(&alt.expr)).sum::<usize>()
+        }
+    }
+}
+
+fn free_variables(expr: &Expr) -> FnvSet<Symbol> {
+    let mut free = FnvSet::default();
+    match *expr {
+        Expr::Const(..) => (),
+        Expr::Ident(ref id, _) => {
+            free.insert(id.name.clone());
+        }
+        Expr::Call(ref f, ref args, _) => {
+            free.extend(free_variables(f));
+            for arg in args {
+                free.extend(free_variables(arg));
+            }
+        }
+        Expr::Lambda(ref closure, _) => free = closure_free_variables(closure),
+        Expr::Data(_, ref exprs, _) => {
+            for expr in exprs {
+                free.extend(free_variables(expr));
+            }
+        }
+        Expr::Let(Named::Expr(ref id, ref value), ref body, _) => {
+            free = free_variables(body);
+            free.remove(&id.name);
+            free.extend(free_variables(value));
+        }
+        Expr::Let(Named::Recursive(ref closures), ref body, _) => {
+            free = free_variables(body);
+            for closure in closures {
+                free.extend(closure_free_variables(closure));
+            }
+            for closure in closures {
+                free.remove(&closure.id.name);
+            }
+        }
+        Expr::Match(ref scrutinee, ref alts, _) => {
+            free = free_variables(scrutinee);
+            for alt in alts {
+                let mut alt_free = free_variables(&alt.expr);
+                for id in pattern_binders(&alt.pattern) {
+                    alt_free.remove(&id.name);
+                }
+                free.extend(alt_free);
+            }
+        }
+    }
+    free
+}
+
+fn closure_free_variables(closure: &Closure) -> FnvSet<Symbol> {
+    let mut free = free_variables(&closure.body);
+    for arg in &closure.args {
+        free.remove(&arg.name);
+    }
+    free
+}
+
+/// Returns true if evaluating `expr` has no effects and can not fail
+fn is_pure(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Const(..) | Expr::Ident(..) | Expr::Lambda(..) => true,
+        Expr::Data(_, ref exprs, _) => exprs.iter().all(is_pure),
+        Expr::Let(Named::Expr(_, ref value), ref body, _) => is_pure(value) && is_pure(body),
+        Expr::Let(Named::Recursive(_), ref body, _) => is_pure(body),
+        Expr::Call(..) |
+        Expr::Match(..) => false,
+    }
+}
+
+/// Removes unused bindings from `expr`, returning the new expression and its free variables
+fn eliminate(expr: Expr) -> (Expr, FnvSet<Symbol>) {
+    match expr {
+        Expr::Const(..) => (expr, FnvSet::default()),
+        Expr::Ident(..) => {
+            let free = free_variables(&expr);
+            (expr, free)
+        }
+        Expr::Call(f, args, span) => {
+            let (f, mut free) = eliminate(*f);
+            let args = args.into_iter()
+                .map(|arg| {
+                    let (arg, arg_free) = eliminate(arg);
+                    free.extend(arg_free);
+                    arg
+                })
+                .collect();
+            (Expr::Call(Box::new(f), args, span), free)
+        }
+        Expr::Lambda(closure, span) => {
+            let (closure, free) = eliminate_closure(closure);
+            (Expr::Lambda(closure, span), free)
+        }
+        Expr::Data(kind, exprs, span) => {
+            let mut free = FnvSet::default();
+            let exprs = exprs.into_iter()
+                .map(|expr| {
+                    let (expr, expr_free) = eliminate(expr);
+                    free.extend(expr_free);
+                    expr
+                })
+                .collect();
+            (Expr::Data(kind, exprs, span), free)
+        }
+        Expr::Let(Named::Expr(id, value), body, span) => {
+            let (body, mut free) = eliminate(*body);
+            if !free.contains(&id.name) && is_pure(&value) {
+                return (body, free);
+            }
+            free.remove(&id.name);
+            let (value, value_free) = eliminate(*value);
+            free.extend(value_free);
+            (Expr::Let(Named::Expr(id, Box::new(value)), Box::new(body), span), free)
+        }
+        Expr::Let(Named::Recursive(closures), body, span) => {
+            let (body, mut free) = eliminate(*body);
+            let closures = closures.into_iter().map(eliminate_closure).collect::<Vec<_>>();
+            // Keep the functions which are used by the body or by another function that is kept
+            let mut used = vec![false; closures.len()];
+            loop {
+                let mut changed = false;
+                for (i, &(ref closure, ref closure_free)) in closures.iter().enumerate() {
+                    if !used[i] && free.contains(&closure.id.name) {
+                        used[i] = true;
+                        changed = true;
+                        free.extend(closure_free.iter().cloned());
+                    }
+                }
+                if !changed {
+                    break;
+                }
+            }
+            let closures = closures.into_iter()
+                .zip(used)
+                .filter(|&(_, used)| used)
+                .map(|((closure, _), _)| closure)
+                .collect::<Vec<_>>();
+            if closures.is_empty() {
+                return (body, free);
+            }
+            for closure in &closures {
+                free.remove(&closure.id.name);
+            }
+            (Expr::Let(Named::Recursive(closures), Box::new(body), span), free)
+        }
+        Expr::Match(scrutinee, alts, span) => {
+            let (scrutinee, mut free) = eliminate(*scrutinee);
+            let alts = alts.into_iter()
+                .map(|alt| {
+                    let (expr, mut alt_free) = eliminate(alt.expr);
+                    let pattern = match alt.pattern {
+                        // Fields which are never used do not need to be retrieved
+                        Pattern::Record { fields, size } => {
+                            Pattern::Record {
+                                fields: fields.into_iter()
+                                    .filter(|field| alt_free.contains(&field.1.name))
+                                    .collect(),
+                                size: size,
+                            }
+                        }
+                        pattern => pattern,
+                    };
+                    for id in pattern_binders(&pattern) {
+                        alt_free.remove(&id.name);
+                    }
+                    free.extend(alt_free);
+                    Alternative {
+                        pattern: pattern,
+                        expr: expr,
+                    }
+                })
+                .collect();
+            (Expr::Match(Box::new(scrutinee), alts, span), free)
+        }
+    }
+}
+
+fn eliminate_closure(closure: Closure) -> (Closure, FnvSet<Symbol>) {
+    let (body, mut free) = eliminate(*closure.body);
+    for arg in &closure.args {
+        free.remove(&arg.name);
+    }
+    let closure = Closure {
+        id: closure.id,
+        args: closure.args,
+        body: Box::new(body),
+    };
+    (closure, free)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::ast::{Literal, TypedIdent};
+    use base::pos::{BytePos, Span};
+    use base::symbol::Symbol;
+
+    use core::{Closure, Expr, Named};
+
+    fn span() -> Span<BytePos> {
+        Span {
+            start: BytePos::from(0),
+            end: BytePos::from(0),
+        }
+    }
+
+    fn ident(id: &TypedIdent) -> Expr {
+        Expr::Ident(id.clone(), span())
+    }
+
+    fn int(i: i64) -> Expr {
+        Expr::Const(Literal::Int(i), span())
+    }
+
+    fn add(l: Expr, r: Expr) -> Expr {
+        let op = TypedIdent::new(Symbol::from("#Int+"));
+        Expr::Call(Box::new(ident(&op)), vec![l, r], span())
+    }
+
+    #[test]
+    fn inline_small_function() {
+        // let f x = x #Int+ 1 in f 2
+        let f = TypedIdent::new(Symbol::from("f"));
+        let x = TypedIdent::new(Symbol::from("x"));
+        let closure = Closure {
+            id: f.clone(),
+            args: vec![x.clone()],
+            body: Box::new(add(ident(&x), int(1))),
+        };
+        let expr = Expr::Let(Named::Recursive(vec![closure]),
+                             Box::new(Expr::Call(Box::new(ident(&f)), vec![int(2)], span())),
+                             span());
+        assert_eq!(eliminate_dead_bindings(inline(expr)), add(int(2), int(1)));
+    }
+
+    #[test]
+    fn do_not_inline_when_free_variable_is_shadowed() {
+        // let y = 1 + 1 in let f x = x #Int+ y in let y = 2 + 2 in f y
+        let y = TypedIdent::new(Symbol::from("y"));
+        let f = TypedIdent::new(Symbol::from("f"));
+        let x = TypedIdent::new(Symbol::from("x"));
+        let closure = Closure {
+            id: f.clone(),
+            args: vec![x.clone()],
+            body: Box::new(add(ident(&x), ident(&y))),
+        };
+        let call = Expr::Call(Box::new(ident(&f)), vec![ident(&y)], span());
+        let shadowing = Expr::Let(Named::Expr(y.clone(), Box::new(add(int(2), int(2)))),
+                                  Box::new(call),
+                                  span());
+        let expr = Expr::Let(Named::Expr(y.clone(), Box::new(add(int(1), int(1)))),
+                             Box::new(Expr::Let(Named::Recursive(vec![closure]),
+                                                Box::new(shadowing),
+                                                span())),
+                             span());
+        assert_eq!(inline(expr.clone()), expr);
+    }
+}
@@ -15,11 +15,17 @@ extern crate gluon_base as base;
 pub mod api;
 pub mod channel;
 pub mod compiler;
+pub mod core;
 pub mod debug;
+pub mod debugger;
+pub mod disassembler;
 pub mod gc;
+pub mod inline;
 pub mod macros;
+pub mod optimize;
 pub mod thread;
 pub mod primitives;
+pub mod serialization;
 pub mod stack;
 pub mod types;
 mod array;
@@ -32,6 +38,7 @@ mod value;
 mod vm;
 
 use api::ValueRef;
+use stack::Stacktrace;
 use value::Value;
 use types::VmIndex;
 use base::types::ArcType;
@@ -90,6 +97,18 @@ quick_error! {
         StackOverflow(limit: VmIndex) {
             display("The stack has overflowed: Limit `{}`", limit)
         }
+        OutOfFuel {
+            display("Thread ran out of fuel")
+        }
+        Timeout {
+            display("Thread exceeded its deadline")
+        }
+        Interrupted(trace: Stacktrace) {
+            display("Thread was interrupted\n{}", trace)
+        }
+        DebuggerQuit {
+            display("The program was stopped by the debugger")
+        }
         Message(err: String) {
             display("{}", err)
         }
@@ -0,0 +1,264 @@
+//! Optimization passes which run on the bytecode of a `CompiledFunction` after it has been
+//! compiled
+use compiler::{CompiledFunction, SourceMap};
+use types::*;
+
+/// How much optimization that is done on compiled functions
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptimizationLevel {
+    /// The instructions are left exactly as the compiler emitted them
+    None,
+    /// Constant arithmetic is folded, redundant stack manipulation is removed and jumps to jumps
+    /// are threaded
+    Peephole,
+    /// In addition to the peephole optimizations, calls to small functions which are known at
+    /// compile time are inlined and bindings which are never used are removed
+    Inline,
+}
+
+impl Default for OptimizationLevel {
+    fn default() -> OptimizationLevel {
+        OptimizationLevel::None
+    }
+}
+
+/// Optimizes the bytecode of `function` and all of its inner functions according to `level`
+pub fn optimize(function: &mut CompiledFunction, level: OptimizationLevel) {
+    if level == OptimizationLevel::None {
+        return;
+    }
+    for inner in &mut function.inner_functions {
+        optimize(inner, level);
+    }
+    // Each rewrite may expose new opportunities (`1 + 2 + 3` is only folded fully after `1 + 2`
+    // has been folded) so keep going until nothing changes
+    loop {
+        thread_jumps(&mut function.instructions);
+        if !peephole(function) {
+            break;
+        }
+    }
+}
+
+/// Replaces jumps to unconditional jumps with a jump directly to the final destination
+fn thread_jumps(instructions: &mut [Instruction]) {
+    for i in 0..instructions.len() {
+        let target = match instructions[i] {
+            Jump(target) | CJump(target) => target,
+            _ => continue,
+        };
+        let mut final_target = target;
+        // Limit the number of steps so that infinite loops of jumps do not hang the optimizer
+        for _ in 0..instructions.len() {
+            match instructions.get(final_target as usize) {
+                Some(&Jump(next)) if next != final_target => final_target = next,
+                _ => break,
+            }
+        }
+        instructions[i] = match instructions[i] {
+            Jump(_) => Jump(final_target),
+            CJump(_) => CJump(final_target),
+            _ => unreachable!(),
+        };
+    }
+}
+
+/// Returns true if `instruction` only pushes a value to the stack without any other effect
+fn is_pure_push(instruction: Instruction) -> bool {
+    match instruction {
+        PushInt(_) | PushByte(_) | PushFloat(_) | PushString(_) | PushUpVar(_) | Push(_) |
+        PushGlobal(_) => true,
+        _ => false,
+    }
+}
+
+fn bool_value(b: bool) -> Instruction {
+    Construct {
+        tag: if b { 1 } else { 0 },
+        args: 0,
+    }
+}
+
+/// Evaluates `op` on two constant operands. Returns `None` if the operands are not constants or
+/// if the operation would fail or overflow at runtime, in which case it is left for the vm to
+/// report.
+fn fold_binop(l: Instruction, r: Instruction, op: Instruction) -> Option<Instruction> {
+    match (l, r) {
+        (PushInt(l), PushInt(r)) => {
+            match op {
+                AddInt => l.checked_add(r).map(PushInt),
+                SubtractInt => l.checked_sub(r).map(PushInt),
+                MultiplyInt => l.checked_mul(r).map(PushInt),
+                DivideInt => l.checked_div(r).map(PushInt),
+                IntLT => Some(bool_value(l < r)),
+                IntEQ => Some(bool_value(l == r)),
+                _ => None,
+            }
+        }
+        (PushByte(l), PushByte(r)) => {
+            match op {
+                AddByte => l.checked_add(r).map(PushByte),
+                SubtractByte => l.checked_sub(r).map(PushByte),
+                MultiplyByte => l.checked_mul(r).map(PushByte),
+                DivideByte => l.checked_div(r).map(PushByte),
+                ByteLT => Some(bool_value(l < r)),
+                ByteEQ => Some(bool_value(l == r)),
+                _ => None,
+            }
+        }
+        (PushFloat(l), PushFloat(r)) => {
+            match op {
+                AddFloat => Some(PushFloat(l + r)),
+                SubtractFloat => Some(PushFloat(l - r)),
+                MultiplyFloat => Some(PushFloat(l * r)),
+                DivideFloat => Some(PushFloat(l / r)),
+                FloatLT => Some(bool_value(l < r)),
+                FloatEQ => Some(bool_value(l == r)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Attempts to rewrite the instructions at the start of `window`, returning how many
+/// instructions were consumed and what they should be replaced with. `window` never extends past
+/// an instruction which is the target of a jump (except for its first instruction) so the
+/// instructions can be rewritten freely.
+fn rewrite(index: usize, window: &[Instruction]) -> Option<(usize, Vec<Instruction>)> {
+    if window.len() >= 3 {
+        if let Some(folded) = fold_binop(window[0], window[1], window[2]) {
+            return Some((3, vec![folded]));
+        }
+    }
+    if window.len() >= 2 {
+        match (window[0], window[1]) {
+            (Pop(l), Pop(r)) => return Some((2, vec![Pop(l + r)])),
+            (Slide(l), Slide(r)) => return Some((2, vec![Slide(l + r)])),
+            (push, Pop(n)) if n > 0 && is_pure_push(push) => return Some((2, vec![Pop(n - 1)])),
+            (Construct { tag, args: 0 }, CJump(target)) => {
+                return Some((2, if tag != 0 { vec![Jump(target)] } else { vec![] }));
+            }
+            _ => (),
+        }
+    }
+    match window[0] {
+        Pop(0) | Slide(0) => Some((1, vec![])),
+        Jump(target) if target as usize == index + 1 => Some((1, vec![])),
+        _ => None,
+    }
+}
+
+/// Runs a single pass of rewrites over `function`. Returns true if any instructions were
+/// rewritten.
+fn peephole(function: &mut CompiledFunction) -> bool {
+    let (mut instructions, new_index) = match rewrite_instructions(&function.instructions) {
+        Some(x) => x,
+        None => return false,
+    };
+    for instruction in &mut instructions {
+        *instruction = match *instruction {
+            Jump(target) => Jump(new_index[target as usize] as VmIndex),
+            CJump(target) => CJump(new_index[target as usize] as VmIndex),
+            instruction => instruction,
+        };
+    }
+    let mut source_map = SourceMap::new();
+    for &(index, span) in function.source_map.iter() {
+        source_map.emit(new_index[index], span);
+    }
+    function.locals.remap(&new_index);
+    function.instructions = instructions;
+    function.source_map = source_map;
+    true
+}
+
+/// Rewrites `old`, returning the new instructions together with a mapping from each old
+/// instruction index to the index of the instruction which replaced it. The jump targets in the
+/// returned instructions still refer to the old indexes.
+fn rewrite_instructions(old: &[Instruction]) -> Option<(Vec<Instruction>, Vec<usize>)> {
+    let mut is_target = vec![false; old.len() + 1];
+    for instruction in old {
+        match *instruction {
+            Jump(target) | CJump(target) => is_target[target as usize] = true,
+            _ => (),
+        }
+    }
+
+    let mut changed = false;
+    let mut instructions = Vec::with_capacity(old.len());
+    let mut new_index = Vec::with_capacity(old.len() + 1);
+    let mut i = 0;
+    while i < old.len() {
+        let end = (i + 1..old.len()).find(|&j| is_target[j]).unwrap_or(old.len());
+        match rewrite(i, &old[i..end]) {
+            Some((consumed, replacement)) => {
+                changed = true;
+                for _ in 0..consumed {
+                    new_index.push(instructions.len());
+                }
+                instructions.extend(replacement);
+                i += consumed;
+            }
+            None => {
+                new_index.push(instructions.len());
+                instructions.push(old[i]);
+                i += 1;
+            }
+        }
+    }
+    new_index.push(instructions.len());
+    if changed {
+        Some((instructions, new_index))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::symbol::Symbol;
+    use base::types::{ArcType, Type};
+
+    use compiler::CompiledFunction;
+    use types::*;
+
+    fn optimized(instructions: Vec<Instruction>) -> String {
+        let typ: ArcType = Type::int();
+        let mut function = CompiledFunction::new(0, Symbol::from("test"), typ);
+        function.instructions = instructions;
+        optimize(&mut function, OptimizationLevel::Peephole);
+        format!("{:?}", function.instructions)
+    }
+
+    #[test]
+    fn fold_constant_arithmetic() {
+        assert_eq!(optimized(vec![PushInt(1), PushInt(2), AddInt, PushInt(3), MultiplyInt]),
+                   format!("{:?}", vec![PushInt(9)]));
+        assert_eq!(optimized(vec![PushFloat(1.0), PushFloat(2.0), FloatLT]),
+                   format!("{:?}", vec![Construct { tag: 1, args: 0 }]));
+        // Division by zero is left to fail at runtime
+        assert_eq!(optimized(vec![PushInt(1), PushInt(0), DivideInt]),
+                   format!("{:?}", vec![PushInt(1), PushInt(0), DivideInt]));
+    }
+
+    #[test]
+    fn remove_redundant_stack_manipulation() {
+        assert_eq!(optimized(vec![Push(0), PushInt(1), Pop(1), Pop(0), Slide(1), Slide(2)]),
+                   format!("{:?}", vec![Push(0), Slide(3)]));
+    }
+
+    #[test]
+    fn thread_jumps_to_jumps() {
+        assert_eq!(optimized(vec![Push(0), CJump(3), Jump(5), Jump(4), PushInt(1), PushInt(2)]),
+                   format!("{:?}", vec![Push(0), CJump(3), Jump(4), PushInt(1), PushInt(2)]));
+    }
+
+    #[test]
+    fn do_not_fold_across_jump_targets() {
+        let instructions = vec![Push(0), CJump(3), PushInt(1), PushInt(2), AddInt];
+        assert_eq!(optimized(instructions.clone()), format!("{:?}", instructions));
+    }
+}
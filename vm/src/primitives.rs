@@ -286,6 +286,7 @@ pub fn load(vm: &Thread) -> Result<()> {
         show_Char => primitive!(1 prim::show_char)
     )));
 
+    try!(vm.define_global("#string_eq", primitive!(2 <str as PartialEq>::eq)));
     try!(vm.define_global("#error",
                           primitive::<fn(StdString) -> A>("#error", prim::error)));
     try!(vm.define_global("error",
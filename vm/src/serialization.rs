@@ -0,0 +1,1063 @@
+//! Binary serialization of `CompiledFunction` which allows compiled modules to be cached on disk.
+//!
+//! A serialized function starts with a header consisting of `MAGIC`, the format `VERSION`, the
+//! version of the compiler, the optimization level, a hash of the source code which the function
+//! were compiled from and a hash of the type of each global (such as an imported module) which the
+//! function refers to. If any of these do not match when reading, the cached function is rejected
+//! and the caller should fall back to compiling the source again. All integers are written in
+//! little endian.
+use std::error::Error as StdError;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::{u64, usize};
+
+use base::fnv::FnvHasher;
+use base::pos::{BytePos, Span};
+use base::symbol::{Symbol, SymbolModule};
+use base::types::{Alias, AliasData, ArcKind, ArcType, BuiltinType, Field, Generic, Kind, Type,
+                  TypeVariable};
+
+use compiler::{CompiledFunction, UpvarInfo};
+use optimize::OptimizationLevel;
+use types::*;
+use vm::GlobalVmState;
+
+/// Bytes which every serialized function starts with
+pub const MAGIC: &'static [u8; 4] = b"GLUC";
+
+/// Version of the serialization format. Must be increased whenever the format, or the meaning of
+/// any instruction, changes.
+pub const VERSION: u32 = 6;
+
+/// How deeply types and inner functions may be nested in a serialized function. Reading them is
+/// recursive so deeper nesting is rejected as malformed instead of overflowing the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Version of the compiler which wrote a serialized function
+pub const COMPILER_VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+quick_error! {
+    /// Errors which can occur when reading or writing a compiled function
+    #[derive(Debug)]
+    pub enum Error {
+        /// Error from the underlying reader or writer
+        Io(err: io::Error) {
+            description(err.description())
+            display("{}", err)
+            from()
+        }
+        /// Error from the virtual machine, such as running out of memory when interning strings
+        Vm(err: ::Error) {
+            description(err.description())
+            display("{}", err)
+            from()
+        }
+        /// The data does not start with `MAGIC`
+        InvalidHeader {
+            description("Invalid header")
+            display("The data is not a compiled gluon function")
+        }
+        /// The data were written using a different version of the format
+        UnsupportedVersion(version: u32) {
+            description("Unsupported version")
+            display("Compiled function has version {} but version {} were expected",
+                    version,
+                    VERSION)
+        }
+        /// The data were written by a different version of the compiler
+        UnsupportedCompiler(version: String) {
+            description("Unsupported compiler version")
+            display("Compiled function were created by version {} of the compiler but this is \
+                     version {}",
+                    version,
+                    COMPILER_VERSION)
+        }
+        /// The function were compiled from a different source, with a different optimization
+        /// level or against globals with different types than the ones given
+        Stale {
+            description("Stale compiled function")
+            display("The compiled function were created from a different source or environment")
+        }
+        /// The data is malformed
+        Malformed(msg: String) {
+            description(msg)
+            display("Malformed compiled function: {}", msg)
+        }
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Hashes `source` so that it can be stored in the header of a serialized function
+pub fn source_hash(source: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(source.as_bytes());
+    hasher.finish()
+}
+
+/// Hashes the type of the global called `name`. Bytecode only depends on the layout of the
+/// globals it refers to, which is determined by their types, so the bytecode is still valid if a
+/// global has been changed without changing its type.
+fn global_hash(vm: &GlobalVmState, name: &str) -> Option<u64> {
+    vm.get_env().globals.get(name).map(|global| {
+        let mut hasher = FnvHasher::default();
+        hasher.write(global.typ.to_string().as_bytes());
+        hasher.finish()
+    })
+}
+
+/// Collects the names of the globals which `function` and its inner functions refer to
+fn referenced_globals<'f>(function: &'f CompiledFunction, globals: &mut Vec<&'f str>) {
+    for global in &function.module_globals {
+        if !globals.contains(&global.as_ref()) {
+            globals.push(global.as_ref());
+        }
+    }
+    for inner in &function.inner_functions {
+        referenced_globals(inner, globals);
+    }
+}
+
+fn optimization_level_tag(level: OptimizationLevel) -> u8 {
+    match level {
+        OptimizationLevel::None => 0,
+        OptimizationLevel::Peephole => 1,
+        OptimizationLevel::Inline => 2,
+    }
+}
+
+/// Writes `function` to `writer`, tagging it with a hash of the `source` it were compiled from, the
+/// optimization `level` it were compiled with and the types of the globals in `vm` it refers to
+pub fn write_compiled<W>(writer: &mut W,
+                         vm: &GlobalVmState,
+                         source: &str,
+                         level: OptimizationLevel,
+                         function: &CompiledFunction)
+                         -> Result<()>
+    where W: Write,
+{
+    try!(writer.write_all(MAGIC));
+    try!(write_u32(writer, VERSION));
+    try!(write_str(writer, COMPILER_VERSION));
+    try!(write_u8(writer, optimization_level_tag(level)));
+    try!(write_u64(writer, source_hash(source)));
+
+    let mut globals = Vec::new();
+    referenced_globals(function, &mut globals);
+    try!(write_len(writer, globals.len()));
+    for name in globals {
+        let hash = match global_hash(vm, name) {
+            Some(hash) => hash,
+            None => return Err(Error::Malformed(format!("Undefined global `{}`", name))),
+        };
+        try!(write_str(writer, name));
+        try!(write_u64(writer, hash));
+    }
+    write_function(writer, function)
+}
+
+/// Reads a function previously written by `write_compiled`. Fails with `Error::Stale` if the
+/// function were compiled from a different source than `source`, with a different optimization
+/// `level` or if any global it refers to is missing from `vm` or has a different type than when
+/// the function were written.
+pub fn read_compiled<R>(reader: &mut R,
+                        vm: &GlobalVmState,
+                        symbols: &mut SymbolModule,
+                        source: &str,
+                        level: OptimizationLevel)
+                        -> Result<CompiledFunction>
+    where R: Read,
+{
+    let mut magic = [0; 4];
+    try!(reader.read_exact(&mut magic));
+    if &magic != MAGIC {
+        return Err(Error::InvalidHeader);
+    }
+    let version = try!(read_u32(reader));
+    if version != VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    let compiler_version = try!(read_string(reader));
+    if compiler_version != COMPILER_VERSION {
+        return Err(Error::UnsupportedCompiler(compiler_version));
+    }
+    if try!(read_u8(reader)) != optimization_level_tag(level) {
+        return Err(Error::Stale);
+    }
+    if try!(read_u64(reader)) != source_hash(source) {
+        return Err(Error::Stale);
+    }
+    let len = try!(read_len(reader));
+    for _ in 0..len {
+        let name = try!(read_string(reader));
+        let hash = try!(read_u64(reader));
+        if global_hash(vm, &name) != Some(hash) {
+            return Err(Error::Stale);
+        }
+    }
+    let function = {
+        let mut reader = FunctionReader {
+            reader: reader,
+            vm: vm,
+            symbols: symbols,
+            depth: 0,
+        };
+        try!(reader.read_function())
+    };
+    {
+        let mut globals = Vec::new();
+        referenced_globals(&function, &mut globals);
+        for name in globals {
+            if !vm.global_exists(name) {
+                return Err(Error::Malformed(format!("Undefined global `{}`", name)));
+            }
+        }
+    }
+    // The function of a module is called without any upvars
+    try!(validate(&function, 0));
+    Ok(function)
+}
+
+fn write_u8<W: Write>(writer: &mut W, value: u8) -> Result<()> {
+    try!(writer.write_all(&[value]));
+    Ok(())
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    let bytes = [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+    try!(writer.write_all(&bytes));
+    Ok(())
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> Result<()> {
+    try!(write_u32(writer, value as u32));
+    write_u32(writer, (value >> 32) as u32)
+}
+
+fn write_len<W: Write>(writer: &mut W, len: usize) -> Result<()> {
+    write_u32(writer, len as u32)
+}
+
+fn write_str<W: Write>(writer: &mut W, s: &str) -> Result<()> {
+    try!(write_len(writer, s.len()));
+    try!(writer.write_all(s.as_bytes()));
+    Ok(())
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut buffer = [0];
+    try!(reader.read_exact(&mut buffer));
+    Ok(buffer[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut b = [0; 4];
+    try!(reader.read_exact(&mut b));
+    Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let low = try!(read_u32(reader)) as u64;
+    let high = try!(read_u32(reader)) as u64;
+    Ok(low | high << 32)
+}
+
+fn read_len<R: Read>(reader: &mut R) -> Result<usize> {
+    read_u32(reader).map(|len| len as usize)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let len = try!(read_len(reader));
+    let mut buffer = Vec::new();
+    try!(reader.take(len as u64).read_to_end(&mut buffer));
+    if buffer.len() != len {
+        return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "Unexpected EOF")));
+    }
+    String::from_utf8(buffer).map_err(|err| Error::Malformed(format!("{}", err)))
+}
+
+fn write_function<W: Write>(writer: &mut W, function: &CompiledFunction) -> Result<()> {
+    try!(write_u32(writer, function.args));
+    try!(write_u32(writer, function.max_stack_size));
+    try!(write_str(writer, function.id.as_ref()));
+    try!(write_type(writer, &function.typ));
+
+    try!(write_len(writer, function.instructions.len()));
+    for instruction in &function.instructions {
+        try!(write_instruction(writer, *instruction));
+    }
+
+    try!(write_len(writer, function.inner_functions.len()));
+    for inner in &function.inner_functions {
+        try!(write_function(writer, inner));
+    }
+
+    try!(write_len(writer, function.strings.len()));
+    for s in &function.strings {
+        try!(write_str(writer, s));
+    }
+
+    try!(write_len(writer, function.module_globals.len()));
+    for global in &function.module_globals {
+        try!(write_str(writer, global.as_ref()));
+    }
+
+    try!(write_len(writer, function.records.len()));
+    for record in &function.records {
+        try!(write_len(writer, record.len()));
+        for field in record {
+            try!(write_str(writer, field.as_ref()));
+        }
+    }
+
+    try!(write_len(writer, function.source_map.iter().len()));
+    for &(index, span) in function.source_map.iter() {
+        try!(write_len(writer, index));
+        try!(write_len(writer, span.start.to_usize()));
+        try!(write_len(writer, span.end.to_usize()));
+    }
+
+    try!(write_len(writer, function.locals.iter().len()));
+    for local in function.locals.iter() {
+        try!(write_len(writer, local.start));
+        // Locals which are in scope until the end of the function have `usize::MAX` as their end
+        let end = if local.end == usize::MAX {
+            u64::MAX
+        } else {
+            local.end as u64
+        };
+        try!(write_u64(writer, end));
+        try!(write_u32(writer, local.index));
+        try!(write_str(writer, local.name.as_ref()));
+        try!(write_type(writer, &local.typ));
+    }
+
+    try!(write_len(writer, function.upvars.len()));
+    for upvar in &function.upvars {
+        try!(write_str(writer, upvar.name.as_ref()));
+        try!(write_type(writer, &upvar.typ));
+    }
+    Ok(())
+}
+
+fn write_instruction<W: Write>(writer: &mut W, instruction: Instruction) -> Result<()> {
+    macro_rules! write_op {
+        ($op: expr $(, $arg: expr)*) => {{
+            try!(write_u8(writer, $op));
+            $(
+                try!(write_u32(writer, $arg));
+            )*
+        }}
+    }
+    match instruction {
+        PushInt(i) => {
+            try!(write_u8(writer, 0));
+            try!(write_u64(writer, i as i64 as u64));
+        }
+        PushByte(b) => {
+            try!(write_u8(writer, 1));
+            try!(write_u8(writer, b));
+        }
+        PushFloat(f) => {
+            try!(write_u8(writer, 2));
+            try!(write_u64(writer, f.to_bits()));
+        }
+        PushString(i) => write_op!(3, i),
+        PushUpVar(i) => write_op!(4, i),
+        Push(i) => write_op!(5, i),
+        PushGlobal(i) => write_op!(6, i),
+        Call(args) => write_op!(7, args),
+        TailCall(args) => write_op!(8, args),
+        Construct { tag, args } => write_op!(9, tag, args),
+        ConstructRecord { record, args } => write_op!(10, record, args),
+        ConstructArray(args) => write_op!(11, args),
+        GetOffset(i) => write_op!(12, i),
+        GetField(i) => write_op!(13, i),
+        Split => write_op!(14),
+        TestTag(tag) => write_op!(15, tag),
+        Jump(i) => write_op!(16, i),
+        CJump(i) => write_op!(17, i),
+        Pop(n) => write_op!(18, n),
+        Slide(n) => write_op!(19, n),
+        MakeClosure { function_index, upvars } => write_op!(20, function_index, upvars),
+        NewClosure { function_index, upvars } => write_op!(21, function_index, upvars),
+        CloseClosure(n) => write_op!(22, n),
+        AddInt => write_op!(23),
+        SubtractInt => write_op!(24),
+        MultiplyInt => write_op!(25),
+        DivideInt => write_op!(26),
+        IntLT => write_op!(27),
+        IntEQ => write_op!(28),
+        AddByte => write_op!(29),
+        SubtractByte => write_op!(30),
+        MultiplyByte => write_op!(31),
+        DivideByte => write_op!(32),
+        ByteLT => write_op!(33),
+        ByteEQ => write_op!(34),
+        AddFloat => write_op!(35),
+        SubtractFloat => write_op!(36),
+        MultiplyFloat => write_op!(37),
+        DivideFloat => write_op!(38),
+        FloatLT => write_op!(39),
+        FloatEQ => write_op!(40),
+    }
+    Ok(())
+}
+
+fn write_kind<W: Write>(writer: &mut W, kind: &ArcKind) -> Result<()> {
+    match **kind {
+        Kind::Variable(id) => {
+            try!(write_u8(writer, 0));
+            write_u32(writer, id)
+        }
+        Kind::Type => write_u8(writer, 1),
+        Kind::Row => write_u8(writer, 2),
+        Kind::Function(ref arg, ref ret) => {
+            try!(write_u8(writer, 3));
+            try!(write_kind(writer, arg));
+            write_kind(writer, ret)
+        }
+    }
+}
+
+fn write_generic<W: Write>(writer: &mut W, generic: &Generic<Symbol>) -> Result<()> {
+    try!(write_str(writer, generic.id.as_ref()));
+    write_kind(writer, &generic.kind)
+}
+
+fn write_alias<W: Write>(writer: &mut W, alias: &AliasData<Symbol, ArcType>) -> Result<()> {
+    try!(write_str(writer, alias.name.as_ref()));
+    try!(write_len(writer, alias.args.len()));
+    for arg in &alias.args {
+        try!(write_generic(writer, arg));
+    }
+    match alias.typ {
+        Some(ref typ) => {
+            try!(write_u8(writer, 1));
+            write_type(writer, typ)
+        }
+        None => write_u8(writer, 0),
+    }
+}
+
+fn write_builtin<W: Write>(writer: &mut W, builtin: BuiltinType) -> Result<()> {
+    let tag = match builtin {
+        BuiltinType::String => 0,
+        BuiltinType::Byte => 1,
+        BuiltinType::Char => 2,
+        BuiltinType::Int => 3,
+        BuiltinType::Float => 4,
+        BuiltinType::Unit => 5,
+        BuiltinType::Array => 6,
+        BuiltinType::Function => 7,
+        BuiltinType::ImplicitFunction => 8,
+    };
+    write_u8(writer, tag)
+}
+
+fn write_type<W: Write>(writer: &mut W, typ: &ArcType) -> Result<()> {
+    match **typ {
+        Type::Hole => write_u8(writer, 0),
+        Type::App(ref f, ref args) => {
+            try!(write_u8(writer, 1));
+            try!(write_type(writer, f));
+            try!(write_len(writer, args.len()));
+            for arg in args {
+                try!(write_type(writer, arg));
+            }
+            Ok(())
+        }
+        Type::Variants(ref variants) => {
+            try!(write_u8(writer, 2));
+            try!(write_len(writer, variants.len()));
+            for &(ref name, ref typ) in variants {
+                try!(write_str(writer, name.as_ref()));
+                try!(write_type(writer, typ));
+            }
+            Ok(())
+        }
+        Type::Variable(ref var) => {
+            try!(write_u8(writer, 3));
+            try!(write_u32(writer, var.id));
+            write_kind(writer, &var.kind)
+        }
+        Type::Generic(ref gen) => {
+            try!(write_u8(writer, 4));
+            write_generic(writer, gen)
+        }
+        Type::Builtin(builtin) => {
+            try!(write_u8(writer, 5));
+            write_builtin(writer, builtin)
+        }
+        Type::Record(ref row) => {
+            try!(write_u8(writer, 6));
+            write_type(writer, row)
+        }
+        Type::EmptyRow => write_u8(writer, 7),
+        Type::ExtendRow { ref types, ref fields, ref rest } => {
+            try!(write_u8(writer, 8));
+            try!(write_len(writer, types.len()));
+            for field in types {
+                try!(write_str(writer, field.name.as_ref()));
+                try!(write_alias(writer, &field.typ));
+            }
+            try!(write_len(writer, fields.len()));
+            for field in fields {
+                try!(write_str(writer, field.name.as_ref()));
+                try!(write_type(writer, &field.typ));
+            }
+            write_type(writer, rest)
+        }
+        Type::Ident(ref id) => {
+            try!(write_u8(writer, 9));
+            write_str(writer, id.as_ref())
+        }
+        Type::Alias(ref alias) => {
+            try!(write_u8(writer, 10));
+            write_alias(writer, alias)
+        }
+        Type::Forall(ref params, ref typ) => {
+            try!(write_u8(writer, 11));
+            try!(write_len(writer, params.len()));
+            for param in params {
+                try!(write_generic(writer, param));
+            }
+            write_type(writer, typ)
+        }
+    }
+}
+
+struct FunctionReader<'r, 'a: 'r, R: 'r> {
+    reader: &'r mut R,
+    vm: &'r GlobalVmState,
+    symbols: &'r mut SymbolModule<'a>,
+    /// The number of functions and types which are currently being read
+    depth: usize,
+}
+
+impl<'r, 'a: 'r, R: Read + 'r> FunctionReader<'r, 'a, R> {
+    fn u8(&mut self) -> Result<u8> {
+        read_u8(self.reader)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        read_u32(self.reader)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        read_len(self.reader)
+    }
+
+    fn symbol(&mut self) -> Result<Symbol> {
+        let name = try!(read_string(self.reader));
+        // Go through the symbol table so that symbols with the same name are the same symbol,
+        // just as if the function had been compiled in this process
+        Ok(self.symbols.symbol(name))
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::Malformed(format!("Nesting exceeds the limit of {}", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn read_function(&mut self) -> Result<CompiledFunction> {
+        try!(self.enter());
+        let result = self.read_function_();
+        self.depth -= 1;
+        result
+    }
+
+    fn read_function_(&mut self) -> Result<CompiledFunction> {
+        let args = try!(self.u32());
+        let max_stack_size = try!(self.u32());
+        let id = try!(self.symbol());
+        let typ = try!(self.read_type());
+        let mut function = CompiledFunction::new(args, id, typ);
+        function.max_stack_size = max_stack_size;
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let instruction = try!(self.read_instruction());
+            function.instructions.push(instruction);
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let inner = try!(self.read_function());
+            function.inner_functions.push(inner);
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let s = try!(read_string(self.reader));
+            function.strings.push(try!(self.vm.intern(&s)));
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let global = try!(self.symbol());
+            function.module_globals.push(global);
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let fields_len = try!(self.len());
+            let mut fields = Vec::new();
+            for _ in 0..fields_len {
+                fields.push(try!(self.symbol()));
+            }
+            function.records.push(fields);
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let index = try!(self.len());
+            let start = try!(self.len());
+            let end = try!(self.len());
+            function.source_map.emit(index,
+                                     Span {
+                                         start: BytePos::from(start),
+                                         end: BytePos::from(end),
+                                     });
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let start = try!(self.len());
+            let end = try!(read_u64(self.reader));
+            let index = try!(self.u32());
+            let name = try!(self.symbol());
+            let typ = try!(self.read_type());
+            function.locals.emit(start, index, name, typ);
+            if end != u64::MAX {
+                function.locals.close(index, end as usize);
+            }
+        }
+
+        let len = try!(self.len());
+        for _ in 0..len {
+            let name = try!(self.symbol());
+            let typ = try!(self.read_type());
+            function.upvars.push(UpvarInfo {
+                name: name,
+                typ: typ,
+            });
+        }
+        Ok(function)
+    }
+
+    fn read_instruction(&mut self) -> Result<Instruction> {
+        let op = try!(self.u8());
+        Ok(match op {
+            0 => PushInt(try!(read_u64(self.reader)) as i64 as isize),
+            1 => PushByte(try!(self.u8())),
+            2 => PushFloat(f64::from_bits(try!(read_u64(self.reader)))),
+            3 => PushString(try!(self.u32())),
+            4 => PushUpVar(try!(self.u32())),
+            5 => Push(try!(self.u32())),
+            6 => PushGlobal(try!(self.u32())),
+            7 => Call(try!(self.u32())),
+            8 => TailCall(try!(self.u32())),
+            9 => {
+                Construct {
+                    tag: try!(self.u32()),
+                    args: try!(self.u32()),
+                }
+            }
+            10 => {
+                ConstructRecord {
+                    record: try!(self.u32()),
+                    args: try!(self.u32()),
+                }
+            }
+            11 => ConstructArray(try!(self.u32())),
+            12 => GetOffset(try!(self.u32())),
+            13 => GetField(try!(self.u32())),
+            14 => Split,
+            15 => TestTag(try!(self.u32())),
+            16 => Jump(try!(self.u32())),
+            17 => CJump(try!(self.u32())),
+            18 => Pop(try!(self.u32())),
+            19 => Slide(try!(self.u32())),
+            20 => {
+                MakeClosure {
+                    function_index: try!(self.u32()),
+                    upvars: try!(self.u32()),
+                }
+            }
+            21 => {
+                NewClosure {
+                    function_index: try!(self.u32()),
+                    upvars: try!(self.u32()),
+                }
+            }
+            22 => CloseClosure(try!(self.u32())),
+            23 => AddInt,
+            24 => SubtractInt,
+            25 => MultiplyInt,
+            26 => DivideInt,
+            27 => IntLT,
+            28 => IntEQ,
+            29 => AddByte,
+            30 => SubtractByte,
+            31 => MultiplyByte,
+            32 => DivideByte,
+            33 => ByteLT,
+            34 => ByteEQ,
+            35 => AddFloat,
+            36 => SubtractFloat,
+            37 => MultiplyFloat,
+            38 => DivideFloat,
+            39 => FloatLT,
+            40 => FloatEQ,
+            _ => return Err(Error::Malformed(format!("Unknown instruction {}", op))),
+        })
+    }
+
+    fn read_kind(&mut self) -> Result<ArcKind> {
+        Ok(match try!(self.u8()) {
+            0 => Kind::variable(try!(self.u32())),
+            1 => Kind::typ(),
+            2 => Kind::row(),
+            3 => {
+                let arg = try!(self.read_kind());
+                let ret = try!(self.read_kind());
+                Kind::function(arg, ret)
+            }
+            tag => return Err(Error::Malformed(format!("Unknown kind {}", tag))),
+        })
+    }
+
+    fn read_generic(&mut self) -> Result<Generic<Symbol>> {
+        let id = try!(self.symbol());
+        let kind = try!(self.read_kind());
+        Ok(Generic {
+            id: id,
+            kind: kind,
+        })
+    }
+
+    fn read_alias(&mut self) -> Result<AliasData<Symbol, ArcType>> {
+        let name = try!(self.symbol());
+        let len = try!(self.len());
+        let mut args = Vec::new();
+        for _ in 0..len {
+            args.push(try!(self.read_generic()));
+        }
+        let typ = match try!(self.u8()) {
+            0 => None,
+            _ => Some(try!(self.read_type())),
+        };
+        Ok(AliasData {
+            name: name,
+            args: args,
+            typ: typ,
+        })
+    }
+
+    fn read_builtin(&mut self) -> Result<BuiltinType> {
+        Ok(match try!(self.u8()) {
+            0 => BuiltinType::String,
+            1 => BuiltinType::Byte,
+            2 => BuiltinType::Char,
+            3 => BuiltinType::Int,
+            4 => BuiltinType::Float,
+            5 => BuiltinType::Unit,
+            6 => BuiltinType::Array,
+            7 => BuiltinType::Function,
+            8 => BuiltinType::ImplicitFunction,
+            tag => return Err(Error::Malformed(format!("Unknown builtin type {}", tag))),
+        })
+    }
+
+    fn read_type(&mut self) -> Result<ArcType> {
+        try!(self.enter());
+        let result = self.read_type_();
+        self.depth -= 1;
+        result
+    }
+
+    fn read_type_(&mut self) -> Result<ArcType> {
+        Ok(match try!(self.u8()) {
+            0 => Type::hole(),
+            1 => {
+                let f = try!(self.read_type());
+                let len = try!(self.len());
+                let mut args = Vec::new();
+                for _ in 0..len {
+                    args.push(try!(self.read_type()));
+                }
+                Type::app(f, args)
+            }
+            2 => {
+                let len = try!(self.len());
+                let mut variants = Vec::new();
+                for _ in 0..len {
+                    let name = try!(self.symbol());
+                    let typ = try!(self.read_type());
+                    variants.push((name, typ));
+                }
+                Type::variants(variants)
+            }
+            3 => {
+                let id = try!(self.u32());
+                let kind = try!(self.read_kind());
+                Type::variable(TypeVariable {
+                    id: id,
+                    kind: kind,
+                })
+            }
+            4 => Type::generic(try!(self.read_generic())),
+            5 => Type::builtin(try!(self.read_builtin())),
+            6 => ArcType::from(Type::Record(try!(self.read_type()))),
+            7 => Type::empty_row(),
+            8 => {
+                let len = try!(self.len());
+                let mut types = Vec::new();
+                for _ in 0..len {
+                    let name = try!(self.symbol());
+                    let alias = try!(self.read_alias());
+                    types.push(Field {
+                        name: name,
+                        typ: Alias::from(alias),
+                    });
+                }
+                let len = try!(self.len());
+                let mut fields = Vec::new();
+                for _ in 0..len {
+                    let name = try!(self.symbol());
+                    let typ = try!(self.read_type());
+                    fields.push(Field {
+                        name: name,
+                        typ: typ,
+                    });
+                }
+                let rest = try!(self.read_type());
+                Type::extend_row(types, fields, rest)
+            }
+            9 => Type::ident(try!(self.symbol())),
+            10 => ArcType::from(Type::Alias(try!(self.read_alias()))),
+            11 => {
+                let len = try!(self.len());
+                let mut params = Vec::new();
+                for _ in 0..len {
+                    params.push(try!(self.read_generic()));
+                }
+                let typ = try!(self.read_type());
+                Type::forall(params, typ)
+            }
+            tag => return Err(Error::Malformed(format!("Unknown type {}", tag))),
+        })
+    }
+}
+
+/// Checks that all indexes in the instructions of `function` and its inner functions refer to
+/// existing items so that a corrupted file is rejected instead of causing out of bounds accesses
+/// in the interpreter. `upvars` is the number of upvars which closures of `function` have.
+///
+/// Stack indexes and the number of values which instructions push and pop are checked against
+/// `max_stack_size`, as the interpreter reserves that many slots before entering the function.
+fn validate(function: &CompiledFunction, upvars: VmIndex) -> Result<()> {
+    let in_bounds = |index: VmIndex, len: usize| (index as usize) < len;
+    let stack = function.max_stack_size;
+    // The number of upvars each inner function is closed over with
+    let mut inner_upvars: Vec<Option<VmIndex>> = vec![None; function.inner_functions.len()];
+    if function.args > stack {
+        return Err(Error::Malformed(format!("`{}` takes more arguments than fits on its stack",
+                                            function.id)));
+    }
+    for (i, instruction) in function.instructions.iter().enumerate() {
+        let valid = match *instruction {
+            PushString(index) |
+            GetField(index) => in_bounds(index, function.strings.len()),
+            PushGlobal(index) => in_bounds(index, function.module_globals.len()),
+            Push(index) => index < stack,
+            PushUpVar(index) => index < upvars,
+            // Calls and `Slide` also pop the value below the arguments
+            Call(args) | TailCall(args) | Slide(args) | CloseClosure(args) => args < stack,
+            Pop(args) |
+            ConstructArray(args) |
+            Construct { args, .. } => args <= stack,
+            ConstructRecord { record, args } => {
+                in_bounds(record, function.records.len()) && args <= stack
+            }
+            MakeClosure { function_index, upvars: closure_upvars } |
+            NewClosure { function_index, upvars: closure_upvars } => {
+                let index = function_index as usize;
+                if index < inner_upvars.len() && closure_upvars <= stack {
+                    let min_upvars = match inner_upvars[index] {
+                        Some(previous) => ::std::cmp::min(previous, closure_upvars),
+                        None => closure_upvars,
+                    };
+                    inner_upvars[index] = Some(min_upvars);
+                    true
+                } else {
+                    false
+                }
+            }
+            Jump(index) | CJump(index) => (index as usize) <= function.instructions.len(),
+            _ => true,
+        };
+        if !valid {
+            return Err(Error::Malformed(format!("Instruction {} ({:?}) in `{}` is out of bounds",
+                                                i,
+                                                instruction,
+                                                function.id)));
+        }
+    }
+    for local in function.locals.iter() {
+        if local.index >= stack {
+            return Err(Error::Malformed(format!("Local `{}` in `{}` is out of bounds",
+                                                local.name,
+                                                function.id)));
+        }
+    }
+    for (inner, upvars) in function.inner_functions.iter().zip(inner_upvars) {
+        // Functions which are never closed over can not refer to any upvars
+        try!(validate(inner, upvars.unwrap_or(0)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base::pos::{BytePos, Span};
+    use base::symbol::{Symbol, SymbolModule, Symbols};
+    use base::types::{ArcType, Type};
+
+    use compiler::{CompiledFunction, UpvarInfo};
+    use optimize::OptimizationLevel;
+    use types::*;
+    use vm::GlobalVmState;
+
+    #[test]
+    fn roundtrip() {
+        let vm = GlobalVmState::new();
+        let mut symbols = Symbols::new();
+        let mut symbols = SymbolModule::new("test".into(), &mut symbols);
+
+        let typ: ArcType = Type::function(vec![Type::int()], Type::array(Type::string()));
+        let mut function = CompiledFunction::new(1, Symbol::from("test"), typ.clone());
+        function.max_stack_size = 3;
+        function.strings.push(vm.intern("field").unwrap());
+        function.records.push(vec![symbols.symbol("x"), symbols.symbol("y")]);
+        function.instructions = vec![PushInt(-3),
+                                     PushFloat(1.5),
+                                     PushString(0),
+                                     ConstructRecord {
+                                         record: 0,
+                                         args: 2,
+                                     },
+                                     GetField(0),
+                                     Jump(6)];
+        function.locals.emit(0, 0, symbols.symbol("x"), Type::int());
+        function.locals.close(0, 4);
+        function.locals.emit(2, 1, symbols.symbol("y"), Type::string());
+        let mut inner = CompiledFunction::new(0, Symbol::from("inner"), typ);
+        inner.upvars.push(UpvarInfo {
+            name: symbols.symbol("x"),
+            typ: Type::int(),
+        });
+        function.inner_functions.push(inner);
+        function.source_map.emit(0,
+                                 Span {
+                                     start: BytePos::from(3),
+                                     end: BytePos::from(10),
+                                 });
+
+        let source = "let x = 1 in x";
+        let mut buffer = Vec::new();
+        write_compiled(&mut buffer, &vm, source, OptimizationLevel::None, &function).unwrap();
+        let read = read_compiled(&mut &buffer[..],
+                                 &vm,
+                                 &mut symbols,
+                                 source,
+                                 OptimizationLevel::None)
+            .unwrap();
+
+        assert_eq!(format!("{:?}", read.instructions),
+                   format!("{:?}", function.instructions));
+        assert_eq!(read.typ, function.typ);
+        assert_eq!(read.strings, function.strings);
+        assert_eq!(read.records, function.records);
+        assert_eq!(read.source_map, function.source_map);
+        assert_eq!(read.locals, function.locals);
+        assert_eq!(read.inner_functions.len(), 1);
+        assert_eq!(read.inner_functions[0].upvars, function.inner_functions[0].upvars);
+
+        match read_compiled(&mut &buffer[..],
+                            &vm,
+                            &mut symbols,
+                            "let x = 2 in x",
+                            OptimizationLevel::None) {
+            Err(Error::Stale) => (),
+            x => panic!("Expected stale error, got {:?}", x.map(|_| ())),
+        }
+        match read_compiled(&mut &buffer[..],
+                            &vm,
+                            &mut symbols,
+                            source,
+                            OptimizationLevel::Inline) {
+            Err(Error::Stale) => (),
+            x => panic!("Expected stale error, got {:?}", x.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reject_out_of_bounds_indexes() {
+        let vm = GlobalVmState::new();
+        let mut symbols = Symbols::new();
+        let mut symbols = SymbolModule::new("test".into(), &mut symbols);
+
+        let source = "1";
+        let invalid = vec![Push(1), PushUpVar(0), Call(1), PushGlobal(0), PushString(0)];
+        for instruction in invalid {
+            let mut function = CompiledFunction::new(0, Symbol::from("test"), Type::int());
+            function.max_stack_size = 1;
+            function.instructions.push(instruction);
+
+            let mut buffer = Vec::new();
+            write_compiled(&mut buffer, &vm, source, OptimizationLevel::None, &function).unwrap();
+            match read_compiled(&mut &buffer[..],
+                                &vm,
+                                &mut symbols,
+                                source,
+                                OptimizationLevel::None) {
+                Err(Error::Malformed(_)) => (),
+                x => panic!("Expected malformed error for {:?}, got {:?}",
+                            instruction,
+                            x.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn reject_deeply_nested_types() {
+        let vm = GlobalVmState::new();
+        let mut symbols = Symbols::new();
+        let mut symbols = SymbolModule::new("test".into(), &mut symbols);
+
+        let mut typ: ArcType = Type::int();
+        for _ in 0..MAX_DEPTH {
+            typ = Type::array(typ);
+        }
+        let function = CompiledFunction::new(0, Symbol::from("test"), typ);
+
+        let source = "1";
+        let mut buffer = Vec::new();
+        write_compiled(&mut buffer, &vm, source, OptimizationLevel::None, &function).unwrap();
+        match read_compiled(&mut &buffer[..],
+                            &vm,
+                            &mut symbols,
+                            source,
+                            OptimizationLevel::None) {
+            Err(Error::Malformed(_)) => (),
+            x => panic!("Expected malformed error, got {:?}", x.map(|_| ())),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt;
 use std::ops::{Deref, DerefMut, Index, IndexMut, Range, RangeTo, RangeFrom, RangeFull};
 
+use base::pos::Location;
 use base::symbol::Symbol;
 
 use Variants;
@@ -94,6 +95,13 @@ impl Stack {
         }
     }
 
+    /// Removes all frames above `frame_level` and all values above `len`, discarding whatever
+    /// state was left by an execution which did not return normally
+    pub fn unwind(&mut self, frame_level: usize, len: VmIndex) {
+        self.frames.truncate(frame_level);
+        self.values.truncate(len as usize);
+    }
+
     /// Release a lock on the stack.
     ///
     /// Panics if the lock is not the top-most lock
@@ -160,6 +168,12 @@ impl<'a: 'b, 'b> StackFrame<'b> {
         self.stack.len() - self.frame.offset
     }
 
+    /// Writes the cached frame back to the stack, making any changes to it visible through
+    /// `Stack::get_frames`
+    pub fn store_frame(&mut self) {
+        *self.stack.frames.last_mut().unwrap() = self.frame;
+    }
+
     pub fn push(&mut self, v: Value) {
         self.stack.values.push(v);
     }
@@ -277,8 +291,31 @@ impl<'a: 'b, 'b> StackFrame<'b> {
             .iter()
             .filter_map(|frame| {
                 match frame.state {
-                    State::Closure(ref closure) => Some(Some(closure.function.name.clone())),
-                    State::Extern(ref ext) => Some(Some(ext.id.clone())),
+                    State::Closure(ref closure) => {
+                        let function = &closure.function;
+                        // `instruction_index` points to the instruction after the call which
+                        // entered the next frame
+                        let index = frame.instruction_index.saturating_sub(1);
+                        let location = function.source
+                            .as_ref()
+                            .and_then(|source| {
+                                // The source file keeps the position of each line so the
+                                // location is found without scanning the text
+                                function.source_map
+                                    .span(index)
+                                    .map(|span| (source.name.clone(), source.location(span.start)))
+                            });
+                        Some(Some(StacktraceFrame {
+                            name: function.name.clone(),
+                            location: location,
+                        }))
+                    }
+                    State::Extern(ref ext) => {
+                        Some(Some(StacktraceFrame {
+                            name: ext.id.clone(),
+                            location: None,
+                        }))
+                    }
                     State::Unknown => Some(None),
                     State::Lock | State::Excess => None,
                 }
@@ -381,17 +418,37 @@ impl<'b> IndexMut<RangeFrom<VmIndex>> for StackFrame<'b> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+pub struct StacktraceFrame {
+    pub name: Symbol,
+    /// The name of the source file and the location in it which the frame is currently
+    /// executing. `None` for extern functions and functions without any source information
+    pub location: Option<(String, Location)>,
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Stacktrace {
-    frames: Vec<Option<Symbol>>,
+    pub frames: Vec<Option<StacktraceFrame>>,
 }
 
 impl fmt::Display for Stacktrace {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         try!(writeln!(f, "Stacktrace:\n"));
         for (i, frame) in self.frames.iter().enumerate() {
-            let name = frame.as_ref().map_or("<unknown>", |frame| frame.as_ref());
-            try!(writeln!(f, "{}: {}", i, name));
+            match *frame {
+                Some(ref frame) => {
+                    try!(write!(f, "{}: {}", i, frame.name));
+                    if let Some((ref source_name, ref location)) = frame.location {
+                        try!(write!(f,
+                                    " at {}:{}:{}",
+                                    source_name,
+                                    location.line,
+                                    location.column));
+                    }
+                    try!(writeln!(f, ""));
+                }
+                None => try!(writeln!(f, "{}: <unknown>", i)),
+            }
         }
         Ok(())
     }
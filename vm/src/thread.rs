@@ -8,9 +8,12 @@ use std::ops::{Add, Sub, Mul, Div, Deref, DerefMut};
 use std::string::String as StdString;
 use std::result::Result as StdResult;
 use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool};
+use std::time::Instant;
 use std::usize;
 
 use base::metadata::Metadata;
+use base::pos::Line;
 use base::symbol::Symbol;
 use base::types::ArcType;
 use base::types;
@@ -23,8 +26,9 @@ use macros::MacroEnv;
 use api::{Getable, Pushable, VmType};
 use array::Str;
 use compiler::CompiledFunction;
+use debugger::{DebugInfo, HookEvent, HookFlags};
 use gc::{DataDef, Gc, GcPtr, Move};
-use stack::{Stack, StackFrame, State};
+use stack::{Stack, StackFrame, State, Stacktrace};
 use types::*;
 use vm::{GlobalVmState, VmEnv};
 use value::{Value, ClosureData, ClosureInitDef, ClosureDataDef, Def, ExternFunction,
@@ -156,6 +160,9 @@ pub struct Thread {
     /// the roots of all its children as well since those may contain references to this threads
     /// garbage collected values
     child_threads: RwLock<Vec<GcPtr<Thread>>>,
+    /// Set by an `InterruptHandle` to make the thread stop executing. Kept outside of `context`
+    /// so that it can be read and written without waiting on the thread
+    interrupt: Arc<AtomicBool>,
     context: Mutex<Context>,
 }
 
@@ -241,11 +248,16 @@ impl RootedThread {
                 stack: Stack::new(),
                 record_map: FieldMap::new(),
                 hook: None,
+                debug_hook: None,
+                hook_flags: HookFlags::default(),
                 max_stack_size: VmIndex::max_value(),
+                fuel: None,
+                deadline: None,
             }),
             roots: RwLock::new(Vec::new()),
             rooted_values: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
+            interrupt: Arc::new(AtomicBool::new(false)),
         };
         let mut gc = Gc::new(0, usize::MAX);
         let vm =
@@ -287,11 +299,16 @@ impl Thread {
                 stack: Stack::new(),
                 record_map: FieldMap::new(),
                 hook: None,
+                debug_hook: None,
+                hook_flags: HookFlags::default(),
                 max_stack_size: VmIndex::max_value(),
+                fuel: None,
+                deadline: None,
             }),
             roots: RwLock::new(Vec::new()),
             rooted_values: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
+            interrupt: Arc::new(AtomicBool::new(false)),
         };
         // Enter the top level scope
         {
@@ -402,6 +419,12 @@ impl Thread {
     pub fn set_memory_limit(&self, memory_limit: usize) {
         self.current_context().gc.set_memory_limit(memory_limit)
     }
+
+    /// Returns a handle which can be used to interrupt this thread from another OS thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.interrupt.clone())
+    }
+
     fn current_context(&self) -> OwnedContext {
         OwnedContext {
             thread: self,
@@ -448,6 +471,32 @@ impl Thread {
         try!(context.borrow_mut().do_call(args));
         context.execute()
     }
+
+    /// If `result` is an `Interrupted` error the stack is unwound to `frame_level` and `len` so
+    /// that the thread can be used again.
+    fn unwind_interrupted<T>(&self,
+                             result: Result<T>,
+                             frame_level: usize,
+                             len: VmIndex)
+                             -> Result<T> {
+        if let Err(Error::Interrupted(_)) = result {
+            let mut context = self.current_context();
+            context.stack.unwind(frame_level, len);
+            // Only clear the interrupt once no function is left executing, otherwise the
+            // functions which are left would continue running after the interrupted call
+            let executing = context.stack
+                .get_frames()
+                .iter()
+                .any(|frame| match frame.state {
+                    State::Closure(_) | State::Extern(_) => true,
+                    _ => false,
+                });
+            if !executing {
+                self.interrupt.store(false, atomic::Ordering::SeqCst);
+            }
+        }
+        result
+    }
 }
 
 /// Internal functions for interacting with threads. These functions should be considered both
@@ -560,9 +609,12 @@ impl ThreadInternal for Thread {
 
     fn call_thunk(&self, closure: GcPtr<ClosureData>) -> Result<Value> {
         let mut context = self.current_context();
+        let frame_level = context.stack.get_frames().len();
+        let len = context.stack.len();
         context.stack.push(Closure(closure));
         context.borrow_mut().enter_scope(0, State::Closure(closure));
-        try!(context.execute());
+        let result = context.execute();
+        try!(self.unwind_interrupted(result, frame_level, len));
         Ok(self.current_context().stack.pop())
     }
 
@@ -573,6 +625,8 @@ impl ThreadInternal for Thread {
             thread: self,
             context: self.context.lock().unwrap(),
         };
+        let frame_level = context.stack.get_frames().len();
+        let len = context.stack.len();
         // Dummy value to fill the place of the function for TailCall
         context.stack.push(Int(0));
 
@@ -580,7 +634,8 @@ impl ThreadInternal for Thread {
         context.stack.push(Int(0));
 
         context.borrow_mut().enter_scope(2, State::Unknown);
-        context = try!(self.call_context(context, 1))
+        let result = self.call_context(context, 1);
+        context = try!(self.unwind_interrupted(result, frame_level, len))
             .expect("call_module to have the stack remaining");
         let result = context.stack.pop();
         {
@@ -600,7 +655,10 @@ impl ThreadInternal for Thread {
                          context: OwnedContext<'b>,
                          args: VmIndex)
                          -> Result<Option<OwnedContext<'b>>> {
-        self.call_context(context, args)
+        let frame_level = context.stack.get_frames().len();
+        let len = context.stack.len() - args - 1;
+        let result = self.call_context(context, args);
+        self.unwind_interrupted(result, frame_level, len)
     }
 
     fn resume(&self) -> Result<()> {
@@ -626,13 +684,34 @@ impl ThreadInternal for Thread {
 
 pub type HookFn = Box<FnMut(&Thread) -> Result<()> + Send + Sync>;
 
+/// Hook which is called with information about the executing code, see `Context::set_debug_hook`
+pub type DebugHookFn = Box<FnMut(&Thread, DebugInfo) -> Result<()> + Send + Sync>;
+
+/// Handle which can be used to interrupt a thread from another OS thread.
+///
+/// The interrupted thread stops the next time it enters or returns to a function, returning
+/// `Error::Interrupted` from the nearest `call_function` or `execute_io` call.
+#[derive(Clone, Debug)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Requests that the thread this handle was created from stops executing
+    pub fn interrupt(&self) {
+        self.0.store(true, atomic::Ordering::SeqCst);
+    }
+}
+
 pub struct Context {
     // FIXME It is dangerous to write to gc and stack
     pub stack: Stack,
     pub gc: Gc,
     record_map: FieldMap,
     hook: Option<HookFn>,
+    debug_hook: Option<DebugHookFn>,
+    hook_flags: HookFlags,
     max_stack_size: VmIndex,
+    fuel: Option<usize>,
+    deadline: Option<Instant>,
 }
 
 impl Context {
@@ -663,9 +742,59 @@ impl Context {
         mem::replace(&mut self.hook, hook)
     }
 
+    /// Sets a hook which is called for the events selected by `set_hook_flags` and which can
+    /// inspect the executing code through `DebugInfo`. It is called in addition to the hook set
+    /// with `set_hook`.
+    pub fn set_debug_hook(&mut self, hook: Option<DebugHookFn>) -> Option<DebugHookFn> {
+        mem::replace(&mut self.debug_hook, hook)
+    }
+
+    /// Sets which events cause the debug hook to be called
+    pub fn set_hook_flags(&mut self, flags: HookFlags) {
+        self.hook_flags = flags;
+    }
+
+    pub fn hook_flags(&self) -> HookFlags {
+        self.hook_flags
+    }
+
     pub fn set_max_stack_size(&mut self, limit: VmIndex) {
         self.max_stack_size = limit;
     }
+
+    /// Limits the number of instructions this thread may execute before returning
+    /// `Error::OutOfFuel`. Passing `None` removes the limit.
+    ///
+    /// When the fuel runs out the thread stops before executing the next instruction so it can
+    /// be continued with `ThreadInternal::resume` after more fuel has been added. The stack is
+    /// not unwound, the frames of the stopped call are left on the thread until it is resumed to
+    /// completion. A thread which is not going to be resumed should be dropped rather than used
+    /// to call other functions as those calls would run on top of the stopped frames.
+    pub fn set_fuel(&mut self, fuel: Option<usize>) {
+        self.fuel = fuel;
+    }
+
+    /// Returns the number of instructions this thread may still execute or `None` if it is
+    /// unlimited
+    pub fn fuel(&self) -> Option<usize> {
+        self.fuel
+    }
+
+    /// Makes the thread return `Error::Timeout` if it is still executing after `deadline`.
+    /// Passing `None` removes the deadline.
+    ///
+    /// The deadline is checked each time a function is entered or returned to and on each jump
+    /// backwards in a function. As with `set_fuel` the stack is left as it were when the deadline
+    /// passed, so the thread can be continued with `ThreadInternal::resume` after the deadline is
+    /// extended but should otherwise be dropped.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Returns the deadline of this thread if one is set
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
 }
 
 impl<'b> OwnedContext<'b> {
@@ -729,9 +858,24 @@ impl<'b> OwnedContext<'b> {
             match state {
                 State::Extern(_) |
                 State::Closure(_) => {
+                    if context.thread.interrupt.load(atomic::Ordering::SeqCst) {
+                        return Err(context.interrupted());
+                    }
+                    if let Some(deadline) = context.deadline {
+                        if Instant::now() >= deadline {
+                            return Err(Error::Timeout);
+                        }
+                    }
                     if let Some(ref mut hook) = context.hook {
                         try!(hook(context.thread))
                     }
+                    if context.hook_flags.call {
+                        let thread = context.thread;
+                        let Context { ref mut debug_hook, ref stack, .. } = **context;
+                        if let Some(ref mut hook) = *debug_hook {
+                            try!(hook(thread, DebugInfo::new(stack, HookEvent::Call)))
+                        }
+                    }
                 }
                 _ => (),
             }
@@ -827,6 +971,11 @@ impl<'b> OwnedContext<'b> {
         match status {
             Status::Ok => Ok(self),
             Status::Yield => Err(Error::Yield),
+            // The function failed due to this thread being interrupted while it was calling
+            // back into gluon so propagate the interrupt instead of the error message
+            Status::Error if self.thread.interrupt.load(atomic::Ordering::SeqCst) => {
+                Err(self.interrupted())
+            }
             Status::Error => {
                 match self.stack.pop() {
                     String(s) => Err(Error::Panic(s.to_string())),
@@ -839,6 +988,10 @@ impl<'b> OwnedContext<'b> {
         }
     }
 
+    fn interrupted(&mut self) -> Error {
+        Error::Interrupted(StackFrame::current(&mut self.stack).stacktrace(0))
+    }
+
     fn borrow_mut(&mut self) -> ExecuteContext {
         let context = &mut **self;
         ExecuteContext {
@@ -846,6 +999,10 @@ impl<'b> OwnedContext<'b> {
             gc: &mut context.gc,
             stack: StackFrame::current(&mut context.stack),
             record_map: &mut context.record_map,
+            fuel: &mut context.fuel,
+            deadline: context.deadline,
+            debug_hook: &mut context.debug_hook,
+            hook_flags: context.hook_flags,
         }
     }
 }
@@ -855,6 +1012,10 @@ struct ExecuteContext<'b> {
     stack: StackFrame<'b>,
     gc: &'b mut Gc,
     record_map: &'b mut FieldMap,
+    fuel: &'b mut Option<usize>,
+    deadline: Option<Instant>,
+    debug_hook: &'b mut Option<DebugHookFn>,
+    hook_flags: HookFlags,
 }
 
 impl<'b> ExecuteContext<'b> {
@@ -870,6 +1031,18 @@ impl<'b> ExecuteContext<'b> {
         self.stack.enter_scope(args, state);
     }
 
+    /// Returns `Error::Timeout` if the deadline has passed, saving `index` as the instruction
+    /// to continue from if the thread is resumed
+    fn check_deadline(&mut self, index: usize) -> Result<()> {
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                self.stack.frame.instruction_index = index;
+                return Err(Error::Timeout);
+            }
+        }
+        Ok(())
+    }
+
     fn exit_scope(&mut self) -> StdResult<(), ()> {
         match self.stack.exit_scope() {
             Ok(_) => Ok(()),
@@ -982,7 +1155,35 @@ impl<'b> ExecuteContext<'b> {
                    &self.stack[..],
                    self.stack.frame);
         }
+        // The line of the previous instruction, used to detect when a new line is entered
+        let mut last_line = if self.hook_flags.line && index > 0 {
+            line_of(function, index - 1)
+        } else {
+            None
+        };
         while let Some(&instr) = instructions.get(index) {
+            if self.hook_flags.line {
+                let line = line_of(function, index);
+                if line.is_some() && line != last_line {
+                    last_line = line;
+                    // Make the position of this frame visible to the hook and let execution
+                    // resume from this instruction if the hook returns an error
+                    self.stack.frame.instruction_index = index;
+                    self.stack.store_frame();
+                    if let Some(ref mut hook) = *self.debug_hook {
+                        let info = DebugInfo::new(&*self.stack.stack, HookEvent::Line);
+                        try!(hook(self.thread, info));
+                    }
+                }
+            }
+            if let Some(ref mut fuel) = *self.fuel {
+                if *fuel == 0 {
+                    // Save the position so that execution can continue at this instruction
+                    self.stack.frame.instruction_index = index;
+                    return Err(Error::OutOfFuel);
+                }
+                *fuel -= 1;
+            }
             debug_instruction(&self.stack, index, instr, function);
             match instr {
                 Push(i) => {
@@ -1140,6 +1341,11 @@ impl<'b> ExecuteContext<'b> {
                     }
                 }
                 Jump(i) => {
+                    // Loops do not necessarily enter a function so jumping backwards also needs
+                    // to check the deadline
+                    if i as usize <= index {
+                        try!(self.check_deadline(i as usize));
+                    }
                     index = i as usize;
                     continue;
                 }
@@ -1147,6 +1353,9 @@ impl<'b> ExecuteContext<'b> {
                     match self.stack.pop() {
                         Value::Tag(0) => (),
                         _ => {
+                            if i as usize <= index {
+                                try!(self.check_deadline(i as usize));
+                            }
                             index = i as usize;
                             continue;
                         }
@@ -1317,6 +1526,13 @@ fn binop<'b, F, T>(vm: &'b Thread, stack: &mut StackFrame<'b>, f: F)
     }
 }
 
+/// Returns the source line which the instruction at `index` were compiled from
+fn line_of(function: &BytecodeFunction, index: usize) -> Option<Line> {
+    function.source
+        .as_ref()
+        .and_then(|source| function.source_map.span(index).map(|span| source.line(span.start)))
+}
+
 fn debug_instruction(stack: &StackFrame,
                      index: usize,
                      instr: Instruction,
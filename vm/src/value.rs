@@ -1,11 +1,13 @@
 use std::fmt;
 use std::collections::hash_map::Entry;
 use std::result::Result as StdResult;
+use std::sync::Arc;
 
 use base::symbol::Symbol;
 use types::*;
 use base::fnv::FnvMap;
 
+use compiler::{LocalMap, SourceFile, SourceMap, UpvarInfo};
 use interner::InternedStr;
 use gc::{Gc, GcPtr, Traverseable, DataDef, WriteOnly};
 use array::{Array, Str};
@@ -104,7 +106,13 @@ pub struct BytecodeFunction {
     pub inner_functions: Vec<GcPtr<BytecodeFunction>>,
     pub strings: Vec<InternedStr>,
     pub globals: Vec<Value>,
+    /// The names of the values in `globals`
+    pub global_names: Vec<Symbol>,
     pub records: Vec<Vec<InternedStr>>,
+    pub source_map: SourceMap,
+    pub source: Option<Arc<SourceFile>>,
+    pub locals: LocalMap,
+    pub upvars: Vec<UpvarInfo>,
 }
 
 impl Traverseable for BytecodeFunction {
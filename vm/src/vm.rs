@@ -39,14 +39,26 @@ fn new_bytecode(gc: &mut Gc,
                            strings,
                            module_globals,
                            records,
+                           source_map,
+                           source,
+                           locals,
+                           upvars,
                            .. } = f;
     let fs = try!(inner_functions.into_iter()
         .map(|inner| new_bytecode(gc, vm, inner))
         .collect());
 
-    let globals = module_globals.into_iter()
-        .map(|index| vm.env.read().unwrap().globals[index.as_ref()].value)
-        .collect();
+    let globals = try!(module_globals.iter()
+        .map(|index| {
+            vm.env
+                .read()
+                .unwrap()
+                .globals
+                .get(index.as_ref())
+                .map(|global| global.value)
+                .ok_or_else(|| Error::UndefinedBinding(index.as_ref().into()))
+        })
+        .collect());
     let records = try!(records.into_iter()
         .map(|vec| {
             vec.into_iter()
@@ -62,7 +74,12 @@ fn new_bytecode(gc: &mut Gc,
         inner_functions: fs,
         strings: strings,
         globals: globals,
+        global_names: module_globals,
         records: records,
+        source_map: source_map,
+        source: source,
+        locals: locals,
+        upvars: upvars,
     }))
 }
 